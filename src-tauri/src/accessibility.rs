@@ -0,0 +1,79 @@
+// OS accessibility preferences (high contrast, reduced motion) for the windows and overlays to
+// follow: read with `get_accessibility_prefs`, re-sent as `accessibility_changed` when the
+// user flips them while the app runs.
+
+use crate::events;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::time::Duration;
+
+// Windows has change events for these, but only on UI threads; a slow poll is enough
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Default, Debug, JsonSchema)]
+pub struct AccessibilityPrefs {
+    pub high_contrast: bool,
+    // "Show animations in Windows" is off
+    pub reduced_motion: bool,
+}
+
+#[cfg(windows)]
+fn read() -> AccessibilityPrefs {
+    use windows::UI::ViewManagement::{AccessibilitySettings, UISettings};
+
+    let high_contrast = AccessibilitySettings::new()
+        .and_then(|s| s.HighContrast())
+        .unwrap_or(false);
+    let animations = UISettings::new()
+        .and_then(|s| s.AnimationsEnabled())
+        .unwrap_or(true);
+    AccessibilityPrefs {
+        high_contrast,
+        reduced_motion: !animations,
+    }
+}
+
+// No portable way to ask; overlays can still use the CSS media queries
+#[cfg(not(windows))]
+fn read() -> AccessibilityPrefs {
+    AccessibilityPrefs::default()
+}
+
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut last = read();
+        loop {
+            tokio::time::sleep(CHECK_EVERY).await;
+            let now = read();
+            if now != last {
+                last = now;
+                events::emit(&app, "accessibility_changed", now);
+            }
+        }
+    });
+}
+
+// A single plain sentence for screen readers and text-to-speech sources, without the
+// separators and emoji overlay templates tend to use
+pub fn plain_summary(track: &str, artists: &[String], album: Option<&str>) -> String {
+    if track.trim().is_empty() {
+        return "Nothing is playing.".into();
+    }
+    let mut out = format!("Now playing {}", track.trim());
+    match artists {
+        [] => {}
+        [one] => out += &format!(", by {one}"),
+        [rest @ .., last] => out += &format!(", by {} and {last}", rest.join(", ")),
+    }
+    if let Some(album) = album.map(str::trim).filter(|a| !a.is_empty()) {
+        out += &format!(", from the album {album}");
+    }
+    out.push('.');
+    out.replace(['\n', '\r'], " ")
+}
+
+#[tauri::command]
+pub fn get_accessibility_prefs() -> AccessibilityPrefs {
+    read()
+}
@@ -0,0 +1,278 @@
+// Maintenance for `artcache`: covers extracted from a large library repeat a lot (every track
+// of an album carries the same picture, and each gets its own cache file). Files with the same
+// bytes are hardlinked to one copy; optionally, files of the same format that only differ in
+// encoding but look the same (same difference hash, and close pixels once scaled down) are
+// too, keeping the largest picture.
+// Runs by itself at most once a week (byte-identical only), or on demand.
+//
+// Writers replace cache files instead of writing through them (see
+// `extract_embedded_art_to_cache`), so a linked file never changes under the others.
+
+use crate::{read_settings, write_setting};
+use image::imageops::FilterType;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::Manager;
+
+const AUTO_EVERY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// after startup, so the first polls and the library index go first
+const AUTO_DELAY: Duration = Duration::from_secs(120);
+
+const IMAGE_EXTS: &[&str] = &["jpg", "jpeg", "png", "webp", "bin"];
+
+// (path, size in bytes)
+type Files = Vec<(PathBuf, u64)>;
+
+#[derive(Serialize, Default, JsonSchema)]
+pub struct DedupeReport {
+    pub scanned_files: usize,
+    // sets of two or more files with the same picture
+    pub groups: usize,
+    // files now linked to another copy (or that would be, in a dry run)
+    pub linked_files: usize,
+    pub reclaimed_bytes: u64,
+    // files that couldn't be linked, e.g. on file systems without hardlinks
+    pub failed: usize,
+}
+
+fn cache_dir(app: &tauri::AppHandle) -> Option<PathBuf> {
+    crate::portable::data_dir(app)
+        .ok()
+        .map(|d| d.join("artcache"))
+}
+
+// Cached covers; `variants` are renders of these and are left alone
+fn cached_images(dir: &Path) -> Files {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Files = entries
+        .flatten()
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            let path = e.path();
+            let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+            (meta.is_file() && IMAGE_EXTS.contains(&ext.as_str())).then_some((path, meta.len()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut h = DefaultHasher::new();
+    bytes.hash(&mut h);
+    h.finish()
+}
+
+// 64-bit difference hash: brighter-than-the-right-neighbour bits of a 9x8 grayscale thumbnail
+fn dhash(path: &Path) -> Option<(u64, u64)> {
+    let img = image::open(path).ok()?;
+    let pixels = u64::from(img.width()) * u64::from(img.height());
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut bits = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            bits <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                bits |= 1;
+            }
+        }
+    }
+    Some((bits, pixels))
+}
+
+// 32x32 RGB thumbnail, to confirm a dHash match: unrelated covers (dark or flat ones
+// especially) can share a hash, but not the colours in every part of the picture
+fn thumbnail(path: &Path) -> Option<Vec<u8>> {
+    let img = image::open(path).ok()?;
+    Some(
+        img.resize_exact(32, 32, FilterType::Triangle)
+            .to_rgb8()
+            .into_raw(),
+    )
+}
+
+// Re-encodes of one cover stay within a few levels on average; different pictures don't
+const MAX_MEAN_DIFF: u64 = 6;
+
+fn looks_same(a: &[u8], b: &[u8]) -> bool {
+    let total: u64 = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| u64::from(x.abs_diff(*y)))
+        .sum();
+    a.len() == b.len() && total <= MAX_MEAN_DIFF * a.len() as u64
+}
+
+// Replaces `dup` with a hardlink to `keep`, via a temporary name so `dup` is never missing
+fn link(keep: &Path, dup: &Path) -> std::io::Result<()> {
+    let tmp = dup.with_extension("dedupe-tmp");
+    let _ = fs::remove_file(&tmp);
+    fs::hard_link(keep, &tmp)?;
+    fs::rename(&tmp, dup).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+// Links every file in `group` to its first one, skipping files in `done`
+fn link_group(
+    group: &[(PathBuf, u64)],
+    dry_run: bool,
+    done: &mut HashSet<PathBuf>,
+    report: &mut DedupeReport,
+) {
+    let Some((keep, _)) = group.first() else {
+        return;
+    };
+    let mut linked_any = false;
+    for (dup, size) in &group[1..] {
+        if done.contains(dup) || same_file::is_same_file(keep, dup).unwrap_or(false) {
+            continue;
+        }
+        let linked = if dry_run { Ok(()) } else { link(keep, dup) };
+        match linked {
+            Ok(()) => {
+                report.linked_files += 1;
+                report.reclaimed_bytes += size;
+                linked_any = true;
+                done.insert(dup.clone());
+            }
+            Err(e) => {
+                eprintln!("[artcache] link {}: {e}", dup.display());
+                report.failed += 1;
+            }
+        }
+    }
+    if linked_any {
+        report.groups += 1;
+    }
+}
+
+fn dedupe(dir: &Path, perceptual: bool, dry_run: bool) -> DedupeReport {
+    let files = cached_images(dir);
+    let mut report = DedupeReport {
+        scanned_files: files.len(),
+        ..Default::default()
+    };
+    let mut done = HashSet::new();
+
+    // same size first, so only candidates get read
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in &files {
+        by_size.entry(*size).or_default().push(path.clone());
+    }
+    for (size, paths) in by_size.into_iter().filter(|(_, p)| p.len() > 1) {
+        // hash -> (bytes of the first file, files with those bytes)
+        let mut groups: HashMap<u64, Vec<(Vec<u8>, Files)>> = HashMap::new();
+        for path in paths {
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            let buckets = groups.entry(content_hash(&bytes)).or_default();
+            // a hash match still gets compared byte for byte
+            match buckets.iter_mut().find(|(b, _)| *b == bytes) {
+                Some((_, group)) => group.push((path, size)),
+                None => buckets.push((bytes, vec![(path, size)])),
+            }
+        }
+        for (_, group) in groups.into_values().flatten() {
+            link_group(&group, dry_run, &mut done, &mut report);
+        }
+    }
+
+    if perceptual {
+        // (extension, hash) -> files; a .jpg linked under a .png name wouldn't decode
+        let mut looks: HashMap<(String, u64), Files> = HashMap::new();
+        let mut pixels = HashMap::new();
+        for (path, size) in &files {
+            let ext = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if let Some((hash, px)) = dhash(path) {
+                looks
+                    .entry((ext, hash))
+                    .or_default()
+                    .push((path.clone(), *size));
+                pixels.insert(path.clone(), px);
+            }
+        }
+        for mut group in looks.into_values().filter(|g| g.len() > 1) {
+            // the sharpest copy stays
+            group.sort_by_key(|(p, size)| std::cmp::Reverse((pixels.get(p).copied(), *size)));
+            // (thumbnail of the first file, files that look like it)
+            let mut same: Vec<(Vec<u8>, Files)> = Vec::new();
+            for (path, size) in group {
+                let Some(thumb) = thumbnail(&path) else {
+                    continue;
+                };
+                match same.iter_mut().find(|(t, _)| looks_same(t, &thumb)) {
+                    Some((_, files)) => files.push((path, size)),
+                    None => same.push((thumb, vec![(path, size)])),
+                }
+            }
+            for (_, files) in same {
+                link_group(&files, dry_run, &mut done, &mut report);
+            }
+        }
+    }
+    report
+}
+
+pub fn start(app: &tauri::AppHandle) {
+    let due = read_settings(app)
+        .get("artcache_dedupe_at")
+        .and_then(|v| v.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .is_none_or(|t| {
+            (chrono::Utc::now() - t.to_utc())
+                .to_std()
+                .is_ok_and(|d| d >= AUTO_EVERY)
+        });
+    let Some(dir) = cache_dir(app).filter(|_| due) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(AUTO_DELAY).await;
+        let report =
+            match tauri::async_runtime::spawn_blocking(move || dedupe(&dir, false, false)).await {
+                Ok(r) => r,
+                Err(e) => return eprintln!("[artcache] dedupe task: {e}"),
+            };
+        if report.linked_files > 0 {
+            eprintln!(
+                "[artcache] linked {} duplicate covers, {} KB freed",
+                report.linked_files,
+                report.reclaimed_bytes / 1024
+            );
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        if let Err(e) = write_setting(&app, "artcache_dedupe_at", serde_json::json!(now)) {
+            eprintln!("[artcache] {e}");
+        }
+    });
+}
+
+// `perceptual` also links same-format covers that only differ in encoding; `dry_run` just
+// reports what would be linked
+#[tauri::command]
+pub async fn dedupe_artcache(
+    window: tauri::Window,
+    perceptual: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<DedupeReport, String> {
+    let dir = cache_dir(window.app_handle()).ok_or("No app data directory")?;
+    let (perceptual, dry_run) = (perceptual.unwrap_or(false), dry_run.unwrap_or(false));
+    tauri::async_runtime::spawn_blocking(move || dedupe(&dir, perceptual, dry_run))
+        .await
+        .map_err(|e| format!("dedupe task: {e}"))
+}
@@ -0,0 +1,245 @@
+// Artwork lookups against public catalog search APIs, for sources that only hand us
+// title/artist and no usable thumbnail. Results are kept in `artcache/lookups.json` so a
+// restart doesn't search everything again.
+
+use crate::{compilation, NowPlaying, SharedCaches};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+use tauri::Manager;
+use url::Url;
+
+// shared by the other metadata lookups
+pub static HTTP: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap_or_default()
+});
+
+#[derive(Deserialize)]
+struct DeezerSearch {
+    #[serde(default)]
+    data: Vec<DeezerTrack>,
+    // quota and other errors come back as 200 with this instead of `data`
+    error: Option<DeezerError>,
+}
+
+#[derive(Deserialize)]
+struct DeezerError {
+    #[serde(default)]
+    message: String,
+    code: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct DeezerTrack {
+    album: DeezerAlbum,
+}
+
+#[derive(Deserialize)]
+struct DeezerAlbum {
+    title: String,
+    cover_xl: Option<String>,
+    cover_big: Option<String>,
+}
+
+// Deezer's search endpoint needs no API key
+async fn deezer_search(title: &str, artist: &str) -> Result<Option<(String, String)>, String> {
+    let q = if artist.is_empty() {
+        format!("track:\"{title}\"")
+    } else {
+        format!("artist:\"{artist}\" track:\"{title}\"")
+    };
+    let url = Url::parse_with_params("https://api.deezer.com/search", &[("q", q.as_str())])
+        .map_err(|e| e.to_string())?;
+
+    let bytes = HTTP
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("deezer search: {e}"))?
+        .bytes()
+        .await
+        .map_err(|e| format!("deezer search body: {e}"))?;
+    let res: DeezerSearch =
+        serde_json::from_slice(&bytes).map_err(|e| format!("deezer search json: {e}"))?;
+    if let Some(e) = res.error {
+        return Err(format!(
+            "deezer search: {} (code {})",
+            e.message,
+            e.code.unwrap_or_default()
+        ));
+    }
+
+    Ok(res.data.into_iter().find_map(|t| {
+        let cover = t.album.cover_xl.or(t.album.cover_big)?;
+        Some((cover, t.album.title))
+    }))
+}
+
+#[derive(Deserialize)]
+struct ItunesSearch {
+    #[serde(default)]
+    results: Vec<ItunesTrack>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItunesTrack {
+    artwork_url100: Option<String>,
+    collection_name: Option<String>,
+}
+
+// The Apple Music catalog, which is what the Apple Music app is playing from anyway
+async fn itunes_search(title: &str, artist: &str) -> Result<Option<(String, String)>, String> {
+    let term = format!("{artist} {title}");
+    let url = Url::parse_with_params(
+        "https://itunes.apple.com/search",
+        &[
+            ("term", term.trim()),
+            ("media", "music"),
+            ("entity", "song"),
+            ("limit", "5"),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let bytes = HTTP
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("itunes search: {e}"))?
+        .bytes()
+        .await
+        .map_err(|e| format!("itunes search body: {e}"))?;
+    let res: ItunesSearch =
+        serde_json::from_slice(&bytes).map_err(|e| format!("itunes search json: {e}"))?;
+
+    Ok(res.results.into_iter().find_map(|t| {
+        // the 100px URL serves any size if asked
+        let cover = t.artwork_url100?.replace("100x100bb", "600x600bb");
+        Some((cover, t.collection_name.unwrap_or_default()))
+    }))
+}
+
+#[derive(Clone, Copy)]
+enum Service {
+    Deezer,
+    Itunes,
+}
+
+impl Service {
+    fn name(self) -> &'static str {
+        match self {
+            Service::Deezer => "deezer",
+            Service::Itunes => "itunes",
+        }
+    }
+
+    async fn search(self, title: &str, artist: &str) -> Result<Option<(String, String)>, String> {
+        match self {
+            Service::Deezer => deezer_search(title, artist).await,
+            Service::Itunes => itunes_search(title, artist).await,
+        }
+    }
+}
+
+fn cache_file(app: &tauri::AppHandle) -> Option<PathBuf> {
+    Some(
+        crate::portable::data_dir(app)
+            .ok()?
+            .join("artcache")
+            .join("lookups.json"),
+    )
+}
+
+pub fn load_cache(app: &tauri::AppHandle) -> HashMap<String, Option<(String, String)>> {
+    cache_file(app)
+        .and_then(|p| std::fs::read(p).ok())
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(app: &tauri::AppHandle, cache: &HashMap<String, Option<(String, String)>>) {
+    let Some(path) = cache_file(app) else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(bytes) = serde_json::to_vec(cache) {
+        if let Err(e) = std::fs::write(&path, bytes) {
+            eprintln!("[artwork] save lookup cache: {e}");
+        }
+    }
+}
+
+// Fills `artwork_url` (and a missing album name) from Deezer. TIDAL's catalog API needs
+// developer credentials, so Deezer covers TIDAL sessions as well.
+pub async fn fill_from_deezer(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    fill(app, np, Service::Deezer).await
+}
+
+// Same, from the iTunes Search API (Apple Music doesn't publish a GSMTC thumbnail)
+pub async fn fill_from_itunes(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    fill(app, np, Service::Itunes).await
+}
+
+// Deezer first, then iTunes for whatever Deezer doesn't know; for sources with no catalog of
+// their own, like web radio
+pub async fn fill_with_fallbacks(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    fill(app, np, Service::Deezer).await;
+    if np.artwork_url.is_none() {
+        fill(app, np, Service::Itunes).await;
+    }
+}
+
+// Results, including misses, are cached per service+title+artist so the watcher doesn't
+// search again every poll.
+async fn fill(app: &tauri::AppHandle, np: &mut NowPlaying, service: Service) {
+    let Some(title) = np.track_name.clone() else {
+        return;
+    };
+    let artist = compilation::first_real_artist(&np.artists)
+        .cloned()
+        .unwrap_or_default();
+    let key = format!(
+        "{}:{}|{}",
+        service.name(),
+        title.trim().to_lowercase(),
+        artist.trim().to_lowercase()
+    );
+
+    let caches = app.state::<SharedCaches>();
+    let cached = caches.lock().art_lookup.get(&key).cloned();
+    let hit = match cached {
+        Some(hit) => hit,
+        None => {
+            let hit = match service.search(&title, &artist).await {
+                Ok(hit) => hit,
+                Err(e) => {
+                    // don't cache transient failures
+                    eprintln!("[artwork] {e}");
+                    return;
+                }
+            };
+            let snapshot = {
+                let mut c = caches.lock();
+                c.art_lookup.insert(key, hit.clone());
+                c.art_lookup.clone()
+            };
+            save_cache(app, &snapshot);
+            hit
+        }
+    };
+
+    if let Some((url, album)) = hit {
+        np.artwork_url = Some(url);
+        if np.album.is_none() && !album.is_empty() {
+            np.album = Some(album);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// Processed versions of the current cover (grayscale, duotone in the stream's brand colors,
+// pixelated) so an overlay theme can keep the art on-brand. Rendered on demand and cached
+// under `$APP/artcache/variants`, one file per cover + style.
+
+use crate::{export, SharedStore};
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use serde::Deserialize;
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+use tauri::{Manager, State};
+
+#[derive(Deserialize, Hash)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VariantStyle {
+    Grayscale,
+    // "#rrggbb" colors the shadows and highlights are mapped to
+    Duotone { dark: String, light: String },
+    // size of one block in pixels of the source image
+    Pixelate { block: u32 },
+}
+
+fn parse_hex(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("invalid color {s:?}, expected #rrggbb"))
+    };
+    if hex.len() != 6 {
+        return Err(format!("invalid color {s:?}, expected #rrggbb"));
+    }
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn duotone(img: &DynamicImage, dark: [u8; 3], light: [u8; 3]) -> RgbaImage {
+    let luma = img.to_luma_alpha8();
+    RgbaImage::from_fn(luma.width(), luma.height(), |x, y| {
+        let [l, a] = luma.get_pixel(x, y).0;
+        let t = f32::from(l) / 255.0;
+        let mix =
+            |i: usize| (f32::from(dark[i]) + (f32::from(light[i]) - f32::from(dark[i])) * t) as u8;
+        Rgba([mix(0), mix(1), mix(2), a])
+    })
+}
+
+fn pixelate(img: &DynamicImage, block: u32) -> DynamicImage {
+    let block = block.max(1);
+    let (w, h) = (img.width(), img.height());
+    img.resize_exact(
+        w.div_ceil(block),
+        h.div_ceil(block),
+        imageops::FilterType::Triangle,
+    )
+    .resize_exact(w, h, imageops::FilterType::Nearest)
+}
+
+fn apply(img: &DynamicImage, style: &VariantStyle) -> Result<DynamicImage, String> {
+    Ok(match style {
+        VariantStyle::Grayscale => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+        VariantStyle::Duotone { dark, light } => {
+            DynamicImage::ImageRgba8(duotone(img, parse_hex(dark)?, parse_hex(light)?))
+        }
+        VariantStyle::Pixelate { block } => pixelate(img, *block),
+    })
+}
+
+fn cache_path(app: &tauri::AppHandle, track_key: &str, style: &VariantStyle) -> Option<PathBuf> {
+    let mut h = DefaultHasher::new();
+    track_key.hash(&mut h);
+    style.hash(&mut h);
+    let dir = crate::portable::data_dir(app)
+        .ok()?
+        .join("artcache")
+        .join("variants");
+    Some(dir.join(format!("{:016x}.png", h.finish())))
+}
+
+// Returns a local file path (for `convertFileSrc`) of the current cover in `style`
+#[tauri::command]
+pub async fn get_artwork_variant(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    style: VariantStyle,
+) -> Result<String, String> {
+    let np = state
+        .lock()
+        .last_now_playing
+        .clone()
+        .filter(|np| np.track_name.is_some())
+        .ok_or("Nothing is playing")?;
+    let source = np
+        .artwork_path
+        .clone()
+        .or_else(|| np.artwork_url.clone())
+        .ok_or("No artwork for this track")?;
+
+    let path = cache_path(window.app_handle(), &source, &style)
+        .ok_or("No app data directory for the artwork cache")?;
+    if path.exists() {
+        return Ok(path.to_string_lossy().to_string());
+    }
+
+    let img = match np.artwork_path.as_deref() {
+        Some(p) if Path::new(p).exists() => image::open(p).map_err(|e| format!("open {p}: {e}"))?,
+        _ => export::fetch_image(np.artwork_url.as_deref().unwrap_or(&source)).await?,
+    };
+    let out = apply(&img, &style)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    }
+    out.save_with_format(&path, image::ImageFormat::Png)
+        .map_err(|e| format!("write variant: {e}"))?;
+    Ok(path.to_string_lossy().to_string())
+}
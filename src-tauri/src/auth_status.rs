@@ -0,0 +1,130 @@
+// What the Spotify sign-in currently amounts to, for settings to show "Connected as X, token
+// valid for 43 min" and spot a token missing scopes a newer build asks for. A stored token
+// that lacks some is reported with `scopes_missing`; `reauthorize_with_scopes` fixes it.
+
+use crate::{events, pick_image_url, spotify_scopes, watchdog, SharedStore};
+use rspotify::{
+    clients::{BaseClient, OAuthClient},
+    prelude::Id,
+    AuthCodePkceSpotify, Token,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+#[derive(Serialize, JsonSchema)]
+pub struct AuthUser {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    // "premium", "free", ...; only with the user-read-private scope
+    pub product: Option<String>,
+}
+
+#[derive(Serialize, Default, JsonSchema)]
+pub struct AuthStatus {
+    pub connected: bool,
+    // a browser or paste-the-code sign-in is waiting to finish
+    pub signing_in: bool,
+    // RFC 3339; the token is refreshed automatically once it passes
+    pub expires_at: Option<String>,
+    pub expires_in_secs: Option<i64>,
+    pub scopes: Vec<String>,
+    // asked for by this build but not granted to the token
+    pub missing_scopes: Vec<String>,
+    // None while signed out, or when Spotify couldn't be reached
+    pub user: Option<AuthUser>,
+}
+
+// Scopes this build asks for that `token` wasn't granted, sorted
+fn missing_scopes(token: &Token) -> Vec<String> {
+    let mut missing: Vec<String> = spotify_scopes()
+        .into_iter()
+        .filter(|s| !token.scopes.contains(s))
+        .collect();
+    missing.sort();
+    missing
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct ScopesMissing {
+    pub missing: Vec<String>,
+    pub granted: Vec<String>,
+}
+
+// Emits `scopes_missing` when the client's token is from before features that need more
+pub async fn check_scopes(app: &tauri::AppHandle, client: &AuthCodePkceSpotify) {
+    let token = match client.get_token().lock().await {
+        Ok(guard) => guard.clone(),
+        Err(_) => return,
+    };
+    let Some(token) = token else {
+        return;
+    };
+    let missing = missing_scopes(&token);
+    if missing.is_empty() {
+        return;
+    }
+    let mut granted: Vec<String> = token.scopes.iter().cloned().collect();
+    granted.sort();
+    eprintln!("[auth] token lacks {}", missing.join(", "));
+    events::emit(app, "scopes_missing", ScopesMissing { missing, granted });
+}
+
+#[tauri::command]
+pub async fn get_auth_status(state: State<'_, SharedStore>) -> Result<AuthStatus, String> {
+    let (client, signing_in) = {
+        let s = state.lock();
+        (
+            s.client.clone(),
+            s.auth_cancel.is_some() || s.paste_auth.is_some(),
+        )
+    };
+    let Some(client) = client else {
+        return Ok(AuthStatus {
+            signing_in,
+            ..Default::default()
+        });
+    };
+
+    let token = client
+        .get_token()
+        .lock()
+        .await
+        .map_err(|_| "Token lock failed".to_string())?
+        .clone();
+    let mut status = AuthStatus {
+        connected: true,
+        signing_in,
+        ..Default::default()
+    };
+    if let Some(token) = token {
+        status.expires_at = token.expires_at.map(|t| t.to_rfc3339());
+        status.expires_in_secs = token
+            .expires_at
+            .map(|t| (t - chrono::Utc::now()).num_seconds().max(0));
+        status.scopes = token.scopes.iter().cloned().collect();
+        status.scopes.sort();
+        status.missing_scopes = missing_scopes(&token);
+    }
+
+    match watchdog::within(
+        "current user",
+        watchdog::SPOTIFY_TIMEOUT,
+        client.current_user(),
+    )
+    .await
+    {
+        Ok(Ok(me)) => {
+            status.user = Some(AuthUser {
+                id: me.id.id().to_string(),
+                display_name: me.display_name,
+                avatar_url: me.images.as_deref().and_then(|i| pick_image_url(i, 64)),
+                product: me.product.map(|p| <&str>::from(p).to_string()),
+            })
+        }
+        Ok(Err(e)) => eprintln!("[auth] current user: {e}"),
+        Err(_) => {}
+    }
+    Ok(status)
+}
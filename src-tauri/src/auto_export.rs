@@ -0,0 +1,269 @@
+// Export profiles that keep themselves up to date instead of waiting for
+// `write_now_playing_assets`: on every new track, on any change to what's showing (paused,
+// artwork arriving, ...), or every N seconds. Each profile picks its own, so a slow network
+// share doesn't have to follow the cadence of the OBS folder. Profiles with a placeholder write
+// it when nothing is playing (see `placeholder`).
+
+use crate::{
+    export::{self, ArtworkSource, ExportPayload, ExportProfile, RenderedExport},
+    placeholder, virtual_files, NowPlaying, SharedStore,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+use tokio::sync::watch;
+
+// how often interval profiles are checked; also their finest resolution
+const TICK: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, JsonSchema)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ExportTrigger {
+    // only when `write_now_playing_assets` is called
+    #[default]
+    Manual,
+    TrackChange,
+    // anything but the position
+    StateChange,
+    Interval {
+        secs: u32,
+    },
+}
+
+// profile name -> when an interval profile was last written
+static LAST_WRITTEN: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Mutex::default);
+// `render_key` of the last update handed to the writer
+static LAST_RENDERED: Lazy<Mutex<Option<u64>>> = Lazy::new(Mutex::default);
+// profiles whose files hold their placeholder right now
+static SHOWING_PLACEHOLDER: Lazy<Mutex<HashSet<String>>> = Lazy::new(Mutex::default);
+
+// Held by the writer and by manual exports, so their files never interleave
+pub static WRITING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+fn render_key(np: &NowPlaying) -> u64 {
+    let payload = ExportPayload::from(np);
+    let mut hasher = DefaultHasher::new();
+    (export::text_files(&payload), payload.artwork_source()).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn profiles_where(
+    app: &tauri::AppHandle,
+    due: impl Fn(&ExportProfile) -> bool,
+) -> Vec<ExportProfile> {
+    app.state::<SharedStore>()
+        .lock()
+        .export_profiles
+        .iter()
+        .filter(|p| p.enabled && due(p))
+        .cloned()
+        .collect()
+}
+
+// What the writer does next: the latest state, and every profile due since its last pass
+#[derive(Default)]
+struct Job {
+    np: Option<NowPlaying>,
+    profiles: Vec<ExportProfile>,
+}
+
+// Feeds the one writer task (see `start`). Updates arriving while it's busy are merged into
+// its next pass, so a slow share or a short interval never stacks up work.
+static JOBS: Lazy<watch::Sender<Job>> = Lazy::new(|| watch::channel(Job::default()).0);
+
+// Hands `np` and `profiles` to the writer
+fn export(np: NowPlaying, profiles: Vec<ExportProfile>) {
+    JOBS.send_modify(|job| {
+        job.np = Some(np);
+        for p in profiles {
+            job.profiles.retain(|q| q.name != p.name);
+            job.profiles.push(p);
+        }
+    });
+}
+
+// The cover of the previous pass and where it came from; state changes and interval passes on
+// the same track reuse it instead of fetching and encoding it again
+type ArtworkCache = Option<(ArtworkSource, Option<Vec<u8>>)>;
+
+async fn render(
+    payload: &ExportPayload,
+    artwork: &mut ArtworkCache,
+) -> Result<RenderedExport, String> {
+    let source = payload.artwork_source();
+    let artwork_png = match artwork.take() {
+        Some((cached, png)) if cached == source => png,
+        _ => export::render_artwork_png(payload).await?,
+    };
+    *artwork = Some((source, artwork_png.clone()));
+    Ok(RenderedExport {
+        files: export::text_files(payload),
+        artwork_png,
+    })
+}
+
+// Renders once, refreshes the cover for `/artwork`, OBS and the saved cover, and writes every
+// profile in `profiles`; placeholders where they apply
+async fn run(
+    app: &tauri::AppHandle,
+    np: NowPlaying,
+    profiles: Vec<ExportProfile>,
+    artwork: &mut ArtworkCache,
+) {
+    let _writing = WRITING.lock().await;
+    let (idle, mut profiles): (Vec<_>, Vec<_>) = profiles
+        .into_iter()
+        .partition(|p| placeholder::shows(p, &np));
+    // manual ones queued for their placeholder, but the track came back before this pass;
+    // `restore` does write them
+    profiles.retain(|p| np.stale || p.trigger != ExportTrigger::Manual);
+    {
+        let mut showing = SHOWING_PLACEHOLDER.lock();
+        showing.extend(idle.iter().map(|p| p.name.clone()));
+        for p in &profiles {
+            showing.remove(&p.name);
+        }
+    }
+    for p in &idle {
+        if let Err(e) = placeholder::write(app, p).await {
+            eprintln!("[export] {} placeholder: {e}", p.name);
+        }
+    }
+    if !idle.is_empty() {
+        crate::usage::record(app, "exports");
+    }
+
+    if np.track_name.is_none() {
+        return;
+    }
+    let rendered = match render(&ExportPayload::from(&np), artwork).await {
+        Ok(r) => r,
+        Err(e) => return eprintln!("[export] render: {e}"),
+    };
+    virtual_files::set_cover(rendered.artwork_png.clone());
+    if let Some(png) = &rendered.artwork_png {
+        crate::last_played::save_artwork(app, &np, png);
+    }
+    crate::obs::update(app, &np, &rendered);
+    if profiles.is_empty() {
+        return;
+    }
+    for p in &profiles {
+        if let Err(e) = export::write_with_fallback(app, &rendered, p) {
+            eprintln!("[export] {}: {e}", p.name);
+        }
+    }
+    crate::usage::record(app, "exports");
+}
+
+// `profiles` were just exported by hand, so they show the track again
+pub fn showing_track(profiles: &[ExportProfile]) {
+    let mut showing = SHOWING_PLACEHOLDER.lock();
+    for p in profiles {
+        showing.remove(&p.name);
+    }
+}
+
+// Called with every settled update; `changed` is anything but the position
+pub fn on_update(app: &tauri::AppHandle, np: &NowPlaying, new_track: bool, changed: bool) {
+    if !(changed || new_track) {
+        return;
+    }
+    let showing = SHOWING_PLACEHOLDER.lock().clone();
+    let mut profiles = profiles_where(app, |p| {
+        // from the track to the placeholder or back
+        let flips = placeholder::shows(p, np) != showing.contains(&p.name);
+        match p.trigger {
+            ExportTrigger::TrackChange => new_track || flips,
+            ExportTrigger::StateChange => np.track_name.is_some() || flips,
+            _ => false,
+        }
+    });
+    // Manual profiles and the export without profiles only get the track on request, but
+    // switch to their placeholder when it stops, once they hold something to replace
+    if let Ok(manual) = export::manual_outputs(&app.state::<SharedStore>()) {
+        profiles.extend(manual.into_iter().filter(|p| {
+            placeholder::shows(p, np)
+                && !showing.contains(&p.name)
+                && export::long_path(Path::new(&p.dir)).is_dir()
+        }));
+    }
+    // Without a profile to write, the render is only for `/artwork`, OBS and the saved cover,
+    // which change with the text and the cover alone, not with pausing and the like
+    let key = render_key(np);
+    let same = LAST_RENDERED.lock().replace(key) == Some(key);
+    if profiles.is_empty() && same {
+        return;
+    }
+    export(np.clone(), profiles);
+}
+
+// The track restored from the last run, so the in-memory files and every export directory have
+// its cover before the first poll. Manual ones, and the export without profiles, only where
+// they were exported to before.
+pub fn restore(app: &tauri::AppHandle, np: &NowPlaying) {
+    let mut profiles = profiles_where(app, |p| p.trigger != ExportTrigger::Manual);
+    if let Ok(manual) = export::manual_outputs(&app.state::<SharedStore>()) {
+        profiles.extend(
+            manual
+                .into_iter()
+                .filter(|p| export::long_path(Path::new(&p.dir)).is_dir()),
+        );
+    }
+    export(np.clone(), profiles);
+}
+
+// Starts the writer and the interval clock
+pub fn start(app: &tauri::AppHandle) {
+    let mut jobs = JOBS.subscribe();
+    // anything handed over before now, like `restore`
+    jobs.mark_changed();
+    let writer = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut artwork = None;
+        while jobs.changed().await.is_ok() {
+            let mut job = Job::default();
+            JOBS.send_if_modified(|pending| {
+                job = std::mem::take(pending);
+                false
+            });
+            if let Some(np) = job.np {
+                run(&writer, np, job.profiles, &mut artwork).await;
+            }
+        }
+    });
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(TICK).await;
+            let np = app.state::<SharedStore>().lock().last_now_playing.clone();
+            let np = np.unwrap_or_default();
+            let now = Instant::now();
+            let profiles = {
+                let mut last = LAST_WRITTEN.lock();
+                let due = profiles_where(&app, |p| match p.trigger {
+                    // nothing to write without a track or a placeholder
+                    _ if np.track_name.is_none() && !placeholder::shows(p, &np) => false,
+                    ExportTrigger::Interval { secs } if secs > 0 => last
+                        .get(&p.name)
+                        .is_none_or(|t| now - *t >= Duration::from_secs(secs.into())),
+                    _ => false,
+                });
+                for p in &due {
+                    last.insert(p.name.clone(), now);
+                }
+                due
+            };
+            if !profiles.is_empty() {
+                export(np, profiles);
+            }
+        }
+    });
+}
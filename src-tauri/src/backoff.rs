@@ -0,0 +1,150 @@
+// Backing off the Spotify API after failed polls: errors are sorted into a few kinds, the
+// wait doubles with each failure in a row (with some jitter so several clients don't retry in
+// lockstep), and a 429's Retry-After always wins. Every change is sent as `watcher_status` so
+// the UI can say "rate limited, retrying in 30s".
+
+use crate::{events, SharedStore};
+use rspotify::{http::HttpError, ClientError};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const BASE_DELAY: Duration = Duration::from_secs(2);
+const MAX_DELAY: Duration = Duration::from_secs(300);
+// no Retry-After header: about what Spotify usually asks for
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    // 401: the token was refused even after refreshing
+    Unauthorized,
+    // 403: usually a missing scope or a Premium-only endpoint
+    Forbidden,
+    // 429
+    RateLimited,
+    // 5xx
+    Server,
+    // no response at all: offline, DNS, timeouts
+    Network,
+    Other,
+}
+
+pub fn classify(e: &ClientError) -> (ErrorKind, Option<Duration>) {
+    let ClientError::Http(http) = e else {
+        return (ErrorKind::Other, None);
+    };
+    let res = match http.as_ref() {
+        HttpError::StatusCode(res) => res,
+        HttpError::Client(_) => return (ErrorKind::Network, None),
+    };
+    match res.status().as_u16() {
+        401 => (ErrorKind::Unauthorized, None),
+        403 => (ErrorKind::Forbidden, None),
+        429 => {
+            let wait = res
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RETRY_AFTER);
+            (ErrorKind::RateLimited, Some(wait))
+        }
+        500..=599 => (ErrorKind::Server, None),
+        _ => (ErrorKind::Other, None),
+    }
+}
+
+#[derive(Default)]
+pub struct Backoff {
+    // failed polls in a row
+    failures: u32,
+    until: Option<Instant>,
+}
+
+impl Backoff {
+    // Time left before the next attempt, if we're waiting
+    pub fn remaining(&self) -> Option<Duration> {
+        self.until
+            .map(|t| t.saturating_duration_since(Instant::now()))
+            .filter(|d| !d.is_zero())
+    }
+
+    fn failed(&mut self, retry_after: Option<Duration>) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        let wait = retry_after.unwrap_or_else(|| {
+            let exp = BASE_DELAY.saturating_mul(1 << (self.failures - 1).min(16));
+            with_jitter(exp.min(MAX_DELAY))
+        });
+        self.until = Some(Instant::now() + wait);
+        wait
+    }
+
+    // Whether this ends a run of failures
+    fn succeeded(&mut self) -> bool {
+        self.until = None;
+        std::mem::take(&mut self.failures) > 0
+    }
+}
+
+// up to +25%, without pulling in an RNG for it
+fn with_jitter(d: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.subsec_nanos())
+        .unwrap_or(0);
+    d + d.mul_f64(f64::from(nanos % 1000) / 4000.0)
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct WatcherStatus {
+    // "spotify"
+    pub source: &'static str,
+    // "ok" | "backing_off"
+    pub state: &'static str,
+    pub error: Option<ErrorKind>,
+    pub message: Option<String>,
+    pub retry_in_secs: Option<u64>,
+    pub failures: u32,
+}
+
+pub fn record_failure(app: &tauri::AppHandle, state: &SharedStore, e: &ClientError) {
+    let (kind, retry_after) = classify(e);
+    let (wait, failures) = {
+        let mut s = state.lock();
+        let wait = s.spotify_backoff.failed(retry_after);
+        (wait, s.spotify_backoff.failures)
+    };
+    eprintln!("[spotify] {kind:?} ({e}), retrying in {}s", wait.as_secs());
+    events::emit(
+        app,
+        "watcher_status",
+        WatcherStatus {
+            source: "spotify",
+            state: "backing_off",
+            error: Some(kind),
+            message: Some(e.to_string()),
+            retry_in_secs: Some(wait.as_secs().max(1)),
+            failures,
+        },
+    );
+}
+
+pub fn record_success(app: &tauri::AppHandle, state: &SharedStore) {
+    if !state.lock().spotify_backoff.succeeded() {
+        return;
+    }
+    events::emit(
+        app,
+        "watcher_status",
+        WatcherStatus {
+            source: "spotify",
+            state: "ok",
+            error: None,
+            message: None,
+            retry_in_secs: None,
+            failures: 0,
+        },
+    );
+}
@@ -0,0 +1,164 @@
+// `run_benchmark`: times the local pipeline on the user's own library and machine, so a
+// "it got slow after updating" report comes with numbers that can be compared.
+
+use crate::{
+    build_local_index, export, extract_embedded_art_to_cache, find_local_art_in_base,
+    library_snapshot, SharedStore,
+};
+use serde::Serialize;
+use std::time::Instant;
+use tauri::{Manager, State};
+
+// Enough to average out disk noise without rewriting the user's whole art cache
+const EXTRACT_SAMPLES: usize = 10;
+const LOOKUP_SAMPLES: usize = 1_000;
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct Stage {
+    name: &'static str,
+    // average per item when `items` is set, otherwise the whole stage
+    ms: Option<f64>,
+    items: Option<usize>,
+    // why the stage was skipped, or what it measured
+    note: Option<String>,
+}
+
+impl Stage {
+    fn skipped(name: &'static str, why: &str) -> Self {
+        Self {
+            name,
+            ms: None,
+            items: None,
+            note: Some(why.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct BenchmarkReport {
+    app_version: String,
+    os: &'static str,
+    stages: Vec<Stage>,
+    total_ms: f64,
+}
+
+fn ms_since(t: Instant) -> f64 {
+    t.elapsed().as_secs_f64() * 1000.0
+}
+
+// Index, lookups and extraction run on a blocking thread; they're all disk-bound
+fn local_stages(app: &tauri::AppHandle, dir: Option<std::path::PathBuf>) -> Vec<Stage> {
+    const NO_DIR: &str = "no local music folder set";
+    let Some(dir) = dir else {
+        return vec![
+            Stage::skipped("index_build", NO_DIR),
+            Stage::skipped("index_lookup", NO_DIR),
+            Stage::skipped("fallback_scan", NO_DIR),
+            Stage::skipped("artwork_extraction", NO_DIR),
+        ];
+    };
+    let mut stages = Vec::new();
+
+    let t = Instant::now();
+    let index = build_local_index(&dir);
+    stages.push(Stage {
+        name: "index_build",
+        ms: Some(ms_since(t)),
+        items: Some(index.len()),
+        note: Some("index entries".into()),
+    });
+
+    let keys: Vec<&String> = index.keys().take(LOOKUP_SAMPLES).collect();
+    if keys.is_empty() {
+        stages.push(Stage::skipped("index_lookup", "index is empty"));
+    } else {
+        let t = Instant::now();
+        let hits = keys
+            .iter()
+            .filter(|k| index.contains_key(k.as_str()))
+            .count();
+        stages.push(Stage {
+            name: "index_lookup",
+            ms: Some(ms_since(t) / keys.len() as f64),
+            items: Some(hits),
+            note: None,
+        });
+    }
+
+    // a miss walks the whole folder, the worst case for tracks that aren't indexed
+    let t = Instant::now();
+    let _ = find_local_art_in_base(&dir, "", None, "\u{0}benchmark miss");
+    stages.push(Stage {
+        name: "fallback_scan",
+        ms: Some(ms_since(t)),
+        items: None,
+        note: Some("full scan for a track that isn't there".into()),
+    });
+
+    let mut files: Vec<_> = index.values().collect();
+    files.sort();
+    files.dedup();
+    let samples: Vec<_> = files.into_iter().take(EXTRACT_SAMPLES).collect();
+    if samples.is_empty() {
+        stages.push(Stage::skipped(
+            "artwork_extraction",
+            "no audio files indexed",
+        ));
+    } else {
+        let t = Instant::now();
+        let extracted = samples
+            .iter()
+            .filter(|p| extract_embedded_art_to_cache(app, p).is_some())
+            .count();
+        stages.push(Stage {
+            name: "artwork_extraction",
+            ms: Some(ms_since(t) / samples.len() as f64),
+            items: Some(extracted),
+            note: Some(format!("{} files tried", samples.len())),
+        });
+    }
+    stages
+}
+
+#[tauri::command]
+pub async fn run_benchmark(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+) -> Result<BenchmarkReport, String> {
+    let app = window.app_handle().clone();
+    let started = Instant::now();
+    let dir = library_snapshot(&app).0;
+    let last = state.lock().last_now_playing.clone();
+
+    let blocking_app = app.clone();
+    let mut stages = tauri::async_runtime::spawn_blocking(move || local_stages(&blocking_app, dir))
+        .await
+        .map_err(|e| format!("spawn_blocking join error: {e}"))?;
+
+    match last.as_ref().filter(|np| np.track_name.is_some()) {
+        Some(np) => {
+            let payload = export::ExportPayload::from(np);
+            let t = Instant::now();
+            let res = export::render_export(&payload).await;
+            stages.push(Stage {
+                name: "export_render",
+                ms: Some(ms_since(t)),
+                items: None,
+                note: Some(match res {
+                    // a remote cover means this includes the download
+                    Ok(r) if r.artwork_png.is_some() => "with artwork".into(),
+                    Ok(_) => "text only".into(),
+                    Err(e) => format!("failed: {e}"),
+                }),
+            });
+        }
+        None => stages.push(Stage::skipped("export_render", "nothing playing")),
+    }
+
+    Ok(BenchmarkReport {
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS,
+        stages,
+        total_ms: ms_since(started),
+    })
+}
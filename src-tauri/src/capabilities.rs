@@ -0,0 +1,142 @@
+// What this build and this run can do, so frontends and external tools can feature-detect
+// instead of invoking commands that may not exist here (GSMTC off Windows, GPU without the
+// `gpu` feature, everything with a port in safe mode). Also served as `GET /capabilities`. The
+// API version is the schema's `VERSION`: bumped whenever a command or payload field is renamed
+// or removed.
+
+use crate::{providers::Provider, schema, SharedStore};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::Manager;
+
+#[derive(Serialize, Clone, Copy, JsonSchema)]
+pub struct Availability {
+    // built into this binary (platform, cargo features)
+    pub compiled: bool,
+    // turned on and running in this session
+    pub enabled: bool,
+}
+
+impl Availability {
+    fn new(compiled: bool, enabled: bool) -> Self {
+        Self {
+            compiled,
+            enabled: compiled && enabled,
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ApiVersion {
+    // the `/schema` version
+    pub api: u32,
+    pub app: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct Capabilities {
+    pub api_version: u32,
+    // "lyrics", "history", "http_server", "gsmtc", "gpu", ... -> availability
+    pub subsystems: BTreeMap<String, Availability>,
+    // every source; enabled when it's in the provider chain
+    pub sources: BTreeMap<Provider, Availability>,
+    // integrations with ports are skipped this run (see `safe_mode`)
+    pub safe_mode: bool,
+}
+
+const SOURCES: &[Provider] = &[
+    Provider::Spotify,
+    Provider::Gsmtc,
+    Provider::WebNowPlaying,
+    Provider::Librespot,
+    Provider::Icecast,
+    Provider::Traktor,
+    Provider::DjHistory,
+    Provider::Osc,
+    Provider::Manual,
+    Provider::Tidal,
+    Provider::Deezer,
+    Provider::AppleMusic,
+];
+
+fn source_compiled(p: Provider) -> bool {
+    match p {
+        Provider::Gsmtc | Provider::Tidal | Provider::Deezer | Provider::AppleMusic => {
+            cfg!(windows)
+        }
+        Provider::Spotify
+        | Provider::WebNowPlaying
+        | Provider::Librespot
+        | Provider::Icecast
+        | Provider::Traktor
+        | Provider::DjHistory
+        | Provider::Osc
+        | Provider::Manual => true,
+    }
+}
+
+pub fn capabilities(app: &tauri::AppHandle) -> Capabilities {
+    let history = crate::history::is_open(app);
+    let updater = crate::updater::has_pubkey(app);
+    let state = app.state::<SharedStore>();
+    let s = state.lock();
+    let safe_mode = s.safe_mode.is_some();
+    let chain = &s.provider_chain.providers;
+
+    let subsystems = [
+        // not part of this version; listed so tools can tell "absent" from "unknown"
+        ("lyrics", Availability::new(false, false)),
+        ("history", Availability::new(true, history)),
+        ("http_server", Availability::new(true, !safe_mode)),
+        (
+            "webnowplaying",
+            Availability::new(true, !safe_mode && s.wnp.enabled()),
+        ),
+        (
+            "gsmtc",
+            Availability::new(cfg!(windows), chain.iter().any(|p| p.is_gsmtc())),
+        ),
+        (
+            "gpu",
+            Availability::new(cfg!(feature = "gpu"), s.gpu_images),
+        ),
+        ("updater", Availability::new(updater, true)),
+        ("trivia", Availability::new(true, s.trivia.enabled)),
+        (
+            "catalog_search",
+            Availability::new(true, s.catalog_search.enabled),
+        ),
+        ("tts", Availability::new(true, s.tts.enabled)),
+        ("companion", Availability::new(true, s.companion.enabled)),
+        ("obs", Availability::new(true, s.obs.running())),
+        ("discord", Availability::new(true, s.discord.running())),
+    ];
+    let sources = SOURCES
+        .iter()
+        .map(|&p| (p, Availability::new(source_compiled(p), chain.contains(&p))))
+        .collect();
+
+    Capabilities {
+        api_version: schema::VERSION,
+        subsystems: subsystems
+            .into_iter()
+            .map(|(name, a)| (name.to_string(), a))
+            .collect(),
+        sources,
+        safe_mode,
+    }
+}
+
+#[tauri::command]
+pub fn get_api_version() -> ApiVersion {
+    ApiVersion {
+        api: schema::VERSION,
+        app: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+#[tauri::command]
+pub fn get_capabilities(window: tauri::Window) -> Capabilities {
+    capabilities(window.app_handle())
+}
@@ -0,0 +1,373 @@
+// Chart-ready aggregates over the local history (see `history`), so a stats page gets series
+// it can plot as-is instead of pulling every play into JS. Ranges are local calendar days,
+// both ends included; hours and periods are local time too.
+
+use crate::{history, spotify_client, watchdog, SharedStore};
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, TimeZone, Timelike, Utc};
+use rspotify::{
+    clients::BaseClient,
+    model::{SearchResult, SearchType},
+};
+use rusqlite::{params, Connection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tauri::Manager;
+
+// artists looked up per `get_top_genres` call; the rest are counted as unclassified until a
+// later call gets to them
+const GENRE_LOOKUPS: usize = 25;
+// periods in one timeline, e.g. ten years of days
+const MAX_PERIODS: usize = 4000;
+
+// `played_at` bounds for a local date range; `to` is included, so the bound is the next midnight
+fn bounds(from: Option<NaiveDate>, to: Option<NaiveDate>) -> (String, String) {
+    let midnight = |d: NaiveDate| {
+        Local
+            .from_local_datetime(&d.and_time(Default::default()))
+            .earliest()
+            .map(|t| history::timestamp(t.with_timezone(&Utc)))
+    };
+    let from = from.and_then(midnight).unwrap_or_default();
+    let to = to
+        .and_then(|d| d.checked_add_days(Days::new(1)))
+        .and_then(midnight)
+        .unwrap_or_else(|| "9999".into());
+    (from, to)
+}
+
+fn local(played_at: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(played_at)
+        .ok()
+        .map(|t| t.with_timezone(&Local))
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct HourBucket {
+    // 0-23
+    pub hour: u32,
+    pub plays: u64,
+    pub ms_played: u64,
+}
+
+// All 24 hours, empty ones included
+#[tauri::command]
+pub async fn get_listening_by_hour(
+    window: tauri::Window,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<HourBucket>, String> {
+    let (from, to) = bounds(from, to);
+    history::with_db_blocking(window.app_handle(), move |conn| {
+        let mut hours: Vec<HourBucket> = (0..24)
+            .map(|hour| HourBucket {
+                hour,
+                plays: 0,
+                ms_played: 0,
+            })
+            .collect();
+        let mut q = conn
+            .prepare(
+                "SELECT played_at, ms_played FROM plays WHERE played_at >= ?1 AND played_at < ?2",
+            )
+            .map_err(|e| format!("read history: {e}"))?;
+        let rows = q
+            .query_map(params![from, to], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
+            })
+            .map_err(|e| format!("read history: {e}"))?;
+        for (played_at, ms) in rows.flatten() {
+            let Some(t) = local(&played_at) else {
+                continue;
+            };
+            let bucket = &mut hours[t.hour() as usize];
+            bucket.plays += 1;
+            bucket.ms_played += ms.max(0) as u64;
+        }
+        Ok(hours)
+    })
+    .await
+}
+
+// Artists by plays in the range, most played first
+fn top_artists(
+    conn: &Connection,
+    from: &str,
+    to: &str,
+    limit: Option<usize>,
+) -> Result<Vec<(String, u64)>, String> {
+    let mut q = conn
+        .prepare(
+            "SELECT artist, COUNT(*) AS n FROM plays
+             WHERE played_at >= ?1 AND played_at < ?2
+             GROUP BY artist COLLATE NOCASE ORDER BY n DESC LIMIT ?3",
+        )
+        .map_err(|e| format!("read history: {e}"))?;
+    let rows = q
+        .query_map(params![from, to, limit.map_or(-1, |n| n as i64)], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)? as u64))
+        })
+        .map_err(|e| format!("read history: {e}"))?;
+    Ok(rows.flatten().collect())
+}
+
+fn cached_genres(conn: &Connection) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut q = conn
+        .prepare("SELECT artist, genres FROM artist_genres")
+        .map_err(|e| format!("read genres: {e}"))?;
+    let rows = q
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))
+        .map_err(|e| format!("read genres: {e}"))?;
+    Ok(rows
+        .flatten()
+        .map(|(artist, genres)| {
+            (
+                artist.to_lowercase(),
+                serde_json::from_str(&genres).unwrap_or_default(),
+            )
+        })
+        .collect())
+}
+
+// Genres of the Spotify artist by that name; empty when the search finds someone else
+async fn lookup_genres(app: &tauri::AppHandle, artist: &str) -> Result<Vec<String>, String> {
+    let client = spotify_client(&app.state::<SharedStore>())?;
+    let q = format!("artist:\"{artist}\"");
+    let res = watchdog::within(
+        "artist search",
+        watchdog::SPOTIFY_TIMEOUT,
+        client.search(&q, SearchType::Artist, None, None, Some(1), None),
+    )
+    .await?
+    .map_err(|e| format!("spotify search: {e}"))?;
+    let SearchResult::Artists(page) = res else {
+        return Ok(Vec::new());
+    };
+    Ok(page
+        .items
+        .into_iter()
+        .find(|a| a.name.eq_ignore_ascii_case(artist))
+        .map(|a| a.genres)
+        .unwrap_or_default())
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GenreCount {
+    pub genre: String,
+    pub plays: u64,
+    // of the classified plays, 0-1
+    pub share: f64,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct TopGenres {
+    pub genres: Vec<GenreCount>,
+    // plays by artists without known genres: not looked up yet, not on Spotify, or untagged
+    pub unclassified_plays: u64,
+}
+
+// Genres come from Spotify's artist pages and are cached; a play counts once towards each of
+// its artist's genres. Artists are looked up while connected to Spotify, a few per call.
+#[tauri::command]
+pub async fn get_top_genres(
+    window: tauri::Window,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: Option<usize>,
+) -> Result<TopGenres, String> {
+    let app = window.app_handle().clone();
+    let (from, to) = bounds(from, to);
+    let (artists, mut known) = history::with_db_blocking(&app, move |conn| {
+        Ok((top_artists(conn, &from, &to, None)?, cached_genres(conn)?))
+    })
+    .await?;
+
+    let missing: Vec<String> = artists
+        .iter()
+        .map(|(a, _)| a)
+        .filter(|a| !known.contains_key(&a.to_lowercase()))
+        .take(GENRE_LOOKUPS)
+        .cloned()
+        .collect();
+    if !missing.is_empty() && app.state::<SharedStore>().lock().client.is_some() {
+        let mut found = Vec::new();
+        for artist in missing {
+            match lookup_genres(&app, &artist).await {
+                Ok(genres) => found.push((artist, genres)),
+                Err(e) => {
+                    eprintln!("[charts] {e}");
+                    break;
+                }
+            }
+        }
+        history::with_db(&app, |conn| {
+            for (artist, genres) in &found {
+                conn.execute(
+                    "INSERT OR REPLACE INTO artist_genres (artist, genres) VALUES (?1, ?2)",
+                    params![artist, serde_json::json!(genres).to_string()],
+                )
+                .map_err(|e| format!("save genres: {e}"))?;
+            }
+            Ok(())
+        })?;
+        known.extend(found.into_iter().map(|(a, g)| (a.to_lowercase(), g)));
+    }
+
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut classified = 0;
+    let mut unclassified_plays = 0;
+    for (artist, plays) in &artists {
+        match known.get(&artist.to_lowercase()).filter(|g| !g.is_empty()) {
+            Some(genres) => {
+                classified += plays;
+                for g in genres {
+                    *counts.entry(g.clone()).or_default() += plays;
+                }
+            }
+            None => unclassified_plays += plays,
+        }
+    }
+    let mut genres: Vec<GenreCount> = counts
+        .into_iter()
+        .map(|(genre, plays)| GenreCount {
+            share: plays as f64 / classified.max(1) as f64,
+            genre,
+            plays,
+        })
+        .collect();
+    genres.sort_by(|a, b| b.plays.cmp(&a.plays).then_with(|| a.genre.cmp(&b.genre)));
+    genres.truncate(limit.unwrap_or(10));
+    Ok(TopGenres {
+        genres,
+        unclassified_plays,
+    })
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    // starting Monday
+    Week,
+    #[default]
+    Month,
+    Year,
+}
+
+impl Period {
+    fn start(self, d: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => d,
+            Period::Week => d - Days::new(d.weekday().num_days_from_monday().into()),
+            Period::Month => d.with_day(1).unwrap_or(d),
+            Period::Year => d.with_ordinal(1).unwrap_or(d),
+        }
+    }
+
+    fn next(self, d: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Period::Day => d.checked_add_days(Days::new(1)),
+            Period::Week => d.checked_add_days(Days::new(7)),
+            Period::Month => d.checked_add_months(Months::new(1)),
+            Period::Year => d.checked_add_months(Months::new(12)),
+        }
+    }
+
+    fn label(self, d: NaiveDate) -> String {
+        match self {
+            Period::Day | Period::Week => d.format("%Y-%m-%d").to_string(),
+            Period::Month => d.format("%Y-%m").to_string(),
+            Period::Year => d.format("%Y").to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ArtistSeries {
+    pub artist: String,
+    // plays per period, lined up with `periods`
+    pub plays: Vec<u64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ArtistTimeline {
+    // every period from the first play to the last, empty ones included: "2024-03-11" (day,
+    // or the Monday of a week), "2024-03" (month), "2024" (year)
+    pub periods: Vec<String>,
+    pub series: Vec<ArtistSeries>,
+}
+
+// Plays per period for `artists`, or for the `limit` most played artists in the range
+#[tauri::command]
+pub async fn get_artist_timeline(
+    window: tauri::Window,
+    artists: Option<Vec<String>>,
+    period: Option<Period>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: Option<usize>,
+) -> Result<ArtistTimeline, String> {
+    let period = period.unwrap_or_default();
+    let (from, to) = bounds(from, to);
+    // artist -> period start -> plays
+    let per_artist: Vec<(String, BTreeMap<NaiveDate, u64>)> =
+        history::with_db_blocking(window.app_handle(), move |conn| {
+            let artists = match artists {
+                Some(a) => a,
+                None => top_artists(conn, &from, &to, Some(limit.unwrap_or(5)))?
+                    .into_iter()
+                    .map(|(a, _)| a)
+                    .collect(),
+            };
+            let mut q = conn
+                .prepare(
+                    "SELECT played_at FROM plays
+                     WHERE artist = ?1 COLLATE NOCASE AND played_at >= ?2 AND played_at < ?3",
+                )
+                .map_err(|e| format!("read history: {e}"))?;
+            artists
+                .into_iter()
+                .map(|artist| {
+                    let mut buckets = BTreeMap::new();
+                    let rows = q
+                        .query_map(params![artist, from, to], |r| r.get::<_, String>(0))
+                        .map_err(|e| format!("read history: {e}"))?;
+                    for played_at in rows.flatten() {
+                        if let Some(t) = local(&played_at) {
+                            *buckets.entry(period.start(t.date_naive())).or_default() += 1;
+                        }
+                    }
+                    Ok((artist, buckets))
+                })
+                .collect()
+        })
+        .await?;
+
+    let first = per_artist.iter().filter_map(|(_, b)| b.keys().next()).min();
+    let last = per_artist.iter().filter_map(|(_, b)| b.keys().last()).max();
+    let mut starts = Vec::new();
+    if let (Some(&first), Some(&last)) = (first, last) {
+        let mut d = Some(first);
+        while let Some(day) = d.filter(|d| *d <= last) {
+            if starts.len() >= MAX_PERIODS {
+                return Err("Too many periods; pick a longer period or a shorter range".into());
+            }
+            starts.push(day);
+            d = period.next(day);
+        }
+    }
+
+    Ok(ArtistTimeline {
+        periods: starts.iter().map(|d| period.label(*d)).collect(),
+        series: per_artist
+            .into_iter()
+            .map(|(artist, buckets)| ArtistSeries {
+                plays: starts
+                    .iter()
+                    .map(|d| buckets.get(d).copied().unwrap_or(0))
+                    .collect(),
+                artist,
+            })
+            .collect(),
+    })
+}
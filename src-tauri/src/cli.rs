@@ -0,0 +1,223 @@
+// One-shot use from shell scripts and schedulers, instead of opening the window:
+//
+//   now-playing --print-now-playing [--format text|json] [--port N] [--client-id ID]
+//   now-playing --export-once [--port N]
+//
+// Both ask the running app through its HTTP server (see `server`). When it isn't running,
+// --print-now-playing polls Spotify once by itself, with the token the app saved and the client
+// ID from --client-id or SPOTIFY_CLIENT_ID; --export-once needs the app, which knows the export
+// profiles. Exit codes: 0 done, 1 nothing playing, 2 failed.
+
+use crate::{
+    build_now_playing_from_ctx, playback, server, spotify_scopes, token_store, NowPlaying,
+};
+use rspotify::{AuthCodePkceSpotify, Config, Credentials, OAuth};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Action {
+    Print,
+    Export,
+}
+
+struct Args {
+    action: Action,
+    json: bool,
+    port: u16,
+    client_id: Option<String>,
+}
+
+fn parse(args: &[String]) -> Result<Option<Args>, String> {
+    let mut action = None;
+    let mut parsed = Args {
+        action: Action::Print,
+        json: false,
+        port: server::DEFAULT_PORT,
+        client_id: None,
+    };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--print-now-playing" => action = Some(Action::Print),
+            "--export-once" => action = Some(Action::Export),
+            "--format" => {
+                parsed.json = match value()?.as_str() {
+                    "json" => true,
+                    "text" => false,
+                    other => return Err(format!("unknown format {other}; use text or json")),
+                }
+            }
+            "--port" => {
+                parsed.port = value()?
+                    .parse()
+                    .map_err(|_| "--port needs a port number".to_string())?
+            }
+            "--client-id" => parsed.client_id = Some(value()?.clone()),
+            // anything else is for the app (--safe-mode, ...)
+            _ => {}
+        }
+    }
+    Ok(action.map(|action| Args { action, ..parsed }))
+}
+
+// Windows GUI builds start without a console; print into the one the command came from
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// Status code and body, or None when nothing listens on `port`
+fn request(port: u16, method: &str, path: &str) -> Result<Option<(u16, Vec<u8>)>, String> {
+    let mut stream = match TcpStream::connect_timeout(&([127, 0, 0, 1], port).into(), TIMEOUT) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => return Ok(None),
+        Err(e) => return Err(format!("connect to 127.0.0.1:{port}: {e}")),
+    };
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: 127.0.0.1\r\n{}: 1\r\nContent-Length: 0\r\n\
+         Connection: close\r\n\r\n",
+        server::ACTION_HEADER
+    )
+    .map_err(|e| format!("send request: {e}"))?;
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .map_err(|e| format!("read response: {e}"))?;
+
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut parsed = httparse::Response::new(&mut headers);
+    match parsed.parse(&response) {
+        Ok(httparse::Status::Complete(len)) => {
+            Ok(Some((parsed.code.unwrap_or(0), response[len..].to_vec())))
+        }
+        _ => Err("malformed response from the app".into()),
+    }
+}
+
+// Polls Spotify once without the app
+fn poll_standalone(client_id: Option<String>) -> Result<NowPlaying, String> {
+    let client_id = client_id
+        .or_else(|| std::env::var("SPOTIFY_CLIENT_ID").ok())
+        .filter(|id| !id.trim().is_empty())
+        .ok_or("The app isn't running; pass --client-id or set SPOTIFY_CLIENT_ID to ask Spotify")?;
+    let token = token_store::load_saved()?.ok_or("Not signed in; sign in from the app first")?;
+    let client = AuthCodePkceSpotify::from_token_with_config(
+        token,
+        Credentials::new(client_id.trim(), ""),
+        OAuth {
+            scopes: spotify_scopes(),
+            ..Default::default()
+        },
+        Config {
+            token_cached: false,
+            token_refreshing: true,
+            ..Default::default()
+        },
+    );
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("runtime: {e}"))?;
+    runtime.block_on(async {
+        let current = playback::current(&client)
+            .await
+            .map_err(|e| format!("Spotify: {e}"))?;
+        // a refresh along the way has to be kept, like the app does
+        token_store::persist(&client).await;
+        Ok(current
+            .map(|p| {
+                let mut np = build_now_playing_from_ctx(&p.ctx);
+                p.apply_modes(&mut np);
+                np
+            })
+            .unwrap_or_default())
+    })
+}
+
+fn now_playing(args: &Args) -> Result<NowPlaying, String> {
+    match request(args.port, "GET", "/nowplaying")? {
+        Some((200, body)) => {
+            serde_json::from_slice(&body).map_err(|e| format!("parse now playing: {e}"))
+        }
+        Some((code, body)) => Err(format!("{code}: {}", String::from_utf8_lossy(&body))),
+        None => poll_standalone(args.client_id.clone()),
+    }
+}
+
+fn print_now_playing(args: &Args) -> Result<i32, String> {
+    let np = now_playing(args)?;
+    if args.json {
+        let json = serde_json::to_string_pretty(&np).map_err(|e| e.to_string())?;
+        println!("{json}");
+        return Ok(if np.track_name.is_some() { 0 } else { 1 });
+    }
+    match &np.track_name {
+        Some(track) if np.artists.is_empty() => println!("{track}"),
+        Some(track) => println!("{} - {track}", np.artists.join(", ")),
+        None => {
+            eprintln!("Nothing playing");
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+fn export_once(args: &Args) -> Result<i32, String> {
+    match request(args.port, "POST", "/export")? {
+        Some((200, body)) => {
+            let dir = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v.get("dir")?.as_str().map(str::to_string))
+                .unwrap_or_default();
+            if args.json {
+                println!("{}", serde_json::json!({ "dir": dir }));
+            } else {
+                println!("{dir}");
+            }
+            Ok(0)
+        }
+        Some((409, _)) => {
+            eprintln!("Nothing playing");
+            Ok(1)
+        }
+        Some((code, body)) => Err(format!("{code}: {}", String::from_utf8_lossy(&body))),
+        None => Err(format!(
+            "The app isn't running (nothing on 127.0.0.1:{}); start it to export",
+            args.port
+        )),
+    }
+}
+
+// Handles the command line when it asks for one of the above; returns the exit code then
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let parsed = parse(&args);
+    if matches!(parsed, Ok(None)) {
+        return None;
+    }
+    attach_console();
+    let result = parsed.and_then(|args| {
+        let args = args.expect("checked above");
+        match args.action {
+            Action::Print => print_now_playing(&args),
+            Action::Export => export_once(&args),
+        }
+    });
+    let code = result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        2
+    });
+    let _ = std::io::stdout().flush();
+    Some(code)
+}
@@ -0,0 +1,109 @@
+// Session recap: every distinct cover seen this run, tiled into one image that is written to
+// the export directories on exit (or on demand), ready to share after a stream.
+
+use crate::{export, NowPlaying, SharedStore};
+use image::{imageops, RgbaImage};
+use std::{collections::HashSet, path::PathBuf};
+use tauri::{Manager, State};
+
+const TILE_PX: u32 = 200;
+// a 12x12 grid; also caps what's held in memory
+const MAX_TILES: usize = 144;
+
+#[derive(Default)]
+pub struct SessionCovers {
+    // artwork path or URL of every cover taken so far
+    seen: HashSet<String>,
+    tiles: Vec<RgbaImage>,
+}
+
+// Called on every track change. Covers are scaled down as they come in, so writing the
+// collage at exit doesn't have to fetch anything.
+pub fn record(app: &tauri::AppHandle, np: &NowPlaying) {
+    let Some(key) = np.artwork_path.clone().or_else(|| np.artwork_url.clone()) else {
+        return;
+    };
+    {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        let covers = &mut s.session_covers;
+        if covers.seen.len() >= MAX_TILES || !covers.seen.insert(key) {
+            return;
+        }
+    }
+
+    let (path, url) = (np.artwork_path.clone(), np.artwork_url.clone());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let img = match (path, url) {
+            (Some(p), _) => image::open(&p).map_err(|e| format!("open {p}: {e}")),
+            (None, Some(u)) => export::fetch_image(&u).await,
+            (None, None) => return,
+        };
+        match img {
+            Ok(img) => {
+                let tile = img
+                    .resize_to_fill(TILE_PX, TILE_PX, imageops::FilterType::Triangle)
+                    .to_rgba8();
+                app.state::<SharedStore>()
+                    .lock()
+                    .session_covers
+                    .tiles
+                    .push(tile);
+            }
+            Err(e) => eprintln!("[collage] {e}"),
+        }
+    });
+}
+
+// Near-square grid, filled row by row; the last row may be short
+fn render(tiles: &[RgbaImage]) -> RgbaImage {
+    let cols = (tiles.len() as f64).sqrt().ceil() as u32;
+    let rows = (tiles.len() as u32).div_ceil(cols);
+    let mut out =
+        RgbaImage::from_pixel(cols * TILE_PX, rows * TILE_PX, image::Rgba([0, 0, 0, 255]));
+    for (i, tile) in tiles.iter().enumerate() {
+        let (x, y) = (i as u32 % cols, i as u32 / cols);
+        imageops::replace(
+            &mut out,
+            tile,
+            i64::from(x * TILE_PX),
+            i64::from(y * TILE_PX),
+        );
+    }
+    out
+}
+
+// Writes `session-<date>-<time>.png` to every export directory. `None` when no cover was seen.
+fn write(app: &tauri::AppHandle, tiles: &[RgbaImage]) -> Result<Option<PathBuf>, String> {
+    if tiles.is_empty() {
+        return Ok(None);
+    }
+    let img = render(tiles);
+    let name = format!("session-{}.png", chrono::Local::now().format("%Y%m%d-%H%M"));
+    let dirs = export::output_dirs(&app.state::<SharedStore>())?;
+    for dir in &dirs {
+        std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+        img.save_with_format(dir.join(&name), image::ImageFormat::Png)
+            .map_err(|e| format!("write collage: {e}"))?;
+    }
+    Ok(dirs.first().map(|d| d.join(&name)))
+}
+
+// Exit path: writes whatever was collected and forgets it, so a second exit hook is a no-op
+pub fn finish(app: &tauri::AppHandle) {
+    let tiles = std::mem::take(&mut app.state::<SharedStore>().lock().session_covers.tiles);
+    if let Err(e) = write(app, &tiles) {
+        eprintln!("[collage] {e}");
+    }
+}
+
+// Writes the collage so far without ending the session. Returns the file written, if any.
+#[tauri::command]
+pub fn export_session_collage(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+) -> Result<Option<String>, String> {
+    let tiles = state.lock().session_covers.tiles.clone();
+    Ok(write(window.app_handle(), &tiles)?.map(|p| p.to_string_lossy().to_string()))
+}
@@ -0,0 +1,99 @@
+// Companion fields sent along with the track: local time/date in the user's format and how
+// long this session has been running, so one browser source can draw a whole info bar.
+// They ride on every `now_playing_update`; the watcher's poll keeps the clock current.
+
+use crate::{read_settings, write_setting, NowPlaying, SharedStore};
+use chrono::format::StrftimeItems;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct CompanionConfig {
+    pub enabled: bool,
+    // strftime patterns, e.g. "%H:%M" or "%-I:%M %p"
+    pub time_format: String,
+    pub date_format: String,
+}
+
+impl Default for CompanionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time_format: "%H:%M".into(),
+            date_format: "%A %-d %B".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[serde(default)]
+pub struct Companion {
+    pub local_time: String,
+    pub local_date: String,
+    // "+02:00"
+    pub utc_offset: String,
+    pub session_uptime_secs: u64,
+    // "1:05:09", or "5:09" under an hour
+    pub session_uptime: String,
+}
+
+pub fn load_config(app: &tauri::AppHandle) -> CompanionConfig {
+    read_settings(app)
+        .get("companion")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn clock(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+pub fn apply(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    let state = app.state::<SharedStore>();
+    let (config, uptime) = {
+        let s = state.lock();
+        (s.companion.clone(), s.usage.session_uptime())
+    };
+    if !config.enabled {
+        np.companion = None;
+        return;
+    }
+    let now = chrono::Local::now();
+    let secs = uptime.as_secs();
+    np.companion = Some(Companion {
+        local_time: now.format(&config.time_format).to_string(),
+        local_date: now.format(&config.date_format).to_string(),
+        utc_offset: now.format("%:z").to_string(),
+        session_uptime_secs: secs,
+        session_uptime: clock(secs),
+    });
+}
+
+#[tauri::command]
+pub fn get_companion_config(state: State<'_, SharedStore>) -> CompanionConfig {
+    state.lock().companion.clone()
+}
+
+#[tauri::command]
+pub fn set_companion_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: CompanionConfig,
+) -> Result<(), String> {
+    // chrono panics while formatting a bad pattern, so reject it here
+    for (what, fmt) in [("time", &config.time_format), ("date", &config.date_format)] {
+        StrftimeItems::new(fmt)
+            .parse()
+            .map_err(|_| format!("Invalid {what} format: {fmt}"))?;
+    }
+    write_setting(window.app_handle(), "companion", serde_json::json!(config))?;
+    state.lock().companion = config;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+// Compilations: albums credited to "Various Artists". That name says nothing about the track,
+// so it is kept out of the displayed artists and out of anything matched by artist (the local
+// library index, artwork lookups); `NowPlaying::compilation` tells overlays what it was.
+
+use crate::NowPlaying;
+
+// normalized like the library index keys
+const VARIOUS: &[&str] = &[
+    "variousartists",
+    "variousartist",
+    "various",
+    "va",
+    "verschiedeneinterpreten",
+    "artistesdivers",
+    "artistasvarios",
+    "variosartistas",
+    "artistivari",
+    "diverseartiesten",
+];
+
+pub fn is_various(name: &str) -> bool {
+    let norm: String = name
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    VARIOUS.contains(&norm.as_str())
+}
+
+// The track artists, when a source credited the album instead; flags the compilation
+pub fn apply(np: &mut NowPlaying) {
+    if np.artists.iter().any(|a| is_various(a)) {
+        np.compilation = true;
+        if np.artists.iter().any(|a| !is_various(a)) {
+            np.artists.retain(|a| !is_various(a));
+        }
+    }
+}
+
+// First artist that names someone, for keys and searches
+pub fn first_real_artist(artists: &[String]) -> Option<&String> {
+    artists.iter().find(|a| !is_various(a))
+}
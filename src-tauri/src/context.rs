@@ -0,0 +1,144 @@
+// What the Spotify track is playing from (playlist, album, artist, show), resolved to a name
+// and cover image for overlays that show "from <cover> Chill Mix".
+
+use crate::{pick_image_url, NowPlaying, SharedCaches};
+use rspotify::{
+    clients::BaseClient,
+    model::{AlbumId, ArtistId, CurrentlyPlayingContext, PlaylistId, ShowId, Type},
+    prelude::Id,
+    AuthCodePkceSpotify,
+};
+use serde::Serialize;
+use tauri::Manager;
+
+#[derive(Serialize, Clone)]
+pub struct ContextInfo {
+    // "playlist" | "radio" | "album" | "artist" | "show" | "collection"
+    pub kind: String,
+    pub name: Option<String>,
+    // playlist owner, album artists or show publisher
+    pub owner: Option<String>,
+    pub artwork_url: Option<String>,
+}
+
+async fn resolve(
+    client: &AuthCodePkceSpotify,
+    kind: &Type,
+    uri: &str,
+) -> Result<ContextInfo, String> {
+    let info = |kind: &str, name: String, owner: Option<String>, art: Option<String>| ContextInfo {
+        kind: kind.into(),
+        name: Some(name),
+        owner,
+        artwork_url: art,
+    };
+    let id_err = |e| format!("context uri {uri}: {e}");
+
+    Ok(match kind {
+        Type::Playlist => {
+            let id = PlaylistId::from_uri(uri).map_err(id_err)?;
+            let p = client
+                .playlist(id, None, None)
+                .await
+                .map_err(|e| format!("playlist: {e}"))?;
+            // artist/track radio is a playlist Spotify generates, named "<Artist> Radio"
+            let kind = if p.owner.id.id() == "spotify" && p.name.ends_with(" Radio") {
+                "radio"
+            } else {
+                "playlist"
+            };
+            let owner = p
+                .owner
+                .display_name
+                .or_else(|| Some(p.owner.id.id().into()));
+            info(kind, p.name, owner, pick_image_url(&p.images, 300))
+        }
+        Type::Album => {
+            let id = AlbumId::from_uri(uri).map_err(id_err)?;
+            let a = client
+                .album(id, None)
+                .await
+                .map_err(|e| format!("album: {e}"))?;
+            let artists: Vec<String> = a.artists.into_iter().map(|a| a.name).collect();
+            let owner = (!artists.is_empty()).then(|| artists.join(", "));
+            info("album", a.name, owner, pick_image_url(&a.images, 300))
+        }
+        Type::Artist => {
+            let id = ArtistId::from_uri(uri).map_err(id_err)?;
+            let a = client
+                .artist(id)
+                .await
+                .map_err(|e| format!("artist: {e}"))?;
+            info("artist", a.name, None, pick_image_url(&a.images, 300))
+        }
+        Type::Show => {
+            let id = ShowId::from_uri(uri).map_err(id_err)?;
+            let s = client
+                .get_a_show(id, None)
+                .await
+                .map_err(|e| format!("show: {e}"))?;
+            info(
+                "show",
+                s.name,
+                Some(s.publisher),
+                pick_image_url(&s.images, 300),
+            )
+        }
+        // Liked Songs has no endpoint of its own
+        Type::Collection => info("collection", "Liked Songs".into(), None, None),
+        other => return Err(format!("unsupported context type {other:?}")),
+    })
+}
+
+// "Playlist: Focus Beats", "Album: Discovery", ...
+fn label(info: &ContextInfo) -> Option<String> {
+    let name = info.name.as_deref()?;
+    let kind = match info.kind.as_str() {
+        "playlist" => "Playlist",
+        "album" => "Album",
+        "artist" => "Artist",
+        "show" => "Podcast",
+        // "Liked Songs" and "Daft Punk Radio" say it all
+        _ => return Some(name.to_string()),
+    };
+    Some(format!("{kind}: {name}"))
+}
+
+// Fills the context fields on `np`. Lookups are cached per context URI, failures included
+// (Spotify-generated mixes often 404 for third-party apps), so this costs one request per
+// context rather than per poll.
+pub async fn enrich(
+    app: &tauri::AppHandle,
+    client: &AuthCodePkceSpotify,
+    ctx: &CurrentlyPlayingContext,
+    np: &mut NowPlaying,
+) {
+    let Some(c) = &ctx.context else {
+        return;
+    };
+
+    let caches = app.state::<SharedCaches>();
+    let cached = caches.lock().context.get(&c.uri).cloned();
+    let info = match cached {
+        Some(info) => info,
+        None => {
+            let info = match resolve(client, &c._type, &c.uri).await {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    eprintln!("[context] {e}");
+                    None
+                }
+            };
+            caches.lock().context.insert(c.uri.clone(), info.clone());
+            info
+        }
+    };
+
+    if let Some(info) = info {
+        np.context_label = label(&info);
+        np.context_type = Some(info.kind);
+        np.context_name = info.name;
+        np.context_owner = info.owner;
+        np.context_artwork_url = info.artwork_url;
+    }
+}
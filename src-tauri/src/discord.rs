@@ -0,0 +1,426 @@
+// Discord Rich Presence: shows the current track as "Listening to ..." on the user's profile
+// through the local Discord client's IPC socket. Discord's own Spotify integration only covers
+// the Spotify app; this works for every source, GSMTC players and local files included.
+//
+// Discord names the activity after the application whose ID is configured (made at
+// discord.com/developers); `status_display` can put the song or artist there instead.
+
+use crate::{
+    export, read_settings, resilience::Retry, watchdog, write_setting, NowPlaying, SharedStore,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{Manager, State};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+// a connection that lasted this long counts as working; the next drop starts the retry
+// policy over
+const HEALTHY_AFTER: Duration = Duration::from_secs(30);
+// Discord's limit for every text field
+const MAX_TEXT: usize = 128;
+// position jitter between polls that isn't a seek
+const SEEK_TOLERANCE_MS: i64 = 2_000;
+// Discord's frames are small JSON; anything bigger is not Discord on the other end
+const MAX_FRAME: u32 = 64 * 1024;
+// a socket that accepts but never answers would hold the session forever
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusDisplay {
+    // "Listening to <application name>"
+    #[default]
+    Name,
+    // "Listening to <state line>"
+    State,
+    // "Listening to <details line>"
+    Details,
+}
+
+impl StatusDisplay {
+    fn code(self) -> u8 {
+        match self {
+            StatusDisplay::Name => 0,
+            StatusDisplay::State => 1,
+            StatusDisplay::Details => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, schemars::JsonSchema)]
+#[serde(default)]
+pub struct DiscordConfig {
+    pub enabled: bool,
+    // application ID from discord.com/developers
+    pub client_id: String,
+    // with `{song}`, `{artist}`, `{album}` and `{context}`, as in the export templates
+    pub details_format: String,
+    pub state_format: String,
+    // tooltip of the cover
+    pub image_text_format: String,
+    pub status_display: StatusDisplay,
+    // art asset key of the application, for tracks without a cover URL (local files)
+    pub fallback_image: String,
+    // clear the presence while paused instead of showing it without timestamps
+    pub clear_when_paused: bool,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            client_id: String::new(),
+            details_format: "{song}".into(),
+            state_format: "by {artist}".into(),
+            image_text_format: "{album}".into(),
+            status_display: StatusDisplay::Name,
+            fallback_image: String::new(),
+            clear_when_paused: true,
+        }
+    }
+}
+
+// What the presence shows; None clears it
+#[derive(Clone, PartialEq)]
+struct Presence {
+    details: Option<String>,
+    state: Option<String>,
+    image: Option<String>,
+    image_text: Option<String>,
+    // unix milliseconds, while playing with a known position
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl Presence {
+    // The same apart from the clock drifting between polls
+    fn same_as(&self, other: &Presence) -> bool {
+        let close = |a: Option<i64>, b: Option<i64>| match (a, b) {
+            (Some(a), Some(b)) => (a - b).abs() < SEEK_TOLERANCE_MS,
+            (a, b) => a == b,
+        };
+        close(self.start, other.start)
+            && close(self.end, other.end)
+            && Presence {
+                start: other.start,
+                end: other.end,
+                ..self.clone()
+            } == *other
+    }
+}
+
+#[derive(Default)]
+pub struct DiscordPresence {
+    config: DiscordConfig,
+    cancel: Option<CancellationToken>,
+    updates: watch::Sender<Option<Presence>>,
+}
+
+impl DiscordPresence {
+    pub fn running(&self) -> bool {
+        self.cancel.is_some()
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let config: DiscordConfig = read_settings(app)
+        .get("discord")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    restart(app, config);
+}
+
+fn restart(app: &tauri::AppHandle, config: DiscordConfig) {
+    let token = CancellationToken::new();
+    let mut updates = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.discord.cancel.take() {
+            old.cancel();
+        }
+        s.discord.config = config.clone();
+        if !config.enabled {
+            return;
+        }
+        s.discord.cancel = Some(token.clone());
+        // built again with the new formats
+        if let Some(np) = &s.last_now_playing {
+            let presence = presence(&config, np);
+            s.discord.updates.send_replace(presence);
+        }
+        s.discord.updates.subscribe()
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut retry = Retry::new(&app, "discord");
+        loop {
+            let connected_at = Instant::now();
+            tokio::select! {
+                _ = token.cancelled() => break,
+                res = session(&config, &mut updates) => {
+                    if let Err(e) = res {
+                        eprintln!("[discord] {e}");
+                    }
+                }
+            }
+            if connected_at.elapsed() >= HEALTHY_AFTER {
+                retry.reset();
+            }
+            if !retry.wait(&token).await {
+                if !token.is_cancelled() {
+                    eprintln!("[discord] giving up reconnecting");
+                }
+                break;
+            }
+        }
+    });
+}
+
+// At most `MAX_TEXT` characters; Discord rejects anything under 2
+fn fit(text: String) -> Option<String> {
+    let text: String = text.trim().chars().take(MAX_TEXT).collect();
+    (text.chars().count() >= 2).then_some(text)
+}
+
+fn presence(config: &DiscordConfig, np: &NowPlaying) -> Option<Presence> {
+    np.track_name.as_ref()?;
+    if np.stale || (!np.is_playing && config.clear_when_paused) {
+        return None;
+    }
+    // as in the export files
+    let fields = [
+        ("song.txt", np.track_name.clone().unwrap_or_default()),
+        ("artist.txt", np.artists.join(", ")),
+        ("album.txt", np.album.clone().unwrap_or_default()),
+        ("context.txt", np.context_label.clone().unwrap_or_default()),
+    ];
+    let fill = |format: &str| fit(export::fill_template(format, &fields));
+    let (start, end) = match (np.is_playing, np.position_ms) {
+        (true, Some(position)) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as i64);
+            let start = now - position as i64;
+            (Some(start), np.duration_ms.map(|d| start + d as i64))
+        }
+        _ => (None, None),
+    };
+    let image = np
+        .artwork_url
+        .clone()
+        .filter(|url| url.starts_with("https://"))
+        .or_else(|| Some(config.fallback_image.clone()).filter(|key| !key.is_empty()));
+    Some(Presence {
+        details: fill(&config.details_format),
+        state: fill(&config.state_format),
+        image,
+        image_text: fill(&config.image_text_format),
+        start,
+        end,
+    })
+}
+
+// Called with every settled update
+pub fn update(app: &tauri::AppHandle, np: &NowPlaying) {
+    let state = app.state::<SharedStore>();
+    let s = state.lock();
+    if s.discord.cancel.is_none() {
+        return;
+    }
+    let presence = presence(&s.discord.config, np);
+    s.discord.updates.send_if_modified(|current| {
+        let same = match (current.as_ref(), presence.as_ref()) {
+            (Some(a), Some(b)) => a.same_as(b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        if !same {
+            *current = presence;
+        }
+        !same
+    });
+}
+
+fn activity(config: &DiscordConfig, p: &Presence) -> Value {
+    let mut activity = json!({
+        // "Listening to"
+        "type": 2,
+        "status_display_type": config.status_display.code(),
+    });
+    if let Some(details) = &p.details {
+        activity["details"] = json!(details);
+    }
+    if let Some(state) = &p.state {
+        activity["state"] = json!(state);
+    }
+    if let Some(image) = &p.image {
+        activity["assets"] = json!({ "large_image": image });
+        if let Some(text) = &p.image_text {
+            activity["assets"]["large_text"] = json!(text);
+        }
+    }
+    if let Some(start) = p.start {
+        activity["timestamps"] = json!({ "start": start });
+        if let Some(end) = p.end {
+            activity["timestamps"]["end"] = json!(end);
+        }
+    }
+    activity
+}
+
+trait Ipc: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Ipc for T {}
+
+// Discord listens on the first free of discord-ipc-0..9
+#[cfg(windows)]
+async fn connect() -> Result<Box<dyn Ipc>, String> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    for i in 0..10 {
+        if let Ok(pipe) = ClientOptions::new().open(format!(r"\\.\pipe\discord-ipc-{i}")) {
+            return Ok(Box::new(pipe));
+        }
+    }
+    Err("Discord isn't running".into())
+}
+
+// In the runtime dir, or the Flatpak / Snap sandbox's copy of it
+#[cfg(unix)]
+async fn connect() -> Result<Box<dyn Ipc>, String> {
+    let base = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(std::env::var_os)
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| "/tmp".into());
+    for dir in ["", "app/com.discordapp.Discord", "snap.discord"] {
+        for i in 0..10 {
+            let path = base.join(dir).join(format!("discord-ipc-{i}"));
+            if let Ok(socket) = tokio::net::UnixStream::connect(path).await {
+                return Ok(Box::new(socket));
+            }
+        }
+    }
+    Err("Discord isn't running".into())
+}
+
+// Frames are opcode and length (u32 little-endian each), then JSON
+async fn write_frame(
+    w: &mut (impl AsyncWrite + Unpin),
+    op: u32,
+    data: &Value,
+) -> Result<(), String> {
+    let body = data.to_string();
+    let mut frame = Vec::with_capacity(8 + body.len());
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(body.as_bytes());
+    w.write_all(&frame).await.map_err(|e| format!("write: {e}"))
+}
+
+async fn read_frame(r: &mut (impl AsyncRead + Unpin)) -> Result<(u32, Value), String> {
+    let mut header = [0u8; 8];
+    r.read_exact(&mut header)
+        .await
+        .map_err(|e| format!("read: {e}"))?;
+    let op = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if len > MAX_FRAME {
+        return Err(format!("frame of {len} bytes is too big"));
+    }
+    let mut body = vec![0u8; len as usize];
+    r.read_exact(&mut body)
+        .await
+        .map_err(|e| format!("read: {e}"))?;
+    let data = serde_json::from_slice(&body).map_err(|e| format!("bad frame: {e}"))?;
+    Ok((op, data))
+}
+
+async fn session(
+    config: &DiscordConfig,
+    updates: &mut watch::Receiver<Option<Presence>>,
+) -> Result<(), String> {
+    let (mut rd, mut wr) = tokio::io::split(connect().await?);
+    let handshake = async {
+        write_frame(
+            &mut wr,
+            0,
+            &json!({ "v": 1, "client_id": config.client_id.trim() }),
+        )
+        .await?;
+        read_frame(&mut rd).await
+    };
+    let (op, ready) = watchdog::within("Discord handshake", HANDSHAKE_TIMEOUT, handshake).await??;
+    if op != 1 || ready["evt"] != "READY" {
+        let message = ready["message"].as_str().unwrap_or("handshake refused");
+        return Err(format!("{message} (check the application ID)"));
+    }
+
+    // a frame read can't be abandoned halfway, so the reading runs on its own
+    let (frames_tx, mut frames) = mpsc::channel(8);
+    let reader = tauri::async_runtime::spawn(async move {
+        while let Ok(frame) = read_frame(&mut rd).await {
+            if frames_tx.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+    let res = async {
+        // whatever is showing now, then every change
+        updates.mark_changed();
+        let mut nonce = 0u64;
+        loop {
+            tokio::select! {
+                changed = updates.changed() => {
+                    changed.map_err(|_| "app is shutting down".to_string())?;
+                    let presence = updates.borrow_and_update().clone();
+                    nonce += 1;
+                    let activity = presence.map(|p| activity(config, &p));
+                    let command = json!({
+                        "cmd": "SET_ACTIVITY",
+                        "args": { "pid": std::process::id(), "activity": activity },
+                        "nonce": nonce.to_string(),
+                    });
+                    write_frame(&mut wr, 1, &command).await?;
+                }
+                frame = frames.recv() => match frame {
+                    None => return Err("Discord closed the connection".to_string()),
+                    Some((2, data)) => {
+                        let message = data["message"].as_str().unwrap_or_default();
+                        return Err(format!("Discord closed the connection: {message}"));
+                    }
+                    // ping
+                    Some((3, data)) => write_frame(&mut wr, 4, &data).await?,
+                    Some((_, data)) if data["evt"] == "ERROR" => {
+                        eprintln!("[discord] {}", data["data"]["message"]);
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+    }
+    .await;
+    reader.abort();
+    res
+}
+
+#[tauri::command]
+pub fn get_discord_config(state: State<'_, SharedStore>) -> DiscordConfig {
+    state.lock().discord.config.clone()
+}
+
+#[tauri::command]
+pub fn set_discord_config(window: tauri::Window, config: DiscordConfig) -> Result<(), String> {
+    let id = config.client_id.trim();
+    if config.enabled && (id.is_empty() || !id.chars().all(|c| c.is_ascii_digit())) {
+        return Err("Enter the application ID from the Discord developer portal".into());
+    }
+    let app = window.app_handle();
+    write_setting(app, "discord", serde_json::json!(config))?;
+    restart(app, config);
+    Ok(())
+}
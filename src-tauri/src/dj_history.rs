@@ -0,0 +1,422 @@
+// DJ software history files.
+//
+// Serato appends every loaded track to a binary `.session` file under
+// `Music/_Serato_/History/Sessions` and marks it with the deck and start/end times. The
+// current track is the most recently started one still live on a deck, not one merely loaded
+// and pulled again (the last entry, for versions that don't write those fields). rekordbox can
+// write its history out as a tab-separated `.txt` or an `.m3u8` playlist, whose last entry is
+// the current track. We point at a file or a folder (newest file wins) and re-read it
+// whenever it changes.
+
+use crate::{parse_artists, read_settings, write_setting, NowPlaying, SharedStore};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tauri::{Manager, State};
+
+const EXTENSIONS: &[&str] = &["session", "txt", "m3u8", "m3u"];
+
+// A history file nobody has written to in this long is from an old set
+const IDLE_AFTER: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    // (file, mtime) of the last parse and what it gave, so unchanged files aren't re-read
+    cache: Option<(PathBuf, SystemTime, Option<NowPlaying>)>,
+}
+
+impl History {
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+fn serato_default_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME"))?;
+    let dir = PathBuf::from(home).join("Music/_Serato_/History/Sessions");
+    dir.is_dir().then_some(dir)
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let path = read_settings(app)
+        .get("dj_history_path")
+        .and_then(|v| v.as_str())
+        .map(PathBuf::from)
+        .or_else(serato_default_dir);
+    app.state::<SharedStore>().lock().dj_history.path = path;
+}
+
+fn has_history_ext(p: &Path) -> bool {
+    p.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+fn newest_file(path: &Path) -> Option<(PathBuf, SystemTime)> {
+    let mtime = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    if path.is_file() {
+        return Some((path.to_path_buf(), mtime(path)?));
+    }
+    fs::read_dir(path)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && has_history_ext(p))
+        .filter_map(|p| mtime(&p).map(|t| (p, t)))
+        .max_by_key(|(_, t)| *t)
+}
+
+pub async fn current(state: &SharedStore) -> Result<Option<NowPlaying>, String> {
+    let Some(path) = state.lock().dj_history.path.clone() else {
+        return Err("No DJ history path configured".into());
+    };
+    let Some((file, mtime)) = newest_file(&path) else {
+        return Ok(None);
+    };
+
+    let cached = state
+        .lock()
+        .dj_history
+        .cache
+        .as_ref()
+        .filter(|(f, t, _)| *f == file && *t == mtime)
+        .map(|(_, _, np)| np.clone());
+    let np = match cached {
+        Some(np) => np,
+        None => {
+            let f = file.clone();
+            let np = tauri::async_runtime::spawn_blocking(move || read_last_entry(&f))
+                .await
+                .map_err(|e| format!("spawn_blocking join error: {e}"))??;
+            state.lock().dj_history.cache = Some((file, mtime, np.clone()));
+            np
+        }
+    };
+
+    let idle = mtime.elapsed().is_ok_and(|age| age > IDLE_AFTER);
+    Ok(np.map(|np| NowPlaying {
+        is_playing: !idle,
+        ..np
+    }))
+}
+
+fn read_last_entry(file: &Path) -> Result<Option<NowPlaying>, String> {
+    let bytes = fs::read(file).map_err(|e| format!("read {}: {e}", file.display()))?;
+    let ext = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    Ok(match ext.as_str() {
+        "session" => serato_current(&bytes),
+        "m3u8" | "m3u" => m3u_last_entry(&decode_text(&bytes)),
+        _ => tsv_last_entry(&decode_text(&bytes)),
+    })
+}
+
+#[derive(Default)]
+struct SeratoEntry {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    path: Option<String>,
+    deck: Option<u32>,
+    // unix seconds
+    started: Option<u32>,
+    // set once the track leaves the deck
+    ended: Option<u32>,
+    played: bool,
+}
+
+impl SeratoEntry {
+    fn parse(adat: &[u8]) -> Self {
+        let mut e = SeratoEntry::default();
+        for (id, value) in chunks(adat) {
+            let text = || utf16be(value);
+            let num = || value.try_into().ok().map(u32::from_be_bytes);
+            match u32::from_be_bytes(id) {
+                2 => e.path = text(),
+                6 => e.title = text(),
+                7 => e.artist = text(),
+                8 => e.album = text(),
+                28 => e.started = num(),
+                29 => e.ended = num(),
+                31 => e.deck = num(),
+                50 => e.played = value.first().is_some_and(|b| *b != 0),
+                _ => {}
+            }
+        }
+        e
+    }
+
+    fn into_now_playing(self) -> Option<NowPlaying> {
+        // untagged files only have a path
+        let title = self.title.or_else(|| {
+            self.path
+                .as_deref()
+                .and_then(|p| Path::new(p).file_stem())
+                .map(|s| s.to_string_lossy().into_owned())
+        })?;
+        let mut np = entry(
+            title,
+            self.artist.as_deref().unwrap_or_default(),
+            self.album,
+        );
+        if let Some(deck) = self.deck {
+            np.context_type = Some("deck".into());
+            np.context_name = Some(format!("Deck {deck}"));
+            np.context_label = np.context_name.clone();
+        }
+        Some(np)
+    }
+}
+
+// Serato: a list of `tag | u32 BE length | data` chunks. Each "oent" holds an "adat" whose
+// data is `u32 field id | u32 length | value` records, strings in UTF-16BE.
+//
+// The current track is the most recently started one that was actually played and hasn't
+// left its deck yet; older Serato versions don't write those fields, so fall back to the last
+// entry.
+fn serato_current(bytes: &[u8]) -> Option<NowPlaying> {
+    let entries: Vec<SeratoEntry> = chunks(bytes)
+        .filter(|(tag, _)| tag == b"oent")
+        .filter_map(|(_, data)| chunks(data).find(|(tag, _)| tag == b"adat"))
+        .map(|(_, adat)| SeratoEntry::parse(adat))
+        .collect();
+
+    let live = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.played && e.ended.is_none() && e.started.is_some())
+        .max_by_key(|(i, e)| (e.started, *i))
+        .map(|(i, _)| i);
+    let idx = live.or(entries.len().checked_sub(1))?;
+    entries.into_iter().nth(idx)?.into_now_playing()
+}
+
+fn chunks(mut buf: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let tag: [u8; 4] = buf.get(..4)?.try_into().ok()?;
+        let len = u32::from_be_bytes(buf.get(4..8)?.try_into().ok()?) as usize;
+        let data = buf.get(8..8 + len)?;
+        buf = &buf[8 + len..];
+        Some((tag, data))
+    })
+}
+
+fn utf16be(b: &[u8]) -> Option<String> {
+    let units: Vec<u16> = b
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+    let s = String::from_utf16_lossy(&units);
+    let s = s.trim_matches(char::from(0)).trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+// rekordbox writes its text exports as UTF-16LE with a BOM
+fn decode_text(b: &[u8]) -> String {
+    match b {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(b).into_owned(),
+    }
+}
+
+// "#EXTINF:312,Artist - Title" before each path
+fn m3u_last_entry(text: &str) -> Option<NowPlaying> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let path_idx = lines.iter().rposition(|l| !l.starts_with('#'))?;
+    let info = lines[..path_idx]
+        .last()
+        .and_then(|l| l.strip_prefix("#EXTINF:"))
+        .and_then(|l| l.split_once(',').map(|(_, name)| name.trim()));
+
+    match info.and_then(|i| i.split_once(" - ")) {
+        Some((artist, title)) => Some(entry(title.trim().to_string(), artist, None)),
+        None => {
+            let title = info.map(str::to_string).or_else(|| {
+                Path::new(lines[path_idx])
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+            })?;
+            Some(entry(title, "", None))
+        }
+    }
+}
+
+// Header row names the columns ("Track Title", "Artist", "Album", ...)
+fn tsv_last_entry(text: &str) -> Option<NowPlaying> {
+    let mut rows = text.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<String> = rows
+        .next()?
+        .split('\t')
+        .map(|h| h.trim().to_ascii_lowercase())
+        .collect();
+    let col = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+    let title_col = col(&["track title", "title", "name"])?;
+    let artist_col = col(&["artist"]);
+    let album_col = col(&["album"]);
+
+    let last: Vec<&str> = rows.next_back()?.split('\t').map(str::trim).collect();
+    let get = |c: Option<usize>| c.and_then(|c| last.get(c)).filter(|v| !v.is_empty());
+    let title = get(Some(title_col))?.to_string();
+    Some(entry(
+        title,
+        get(artist_col).copied().unwrap_or_default(),
+        get(album_col).map(|a| a.to_string()),
+    ))
+}
+
+fn entry(title: String, artist: &str, album: Option<String>) -> NowPlaying {
+    NowPlaying {
+        is_playing: true,
+        track_name: Some(title),
+        artists: parse_artists(artist),
+        album,
+        ..Default::default()
+    }
+}
+
+#[tauri::command]
+pub fn get_dj_history_path(state: State<'_, SharedStore>) -> Option<String> {
+    state
+        .lock()
+        .dj_history
+        .path
+        .as_ref()
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+// A history file or a folder of them; `None` goes back to Serato's default folder
+#[tauri::command]
+pub fn set_dj_history_path(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    path: Option<String>,
+) -> Result<(), String> {
+    let path = path.map(PathBuf::from);
+    if let Some(p) = &path {
+        if !p.exists() {
+            return Err(format!("Path does not exist: {}", p.display()));
+        }
+    }
+    write_setting(
+        window.app_handle(),
+        "dj_history_path",
+        serde_json::json!(path.as_ref().map(|p| p.to_string_lossy())),
+    )?;
+
+    {
+        let mut s = state.lock();
+        s.dj_history.path = path.or_else(serato_default_dir);
+        s.dj_history.cache = None;
+    }
+    crate::start_watcher_if_needed(window.app_handle(), &state);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `tag | u32 BE length | data`; adat fields have the same shape with a numeric id
+    fn chunk(tag: [u8; 4], data: &[u8]) -> Vec<u8> {
+        [&tag[..], &(data.len() as u32).to_be_bytes(), data].concat()
+    }
+
+    fn text(id: u32, s: &str) -> Vec<u8> {
+        let value: Vec<u8> = s.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        chunk(id.to_be_bytes(), &value)
+    }
+
+    fn num(id: u32, n: u32) -> Vec<u8> {
+        chunk(id.to_be_bytes(), &n.to_be_bytes())
+    }
+
+    fn oent(fields: &[Vec<u8>]) -> Vec<u8> {
+        chunk(*b"oent", &chunk(*b"adat", &fields.concat()))
+    }
+
+    fn session(entries: &[Vec<u8>]) -> Vec<u8> {
+        [chunk(*b"vrsn", &[0, b'2']), entries.concat()].concat()
+    }
+
+    #[test]
+    fn live_entry_wins_over_later_loads() {
+        let playing = oent(&[
+            text(6, "Windowlicker"),
+            text(7, "Aphex Twin"),
+            text(8, "Windowlicker EP"),
+            num(28, 1_000),
+            num(31, 2),
+            chunk(50u32.to_be_bytes(), &[1]),
+        ]);
+        // loaded on the other deck but never played
+        let loaded = oent(&[text(6, "Xtal"), num(28, 2_000), num(31, 1)]);
+        let np = serato_current(&session(&[playing, loaded])).unwrap();
+        assert_eq!(np.track_name.as_deref(), Some("Windowlicker"));
+        assert_eq!(np.artists, ["Aphex Twin"]);
+        assert_eq!(np.album.as_deref(), Some("Windowlicker EP"));
+        assert_eq!(np.context_name.as_deref(), Some("Deck 2"));
+    }
+
+    #[test]
+    fn last_entry_without_play_fields() {
+        let first = oent(&[text(6, "Xtal")]);
+        // untagged: the title comes from the file name
+        let second = oent(&[text(2, "/music/Aphex Twin - Avril 14th.mp3")]);
+        let np = serato_current(&session(&[first, second])).unwrap();
+        assert_eq!(np.track_name.as_deref(), Some("Aphex Twin - Avril 14th"));
+        assert_eq!(np.context_name, None);
+    }
+
+    #[test]
+    fn truncated_session() {
+        let first = oent(&[text(6, "Xtal")]);
+        let second = oent(&[text(6, "Avril 14th")]);
+        let mut bytes = session(&[first, second]);
+        bytes.truncate(bytes.len() - 5);
+        let np = serato_current(&bytes).unwrap();
+        assert_eq!(np.track_name.as_deref(), Some("Xtal"));
+
+        assert!(serato_current(&bytes[..bytes.len() / 4]).is_none());
+        assert!(serato_current(&[]).is_none());
+    }
+
+    #[test]
+    fn oversized_lengths() {
+        let mut bytes = session(&[oent(&[text(6, "Xtal")])]);
+        bytes.extend_from_slice(b"oent");
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+        let np = serato_current(&bytes).unwrap();
+        assert_eq!(np.track_name.as_deref(), Some("Xtal"));
+
+        // a field claiming more than the adat holds ends the entry there
+        let mut fields = text(7, "Aphex Twin");
+        fields.extend_from_slice(&6u32.to_be_bytes());
+        fields.extend_from_slice(&u32::MAX.to_be_bytes());
+        fields.extend_from_slice(&[0, b'X']);
+        let bytes = session(&[chunk(*b"oent", &chunk(*b"adat", &fields))]);
+        let entry = chunks(&bytes)
+            .find(|(tag, _)| tag == b"oent")
+            .and_then(|(_, data)| chunks(data).next())
+            .map(|(_, adat)| SeratoEntry::parse(adat))
+            .unwrap();
+        assert_eq!(entry.artist.as_deref(), Some("Aphex Twin"));
+        assert_eq!(entry.title, None);
+    }
+}
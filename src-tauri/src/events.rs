@@ -0,0 +1,298 @@
+// Event fan-out with per-window subscriptions and per-event rate policies.
+//
+// Windows that never call `subscribe_events` keep receiving everything. A window that does
+// subscribe only gets the listed event names, which keeps e.g. artwork payloads away from a
+// lyrics-only window. Tauri hands every emit to a global `listen()` whatever the filter says,
+// so the bundled windows listen through their own webview window, and custom windows that
+// subscribe have to do the same.
+//
+// Rapid-fire events go through a small pipeline so a slow webview isn't flooded: coalesced
+// events only deliver the latest value per interval, queued events deliver every value in
+// order but spaced out.
+
+use crate::{push, read_settings, write_setting, SharedStore};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+use tauri::{Emitter, EventTarget, Manager, State};
+
+// window label -> event names it wants ("*" for everything)
+pub type Subscriptions = HashMap<String, HashSet<String>>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, schemars::JsonSchema)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum EmitPolicy {
+    Immediate,
+    // latest value wins, at most one emit per interval
+    Coalesce { interval_ms: u64 },
+    // every value is delivered in order, at most one per interval
+    Queue { interval_ms: u64 },
+}
+
+fn default_policy(event: &str) -> EmitPolicy {
+    match event {
+        "now_playing_update" | "players_update" | "playback_progress" => {
+            EmitPolicy::Coalesce { interval_ms: 250 }
+        }
+        "track_changed" | "gsmtc_track_changed" => EmitPolicy::Queue { interval_ms: 100 },
+        _ => EmitPolicy::Immediate,
+    }
+}
+
+// When the watcher sends `now_playing_update`
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateMode {
+    // after every poll, even if nothing changed
+    Always,
+    // only when more than the position changed; `playback_progress` still ticks every second
+    #[default]
+    OnChange,
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    // user overrides on top of `default_policy`
+    policies: HashMap<String, EmitPolicy>,
+    channels: HashMap<String, Channel>,
+}
+
+#[derive(Default)]
+struct Channel {
+    last_sent: Option<Instant>,
+    pending: VecDeque<serde_json::Value>,
+    flush_scheduled: bool,
+}
+
+impl Pipeline {
+    pub fn with_policies(policies: HashMap<String, EmitPolicy>) -> Self {
+        Self {
+            policies,
+            ..Default::default()
+        }
+    }
+
+    fn policy(&self, event: &str) -> EmitPolicy {
+        self.policies
+            .get(event)
+            .copied()
+            .unwrap_or_else(|| default_policy(event))
+    }
+}
+
+pub fn emit<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    let state = app.state::<SharedStore>();
+    let policy = state.lock().emit_pipeline.policy(event);
+
+    let (coalesce, interval_ms) = match policy {
+        EmitPolicy::Immediate => {
+            deliver(app, event, payload);
+            return;
+        }
+        EmitPolicy::Coalesce { interval_ms } => (true, interval_ms),
+        EmitPolicy::Queue { interval_ms } => (false, interval_ms),
+    };
+
+    let Ok(value) = serde_json::to_value(&payload) else {
+        return;
+    };
+    {
+        let mut s = state.lock();
+        let ch = s
+            .emit_pipeline
+            .channels
+            .entry(event.to_string())
+            .or_default();
+        if coalesce {
+            ch.pending.clear();
+        }
+        ch.pending.push_back(value);
+    }
+    pump(app, event, Duration::from_millis(interval_ms));
+}
+
+// Sends the head of the channel if the interval allows it and schedules a later flush for
+// whatever is left.
+fn pump(app: &tauri::AppHandle, event: &str, interval: Duration) {
+    let (send, flush_in) = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        let ch = s
+            .emit_pipeline
+            .channels
+            .entry(event.to_string())
+            .or_default();
+
+        let wait = ch
+            .last_sent
+            .map(|t| interval.saturating_sub(t.elapsed()))
+            .unwrap_or_default();
+        let send = if wait.is_zero() {
+            ch.pending.pop_front()
+        } else {
+            None
+        };
+        if send.is_some() {
+            ch.last_sent = Some(Instant::now());
+        }
+
+        let flush = !ch.pending.is_empty() && !ch.flush_scheduled;
+        if flush {
+            ch.flush_scheduled = true;
+        }
+        let wait = if send.is_some() { interval } else { wait };
+        (send, flush.then_some(wait))
+    };
+
+    if let Some(value) = send {
+        deliver(app, event, value);
+    }
+
+    if let Some(wait) = flush_in {
+        let app = app.clone();
+        let event = event.to_string();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(wait).await;
+            let interval = {
+                let state = app.state::<SharedStore>();
+                let mut s = state.lock();
+                if let Some(ch) = s.emit_pipeline.channels.get_mut(&event) {
+                    ch.flush_scheduled = false;
+                }
+                match s.emit_pipeline.policy(&event) {
+                    EmitPolicy::Coalesce { interval_ms } | EmitPolicy::Queue { interval_ms } => {
+                        Duration::from_millis(interval_ms)
+                    }
+                    EmitPolicy::Immediate => Duration::ZERO,
+                }
+            };
+            pump(&app, &event, interval);
+        });
+    }
+}
+
+fn deliver<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    push::publish(event, &payload);
+    // Clone so no store lock is held while Rust-side listeners run
+    let subs = app
+        .state::<SharedStore>()
+        .lock()
+        .event_subscriptions
+        .clone();
+    if subs.is_empty() {
+        let _ = app.emit(event, payload);
+        return;
+    }
+
+    let _ = app.emit_filter(event, payload, |target| match target {
+        EventTarget::Window { label }
+        | EventTarget::Webview { label }
+        | EventTarget::WebviewWindow { label }
+        | EventTarget::AnyLabel { label } => wants(&subs, label, event),
+        _ => true,
+    });
+}
+
+fn wants(subs: &Subscriptions, label: &str, event: &str) -> bool {
+    subs.get(label)
+        .is_none_or(|kinds| kinds.contains("*") || kinds.contains(event))
+}
+
+pub fn load_policies(app: &tauri::AppHandle) -> HashMap<String, EmitPolicy> {
+    read_settings(app)
+        .get("emit_policies")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn load_update_mode(app: &tauri::AppHandle) -> UpdateMode {
+    read_settings(app)
+        .get("now_playing_updates")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn get_update_mode(state: State<'_, SharedStore>) -> UpdateMode {
+    state.lock().update_mode
+}
+
+#[tauri::command]
+pub fn set_update_mode(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    mode: UpdateMode,
+) -> Result<(), String> {
+    write_setting(
+        window.app_handle(),
+        "now_playing_updates",
+        serde_json::json!(mode),
+    )?;
+    state.lock().update_mode = mode;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn subscribe_events(
+    state: State<'_, SharedStore>,
+    window_label: String,
+    kinds: Vec<String>,
+) -> Result<(), String> {
+    if kinds.is_empty() {
+        return Err("No event kinds given".into());
+    }
+    state
+        .lock()
+        .event_subscriptions
+        .insert(window_label, kinds.into_iter().collect());
+    Ok(())
+}
+
+// Back to receiving every event
+#[tauri::command]
+pub fn unsubscribe_events(state: State<'_, SharedStore>, window_label: String) {
+    state.lock().event_subscriptions.remove(&window_label);
+}
+
+// Effective policy for the given event names (defaults merged with overrides)
+#[tauri::command]
+pub fn get_emit_policies(
+    state: State<'_, SharedStore>,
+    events: Vec<String>,
+) -> HashMap<String, EmitPolicy> {
+    let s = state.lock();
+    events
+        .into_iter()
+        .map(|e| {
+            let p = s.emit_pipeline.policy(&e);
+            (e, p)
+        })
+        .collect()
+}
+
+// `policy: None` drops the override and goes back to the built-in default
+#[tauri::command]
+pub fn set_emit_policy(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    event: String,
+    policy: Option<EmitPolicy>,
+) -> Result<(), String> {
+    let overrides = {
+        let mut s = state.lock();
+        match policy {
+            Some(p) => s.emit_pipeline.policies.insert(event, p),
+            None => s.emit_pipeline.policies.remove(&event),
+        };
+        s.emit_pipeline.policies.clone()
+    };
+    write_setting(
+        window.app_handle(),
+        "emit_policies",
+        serde_json::json!(overrides),
+    )
+}
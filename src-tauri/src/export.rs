@@ -0,0 +1,844 @@
+// Exporting the current track as text files + artwork.png for OBS text/image sources.
+//
+// Each export profile is a directory that receives the full set of files. Profiles are
+// validated on save so a bad path shows up in settings instead of as a failed export later.
+// A profile whose directory still refuses a write (OneDrive pausing, a file locked by another
+// program, ...) is written to the app's data folder instead, with an `export_fallback` event.
+
+use crate::{
+    accessibility, auto_export::ExportTrigger, export_format::TextFormat,
+    normalizers::ClassicalInfo, placeholder::Placeholder, providers::Provider, spotify_client,
+    watchdog, EpisodeInfo, NowPlaying, SharedStore,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rspotify::{clients::OAuthClient, model::PlayableItem};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::{Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, schemars::JsonSchema)]
+pub struct ExportProfile {
+    pub name: String,
+    pub dir: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    // encoding, line ending and file-name casing of the written files
+    #[serde(default)]
+    pub format: TextFormat,
+    // when the files get rewritten
+    #[serde(default)]
+    pub trigger: ExportTrigger,
+    // extra files, name -> text with `{song}`, `{artist}`, ... (any built-in file's name without
+    // `.txt`); one named like a built-in file replaces it
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    // longest edge of artwork.png in pixels; None keeps the cover's own size
+    #[serde(default)]
+    pub artwork_size: Option<u32>,
+    // written instead of the track when nothing is playing; None keeps the last track's files
+    #[serde(default)]
+    pub placeholder: Option<Placeholder>,
+}
+
+// Largest `artwork_size` accepted
+const MAX_ARTWORK_SIZE: u32 = 4096;
+
+impl ExportProfile {
+    // `<exe dir>/Exported-track` with everything at its default, when no profiles are set up
+    fn fallback() -> Result<Self, String> {
+        Ok(Self {
+            name: String::new(),
+            dir: default_dir()?.to_string_lossy().to_string(),
+            enabled: true,
+            format: TextFormat::default(),
+            trigger: ExportTrigger::Manual,
+            templates: BTreeMap::new(),
+            artwork_size: None,
+            placeholder: None,
+        })
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+// One problem with one profile, for the settings UI to show next to it
+#[derive(Serialize, Debug, schemars::JsonSchema)]
+pub struct ExportIssue {
+    pub profile: String,
+    // "empty_name" | "duplicate_name" | "relative_path" | "protected_dir" | "conflict"
+    // | "bad_interval" | "bad_template" | "bad_artwork_size" | "bad_placeholder" | "save_failed",
+    // or any `WriteError` code
+    pub code: &'static str,
+    pub message: String,
+}
+
+// A write that failed, with a code the UI can suggest a fix for
+#[derive(Debug)]
+pub struct WriteError {
+    // "permission_denied" | "read_only" | "in_use" | "cloud_sync" | "path_too_long"
+    // | "disk_full" | "not_writable"
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<WriteError> for String {
+    fn from(e: WriteError) -> Self {
+        e.message
+    }
+}
+
+impl WriteError {
+    fn new(e: std::io::Error, path: &Path) -> Self {
+        use std::io::ErrorKind;
+
+        let os = e.raw_os_error().unwrap_or_default();
+        let (code, hint) = match e.kind() {
+            // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+            _ if cfg!(windows) && matches!(os, 32 | 33) => (
+                "in_use",
+                "another program has the file open (a sync client or antivirus scan?)",
+            ),
+            // ERROR_CLOUD_FILE_*
+            _ if cfg!(windows) && (358..=405).contains(&os) => (
+                "cloud_sync",
+                "the sync client (OneDrive, ...) refused it; check it's running and the folder is always kept on this device",
+            ),
+            ErrorKind::InvalidFilename => (
+                "path_too_long",
+                "the path is too long or has characters the drive doesn't allow",
+            ),
+            ErrorKind::ReadOnlyFilesystem => ("read_only", "the drive is read-only"),
+            ErrorKind::PermissionDenied => (
+                "permission_denied",
+                "no write access; on Windows, also check Controlled folder access",
+            ),
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => ("disk_full", "the drive is full"),
+            _ => ("not_writable", ""),
+        };
+        let message = match hint {
+            "" => format!("cannot write {}: {e}", path.display()),
+            hint => format!("cannot write {}: {hint} ({e})", path.display()),
+        };
+        Self { code, message }
+    }
+}
+
+// Extended-length form on Windows, so folders nested deep in OneDrive still work past 260
+// characters. Left alone when relative, already prefixed, or holding `..`, which the prefix
+// would stop Windows from resolving.
+#[cfg(windows)]
+pub fn long_path(p: &Path) -> PathBuf {
+    let s = p.to_string_lossy().replace('/', "\\");
+    let dotted = p.components().any(|c| {
+        matches!(
+            c,
+            std::path::Component::ParentDir | std::path::Component::CurDir
+        )
+    });
+    if !p.is_absolute() || s.starts_with(r"\\?\") || dotted {
+        return p.to_path_buf();
+    }
+    match s.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{unc}")),
+        None => PathBuf::from(format!(r"\\?\{s}")),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(p: &Path) -> PathBuf {
+    p.to_path_buf()
+}
+
+pub fn load_profiles(app: &tauri::AppHandle) -> Vec<ExportProfile> {
+    crate::read_settings(app)
+        .get("export_profiles")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn default_dir() -> Result<PathBuf, String> {
+    let exe_dir = std::env::current_exe()
+        .map_err(|e| format!("current_exe: {e}"))?
+        .parent()
+        .ok_or_else(|| "Cannot resolve executable directory".to_string())?
+        .to_path_buf();
+    Ok(exe_dir.join("Exported-track"))
+}
+
+// Case-insensitive on Windows, trailing separators dropped
+fn normalize(p: &Path) -> String {
+    let s = p.to_string_lossy().replace('\\', "/");
+    let s = s.trim_end_matches('/');
+    if cfg!(windows) {
+        s.to_lowercase()
+    } else {
+        s.to_string()
+    }
+}
+
+fn protected_dirs() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        ["SystemRoot", "ProgramFiles", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(PathBuf::from)
+            .collect()
+    } else {
+        ["/bin", "/boot", "/etc", "/lib", "/sbin", "/usr", "/System"]
+            .iter()
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+fn is_protected(dir: &Path) -> bool {
+    let dir = normalize(dir);
+    // a drive or filesystem root is never a sensible export target
+    if dir.is_empty() || (dir.len() == 2 && dir.ends_with(':')) {
+        return true;
+    }
+    protected_dirs().iter().any(|p| {
+        let p = normalize(p);
+        dir == p || dir.starts_with(&format!("{p}/"))
+    })
+}
+
+// Looks at the directory, or the nearest parent that exists when it would still have to be
+// created, and at the built-in files someone made read-only. Touches nothing on disk.
+fn check_writable(dir: &Path, format: &TextFormat) -> Result<(), WriteError> {
+    let Some(existing) = dir.ancestors().find(|a| long_path(a).exists()) else {
+        return Err(WriteError {
+            code: "not_writable",
+            message: format!("cannot write {}: the drive doesn't exist", dir.display()),
+        });
+    };
+    let meta = fs::metadata(long_path(existing)).map_err(|e| WriteError::new(e, dir))?;
+    if !meta.is_dir() {
+        return Err(WriteError {
+            code: "not_writable",
+            message: format!("{} is a file, not a folder", existing.display()),
+        });
+    }
+    // Windows ignores the read-only flag on folders (Documents and others have it set)
+    if !cfg!(windows) && meta.permissions().readonly() {
+        return Err(WriteError {
+            code: "read_only",
+            message: format!("{} is read-only", existing.display()),
+        });
+    }
+    if existing != dir {
+        return Ok(());
+    }
+    let long = long_path(dir);
+    let files = text_files(&ExportPayload::from(&NowPlaying::default()));
+    let names = files.iter().map(|(name, _)| *name).chain(["artwork.png"]);
+    for name in names.map(|n| format.file_name(n)) {
+        let readonly = fs::metadata(long.join(&name)).is_ok_and(|m| m.permissions().readonly());
+        if readonly {
+            return Err(WriteError {
+                code: "read_only",
+                message: format!("{name} in {} is marked read-only", dir.display()),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn validate(profiles: &[ExportProfile]) -> Vec<ExportIssue> {
+    let mut issues = Vec::new();
+    let mut names: HashMap<String, usize> = HashMap::new();
+    // normalized dir -> first enabled profile writing there
+    let mut owners: HashMap<String, &str> = HashMap::new();
+
+    for p in profiles {
+        let issue = |code, message: String| ExportIssue {
+            profile: p.name.clone(),
+            code,
+            message,
+        };
+        let name = p.name.trim();
+        if name.is_empty() {
+            issues.push(issue("empty_name", "Profile needs a name".into()));
+        } else {
+            let seen = names.entry(name.to_lowercase()).or_default();
+            *seen += 1;
+            if *seen == 2 {
+                issues.push(issue(
+                    "duplicate_name",
+                    format!("More than one profile is called \"{name}\""),
+                ));
+            }
+        }
+
+        let dir = Path::new(p.dir.trim());
+        if !dir.is_absolute() {
+            issues.push(issue(
+                "relative_path",
+                format!("\"{}\" is not an absolute path", p.dir),
+            ));
+            continue;
+        }
+        if is_protected(dir) {
+            issues.push(issue(
+                "protected_dir",
+                format!("{} is a system directory", dir.display()),
+            ));
+            continue;
+        }
+        if !p.enabled {
+            continue;
+        }
+        for template in p.templates.keys() {
+            let bad = template.trim().is_empty()
+                || template.contains(['/', '\\', ':'])
+                || template.eq_ignore_ascii_case("artwork.png");
+            if bad {
+                issues.push(issue(
+                    "bad_template",
+                    format!("\"{template}\" can't be used as a file name"),
+                ));
+            }
+        }
+        if p.artwork_size
+            .is_some_and(|s| s == 0 || s > MAX_ARTWORK_SIZE)
+        {
+            issues.push(issue(
+                "bad_artwork_size",
+                format!("Artwork size must be 1 to {MAX_ARTWORK_SIZE} pixels"),
+            ));
+        }
+        if let Some(Err(e)) = p.placeholder.as_ref().map(Placeholder::check) {
+            issues.push(issue("bad_placeholder", e));
+        }
+        if p.trigger == (ExportTrigger::Interval { secs: 0 }) {
+            issues.push(issue(
+                "bad_interval",
+                "Updating every 0 seconds isn't possible; use at least 1".into(),
+            ));
+        }
+        if let Err(e) = check_writable(dir, &p.format) {
+            issues.push(issue(e.code, e.message));
+        }
+        // every profile writes the same file names, so a shared directory means clobbering
+        match owners.get(&normalize(dir)) {
+            Some(other) => issues.push(issue(
+                "conflict",
+                format!("Writes the same files as profile \"{other}\""),
+            )),
+            None => {
+                owners.insert(normalize(dir), &p.name);
+            }
+        }
+    }
+    issues
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPayload {
+    track_name: String,
+    artists: Vec<String>,
+    album: Option<String>,
+    artwork_url: Option<String>,
+    artwork_path: Option<String>,
+    #[serde(default)]
+    context_label: Option<String>,
+    #[serde(default)]
+    trivia: Option<String>,
+    #[serde(default)]
+    episode: Option<EpisodeInfo>,
+    #[serde(default)]
+    classical: Option<ClassicalInfo>,
+}
+
+impl From<&NowPlaying> for ExportPayload {
+    fn from(np: &NowPlaying) -> Self {
+        Self {
+            track_name: np.track_name.clone().unwrap_or_default(),
+            artists: np.artists.clone(),
+            album: np.album.clone(),
+            artwork_url: np.artwork_url.clone(),
+            artwork_path: np.artwork_path.clone(),
+            context_label: np.context_label.clone(),
+            trivia: np.trivia.clone(),
+            episode: np.episode.clone(),
+            classical: np.classical.clone(),
+        }
+    }
+}
+
+// Where a cover gets rendered from: (local path, URL)
+pub type ArtworkSource = (Option<String>, Option<String>);
+
+impl ExportPayload {
+    pub fn artwork_source(&self) -> ArtworkSource {
+        (self.artwork_path.clone(), self.artwork_url.clone())
+    }
+}
+
+// For names on disk; file contents are written as they are
+fn sanitize(s: &str) -> String {
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return String::new();
+    }
+    let bad = ['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\n', '\r'];
+    trimmed
+        .chars()
+        .map(|c| if bad.contains(&c) { '_' } else { c })
+        .collect()
+}
+
+// What an export produces, before anything touches the disk
+pub struct RenderedExport {
+    // (file name, contents)
+    pub files: Vec<(&'static str, String)>,
+    pub artwork_png: Option<Vec<u8>>,
+}
+
+pub async fn render_export(payload: &ExportPayload) -> Result<RenderedExport, String> {
+    Ok(RenderedExport {
+        files: text_files(payload),
+        artwork_png: render_artwork_png(payload).await?,
+    })
+}
+
+// (file name, contents) of every built-in text file
+pub fn text_files(payload: &ExportPayload) -> Vec<(&'static str, String)> {
+    // podcast episodes; empty for music so text sources don't show stale values
+    let ep = payload.episode.clone().unwrap_or_default();
+    // classical mode only, likewise
+    let classical = payload.classical.clone().unwrap_or_default();
+    vec![
+        ("song.txt", payload.track_name.clone()),
+        ("artist.txt", payload.artists.join(", ")),
+        ("album.txt", payload.album.clone().unwrap_or_default()),
+        (
+            "context.txt",
+            payload.context_label.clone().unwrap_or_default(),
+        ),
+        ("fact.txt", payload.trivia.clone().unwrap_or_default()),
+        ("show.txt", ep.show_name),
+        ("description.txt", ep.description),
+        ("release_date.txt", ep.release_date),
+        ("composer.txt", classical.composer.unwrap_or_default()),
+        ("work.txt", classical.work.unwrap_or_default()),
+        ("movement.txt", classical.movement.unwrap_or_default()),
+        // one readable sentence, for screen-reader friendly layouts
+        (
+            "now_playing_plain.txt",
+            accessibility::plain_summary(
+                &payload.track_name,
+                &payload.artists,
+                payload.album.as_deref(),
+            ),
+        ),
+    ]
+}
+
+pub fn encode_png(img: &image::DynamicImage) -> Result<Vec<u8>, String> {
+    let mut out = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut out, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+// artwork -> PNG (prefer local path, else fetch URL)
+pub async fn render_artwork_png(payload: &ExportPayload) -> Result<Option<Vec<u8>>, String> {
+    if let Some(ap) = payload.artwork_path.as_deref() {
+        if !ap.is_empty() && Path::new(ap).exists() {
+            if let Ok(img) = image::open(ap) {
+                return encode_png(&img).map(Some);
+            }
+            if Path::new(ap)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|x| x.eq_ignore_ascii_case("png"))
+            {
+                return fs::read(ap).map(Some).map_err(|e| e.to_string());
+            }
+        }
+    }
+
+    if let Some(url) = payload.artwork_url.as_deref() {
+        if !url.is_empty() {
+            return encode_png(&fetch_image(url).await?).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+pub async fn fetch_image(url: &str) -> Result<image::DynamicImage, String> {
+    let bytes = watchdog::within("image download", watchdog::HTTP_TIMEOUT, async {
+        reqwest::get(url).await?.bytes().await
+    })
+    .await?
+    .map_err(|e| e.to_string())?;
+    image::load_from_memory(&bytes).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtworkFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl ArtworkFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ArtworkFormat::Png => "png",
+            ArtworkFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+// `NowPlaying` carries the ~300px Spotify rendition; thumbnails want the largest one
+async fn largest_spotify_artwork(state: &SharedStore, np: &NowPlaying) -> Option<String> {
+    if np.source != Some(Provider::Spotify) {
+        return None;
+    }
+    let client = spotify_client(state).ok()?;
+    let ctx = client.current_user_playing_item().await.ok()??;
+    let images = match ctx.item? {
+        // the track may have changed since the last poll
+        PlayableItem::Track(t) if np.track_name.as_ref() == Some(&t.name) => t.album.images,
+        PlayableItem::Episode(ep) if np.track_name.as_ref() == Some(&ep.name) => ep.images,
+        _ => return None,
+    };
+    images
+        .into_iter()
+        .max_by_key(|img| img.width.unwrap_or(0))
+        .map(|img| img.url)
+}
+
+// Just the current cover, at the best resolution available, without touching the text files.
+// `path` is a file, or a directory that gets `artwork.<ext>`; `size` scales the image to fit
+// a `size`x`size` box. Returns the file written.
+#[tauri::command]
+pub async fn export_artwork_only(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    path: String,
+    size: Option<u32>,
+    format: Option<ArtworkFormat>,
+) -> Result<String, String> {
+    let np = state
+        .lock()
+        .last_now_playing
+        .clone()
+        .filter(|np| np.track_name.is_some())
+        .ok_or("Nothing is playing")?;
+    let format = format.unwrap_or_default();
+
+    let local = np
+        .artwork_path
+        .as_deref()
+        .filter(|p| Path::new(p).exists())
+        .and_then(|p| image::open(p).ok());
+    let img = match local {
+        Some(img) => img,
+        None => {
+            let url = match largest_spotify_artwork(&state, &np).await {
+                Some(url) => url,
+                None => np.artwork_url.clone().ok_or("No artwork for this track")?,
+            };
+            fetch_image(&url).await?
+        }
+    };
+    let img = match size {
+        Some(0) => return Err("Size must be at least 1 pixel".into()),
+        Some(s) => img.resize(s, s, image::imageops::FilterType::Lanczos3),
+        None => img,
+    };
+
+    let mut target = PathBuf::from(path);
+    if target.is_dir() {
+        target.push(format!("artwork.{}", format.extension()));
+    }
+    if let Some(dir) = target.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    }
+    let result = match format {
+        ArtworkFormat::Png => img.save_with_format(&target, image::ImageFormat::Png),
+        // JPEG has no alpha channel
+        ArtworkFormat::Jpeg => image::DynamicImage::ImageRgb8(img.to_rgb8())
+            .save_with_format(&target, image::ImageFormat::Jpeg),
+    };
+    result.map_err(|e| format!("write {}: {e}", target.display()))?;
+
+    crate::usage::record(window.app_handle(), "artwork_exports");
+    Ok(target.to_string_lossy().to_string())
+}
+
+// Every enabled profile's directory, or `<exe dir>/Exported-track` when none are set up
+pub fn output_dirs(state: &SharedStore) -> Result<Vec<PathBuf>, String> {
+    let dirs: Vec<PathBuf> = state
+        .lock()
+        .export_profiles
+        .iter()
+        .filter(|p| p.enabled)
+        .map(|p| PathBuf::from(&p.dir))
+        .collect();
+    if dirs.is_empty() {
+        return Ok(vec![default_dir()?]);
+    }
+    Ok(dirs)
+}
+
+// The enabled profiles that are only written on request; the fallback profile, with the
+// overlay placeholder, when none are set up. Empty when every profile updates by itself (see
+// `auto_export`).
+pub fn manual_outputs(state: &SharedStore) -> Result<Vec<ExportProfile>, String> {
+    let s = state.lock();
+    if !s.export_profiles.iter().any(|p| p.enabled) {
+        return Ok(vec![ExportProfile {
+            placeholder: s.overlay_placeholder.clone(),
+            ..ExportProfile::fallback()?
+        }]);
+    }
+    Ok(s.export_profiles
+        .iter()
+        .filter(|p| p.enabled && p.trigger == ExportTrigger::Manual)
+        .cloned()
+        .collect())
+}
+
+// `template` with every `{stem}` replaced by the built-in file `stem.txt`
+pub fn fill_template(template: &str, files: &[(&'static str, String)]) -> String {
+    files
+        .iter()
+        .fold(template.to_string(), |out, (name, value)| {
+            let stem = name.trim_end_matches(".txt");
+            out.replace(&format!("{{{stem}}}"), value)
+        })
+}
+
+fn resize_png(png: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory(png).map_err(|e| format!("decode artwork: {e}"))?;
+    encode_png(&img.resize(size, size, image::imageops::FilterType::Lanczos3))
+}
+
+// (file name, content type, contents)
+pub type OutputFile = (String, &'static str, Vec<u8>);
+
+// `rendered` the way `profile` wants it: its format, templates and artwork size
+pub fn profile_files(
+    rendered: &RenderedExport,
+    profile: &ExportProfile,
+) -> Result<Vec<OutputFile>, WriteError> {
+    let format = &profile.format;
+    let text = |name: &str, contents: &str| {
+        let contents = format.encode(contents);
+        (format.file_name(name), format.content_type(), contents)
+    };
+    let mut files: Vec<_> = rendered
+        .files
+        .iter()
+        .filter(|(name, _)| !profile.templates.contains_key(*name))
+        .map(|(name, contents)| text(name, contents))
+        .collect();
+    for (name, template) in &profile.templates {
+        files.push(text(name, &fill_template(template, &rendered.files)));
+    }
+    if let Some(png) = &rendered.artwork_png {
+        let png = match profile.artwork_size {
+            Some(size) => resize_png(png, size).map_err(|message| WriteError {
+                code: "bad_artwork",
+                message,
+            })?,
+            None => png.clone(),
+        };
+        files.push((format.file_name("artwork.png"), "image/png", png));
+    }
+    Ok(files)
+}
+
+// Writes `profile_files` into `dir`
+fn write_files(dir: &Path, files: &[OutputFile]) -> Result<(), WriteError> {
+    let long = long_path(dir);
+    fs::create_dir_all(&long).map_err(|e| WriteError::new(e, dir))?;
+    for (name, _, contents) in files {
+        fs::write(long.join(name), contents).map_err(|e| WriteError::new(e, &dir.join(name)))?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+pub struct ExportFallback {
+    pub profile: String,
+    // why the profile's own directory failed (see `WriteError`)
+    pub code: &'static str,
+    pub message: String,
+    // where the files went instead
+    pub dir: String,
+}
+
+// profiles whose last write went to the fallback, so the event is sent once per failure streak
+static FALLEN_BACK: Lazy<Mutex<HashSet<String>>> = Lazy::new(Mutex::default);
+
+// `<data dir>/exports/<profile name>`
+fn fallback_dir(app: &tauri::AppHandle, profile: &ExportProfile) -> Result<PathBuf, String> {
+    let name = match sanitize(&profile.name) {
+        name if name.is_empty() => "default".to_string(),
+        name => name,
+    };
+    Ok(crate::portable::data_dir(app)?.join("exports").join(name))
+}
+
+// Writes `profile_files` into the profile's directory, or into `fallback_dir` when that refuses
+// them, and into `virtual_files`. Returns the directory written.
+pub fn write_with_fallback(
+    app: &tauri::AppHandle,
+    rendered: &RenderedExport,
+    profile: &ExportProfile,
+) -> Result<PathBuf, String> {
+    let files = profile_files(rendered, profile)?;
+    crate::virtual_files::set(&profile.name, files.clone());
+    let e = match write_files(Path::new(&profile.dir), &files) {
+        Ok(()) => {
+            FALLEN_BACK.lock().remove(&profile.name);
+            return Ok(PathBuf::from(&profile.dir));
+        }
+        Err(e) => e,
+    };
+    let dir = fallback_dir(app, profile)?;
+    write_files(&dir, &files)
+        .map_err(|f| format!("{}; the fallback failed too: {}", e.message, f.message))?;
+    if FALLEN_BACK.lock().insert(profile.name.clone()) {
+        eprintln!("[export] {}: {}", profile.name, e.message);
+        crate::events::emit(
+            app,
+            "export_fallback",
+            ExportFallback {
+                profile: profile.name.clone(),
+                code: e.code,
+                message: e.message,
+                dir: dir.to_string_lossy().to_string(),
+            },
+        );
+    }
+    Ok(dir)
+}
+
+// Writes the export into every enabled manual profile's directory, or `<exe dir>/Exported-track`
+// when no profiles are set up. Returns the first directory written, or the first profile's when
+// they all update by themselves.
+#[tauri::command]
+pub async fn write_now_playing_assets(
+    window: tauri::Window,
+    payload: ExportPayload,
+) -> Result<String, String> {
+    export_manual(window.app_handle(), &payload).await
+}
+
+pub async fn export_manual(
+    app: &tauri::AppHandle,
+    payload: &ExportPayload,
+) -> Result<String, String> {
+    let state = app.state::<SharedStore>();
+    let outputs = manual_outputs(&state)?;
+    let Some(first) = outputs.first() else {
+        return Ok(output_dirs(&state)?[0].to_string_lossy().to_string());
+    };
+
+    let rendered = render_export(payload).await?;
+    let _writing = crate::auto_export::WRITING.lock().await;
+    let dir = write_with_fallback(app, &rendered, first)?;
+    for profile in &outputs[1..] {
+        write_with_fallback(app, &rendered, profile)?;
+    }
+    crate::auto_export::showing_track(&outputs);
+
+    crate::usage::record(app, "exports");
+    Ok(dir.to_string_lossy().to_string())
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct ExportPreview {
+    // file name -> contents
+    files: std::collections::BTreeMap<String, String>,
+    // `data:image/png;base64,...`, ready for an <img>
+    artwork: Option<String>,
+}
+
+// Same output as `write_now_playing_assets` without writing anything, for live previews in
+// settings. Without a payload it previews the track currently showing.
+#[tauri::command]
+pub async fn preview_export(
+    state: State<'_, SharedStore>,
+    payload: Option<ExportPayload>,
+) -> Result<ExportPreview, String> {
+    use base64::Engine;
+
+    let payload = match payload {
+        Some(p) => p,
+        None => state
+            .lock()
+            .last_now_playing
+            .as_ref()
+            .map(ExportPayload::from)
+            .ok_or("Nothing is playing")?,
+    };
+    let rendered = render_export(&payload).await?;
+    Ok(ExportPreview {
+        files: rendered
+            .files
+            .into_iter()
+            .map(|(name, contents)| (name.to_string(), contents))
+            .collect(),
+        artwork: rendered.artwork_png.map(|png| {
+            format!(
+                "data:image/png;base64,{}",
+                base64::engine::general_purpose::STANDARD.encode(png)
+            )
+        }),
+    })
+}
+
+#[tauri::command]
+pub fn get_export_profiles(state: State<'_, SharedStore>) -> Vec<ExportProfile> {
+    state.lock().export_profiles.clone()
+}
+
+// Checks profiles without saving, for inline validation while editing
+#[tauri::command]
+pub fn validate_export_profiles(profiles: Vec<ExportProfile>) -> Vec<ExportIssue> {
+    validate(&profiles)
+}
+
+// Nothing is saved unless every profile passes
+#[tauri::command]
+pub fn set_export_profiles(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    profiles: Vec<ExportProfile>,
+) -> Result<(), Vec<ExportIssue>> {
+    let issues = validate(&profiles);
+    if !issues.is_empty() {
+        return Err(issues);
+    }
+    crate::write_setting(
+        window.app_handle(),
+        "export_profiles",
+        serde_json::json!(profiles),
+    )
+    .map_err(|e| {
+        vec![ExportIssue {
+            profile: String::new(),
+            code: "save_failed",
+            message: e,
+        }]
+    })?;
+    crate::virtual_files::retain(|name| name.is_empty() || profiles.iter().any(|p| p.name == name));
+    state.lock().export_profiles = profiles;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+// How an export profile writes its files, for tools that don't take the default UTF-8 without
+// a line ending: OBS text sources on some Windows locales read BOM-less UTF-8 as the ANSI code
+// page, and older tools want UPPERCASE names or CRLF.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    // little-endian with a BOM, what Windows calls "Unicode"
+    Utf16,
+    // characters outside the code page become "?"
+    Windows1252,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Newline {
+    // contents as they are, no line ending at the end
+    #[default]
+    None,
+    Lf,
+    Crlf,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FileCase {
+    // song.txt
+    #[default]
+    Lower,
+    // SONG.TXT
+    Upper,
+    // Song.txt
+    Title,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug, JsonSchema)]
+#[serde(default)]
+pub struct TextFormat {
+    pub encoding: TextEncoding,
+    pub newline: Newline,
+    pub file_case: FileCase,
+}
+
+// cp1252 0x80-0x9F; the unassigned slots are never produced
+const CP1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+fn windows_1252(c: char) -> u8 {
+    match c as u32 {
+        n @ (0..=0x7F | 0xA0..=0xFF) => n as u8,
+        _ => CP1252_HIGH
+            .iter()
+            .position(|&h| h == c && !('\u{80}'..='\u{9F}').contains(&h))
+            .map_or(b'?', |i| 0x80 + i as u8),
+    }
+}
+
+impl TextFormat {
+    pub fn file_name(&self, name: &str) -> String {
+        match self.file_case {
+            FileCase::Lower => name.to_string(),
+            FileCase::Upper => name.to_uppercase(),
+            FileCase::Title => {
+                let mut chars = name.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    // of what `encode` returns
+    pub fn content_type(&self) -> &'static str {
+        match self.encoding {
+            TextEncoding::Utf8 | TextEncoding::Utf8Bom => "text/plain; charset=utf-8",
+            TextEncoding::Utf16 => "text/plain; charset=utf-16",
+            TextEncoding::Windows1252 => "text/plain; charset=windows-1252",
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let text = match self.newline {
+            Newline::None => text.to_string(),
+            Newline::Lf => format!("{}\n", text.replace("\r\n", "\n")),
+            Newline::Crlf => format!("{}\r\n", text.replace("\r\n", "\n").replace('\n', "\r\n")),
+        };
+        match self.encoding {
+            TextEncoding::Utf8 => text.into_bytes(),
+            TextEncoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+            TextEncoding::Utf16 => [0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            TextEncoding::Windows1252 => text.chars().map(windows_1252).collect(),
+        }
+    }
+}
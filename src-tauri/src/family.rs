@@ -0,0 +1,121 @@
+// Family-friendly mode: masks profanity in titles/artists/albums, and can hide tracks the
+// source flags as explicit (only Spotify reports the flag).
+
+use crate::{read_settings, write_setting, NowPlaying, SharedStore};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+const DEFAULT_WORDS: &[&str] = &[
+    "fuck",
+    "fucking",
+    "fucked",
+    "fucker",
+    "shit",
+    "shitty",
+    "bitch",
+    "bitches",
+    "asshole",
+    "dick",
+    "pussy",
+    "cunt",
+    "motherfucker",
+    "damn",
+    "goddamn",
+    "bastard",
+    "whore",
+];
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplicitAction {
+    // keep the track, mask listed words
+    #[default]
+    Mask,
+    // show nothing for explicit tracks
+    Hide,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, schemars::JsonSchema)]
+#[serde(default)]
+pub struct FamilyFriendly {
+    pub enabled: bool,
+    pub explicit_action: ExplicitAction,
+    // on top of the built-in list
+    pub extra_words: Vec<String>,
+}
+
+impl FamilyFriendly {
+    fn pattern(&self) -> Option<Regex> {
+        let words: Vec<String> = DEFAULT_WORDS
+            .iter()
+            .map(|w| w.to_string())
+            .chain(self.extra_words.iter().map(|w| w.trim().to_lowercase()))
+            .filter(|w| !w.is_empty())
+            .map(|w| regex::escape(&w))
+            .collect();
+        Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|"))).ok()
+    }
+}
+
+pub fn load(app: &tauri::AppHandle) -> FamilyFriendly {
+    read_settings(app)
+        .get("family_friendly")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+// "shit" -> "s***"
+fn mask(re: &Regex, s: &str) -> String {
+    re.replace_all(s, |c: &regex::Captures| {
+        let word = &c[0];
+        let mut chars = word.chars();
+        let first = chars.next().map(String::from).unwrap_or_default();
+        first + &"*".repeat(chars.count())
+    })
+    .into_owned()
+}
+
+// Applied to everything the watcher emits, so overlays and exports get the cleaned version.
+pub fn apply(state: &SharedStore, np: &mut NowPlaying) {
+    let config = state.lock().family_friendly.clone();
+    if !config.enabled {
+        return;
+    }
+    if np.explicit && config.explicit_action == ExplicitAction::Hide {
+        *np = NowPlaying {
+            is_playing: np.is_playing,
+            explicit: true,
+            ..Default::default()
+        };
+        return;
+    }
+    let Some(re) = config.pattern() else {
+        return;
+    };
+    np.track_name = np.track_name.as_deref().map(|t| mask(&re, t));
+    np.album = np.album.as_deref().map(|a| mask(&re, a));
+    np.artists = np.artists.iter().map(|a| mask(&re, a)).collect();
+}
+
+#[tauri::command]
+pub fn get_family_friendly(state: State<'_, SharedStore>) -> FamilyFriendly {
+    state.lock().family_friendly.clone()
+}
+
+#[tauri::command]
+pub fn set_family_friendly(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: FamilyFriendly,
+) -> Result<(), String> {
+    write_setting(
+        window.app_handle(),
+        "family_friendly",
+        serde_json::json!(config),
+    )?;
+    state.lock().family_friendly = config;
+    Ok(())
+}
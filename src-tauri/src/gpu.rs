@@ -0,0 +1,446 @@
+// Optional GPU path for the heavy image work (blurred background + cover composite of the OBS
+// layouts, and the thumbnail their palette is counted on). A 3000px cover blurred and resized on every track change is a noticeable CPU spike
+// on a streaming PC that's also running a game; a compute shader does the same in a few
+// milliseconds. Off unless turned on with `set_gpu_images`, only built with the `gpu` feature,
+// and any failure (no adapter, cover over the texture size limit, device lost) falls back to
+// the CPU code in the caller.
+
+use crate::{read_settings, write_setting, SharedStore};
+use image::{DynamicImage, RgbaImage};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{Manager, State};
+
+// Cover drawn at `at` (size `cover`) over a blurred, darkened fill of the whole frame
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+pub struct Composite {
+    pub width: u32,
+    pub height: u32,
+    pub cover: u32,
+    pub at: (u32, u32),
+    // in frame pixels
+    pub blur_sigma: f32,
+    pub brightness: f32,
+}
+
+pub fn load_enabled(app: &tauri::AppHandle) -> bool {
+    read_settings(app)
+        .get("gpu_images")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(feature = "gpu")]
+mod device {
+    use super::Composite;
+    use image::{DynamicImage, RgbaImage};
+    use std::sync::{mpsc, OnceLock};
+
+    const WORKGROUP: u32 = 8;
+
+    const SHADER: &str = r#"
+struct Params {
+    frame: vec2<f32>,
+    src: vec2<f32>,
+    cover_at: vec2<f32>,
+    cover_size: f32,
+    sigma: f32,
+    brightness: f32,
+}
+
+@group(0) @binding(0) var src: texture_2d<f32>;
+@group(0) @binding(1) var samp: sampler;
+@group(0) @binding(2) var dst: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(3) var<uniform> p: Params;
+
+// UV of the centered crop of the source that fills a box of aspect `aspect`
+fn fill_uv(uv: vec2<f32>, aspect: f32) -> vec2<f32> {
+    let src_aspect = p.src.x / p.src.y;
+    var scale = vec2<f32>(1.0, 1.0);
+    if (src_aspect > aspect) {
+        scale.x = aspect / src_aspect;
+    } else {
+        scale.y = src_aspect / aspect;
+    }
+    return (uv - 0.5) * scale + 0.5;
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (f32(id.x) >= p.frame.x || f32(id.y) >= p.frame.y) {
+        return;
+    }
+    let pos = vec2<f32>(id.xy) + 0.5;
+
+    // background: gaussian over a 13x13 grid spanning +-2 sigma
+    var acc = vec3<f32>(0.0);
+    var weight = 0.0;
+    let step = p.sigma / 3.0;
+    for (var j = -6; j <= 6; j++) {
+        for (var i = -6; i <= 6; i++) {
+            let off = vec2<f32>(f32(i), f32(j)) * step;
+            let w = exp(-dot(off, off) / (2.0 * p.sigma * p.sigma));
+            let uv = fill_uv(clamp((pos + off) / p.frame, vec2<f32>(0.0), vec2<f32>(1.0)), p.frame.x / p.frame.y);
+            acc += textureSampleLevel(src, samp, uv, 0.0).rgb * w;
+            weight += w;
+        }
+    }
+    var color = acc / weight * p.brightness;
+
+    // cover: 4x4 supersampled so a big source doesn't alias when shrunk
+    let local = pos - p.cover_at;
+    if (all(local >= vec2<f32>(0.0)) && all(local < vec2<f32>(p.cover_size))) {
+        var fg = vec4<f32>(0.0);
+        for (var j = 0; j < 4; j++) {
+            for (var i = 0; i < 4; i++) {
+                let sub = local - 0.5 + (vec2<f32>(f32(i), f32(j)) + 0.5) / 4.0;
+                fg += textureSampleLevel(src, samp, fill_uv(sub / p.cover_size, 1.0), 0.0);
+            }
+        }
+        fg /= 16.0;
+        color = fg.rgb * fg.a + color * (1.0 - fg.a);
+    }
+    textureStore(dst, vec2<i32>(id.xy), vec4<f32>(color, 1.0));
+}
+"#;
+
+    pub struct Gpu {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        pipeline: wgpu::ComputePipeline,
+        layout: wgpu::BindGroupLayout,
+        sampler: wgpu::Sampler,
+        max_dim: u32,
+        pub adapter: String,
+    }
+
+    // Set up once; `None` when there's no usable adapter, which is remembered so we don't keep
+    // probing on every track
+    pub fn get() -> Option<&'static Gpu> {
+        static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+        GPU.get_or_init(|| match pollster::block_on(init()) {
+            Ok(gpu) => {
+                eprintln!("[gpu] using {}", gpu.adapter);
+                Some(gpu)
+            }
+            Err(e) => {
+                eprintln!("[gpu] unavailable, images stay on the CPU: {e}");
+                None
+            }
+        })
+        .as_ref()
+    }
+
+    async fn init() -> Result<Gpu, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        // the game gets the fast GPU; this is little work
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("no adapter: {e}"))?;
+        let info = adapter.get_info();
+        let limits = adapter.limits();
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("images"),
+                required_limits: limits.clone(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("request device: {e}"))?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("composite"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("composite"),
+            entries: &[
+                entry(
+                    0,
+                    wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                ),
+                entry(
+                    1,
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                ),
+                entry(
+                    2,
+                    wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                ),
+                entry(
+                    3,
+                    wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                ),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("composite"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("composite"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Gpu {
+            device,
+            queue,
+            pipeline,
+            layout,
+            sampler,
+            max_dim: limits.max_texture_dimension_2d,
+            adapter: format!("{} ({:?})", info.name, info.backend),
+        })
+    }
+
+    impl Gpu {
+        pub fn compose(&self, img: &DynamicImage, c: &Composite) -> Result<RgbaImage, String> {
+            let src = img.to_rgba8();
+            let (sw, sh) = src.dimensions();
+            if sw == 0 || sh == 0 {
+                return Err("empty image".into());
+            }
+            if sw.max(sh).max(c.width).max(c.height) > self.max_dim {
+                return Err(format!("{sw}x{sh} is over the GPU's texture limit"));
+            }
+            let device = &self.device;
+
+            let src_size = wgpu::Extent3d {
+                width: sw,
+                height: sh,
+                depth_or_array_layers: 1,
+            };
+            let src_tex = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("cover"),
+                size: src_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            self.queue.write_texture(
+                src_tex.as_image_copy(),
+                &src,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * sw),
+                    rows_per_image: None,
+                },
+                src_size,
+            );
+
+            let dst_size = wgpu::Extent3d {
+                width: c.width,
+                height: c.height,
+                depth_or_array_layers: 1,
+            };
+            let dst_tex = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("frame"),
+                size: dst_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+
+            // matches `Params` in the shader, padded to 16 bytes
+            let params: Vec<u8> = [
+                c.width as f32,
+                c.height as f32,
+                sw as f32,
+                sh as f32,
+                c.at.0 as f32,
+                c.at.1 as f32,
+                c.cover as f32,
+                c.blur_sigma.max(0.5),
+                c.brightness,
+                0.0,
+                0.0,
+                0.0,
+            ]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+            let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("params"),
+                size: params.len() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.queue.write_buffer(&uniform, 0, &params);
+
+            let src_view = src_tex.create_view(&Default::default());
+            let dst_view = dst_tex.create_view(&Default::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("composite"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&src_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&dst_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            });
+
+            // rows of a texture -> buffer copy have to be 256-byte aligned
+            let row = 4 * c.width;
+            let padded_row = row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+                * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+            let readback = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("readback"),
+                size: u64::from(padded_row) * u64::from(c.height),
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(
+                    c.width.div_ceil(WORKGROUP),
+                    c.height.div_ceil(WORKGROUP),
+                    1,
+                );
+            }
+            encoder.copy_texture_to_buffer(
+                dst_tex.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &readback,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row),
+                        rows_per_image: None,
+                    },
+                },
+                dst_size,
+            );
+            self.queue.submit([encoder.finish()]);
+
+            let slice = readback.slice(..);
+            let (tx, rx) = mpsc::channel();
+            slice.map_async(wgpu::MapMode::Read, move |res| {
+                let _ = tx.send(res);
+            });
+            device
+                .poll(wgpu::PollType::Wait)
+                .map_err(|e| format!("poll: {e}"))?;
+            rx.recv()
+                .map_err(|_| "readback dropped".to_string())?
+                .map_err(|e| format!("map readback: {e}"))?;
+
+            let mut pixels = Vec::with_capacity((row * c.height) as usize);
+            {
+                let data = slice.get_mapped_range();
+                for line in data.chunks_exact(padded_row as usize) {
+                    pixels.extend_from_slice(&line[..row as usize]);
+                }
+            }
+            readback.unmap();
+            RgbaImage::from_raw(c.width, c.height, pixels)
+                .ok_or_else(|| "readback size mismatch".to_string())
+        }
+    }
+}
+
+// Err means "do it on the CPU"
+#[cfg(feature = "gpu")]
+pub fn compose(img: &DynamicImage, c: &Composite) -> Result<RgbaImage, String> {
+    device::get().ok_or("no GPU adapter")?.compose(img, c)
+}
+
+#[cfg(not(feature = "gpu"))]
+pub fn compose(_img: &DynamicImage, _c: &Composite) -> Result<RgbaImage, String> {
+    Err("built without the gpu feature".into())
+}
+
+#[cfg(feature = "gpu")]
+fn adapter() -> Option<String> {
+    device::get().map(|g| g.adapter.clone())
+}
+
+#[cfg(not(feature = "gpu"))]
+fn adapter() -> Option<String> {
+    None
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GpuStatus {
+    pub enabled: bool,
+    // adapter the images would be processed on; None when only the CPU path is available
+    pub adapter: Option<String>,
+}
+
+#[tauri::command]
+pub async fn get_gpu_status(state: State<'_, SharedStore>) -> Result<GpuStatus, String> {
+    let enabled = state.lock().gpu_images;
+    // first call sets up the device, which can take a moment
+    let adapter = tauri::async_runtime::spawn_blocking(adapter)
+        .await
+        .map_err(|e| format!("gpu probe: {e}"))?;
+    Ok(GpuStatus { enabled, adapter })
+}
+
+#[tauri::command]
+pub fn set_gpu_images(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    enabled: bool,
+) -> Result<(), String> {
+    write_setting(
+        window.app_handle(),
+        "gpu_images",
+        serde_json::json!(enabled),
+    )?;
+    state.lock().gpu_images = enabled;
+    Ok(())
+}
@@ -0,0 +1,927 @@
+// Windows GlobalSystemMediaTransportControls (GSMTC) sessions: whatever desktop player is
+// registered with the OS media overlay (Spotify app, Apple Music, browsers, ...).
+
+use crate::playback_change::{self, Change, Seen, SessionChange};
+use crate::progress::PlaybackProgress;
+use crate::{
+    compilation, dedup_push, events, looks_like_artists_block, parse_artists,
+    parse_artists_prefix_from_title, parse_featured_from_title, providers, read_settings,
+    spotify_search, watchdog, write_setting, NowPlaying, SharedStore,
+};
+use futures::executor::block_on;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+use tokio_util::sync::CancellationToken;
+use windows::Foundation::TypedEventHandler;
+use windows::Media::Control::{
+    GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
+    GlobalSystemMediaTransportControlsSessionMediaProperties,
+};
+use windows::Media::MediaPlaybackAutoRepeatMode;
+use windows::Storage::Streams::{DataReader, InputStreamOptions};
+
+type Payload = (serde_json::Value, Option<String>);
+
+async fn session_manager() -> Result<GlobalSystemMediaTransportControlsSessionManager, String> {
+    GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
+        .map_err(|e| format!("RequestAsync failed: {:?}", e))?
+        .await
+        .map_err(|e| format!("Await manager failed: {:?}", e))
+}
+
+// Reads the preferred GSMTC session. Returns the payload plus a "title|artist|album" key
+// used for change detection.
+pub async fn read_gsmtc(app_handle: tauri::AppHandle) -> Result<Payload, String> {
+    let (pinned, priority) = {
+        let state = app_handle.state::<SharedStore>();
+        let s = state.lock();
+        (s.gsmtc_pinned_session.clone(), s.gsmtc_priority.clone())
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        block_on(watchdog::within(
+            "GSMTC read",
+            watchdog::GSMTC_TIMEOUT,
+            async move {
+                let mgr = session_manager().await?;
+                let Some(session) = preferred_session(&mgr, pinned.as_deref(), &priority) else {
+                    return Ok((serde_json::json!({"error": "No active session"}), None));
+                };
+                session_payload(&app_handle, &session).await
+            },
+        ))?
+    })
+    .await
+    .map_err(|e| format!("spawn_blocking join error: {e}"))?
+}
+
+// A session pinned with `select_media_session` wins (exact AUMID, and nothing at all when that
+// player isn't running); then the priority list; otherwise Spotify, then whatever Windows
+// considers current.
+fn preferred_session(
+    mgr: &GlobalSystemMediaTransportControlsSessionManager,
+    pinned: Option<&str>,
+    priority: &[String],
+) -> Option<GlobalSystemMediaTransportControlsSession> {
+    let sessions: Vec<_> = mgr
+        .GetSessions()
+        .ok()
+        .map(|list| {
+            (0..list.Size().unwrap_or(0))
+                .filter_map(|i| list.GetAt(i).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let aumid = |s: &GlobalSystemMediaTransportControlsSession| {
+        s.SourceAppUserModelId()
+            .map(|id| id.to_string())
+            .unwrap_or_default()
+    };
+
+    if let Some(pinned) = pinned {
+        return sessions
+            .into_iter()
+            .find(|s| aumid(s).eq_ignore_ascii_case(pinned));
+    }
+    if let Some(best) = by_priority(&sessions, priority) {
+        return Some(best);
+    }
+    sessions
+        .into_iter()
+        .find(|s| aumid(s).to_ascii_lowercase().contains("spotify"))
+        .or_else(|| mgr.GetCurrentSession().ok())
+}
+
+// Rank in the priority list (AUMID substrings, case-insensitive), lower is better
+fn priority_rank(priority: &[String], aumid: &str) -> Option<usize> {
+    let aumid = aumid.to_lowercase();
+    priority
+        .iter()
+        .position(|p| !p.is_empty() && aumid.contains(&p.to_lowercase()))
+}
+
+// The highest-priority listed session that is playing, else the highest-priority paused
+// one. `None` when no listed player is playing or paused, so the default pick applies.
+fn by_priority(
+    sessions: &[GlobalSystemMediaTransportControlsSession],
+    priority: &[String],
+) -> Option<GlobalSystemMediaTransportControlsSession> {
+    use windows::Media::Control::GlobalSystemMediaTransportControlsSessionPlaybackStatus as Status;
+
+    // (paused?, rank) orders playing before paused, then by rank
+    sessions
+        .iter()
+        .filter_map(|session| {
+            let aumid = session.SourceAppUserModelId().ok()?.to_string();
+            let rank = priority_rank(priority, &aumid)?;
+            let status = session.GetPlaybackInfo().ok()?.PlaybackStatus().ok()?;
+            let paused = match status {
+                Status::Playing => false,
+                Status::Paused => true,
+                _ => return None,
+            };
+            Some(((paused, rank), session))
+        })
+        .min_by_key(|(order, _)| *order)
+        .map(|(_, session)| session.clone())
+}
+
+// Session whose AUMID contains `app_match` (case-insensitive). With `fallback` the system's
+// current session is used when nothing matches; without it a "No active session" payload
+// comes back so one app's data is never reported as another's.
+pub async fn read_app_session(
+    app_handle: tauri::AppHandle,
+    app_match: &'static str,
+    fallback: bool,
+) -> Result<Payload, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        block_on(watchdog::within(
+            "GSMTC app session",
+            watchdog::GSMTC_TIMEOUT,
+            async move {
+                let mgr = session_manager().await?;
+
+                let matched = mgr.GetSessions().ok().and_then(|list| {
+                    (0..list.Size().unwrap_or(0))
+                        .filter_map(|i| list.GetAt(i).ok())
+                        .find(|s| {
+                            s.SourceAppUserModelId().is_ok_and(|aumid| {
+                                aumid.to_string().to_ascii_lowercase().contains(app_match)
+                            })
+                        })
+                });
+                let session: Option<GlobalSystemMediaTransportControlsSession> = if fallback {
+                    matched.or_else(|| mgr.GetCurrentSession().ok())
+                } else {
+                    matched
+                };
+
+                let Some(session) = session else {
+                    return Ok((serde_json::json!({"error": "No active session"}), None));
+                };
+
+                session_payload(&app_handle, &session).await
+            },
+        ))?
+    })
+    .await
+    .map_err(|e| format!("spawn_blocking join error: {e}"))?
+}
+
+// Every registered session, in the order Windows reports them. Sessions that fail to read
+// are skipped rather than failing the whole list.
+pub async fn read_all_gsmtc(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<serde_json::Value>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        block_on(watchdog::within(
+            "GSMTC sessions",
+            watchdog::GSMTC_TIMEOUT,
+            async move {
+                let mgr = session_manager().await?;
+                let list = mgr
+                    .GetSessions()
+                    .map_err(|e| format!("GetSessions: {:?}", e))?;
+
+                let mut out = Vec::new();
+                for i in 0..list.Size().unwrap_or(0) {
+                    let Ok(session) = list.GetAt(i) else {
+                        continue;
+                    };
+                    match session_payload(&app_handle, &session).await {
+                        Ok((payload, _)) => out.push(payload),
+                        Err(e) => eprintln!("[gsmtc] session {i}: {e}"),
+                    }
+                }
+                Ok(out)
+            },
+        ))?
+    })
+    .await
+    .map_err(|e| format!("spawn_blocking join error: {e}"))?
+}
+
+// Thumbnail → bytes → artcache/<artist>_<album>_<title>.png. The file is reused when it
+// exists, so play/pause events don't re-decode the same cover.
+async fn cache_thumbnail(
+    app_handle: &tauri::AppHandle,
+    props: &GlobalSystemMediaTransportControlsSessionMediaProperties,
+    artist: &str,
+    album: &str,
+    title: &str,
+) -> Result<Option<String>, String> {
+    let Ok(th) = props.Thumbnail() else {
+        return Ok(None);
+    };
+
+    // Use the cloned app handle (not `window`) here.
+    let cache_dir = crate::portable::data_dir(app_handle)?.join("artcache");
+    let safe = |s: &str| {
+        s.chars()
+            .map(|c| if r#"\/:*?"<>|"#.contains(c) { '_' } else { c })
+            .collect::<String>()
+    };
+    let stem = format!("{}_{}_{}", safe(artist), safe(album), safe(title));
+    let png_path = cache_dir.join(format!("{stem}.png"));
+    let raw_path = cache_dir.join(format!("{stem}.bin"));
+    for p in [&png_path, &raw_path] {
+        if p.is_file() {
+            return Ok(Some(p.to_string_lossy().to_string()));
+        }
+    }
+
+    let stream = th
+        .OpenReadAsync()
+        .map_err(|e| format!("OpenReadAsync: {:?}", e))?
+        .await
+        .map_err(|e| format!("OpenReadAsync await: {:?}", e))?;
+    let input = stream
+        .GetInputStreamAt(0)
+        .map_err(|e| format!("GetInputStreamAt: {:?}", e))?;
+    let size = (stream.Size().unwrap_or(0).min(u64::from(u32::MAX))) as u32;
+    if size == 0 {
+        return Ok(None);
+    }
+    let reader =
+        DataReader::CreateDataReader(&input).map_err(|e| format!("CreateDataReader: {:?}", e))?;
+    reader
+        .SetInputStreamOptions(InputStreamOptions::ReadAhead)
+        .map_err(|e| format!("SetInputStreamOptions: {:?}", e))?;
+    reader
+        .LoadAsync(size)
+        .map_err(|e| format!("LoadAsync: {:?}", e))?
+        .await
+        .map_err(|e| format!("LoadAsync await: {:?}", e))?;
+
+    let mut bytes = vec![0u8; size as usize];
+    reader
+        .ReadBytes(bytes.as_mut_slice())
+        .map_err(|e| format!("ReadBytes: {:?}", e))?;
+
+    let _ = std::fs::create_dir_all(&cache_dir);
+    let path = match image::load_from_memory(&bytes) {
+        Ok(img) => {
+            img.save(&png_path).map_err(|e| format!("save png: {e}"))?;
+            png_path
+        }
+        Err(_) => {
+            std::fs::write(&raw_path, &bytes).map_err(|e| format!("write thumb: {e}"))?;
+            raw_path
+        }
+    };
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+async fn session_payload(
+    app_handle: &tauri::AppHandle,
+    session: &GlobalSystemMediaTransportControlsSession,
+) -> Result<Payload, String> {
+    let info = session.GetPlaybackInfo().ok();
+    let status = info
+        .as_ref()
+        .and_then(|info| info.PlaybackStatus().ok())
+        .map(|s| format!("{:?}", s))
+        .unwrap_or_else(|| "Unknown".to_string());
+    let playback_rate = info
+        .as_ref()
+        .and_then(|info| info.PlaybackRate().ok())
+        .and_then(|r| r.Value().ok());
+    let repeat_mode = info
+        .as_ref()
+        .and_then(|info| info.AutoRepeatMode().ok())
+        .and_then(|r| r.Value().ok())
+        .map(|r| match r {
+            MediaPlaybackAutoRepeatMode::Track => "track",
+            MediaPlaybackAutoRepeatMode::List => "context",
+            _ => "off",
+        });
+
+    let props = session
+        .TryGetMediaPropertiesAsync()
+        .map_err(|e| format!("TryGetMediaPropertiesAsync: {:?}", e))?
+        .await
+        .map_err(|e| format!("await media properties: {:?}", e))?;
+
+    let title = props.Title().unwrap_or_default().to_string();
+    let album = props.AlbumTitle().unwrap_or_default().to_string();
+    let artist = props.Artist().unwrap_or_default().to_string();
+    let album_artist = props.AlbumArtist().unwrap_or_default().to_string();
+    let subtitle = props.Subtitle().unwrap_or_default().to_string(); // ← NEW
+
+    let mut artists_vec: Vec<String> = Vec::new();
+
+    if artists_vec.len() <= 1 {
+        for n in parse_artists_prefix_from_title(&title) {
+            dedup_push(&mut artists_vec, &n);
+        }
+        for n in parse_artists_prefix_from_title(&subtitle) {
+            dedup_push(&mut artists_vec, &n);
+        }
+    }
+
+    // Primary “Artist”
+    for n in parse_artists(&artist) {
+        dedup_push(&mut artists_vec, &n);
+    }
+
+    // AlbumArtist often has multiple names (labels, teams, etc.); "Various Artists" names nobody
+    let compilation = compilation::is_various(&album_artist);
+    if !compilation {
+        for n in parse_artists(&album_artist) {
+            dedup_push(&mut artists_vec, &n);
+        }
+    }
+
+    // Contributors embedded in the Title (“feat. …”, “with …”)
+    for n in parse_featured_from_title(&title) {
+        dedup_push(&mut artists_vec, &n);
+    }
+
+    // Only trust subtitle if it *looks* like a list of artists (commas, &, x, +, feat, etc.)
+    if looks_like_artists_block(&subtitle) {
+        for n in parse_artists(&subtitle) {
+            dedup_push(&mut artists_vec, &n);
+        }
+    }
+
+    // Also catch "(feat ...)" shapes inside the subtitle
+    for n in parse_featured_from_title(&subtitle) {
+        dedup_push(&mut artists_vec, &n);
+    }
+
+    // If we already have a proper multi-letter artist, drop stray 1-letter tokens like "Y"
+    let have_multi_alpha = artists_vec
+        .iter()
+        .any(|n| n.chars().filter(|c| c.is_alphabetic()).count() > 1);
+    if have_multi_alpha {
+        artists_vec.retain(|n| n.chars().filter(|c| c.is_alphabetic()).count() > 1);
+    }
+
+    // a broken thumbnail shouldn't cost us the rest of the payload
+    let artwork_path = cache_thumbnail(app_handle, &props, &artist, &album, &title)
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("[gsmtc] thumbnail: {e}");
+            None
+        });
+
+    let (position_ms, end_time_ms, last_updated_iso, timeline_updated_ms) =
+        match session.GetTimelineProperties() {
+            Ok(tl) => {
+                let pos_ms = tl.Position().ok().map(|ts| ts.Duration / 10_000);
+                let end_ms = tl.EndTime().ok().map(|ts| ts.Duration / 10_000);
+                let updated = tl.LastUpdatedTime().ok();
+                let last_updated = updated.map(|dt| format!("{:?}", dt));
+                let updated_ms = updated.map(|dt| filetime_to_unix_ms(dt.UniversalTime));
+                (pos_ms, end_ms, last_updated, updated_ms)
+            }
+            Err(_) => (None, None, None, None),
+        };
+
+    let payload = serde_json::json!({
+        "status": status,
+        "title": title,
+        "album": album,
+        "artist": artist,
+        "artists": artists_vec,
+        "compilation": compilation,
+        "position_ms": position_ms,
+        "end_time_ms": end_time_ms,
+        "last_updated": last_updated_iso,
+        "timeline_updated_ms": timeline_updated_ms,
+        "repeat_mode": repeat_mode,
+        "playback_rate": playback_rate,
+        "source_app_id": session.SourceAppUserModelId().ok().map(|s| s.to_string()),
+        "artwork_path": artwork_path
+    });
+
+    Ok((payload, Some(format!("{title}|{artist}|{album}"))))
+}
+
+pub fn to_now_playing(v: &serde_json::Value) -> Option<NowPlaying> {
+    let text = |k: &str| {
+        v.get(k)
+            .and_then(|x| x.as_str())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    let track_name = text("title")?;
+    Some(NowPlaying {
+        is_playing: v.get("status").and_then(|s| s.as_str()) == Some("Playing"),
+        track_name: Some(track_name),
+        artists: v
+            .get("artists")
+            .and_then(|a| serde_json::from_value(a.clone()).ok())
+            .unwrap_or_default(),
+        album: text("album"),
+        artwork_url: None,
+        artwork_path: text("artwork_path"),
+        position_ms: estimated_position_ms(v).and_then(|p| u64::try_from(p).ok()),
+        duration_ms: v
+            .get("end_time_ms")
+            .and_then(|e| e.as_u64())
+            .filter(|e| *e > 0),
+        repeat_mode: text("repeat_mode"),
+        source: Some(providers::Provider::from_app_id(
+            text("source_app_id").as_deref(),
+        )),
+        source_app_id: text("source_app_id"),
+        compilation: v.get("compilation").and_then(|c| c.as_bool()) == Some(true),
+        ..Default::default()
+    })
+}
+
+// WinRT DateTime: 100ns ticks since 1601-01-01
+fn filetime_to_unix_ms(ticks: i64) -> i64 {
+    (ticks - 116_444_736_000_000_000) / 10_000
+}
+
+fn unix_now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+// Apps only refresh the timeline every few seconds, so project the reported position
+// forward by the time since its last update while playing.
+fn estimated_position_ms(v: &serde_json::Value) -> Option<i64> {
+    let pos = v.get("position_ms")?.as_i64()?;
+    if v.get("status").and_then(|s| s.as_str()) != Some("Playing") {
+        return Some(pos);
+    }
+    let updated = v.get("timeline_updated_ms").and_then(|u| u.as_i64());
+    let rate = v
+        .get("playback_rate")
+        .and_then(|r| r.as_f64())
+        .unwrap_or(1.0);
+    let elapsed = updated.map_or(0, |u| (unix_now_ms() - u).max(0));
+    let pos = pos + (elapsed as f64 * rate) as i64;
+    // players don't always refresh the timeline right at the end of a track
+    let end = v
+        .get("end_time_ms")
+        .and_then(|e| e.as_i64())
+        .filter(|e| *e > 0);
+    Some(end.map_or(pos, |e| pos.min(e)))
+}
+
+// `playback_progress` payload: the interpolated position, so overlays can animate a progress
+// bar between the player's (sparse) timeline updates
+fn progress(v: &serde_json::Value) -> Option<PlaybackProgress> {
+    Some(PlaybackProgress {
+        position_ms: u64::try_from(estimated_position_ms(v)?).unwrap_or(0),
+        duration_ms: v.get("end_time_ms").and_then(|d| d.as_u64()),
+        is_playing: v.get("status").and_then(|s| s.as_str()) == Some("Playing"),
+        source_app_id: v
+            .get("source_app_id")
+            .and_then(|s| s.as_str())
+            .map(str::to_string),
+    })
+}
+
+// Compares against what was seen last (by the command or the event watcher) and emits
+// `gsmtc_track_changed` / `gsmtc_seeked` / `gsmtc_status_changed`.
+fn report_changes(app: &tauri::AppHandle, payload: &serde_json::Value, key: &str) {
+    static LAST_GSMTC: Lazy<Mutex<Option<Seen>>> = Lazy::new(Mutex::default);
+
+    let cur = Seen {
+        key: key.to_string(),
+        status: payload
+            .get("status")
+            .and_then(|s| s.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        position_ms: estimated_position_ms(payload),
+        seen_at: Instant::now(),
+    };
+    let change = {
+        let mut guard = LAST_GSMTC.lock();
+        let change = playback_change::detect(guard.as_ref(), &cur);
+        *guard = Some(cur);
+        change
+    };
+
+    let Some(change) = change else {
+        return;
+    };
+    let event = match change {
+        Change::Track | Change::Restart => "gsmtc_track_changed",
+        Change::Seek => "gsmtc_seeked",
+        Change::Status => "gsmtc_status_changed",
+    };
+    let session = payload.as_object().cloned().unwrap_or_default();
+    events::emit(app, event, SessionChange { change, session });
+}
+
+// Same `NowPlaying` shape as `get_current_playing`; a default (not playing) one when no
+// session is around.
+#[tauri::command]
+pub async fn get_current_playing_gsmtc(window: tauri::Window) -> Result<NowPlaying, String> {
+    let app = window.app_handle().clone();
+    let (payload, key) = read_gsmtc(app.clone()).await?;
+
+    // Emit AFTER the await
+    if let Some(key) = &key {
+        report_changes(&app, &payload, key);
+    }
+
+    let mut np = to_now_playing(&payload).unwrap_or_default();
+    spotify_search::enrich(&app, &mut np).await;
+    Ok(np)
+}
+
+// Full re-read every this many `playback_progress` ticks (1s each) while playing
+const PROGRESS_RESYNC_TICKS: u32 = 5;
+
+enum Subscription {
+    Resubscribe,
+    Stop,
+}
+
+// Event-driven updates: the OS tells us when sessions come and go or a session's track or
+// playback state changes, and we push `gsmtc_update` (the same `NowPlaying` as
+// `get_current_playing_gsmtc`) plus a fresh `now_playing_update` when a GSMTC source is live.
+// The WinRT side runs on its own thread; handlers only poke a channel.
+pub fn start_event_watcher(app: &tauri::AppHandle) {
+    let token = CancellationToken::new();
+    {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.gsmtc_cancel.replace(token.clone()) {
+            old.cancel();
+        }
+    }
+
+    let (changed_tx, mut changed_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    let (ctl_tx, ctl_rx) = std::sync::mpsc::channel::<Subscription>();
+
+    let handler_ctl = ctl_tx.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = run_subscriptions(changed_tx, handler_ctl, ctl_rx) {
+            eprintln!("[gsmtc] event watcher: {e}");
+        }
+    });
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // latest read, for `playback_progress` ticks in between reads
+        let mut last: Option<serde_json::Value> = None;
+        let mut tick = tokio::time::interval(Duration::from_secs(1));
+        let mut ticks_since_read = 0u32;
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => {
+                    let _ = ctl_tx.send(Subscription::Stop);
+                    break;
+                }
+                _ = tick.tick() => {
+                    let Some(v) = &last else { continue };
+                    if v.get("status").and_then(|s| s.as_str()) != Some("Playing") {
+                        continue;
+                    }
+                    // seeking doesn't raise any of the events we listen to, so re-sync now and then
+                    ticks_since_read += 1;
+                    if ticks_since_read >= PROGRESS_RESYNC_TICKS {
+                        ticks_since_read = 0;
+                        if let Ok((payload, _)) = read_gsmtc(app.clone()).await {
+                            last = Some(payload);
+                        }
+                    }
+                    if let Some(p) = last.as_ref().and_then(progress) {
+                        events::emit(&app, "playback_progress", p);
+                    }
+                }
+                msg = changed_rx.recv() => {
+                    if msg.is_none() {
+                        break;
+                    }
+                    // players fire several events per track change; settle, then read once
+                    tokio::time::sleep(Duration::from_millis(150)).await;
+                    while changed_rx.try_recv().is_ok() {}
+
+                    if let Ok((payload, key)) = read_gsmtc(app.clone()).await {
+                        if let Some(key) = &key {
+                            report_changes(&app, &payload, key);
+                        }
+                        let mut np = to_now_playing(&payload).unwrap_or_default();
+                        spotify_search::enrich(&app, &mut np).await;
+                        events::emit(&app, "gsmtc_update", np);
+                        if let Some(p) = progress(&payload) {
+                            events::emit(&app, "playback_progress", p);
+                        }
+                        ticks_since_read = 0;
+                        last = Some(payload);
+                    }
+                    providers::refresh_active(&app).await;
+                }
+            }
+        }
+    });
+}
+
+type SessionTokens = (GlobalSystemMediaTransportControlsSession, i64, i64);
+
+fn run_subscriptions(
+    changed: tokio::sync::mpsc::UnboundedSender<()>,
+    ctl_tx: std::sync::mpsc::Sender<Subscription>,
+    ctl_rx: std::sync::mpsc::Receiver<Subscription>,
+) -> Result<(), String> {
+    let mgr = block_on(session_manager())?;
+
+    let tx = changed.clone();
+    let sessions_token = mgr
+        .SessionsChanged(&TypedEventHandler::new(move |_, _| {
+            let _ = ctl_tx.send(Subscription::Resubscribe);
+            let _ = tx.send(());
+            Ok(())
+        }))
+        .map_err(|e| format!("SessionsChanged: {e:?}"))?;
+    let tx = changed.clone();
+    let current_token = mgr
+        .CurrentSessionChanged(&TypedEventHandler::new(move |_, _| {
+            let _ = tx.send(());
+            Ok(())
+        }))
+        .map_err(|e| format!("CurrentSessionChanged: {e:?}"))?;
+
+    let mut sessions = subscribe_sessions(&mgr, &changed);
+    // first read right away
+    let _ = changed.send(());
+
+    while let Ok(Subscription::Resubscribe) = ctl_rx.recv() {
+        unsubscribe_sessions(&sessions);
+        sessions = subscribe_sessions(&mgr, &changed);
+    }
+
+    unsubscribe_sessions(&sessions);
+    let _ = mgr.RemoveSessionsChanged(sessions_token);
+    let _ = mgr.RemoveCurrentSessionChanged(current_token);
+    Ok(())
+}
+
+fn subscribe_sessions(
+    mgr: &GlobalSystemMediaTransportControlsSessionManager,
+    changed: &tokio::sync::mpsc::UnboundedSender<()>,
+) -> Vec<SessionTokens> {
+    let Ok(list) = mgr.GetSessions() else {
+        return Vec::new();
+    };
+    (0..list.Size().unwrap_or(0))
+        .filter_map(|i| list.GetAt(i).ok())
+        .filter_map(|session| {
+            let tx = changed.clone();
+            let media = session
+                .MediaPropertiesChanged(&TypedEventHandler::new(move |_, _| {
+                    let _ = tx.send(());
+                    Ok(())
+                }))
+                .ok()?;
+            let tx = changed.clone();
+            let playback = session
+                .PlaybackInfoChanged(&TypedEventHandler::new(move |_, _| {
+                    let _ = tx.send(());
+                    Ok(())
+                }))
+                .ok()?;
+            Some((session, media, playback))
+        })
+        .collect()
+}
+
+fn unsubscribe_sessions(sessions: &[SessionTokens]) {
+    for (session, media, playback) in sessions {
+        let _ = session.RemoveMediaPropertiesChanged(*media);
+        let _ = session.RemovePlaybackInfoChanged(*playback);
+    }
+}
+
+pub const DEFAULT_POLL_MS: u64 = 2_000;
+const MIN_POLL_MS: u64 = 250;
+
+// Sends a transport command to the session `read_gsmtc` would report
+async fn control<Op>(
+    app: &tauri::AppHandle,
+    what: &'static str,
+    send: impl FnOnce(&GlobalSystemMediaTransportControlsSession) -> windows::core::Result<Op>
+        + Send
+        + 'static,
+) -> Result<(), String>
+where
+    Op: std::future::IntoFuture<Output = windows::core::Result<bool>>,
+{
+    let (pinned, priority) = {
+        let state = app.state::<SharedStore>();
+        let s = state.lock();
+        (s.gsmtc_pinned_session.clone(), s.gsmtc_priority.clone())
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        block_on(watchdog::within(
+            what,
+            watchdog::GSMTC_TIMEOUT,
+            async move {
+                let mgr = session_manager().await?;
+                let session = preferred_session(&mgr, pinned.as_deref(), &priority)
+                    .ok_or("No active media session")?;
+                let accepted = send(&session)
+                    .map_err(|e| format!("{what}: {e:?}"))?
+                    .await
+                    .map_err(|e| format!("{what}: {e:?}"))?;
+                // players can refuse, e.g. "next" on the last track of a non-repeating queue
+                if accepted {
+                    Ok(())
+                } else {
+                    Err(format!("The player ignored {what}"))
+                }
+            },
+        ))?
+    })
+    .await
+    .map_err(|e| format!("spawn_blocking join error: {e}"))?
+}
+
+#[tauri::command]
+pub async fn media_play_pause(window: tauri::Window) -> Result<(), String> {
+    control(window.app_handle(), "play/pause", |s| {
+        s.TryTogglePlayPauseAsync()
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_next(window: tauri::Window) -> Result<(), String> {
+    control(window.app_handle(), "next", |s| s.TrySkipNextAsync()).await
+}
+
+#[tauri::command]
+pub async fn media_previous(window: tauri::Window) -> Result<(), String> {
+    control(window.app_handle(), "previous", |s| {
+        s.TrySkipPreviousAsync()
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn media_seek(window: tauri::Window, position_ms: u64) -> Result<(), String> {
+    // TimeSpan ticks are 100ns
+    let ticks = i64::try_from(position_ms)
+        .map_err(|_| "position out of range".to_string())?
+        .saturating_mul(10_000);
+    control(window.app_handle(), "seek", move |s| {
+        s.TryChangePlaybackPositionAsync(ticks)
+    })
+    .await
+}
+
+#[derive(serde::Serialize, schemars::JsonSchema)]
+pub struct MediaSession {
+    aumid: String,
+    title: String,
+    artist: String,
+    status: String,
+    pinned: bool,
+}
+
+pub fn load_pinned_session(app: &tauri::AppHandle) -> Option<String> {
+    read_settings(app)
+        .get("gsmtc_session")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+// Every registered player, for the session picker
+#[tauri::command]
+pub async fn list_media_sessions(
+    state: State<'_, SharedStore>,
+) -> Result<Vec<MediaSession>, String> {
+    let pinned = state.lock().gsmtc_pinned_session.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        block_on(watchdog::within(
+            "GSMTC sessions",
+            watchdog::GSMTC_TIMEOUT,
+            async move {
+                let mgr = session_manager().await?;
+                let list = mgr
+                    .GetSessions()
+                    .map_err(|e| format!("GetSessions: {:?}", e))?;
+
+                let mut out = Vec::new();
+                for session in (0..list.Size().unwrap_or(0)).filter_map(|i| list.GetAt(i).ok()) {
+                    let Ok(aumid) = session.SourceAppUserModelId().map(|id| id.to_string()) else {
+                        continue;
+                    };
+                    let props = match session.TryGetMediaPropertiesAsync() {
+                        Ok(op) => op.await.ok(),
+                        Err(_) => None,
+                    };
+                    let (title, artist) = props
+                        .map(|p| {
+                            (
+                                p.Title().unwrap_or_default().to_string(),
+                                p.Artist().unwrap_or_default().to_string(),
+                            )
+                        })
+                        .unwrap_or_default();
+                    out.push(MediaSession {
+                        pinned: pinned
+                            .as_deref()
+                            .is_some_and(|p| p.eq_ignore_ascii_case(&aumid)),
+                        title,
+                        artist,
+                        status: session
+                            .GetPlaybackInfo()
+                            .and_then(|i| i.PlaybackStatus())
+                            .map(|s| format!("{:?}", s))
+                            .unwrap_or_else(|_| "Unknown".to_string()),
+                        aumid,
+                    });
+                }
+                Ok(out)
+            },
+        ))?
+    })
+    .await
+    .map_err(|e| format!("spawn_blocking join error: {e}"))?
+}
+
+// Pins GSMTC reads to one player by AUMID; `None` goes back to the Spotify-first default
+#[tauri::command]
+pub fn select_media_session(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    aumid: Option<String>,
+) -> Result<(), String> {
+    let aumid = aumid
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty());
+    write_setting(
+        window.app_handle(),
+        "gsmtc_session",
+        serde_json::json!(aumid),
+    )?;
+    state.lock().gsmtc_pinned_session = aumid;
+    Ok(())
+}
+
+pub fn load_priority(app: &tauri::AppHandle) -> Vec<String> {
+    read_settings(app)
+        .get("gsmtc_priority")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+// Ordered AUMID fragments, e.g. ["Spotify", "MusicBee", "chrome"]
+#[tauri::command]
+pub fn get_gsmtc_priority(state: State<'_, SharedStore>) -> Vec<String> {
+    state.lock().gsmtc_priority.clone()
+}
+
+#[tauri::command]
+pub fn set_gsmtc_priority(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    priority: Vec<String>,
+) -> Result<(), String> {
+    let priority: Vec<String> = priority
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    write_setting(
+        window.app_handle(),
+        "gsmtc_priority",
+        serde_json::json!(priority),
+    )?;
+    state.lock().gsmtc_priority = priority;
+    Ok(())
+}
+
+pub fn load_poll_interval(app: &tauri::AppHandle) -> u64 {
+    read_settings(app)
+        .get("gsmtc_poll_interval_ms")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_POLL_MS)
+        .max(MIN_POLL_MS)
+}
+
+// How often the watcher polls GSMTC-backed sources
+#[tauri::command]
+pub fn get_gsmtc_poll_interval(state: State<'_, SharedStore>) -> u64 {
+    state.lock().gsmtc_poll_ms
+}
+
+#[tauri::command]
+pub fn set_gsmtc_poll_interval(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    interval_ms: u64,
+) -> Result<u64, String> {
+    let ms = interval_ms.max(MIN_POLL_MS);
+    write_setting(
+        window.app_handle(),
+        "gsmtc_poll_interval_ms",
+        serde_json::json!(ms),
+    )?;
+    state.lock().gsmtc_poll_ms = ms;
+    Ok(ms)
+}
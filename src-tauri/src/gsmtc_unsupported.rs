@@ -0,0 +1,47 @@
+// GSMTC is a Windows API. Elsewhere every read reports "not supported" so the provider chain
+// moves on to the sources that do work (Spotify, WebNowPlaying, ...), and the GSMTC commands
+// aren't registered at all.
+
+use crate::NowPlaying;
+
+type Payload = (serde_json::Value, Option<String>);
+
+const NOT_SUPPORTED: &str = "Windows media sessions are not supported on this platform";
+
+pub const DEFAULT_POLL_MS: u64 = 2_000;
+
+pub async fn read_gsmtc(_app_handle: tauri::AppHandle) -> Result<Payload, String> {
+    Err(NOT_SUPPORTED.into())
+}
+
+pub async fn read_app_session(
+    _app_handle: tauri::AppHandle,
+    _app_match: &'static str,
+    _fallback: bool,
+) -> Result<Payload, String> {
+    Err(NOT_SUPPORTED.into())
+}
+
+pub async fn read_all_gsmtc(
+    _app_handle: tauri::AppHandle,
+) -> Result<Vec<serde_json::Value>, String> {
+    Err(NOT_SUPPORTED.into())
+}
+
+pub fn to_now_playing(_v: &serde_json::Value) -> Option<NowPlaying> {
+    None
+}
+
+pub fn start_event_watcher(_app: &tauri::AppHandle) {}
+
+pub fn load_pinned_session(_app: &tauri::AppHandle) -> Option<String> {
+    None
+}
+
+pub fn load_priority(_app: &tauri::AppHandle) -> Vec<String> {
+    Vec::new()
+}
+
+pub fn load_poll_interval(_app: &tauri::AppHandle) -> u64 {
+    DEFAULT_POLL_MS
+}
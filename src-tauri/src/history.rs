@@ -0,0 +1,403 @@
+// Local listening history: one row per play in `history.sqlite3` next to the other app data.
+// Plays are recorded as tracks change; `import_spotify_history` fills in the years before the
+// app from Spotify's "Extended Streaming History" export (Account privacy -> Download your
+// data), so stats don't start from an empty database.
+
+use crate::NowPlaying;
+use chrono::{DateTime, SecondsFormat, Utc};
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::Manager;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS plays (
+        id INTEGER PRIMARY KEY,
+        -- RFC 3339 UTC, whole seconds, when the play started
+        played_at TEXT NOT NULL,
+        ms_played INTEGER NOT NULL,
+        track TEXT NOT NULL,
+        artist TEXT NOT NULL,
+        album TEXT,
+        -- spotify:track:..., when known
+        uri TEXT,
+        -- 'live', 'spotify_export' or 'lastfm'
+        source TEXT NOT NULL,
+        skipped INTEGER,
+        UNIQUE (played_at, track, artist)
+    );
+    CREATE INDEX IF NOT EXISTS plays_played_at ON plays (played_at);
+    -- Spotify's genres per artist name, looked up for charts; '[]' when nothing matched
+    CREATE TABLE IF NOT EXISTS artist_genres (
+        artist TEXT PRIMARY KEY COLLATE NOCASE,
+        genres TEXT NOT NULL
+    );
+";
+
+#[derive(Default)]
+struct Db {
+    conn: Option<Connection>,
+    // row of the play in progress, when it started and how long the track is
+    current: Option<(i64, Instant, Option<u64>)>,
+}
+
+#[derive(Default)]
+pub struct History(Mutex<Db>);
+
+fn db_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    crate::portable::data_dir(app)
+        .ok()
+        .map(|d| d.join("history.sqlite3"))
+}
+
+fn open(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("create data dir: {e}"))?;
+    }
+    let conn = Connection::open(path).map_err(|e| format!("open history db: {e}"))?;
+    conn.execute_batch(SCHEMA)
+        .map_err(|e| format!("create history tables: {e}"))?;
+    Ok(conn)
+}
+
+// The database opened at startup; history commands fail without it
+pub fn is_open(app: &tauri::AppHandle) -> bool {
+    app.try_state::<History>()
+        .is_some_and(|h| h.0.lock().conn.is_some())
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let conn = match db_path(app).ok_or_else(|| "no app data dir".to_string()) {
+        Ok(path) => open(&path),
+        Err(e) => Err(e),
+    };
+    let history = History::default();
+    match conn {
+        Ok(conn) => history.0.lock().conn = Some(conn),
+        Err(e) => eprintln!("[history] {e}"),
+    }
+    app.manage(history);
+}
+
+// Runs `f` on the database; don't await inside, the lock is held throughout
+pub fn with_db<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&Connection) -> Result<T, String>,
+) -> Result<T, String> {
+    let history = app
+        .try_state::<History>()
+        .ok_or("History database isn't available")?;
+    let db = history.0.lock();
+    f(db.conn.as_ref().ok_or("History database isn't available")?)
+}
+
+// `with_db` on a blocking thread, for commands that go through many rows
+pub async fn with_db_blocking<T: Send + 'static>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || with_db(&app, f))
+        .await
+        .map_err(|e| format!("history task: {e}"))?
+}
+
+// How `played_at` is written, so timestamps compare as strings
+pub fn timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// How long the play in progress lasted: wall time, capped at the track length
+fn close_current(db: &mut Db) {
+    let (Some(conn), Some((id, started, duration_ms))) = (&db.conn, db.current.take()) else {
+        return;
+    };
+    let mut ms = started.elapsed().as_millis() as u64;
+    if let Some(d) = duration_ms {
+        ms = ms.min(d);
+    }
+    if let Err(e) = conn.execute(
+        "UPDATE plays SET ms_played = ?1 WHERE id = ?2",
+        params![ms as i64, id],
+    ) {
+        eprintln!("[history] update play: {e}");
+    }
+}
+
+// Called on every track change
+pub fn record(app: &tauri::AppHandle, np: &NowPlaying) {
+    let Some(history) = app.try_state::<History>() else {
+        return;
+    };
+    let mut db = history.0.lock();
+    close_current(&mut db);
+    if np.media_kind.as_deref() == Some("episode") {
+        return;
+    }
+    let (Some(track), Some(artist)) = (np.track_name.as_deref(), np.artists.first()) else {
+        return;
+    };
+    let Some(conn) = &db.conn else {
+        return;
+    };
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO plays (played_at, ms_played, track, artist, album, source)
+         VALUES (?1, 0, ?2, ?3, ?4, 'live')",
+        params![timestamp(Utc::now()), track, artist, np.album],
+    );
+    match inserted {
+        Ok(1) => {
+            let id = conn.last_insert_rowid();
+            db.current = Some((id, Instant::now(), np.duration_ms));
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("[history] record play: {e}"),
+    }
+}
+
+// On the way out, so the last play gets its length
+pub fn finish(app: &tauri::AppHandle) {
+    if let Some(history) = app.try_state::<History>() {
+        close_current(&mut history.0.lock());
+    }
+}
+
+// One entry of `Streaming_History_Audio_*.json`. Podcast episodes and audiobooks share the
+// files but have no track name.
+#[derive(Deserialize)]
+struct ExportEntry {
+    // when the play ended
+    ts: DateTime<Utc>,
+    ms_played: u64,
+    master_metadata_track_name: Option<String>,
+    master_metadata_album_artist_name: Option<String>,
+    master_metadata_album_album_name: Option<String>,
+    spotify_track_uri: Option<String>,
+    skipped: Option<bool>,
+}
+
+// `path` itself, or the files in it that `wanted` picks
+fn export_files(path: &Path, wanted: fn(&Path) -> bool) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+    let mut files: Vec<PathBuf> = walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| p.is_file() && wanted(p))
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn has_ext(p: &Path, ext: &str) -> bool {
+    p.extension().is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+fn is_spotify_export(p: &Path) -> bool {
+    has_ext(p, "json")
+        && p.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("Streaming_History_Audio"))
+}
+
+#[derive(Serialize, Default, JsonSchema)]
+pub struct ImportSummary {
+    pub files: usize,
+    pub added: usize,
+    // already in the database, e.g. from importing the same export twice or the same play
+    // from another source
+    pub duplicates: usize,
+    // entries without a track, artist or date, e.g. podcast episodes
+    pub skipped: usize,
+}
+
+// Reads one file's plays, with None for entries without a track, artist or date
+pub type ImportFile = fn(&Path) -> Result<Vec<Option<ImportedPlay>>, String>;
+
+// Plays written per transaction; the database is free for live plays and charts in between
+const IMPORT_BATCH: usize = 1000;
+
+// Imports `paths` (files, or folders searched for files `wanted` picks). Every file is parsed
+// before anything is written, so one that fails to parse leaves the database as it was.
+pub async fn import(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    wanted: fn(&Path) -> bool,
+    import_file: ImportFile,
+) -> Result<ImportSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let files: Vec<PathBuf> = paths
+            .iter()
+            .flat_map(|p| export_files(Path::new(p), wanted))
+            .collect();
+        if files.is_empty() {
+            return Err("No history files found there".to_string());
+        }
+        let mut summary = ImportSummary::default();
+        let mut plays = Vec::new();
+        for file in &files {
+            for play in import_file(file)? {
+                match play {
+                    Some(play) => plays.push(play),
+                    None => summary.skipped += 1,
+                }
+            }
+            summary.files += 1;
+        }
+
+        let history = app.state::<History>();
+        for batch in plays.chunks(IMPORT_BATCH) {
+            let mut db = history.0.lock();
+            let conn = db.conn.as_mut().ok_or("History database isn't available")?;
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("begin import: {e}"))?;
+            for play in batch {
+                if add_unless_known(&tx, play)? {
+                    summary.added += 1;
+                } else {
+                    summary.duplicates += 1;
+                }
+            }
+            tx.commit().map_err(|e| format!("commit import: {e}"))?;
+        }
+        Ok(summary)
+    })
+    .await
+    .map_err(|e| format!("import task: {e}"))?
+}
+
+// Scrobblers stamp when a track started and Spotify's export when it ended, so the same play
+// from two sources can be this far apart once the export's start is worked out
+const SAME_PLAY_WITHIN_SECS: i64 = 120;
+
+// One play from an import file
+pub struct ImportedPlay {
+    pub played_at: DateTime<Utc>,
+    pub ms_played: u64,
+    pub track: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub uri: Option<String>,
+    pub skipped: Option<bool>,
+    // 'spotify_export' or 'lastfm'
+    pub source: &'static str,
+}
+
+// Adds a play unless the same track is already recorded around that time from another source,
+// or at that exact time from the same one (ignoring case). Returns whether it was added.
+pub fn add_unless_known(conn: &Connection, play: &ImportedPlay) -> Result<bool, String> {
+    let window = chrono::Duration::seconds(SAME_PLAY_WITHIN_SECS);
+    let known = conn
+        .prepare_cached(
+            "SELECT 1 FROM plays
+             WHERE track = ?3 COLLATE NOCASE AND artist = ?4 COLLATE NOCASE
+               AND (played_at = ?5 OR (source != ?6 AND played_at BETWEEN ?1 AND ?2))
+             LIMIT 1",
+        )
+        .and_then(|mut q| {
+            q.exists(params![
+                timestamp(play.played_at - window),
+                timestamp(play.played_at + window),
+                play.track,
+                play.artist,
+                timestamp(play.played_at),
+                play.source,
+            ])
+        })
+        .map_err(|e| format!("look up play: {e}"))?;
+    if known {
+        return Ok(false);
+    }
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO plays
+         (played_at, ms_played, track, artist, album, uri, source, skipped)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .and_then(|mut q| {
+        q.execute(params![
+            timestamp(play.played_at),
+            play.ms_played as i64,
+            play.track,
+            play.artist,
+            play.album,
+            play.uri,
+            play.source,
+            play.skipped,
+        ])
+    })
+    .map(|n| n > 0)
+    .map_err(|e| format!("import play: {e}"))
+}
+
+fn import_spotify_file(path: &Path) -> Result<Vec<Option<ImportedPlay>>, String> {
+    let name = path.display();
+    let bytes = std::fs::read(path).map_err(|e| format!("read {name}: {e}"))?;
+    let entries: Vec<ExportEntry> = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("{name} isn't an Extended Streaming History file: {e}"))?;
+    let plays = entries.into_iter().map(|entry| {
+        let track = entry.master_metadata_track_name?;
+        let artist = entry.master_metadata_album_artist_name?;
+        Some(ImportedPlay {
+            played_at: entry.ts - chrono::Duration::milliseconds(entry.ms_played as i64),
+            ms_played: entry.ms_played,
+            track,
+            artist,
+            album: entry.master_metadata_album_album_name,
+            uri: entry.spotify_track_uri,
+            skipped: entry.skipped,
+            source: "spotify_export",
+        })
+    });
+    Ok(plays.collect())
+}
+
+// `paths` are export JSON files or folders holding them (the unzipped export)
+#[tauri::command]
+pub async fn import_spotify_history(
+    window: tauri::Window,
+    paths: Vec<String>,
+) -> Result<ImportSummary, String> {
+    import(
+        window.app_handle().clone(),
+        paths,
+        is_spotify_export,
+        import_spotify_file,
+    )
+    .await
+}
+
+#[derive(Serialize, Default, JsonSchema)]
+pub struct HistorySummary {
+    pub plays: u64,
+    pub ms_played: u64,
+    // RFC 3339, first and latest play
+    pub first: Option<String>,
+    pub last: Option<String>,
+}
+
+#[tauri::command]
+pub async fn get_history_summary(window: tauri::Window) -> Result<HistorySummary, String> {
+    with_db_blocking(window.app_handle(), |conn| {
+        conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(ms_played), 0), MIN(played_at), MAX(played_at)
+             FROM plays",
+            [],
+            |row| {
+                Ok(HistorySummary {
+                    plays: row.get::<_, i64>(0)? as u64,
+                    ms_played: row.get::<_, i64>(1)? as u64,
+                    first: row.get(2)?,
+                    last: row.get(3)?,
+                })
+            },
+        )
+        .map_err(|e| format!("read history: {e}"))
+    })
+    .await
+}
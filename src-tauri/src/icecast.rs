@@ -0,0 +1,240 @@
+// Icecast / Shoutcast stream metadata.
+//
+// Asking a stream for `Icy-MetaData: 1` makes the server interleave a metadata block every
+// `icy-metaint` bytes of audio. We read the stream, throw the audio away, and turn each
+// `StreamTitle='Artist - Title';` into a regular `NowPlaying` for the provider chain, with the
+// station (`icy-name`) as the context. Radio streams carry no artwork, so covers come from the
+// catalog search (when set up) and the Deezer/iTunes lookups.
+
+use crate::{
+    artwork_lookup, parse_artists,
+    providers::{self, Provider},
+    read_settings,
+    resilience::Retry,
+    spotify_search, write_setting, NowPlaying, SharedStore,
+};
+use once_cell::sync::Lazy;
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+use tokio_util::sync::CancellationToken;
+
+// a connection that lasted this long counts as working; the next drop starts the retry
+// policy over
+const HEALTHY_AFTER: Duration = Duration::from_secs(30);
+
+// No overall timeout here, the response body never ends
+static HTTP: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .build()
+        .unwrap_or_default()
+});
+
+pub fn init(app: &tauri::AppHandle) {
+    let url = read_settings(app)
+        .get("icecast_url")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    if let Some(url) = url {
+        start(app, url);
+    }
+}
+
+fn start(app: &tauri::AppHandle, url: String) {
+    let token = CancellationToken::new();
+    {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.icecast_cancel.replace(token.clone()) {
+            old.cancel();
+        }
+        s.icecast_url = Some(url.clone());
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut retry = Retry::new(&app, "icecast");
+        loop {
+            let connected_at = Instant::now();
+            tokio::select! {
+                _ = token.cancelled() => break,
+                res = read_stream(&app, &url) => {
+                    if let Err(e) = res {
+                        eprintln!("[icecast] {url}: {e}");
+                    }
+                }
+            }
+            // stream ended or dropped: nothing is playing until we're back
+            publish(&app, None).await;
+            if connected_at.elapsed() >= HEALTHY_AFTER {
+                retry.reset();
+            }
+            if !retry.wait(&token).await {
+                if !token.is_cancelled() {
+                    eprintln!("[icecast] {url}: giving up reconnecting");
+                }
+                break;
+            }
+        }
+    });
+}
+
+fn stop(state: &SharedStore) {
+    let mut s = state.lock();
+    if let Some(t) = s.icecast_cancel.take() {
+        t.cancel();
+    }
+    s.icecast_url = None;
+    s.icecast_now_playing = None;
+}
+
+async fn read_stream(app: &tauri::AppHandle, url: &str) -> Result<(), String> {
+    let mut resp = HTTP
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .send()
+        .await
+        .map_err(|e| format!("connect: {e}"))?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+
+    let metaint: usize = resp
+        .headers()
+        .get("icy-metaint")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|n| *n > 0)
+        .ok_or("server sent no icy-metaint, the stream has no metadata")?;
+    let station = resp
+        .headers()
+        .get("icy-name")
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).trim().to_string())
+        .filter(|n| !n.is_empty());
+
+    let mut audio_left = metaint;
+    // length of the metadata block being read, once its length byte has been seen
+    let mut meta_len: Option<usize> = None;
+    let mut meta = Vec::new();
+    let mut last_title = None;
+
+    while let Some(chunk) = resp.chunk().await.map_err(|e| format!("read: {e}"))? {
+        let mut buf = &chunk[..];
+        while !buf.is_empty() {
+            if audio_left > 0 {
+                let n = audio_left.min(buf.len());
+                audio_left -= n;
+                buf = &buf[n..];
+                continue;
+            }
+            match meta_len {
+                None => {
+                    meta_len = Some(buf[0] as usize * 16);
+                    meta.clear();
+                    buf = &buf[1..];
+                }
+                Some(len) => {
+                    let n = (len - meta.len()).min(buf.len());
+                    meta.extend_from_slice(&buf[..n]);
+                    buf = &buf[n..];
+                }
+            }
+
+            if meta_len == Some(meta.len()) {
+                // an empty block means "unchanged"
+                if let Some(title) = stream_title(&String::from_utf8_lossy(&meta)) {
+                    if last_title.as_ref() != Some(&title) {
+                        let np = to_now_playing(&title, station.as_deref());
+                        publish(app, np).await;
+                        last_title = Some(title);
+                    }
+                }
+                meta_len = None;
+                audio_left = metaint;
+            }
+        }
+    }
+    Ok(())
+}
+
+// "StreamTitle='Artist - Title';StreamUrl='...';" -> "Artist - Title"
+fn stream_title(meta: &str) -> Option<String> {
+    let start = meta.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &meta[start..];
+    // titles can contain quotes, the field ends at "';"
+    let end = rest.find("';").unwrap_or(rest.trim_end_matches('\0').len());
+    let title = rest[..end].trim().trim_end_matches('\'');
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+// "Artist - Title", as stream titles and Icecast's `/admin/metadata?song=` carry it
+pub fn song_to_now_playing(song: &str) -> Option<NowPlaying> {
+    let song = song.trim();
+    if song.is_empty() {
+        return None;
+    }
+    let (artists, title) = match song.split_once(" - ") {
+        Some((artist, title)) => (parse_artists(artist), title.trim()),
+        None => (Vec::new(), song),
+    };
+    Some(NowPlaying {
+        is_playing: true,
+        track_name: Some(title.to_string()),
+        artists,
+        ..Default::default()
+    })
+}
+
+fn to_now_playing(title: &str, station: Option<&str>) -> Option<NowPlaying> {
+    Some(NowPlaying {
+        context_type: Some("radio".into()),
+        context_name: station.map(str::to_string),
+        context_label: station.map(|s| format!("Radio: {s}")),
+        ..song_to_now_playing(title)?
+    })
+}
+
+async fn publish(app: &tauri::AppHandle, np: Option<NowPlaying>) {
+    let state = app.state::<SharedStore>();
+    let np = match np {
+        Some(mut np) => {
+            spotify_search::enrich(app, &mut np).await;
+            if np.artwork_url.is_none() {
+                artwork_lookup::fill_with_fallbacks(app, &mut np).await;
+            }
+            Some(np)
+        }
+        None => None,
+    };
+    state.lock().icecast_now_playing = np.clone();
+    providers::publish_pushed(app, Provider::Icecast, np);
+}
+
+#[tauri::command]
+pub fn get_icecast_url(state: State<'_, SharedStore>) -> Option<String> {
+    state.lock().icecast_url.clone()
+}
+
+// `url: None` stops listening
+#[tauri::command]
+pub fn set_icecast_url(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    url: Option<String>,
+) -> Result<(), String> {
+    let url = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+    if let Some(u) = &url {
+        let parsed = url::Url::parse(u).map_err(|e| format!("Invalid stream URL: {e}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err("Stream URL must be http(s)".into());
+        }
+    }
+
+    let app = window.app_handle();
+    write_setting(app, "icecast_url", serde_json::json!(url))?;
+    match url {
+        Some(u) => start(app, u),
+        None => stop(&state),
+    }
+    crate::start_watcher_if_needed(app, &state);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+// Language and script of the title and the artists, so overlays can switch fonts (CJK vs Latin)
+// and tell when a romanized line is worth showing. Titles are short: the script is always
+// known, the language only when the text gives it away.
+
+use crate::NowPlaying;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use whatlang::Script;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct TextLang {
+    // ISO 639-3, e.g. "jpn"; None when the text is too short to tell
+    pub lang: Option<String>,
+    // "Latin", "Cyrillic", "Hiragana", "Mandarin" (Han), ...
+    pub script: String,
+    // font family to pick: "latin" | "cjk" | "cyrillic" | "greek" | "arabic" | "hebrew" |
+    // "indic" | "thai" | "other"
+    pub font: String,
+}
+
+fn font(script: Script) -> &'static str {
+    match script {
+        Script::Latin => "latin",
+        Script::Mandarin | Script::Hiragana | Script::Katakana | Script::Hangul => "cjk",
+        Script::Cyrillic => "cyrillic",
+        Script::Greek => "greek",
+        Script::Arabic => "arabic",
+        Script::Hebrew => "hebrew",
+        Script::Devanagari
+        | Script::Bengali
+        | Script::Gujarati
+        | Script::Gurmukhi
+        | Script::Kannada
+        | Script::Malayalam
+        | Script::Oriya
+        | Script::Tamil
+        | Script::Telugu
+        | Script::Sinhala => "indic",
+        Script::Thai => "thai",
+        _ => "other",
+    }
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' | '\u{ff66}'..='\u{ff9f}')
+}
+
+pub fn detect(text: &str) -> Option<TextLang> {
+    let text = text.trim();
+    let info = whatlang::detect(text)?;
+    let lang = if text.chars().any(is_kana) {
+        // kanji outnumbering the kana still reads as Chinese to whatlang
+        Some(whatlang::Lang::Jpn)
+    } else {
+        Some(info.lang()).filter(|_| info.is_reliable())
+    };
+    Some(TextLang {
+        lang: lang.map(|l| l.code().to_string()),
+        script: info.script().name().to_string(),
+        font: font(info.script()).to_string(),
+    })
+}
+
+// Applied to everything the watcher emits
+pub fn apply(np: &mut NowPlaying) {
+    np.title_lang = np.track_name.as_deref().and_then(detect);
+    np.artist_lang = detect(&np.artists.join(", "));
+}
@@ -0,0 +1,89 @@
+// The last track seen, kept in `last_played.json` so a fresh launch can show it (flagged
+// `stale`) straight away instead of an empty overlay until the first poll comes back. Its cover
+// is kept as `last_artwork.png` beside it, since the player's thumbnail cache or the network
+// may not be there yet on the next start.
+
+use crate::{NowPlaying, SharedStore};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use tauri::Manager;
+
+// hash of the track and cover last written, so repeated exports don't rewrite them
+static SAVED_ARTWORK: Lazy<Mutex<Option<u64>>> = Lazy::new(Mutex::default);
+
+fn path(app: &tauri::AppHandle, name: &str) -> Option<PathBuf> {
+    crate::portable::data_dir(app).ok().map(|d| d.join(name))
+}
+
+pub fn load(app: &tauri::AppHandle) -> Option<NowPlaying> {
+    let bytes = std::fs::read(path(app, "last_played.json")?).ok()?;
+    let mut np: NowPlaying = serde_json::from_slice(&bytes).ok()?;
+    np.track_name.as_ref()?;
+    np.stale = true;
+    np.pending_artwork = false;
+    // e.g. a thumbnail the player has cleaned up since
+    if np
+        .artwork_path
+        .as_deref()
+        .is_some_and(|p| !std::path::Path::new(p).is_file())
+    {
+        np.artwork_path = None;
+    }
+    Some(np)
+}
+
+pub fn save(app: &tauri::AppHandle, np: &NowPlaying) {
+    let Some(path) = path(app, "last_played.json") else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(bytes) = serde_json::to_vec_pretty(np) {
+        if let Err(e) = std::fs::write(&path, bytes) {
+            eprintln!("[last-played] save: {e}");
+        }
+    }
+}
+
+// Called with every rendered export; the cover usually turns up after the track itself
+pub fn save_artwork(app: &tauri::AppHandle, np: &NowPlaying, png: &[u8]) {
+    if np.stale {
+        return;
+    }
+    // a render that finished after the next track started
+    let is_current = app
+        .state::<SharedStore>()
+        .lock()
+        .last_now_playing
+        .as_ref()
+        .is_some_and(|c| c.track_name == np.track_name && c.artists == np.artists);
+    if !is_current {
+        return;
+    }
+    let mut hasher = DefaultHasher::new();
+    (&np.track_name, &np.artists, png).hash(&mut hasher);
+    let hash = hasher.finish();
+    if *SAVED_ARTWORK.lock() == Some(hash) {
+        return;
+    }
+    let Some(file) = path(app, "last_artwork.png") else {
+        return;
+    };
+    if let Some(dir) = file.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = std::fs::write(&file, png) {
+        return eprintln!("[last-played] save artwork: {e}");
+    }
+    *SAVED_ARTWORK.lock() = Some(hash);
+    save(
+        app,
+        &NowPlaying {
+            artwork_path: Some(file.to_string_lossy().to_string()),
+            ..np.clone()
+        },
+    );
+}
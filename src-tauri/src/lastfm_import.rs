@@ -0,0 +1,200 @@
+// Last.fm backups into the local history. Last.fm itself has no export button, so backups come
+// from third-party tools in a few shapes:
+// - CSV with a header naming the columns (artist, album, track, and `uts` or `utc_time`)
+// - CSV without one: artist, album, track, date ("31 Jan 2021 12:34", UTC)
+// - JSON pages of the API's `user.getRecentTracks`, as a list or a single response
+// Scrobbles don't say how long a track played, so they're stored with `ms_played` 0 and count
+// as plays only. A scrobble of a play already in the history (e.g. imported from the Spotify
+// export) is left out, see `history::add_unless_known`.
+
+use crate::history::{self, ImportSummary, ImportedPlay};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+use std::path::Path;
+use tauri::Manager;
+
+struct Scrobble {
+    at: DateTime<Utc>,
+    artist: String,
+    album: Option<String>,
+    track: String,
+}
+
+// Unix seconds (or milliseconds), or one of the date formats the export tools write, in UTC
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<i64>() {
+        let secs = if n > 100_000_000_000 { n / 1000 } else { n };
+        return Utc
+            .timestamp_opt(secs, 0)
+            .single()
+            .filter(|t| t.timestamp() > 0);
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&Utc));
+    }
+    [
+        "%d %b %Y %H:%M",
+        "%d %b %Y, %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+    .map(|t| t.and_utc())
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+fn scrobble(at: &str, artist: &str, album: &str, track: &str) -> Option<Scrobble> {
+    Some(Scrobble {
+        at: parse_time(at)?,
+        artist: non_empty(artist)?,
+        album: non_empty(album),
+        track: non_empty(track)?,
+    })
+}
+
+// None per row that's missing a field
+fn read_csv(bytes: &[u8]) -> Result<Vec<Option<Scrobble>>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+    let mut rows = reader.records();
+    let Some(first) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let first = first.map_err(|e| format!("read csv: {e}"))?;
+
+    let find = |names: &[&str]| {
+        first
+            .iter()
+            .position(|c| names.iter().any(|n| c.trim().eq_ignore_ascii_case(n)))
+    };
+    // artist, album, track, time
+    let (columns, header) = match find(&["artist"]) {
+        Some(artist) => {
+            let track = find(&["track", "name", "title"]).ok_or("csv has no track column")?;
+            let time = find(&["uts", "utc_time", "date", "time", "timestamp"])
+                .ok_or("csv has no date column")?;
+            (
+                [Some(artist), find(&["album"]), Some(track), Some(time)],
+                true,
+            )
+        }
+        None => ([Some(0), Some(1), Some(2), Some(3)], false),
+    };
+
+    let parse = |row: &csv::StringRecord| {
+        let cell = |i: Option<usize>| i.and_then(|i| row.get(i)).unwrap_or("");
+        scrobble(
+            cell(columns[3]),
+            cell(columns[0]),
+            cell(columns[1]),
+            cell(columns[2]),
+        )
+    };
+    let mut scrobbles = Vec::new();
+    if !header {
+        scrobbles.push(parse(&first));
+    }
+    for row in rows {
+        let row = row.map_err(|e| format!("read csv: {e}"))?;
+        scrobbles.push(parse(&row));
+    }
+    Ok(scrobbles)
+}
+
+// `{"#text": ...}` (recent tracks), `{"name": ...}` (extended) or a plain string
+fn text(v: &Value) -> &str {
+    v.get("#text")
+        .or_else(|| v.get("name"))
+        .unwrap_or(v)
+        .as_str()
+        .unwrap_or("")
+}
+
+// Track objects from a response, a page or a list of either
+fn collect_tracks<'a>(v: &'a Value, out: &mut Vec<&'a Value>) {
+    if let Some(inner) = v.get("recenttracks") {
+        collect_tracks(inner, out);
+    } else if let Some(tracks) = v.get("track") {
+        match tracks {
+            Value::Array(list) => out.extend(list),
+            track => out.push(track),
+        }
+    } else if let Value::Array(list) = v {
+        for item in list {
+            if item.get("track").is_some() || item.get("recenttracks").is_some() {
+                collect_tracks(item, out);
+            } else {
+                out.push(item);
+            }
+        }
+    }
+}
+
+fn read_json(bytes: &[u8]) -> Result<Vec<Option<Scrobble>>, String> {
+    let v: Value = serde_json::from_slice(bytes).map_err(|e| format!("parse json: {e}"))?;
+    let mut tracks = Vec::new();
+    collect_tracks(&v, &mut tracks);
+    Ok(tracks
+        .into_iter()
+        .map(|t| {
+            let at = t.get("date").map(|d| match d.get("uts") {
+                Some(uts) => text(uts),
+                None => text(d),
+            });
+            // the track playing when the backup was made has no date yet
+            scrobble(
+                at.unwrap_or(""),
+                t.get("artist").map(text).unwrap_or(""),
+                t.get("album").map(text).unwrap_or(""),
+                t.get("name").and_then(Value::as_str).unwrap_or(""),
+            )
+        })
+        .collect())
+}
+
+fn is_backup(p: &Path) -> bool {
+    history::has_ext(p, "csv") || history::has_ext(p, "json")
+}
+
+fn import_file(path: &Path) -> Result<Vec<Option<ImportedPlay>>, String> {
+    let name = path.display();
+    let bytes = std::fs::read(path).map_err(|e| format!("read {name}: {e}"))?;
+    let scrobbles = if history::has_ext(path, "csv") {
+        read_csv(&bytes)
+    } else {
+        read_json(&bytes)
+    }
+    .map_err(|e| format!("{name}: {e}"))?;
+
+    let plays = scrobbles.into_iter().map(|s| {
+        let s = s?;
+        Some(ImportedPlay {
+            played_at: s.at,
+            ms_played: 0,
+            track: s.track,
+            artist: s.artist,
+            album: s.album,
+            uri: None,
+            skipped: None,
+            source: "lastfm",
+        })
+    });
+    Ok(plays.collect())
+}
+
+// `paths` are backup files, or folders of .csv/.json backups
+#[tauri::command]
+pub async fn import_lastfm_history(
+    window: tauri::Window,
+    paths: Vec<String>,
+) -> Result<ImportSummary, String> {
+    history::import(window.app_handle().clone(), paths, is_backup, import_file).await
+}
@@ -0,0 +1,278 @@
+// Composited overlay images for OBS, in a landscape (16:9) and a portrait (9:16) layout, so a
+// horizontal stream and a vertical one (TikTok, Shorts) running side by side both get a
+// correctly sized picture. Each is the cover over a blurred, darkened copy of itself, with the
+// rest of the frame left for OBS text sources (song.txt, artist.txt, ...). `palette.json`
+// holds the cover's main colours for theming those. Rendered whenever the track or its
+// artwork changes and written next to the other export files.
+
+use crate::{export, gpu, read_settings, write_setting, NowPlaying, SharedStore};
+use image::{imageops, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::HashMap, fs};
+use tauri::{Manager, State};
+
+// The background is blurred at 1/BG_DOWNSCALE of the frame size and scaled back up, which
+// looks the same as blurring at full size for a fraction of the work
+const BG_DOWNSCALE: u32 = 8;
+const BG_BLUR_SIGMA: f32 = 6.0;
+const BG_BRIGHTNESS: f32 = 0.55;
+
+const PALETTE_FILE: &str = "palette.json";
+const PALETTE_COLORS: usize = 5;
+// side of the square thumbnail the colours are counted on
+const PALETTE_SAMPLE: u32 = 64;
+// squared RGB distance under which two colours count as one
+const PALETTE_MIN_DISTANCE: u32 = 40 * 40;
+
+struct Layout {
+    file: &'static str,
+    width: u32,
+    height: u32,
+    cover: u32,
+    // top-left corner of the cover
+    at: (u32, u32),
+}
+
+const LAYOUTS: [Layout; 2] = [
+    // cover on the left, text to its right
+    Layout {
+        file: "layout_landscape.png",
+        width: 1920,
+        height: 1080,
+        cover: 720,
+        at: (120, 180),
+    },
+    // cover up top, text below
+    Layout {
+        file: "layout_portrait.png",
+        width: 1080,
+        height: 1920,
+        cover: 840,
+        at: (120, 240),
+    },
+];
+
+#[derive(Serialize, Deserialize, Clone, Default, schemars::JsonSchema)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub enabled: bool,
+}
+
+#[derive(Default)]
+pub struct Layouts {
+    config: LayoutConfig,
+    // track + artwork the current images were rendered for
+    rendered: Option<String>,
+}
+
+pub fn load(app: &tauri::AppHandle) -> Layouts {
+    let config = read_settings(app)
+        .get("layouts")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    Layouts {
+        config,
+        rendered: None,
+    }
+}
+
+fn compose(cover: &DynamicImage, layout: &Layout, use_gpu: bool) -> RgbaImage {
+    if use_gpu {
+        let composite = gpu::Composite {
+            width: layout.width,
+            height: layout.height,
+            cover: layout.cover,
+            at: layout.at,
+            blur_sigma: BG_BLUR_SIGMA * BG_DOWNSCALE as f32,
+            brightness: BG_BRIGHTNESS,
+        };
+        match gpu::compose(cover, &composite) {
+            Ok(frame) => return frame,
+            Err(e) => eprintln!("[layouts] GPU render failed, using the CPU: {e}"),
+        }
+    }
+    compose_cpu(cover, layout)
+}
+
+fn compose_cpu(cover: &DynamicImage, layout: &Layout) -> RgbaImage {
+    let (w, h) = (layout.width, layout.height);
+    let small = cover.resize_to_fill(
+        (w / BG_DOWNSCALE).max(1),
+        (h / BG_DOWNSCALE).max(1),
+        imageops::FilterType::Triangle,
+    );
+    let blurred = imageops::blur(&small, BG_BLUR_SIGMA);
+    let mut frame = imageops::resize(&blurred, w, h, imageops::FilterType::Triangle);
+    for px in frame.pixels_mut() {
+        for c in &mut px.0[..3] {
+            *c = (f32::from(*c) * BG_BRIGHTNESS) as u8;
+        }
+        px.0[3] = 255;
+    }
+
+    let fg = cover.resize_to_fill(layout.cover, layout.cover, imageops::FilterType::Lanczos3);
+    imageops::overlay(
+        &mut frame,
+        &fg.to_rgba8(),
+        i64::from(layout.at.0),
+        i64::from(layout.at.1),
+    );
+    frame
+}
+
+fn palette_sample(cover: &DynamicImage, use_gpu: bool) -> RgbaImage {
+    if use_gpu {
+        // a frame that's all cover: just the scaled-down cover
+        let sample = gpu::Composite {
+            width: PALETTE_SAMPLE,
+            height: PALETTE_SAMPLE,
+            cover: PALETTE_SAMPLE,
+            at: (0, 0),
+            blur_sigma: 0.5,
+            brightness: 1.0,
+        };
+        match gpu::compose(cover, &sample) {
+            Ok(img) => return img,
+            Err(e) => eprintln!("[layouts] GPU palette sample failed, using the CPU: {e}"),
+        }
+    }
+    cover
+        .resize_to_fill(
+            PALETTE_SAMPLE,
+            PALETTE_SAMPLE,
+            imageops::FilterType::Triangle,
+        )
+        .to_rgba8()
+}
+
+// Most common colours first, as "#rrggbb"; pixels are bucketed at 4 bits per channel
+fn palette(sample: &RgbaImage) -> Vec<String> {
+    // bucket -> (sum of r, g, b, pixel count)
+    let mut buckets: HashMap<u16, [u32; 4]> = HashMap::new();
+    for px in sample.pixels() {
+        let [r, g, b, a] = px.0;
+        if a < 128 {
+            continue;
+        }
+        let key = (u16::from(r >> 4) << 8) | (u16::from(g >> 4) << 4) | u16::from(b >> 4);
+        let sum = buckets.entry(key).or_default();
+        for (s, c) in sum.iter_mut().zip([r, g, b]) {
+            *s += u32::from(c);
+        }
+        sum[3] += 1;
+    }
+    let mut buckets: Vec<[u32; 4]> = buckets.into_values().collect();
+    buckets.sort_by_key(|b| Reverse(b[3]));
+
+    let mut picked: Vec<[u8; 3]> = Vec::new();
+    for [r, g, b, n] in buckets {
+        let color = [r / n, g / n, b / n].map(|c| c as u8);
+        let distinct = picked.iter().all(|p| {
+            let d = |i: usize| u32::from(p[i].abs_diff(color[i])).pow(2);
+            d(0) + d(1) + d(2) >= PALETTE_MIN_DISTANCE
+        });
+        if distinct {
+            picked.push(color);
+        }
+        if picked.len() == PALETTE_COLORS {
+            break;
+        }
+    }
+    picked
+        .iter()
+        .map(|[r, g, b]| format!("#{r:02x}{g:02x}{b:02x}"))
+        .collect()
+}
+
+// Called with every payload the watcher settles on; only renders when the track or its
+// artwork changed since last time
+pub fn update(app: &tauri::AppHandle, np: &NowPlaying) {
+    let art = np.artwork_path.clone().or_else(|| np.artwork_url.clone());
+    let Some(art_key) = art else {
+        return;
+    };
+    let key = format!(
+        "{}|{}|{art_key}",
+        np.track_name.as_deref().unwrap_or_default(),
+        np.artists.join(", ")
+    );
+    let (dirs, use_gpu) = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if !s.layouts.config.enabled || s.layouts.rendered.as_ref() == Some(&key) {
+            return;
+        }
+        s.layouts.rendered = Some(key);
+        let use_gpu = s.gpu_images;
+        drop(s);
+        match export::output_dirs(&state) {
+            Ok(dirs) => (dirs, use_gpu),
+            Err(e) => {
+                eprintln!("[layouts] {e}");
+                return;
+            }
+        }
+    };
+
+    let (path, url) = (np.artwork_path.clone(), np.artwork_url.clone());
+    tauri::async_runtime::spawn(async move {
+        let img = match (path, url) {
+            (Some(p), _) => image::open(&p).map_err(|e| format!("open {p}: {e}")),
+            (None, Some(u)) => export::fetch_image(&u).await,
+            (None, None) => return,
+        };
+        let img = match img {
+            Ok(img) => img,
+            Err(e) => {
+                eprintln!("[layouts] {e}");
+                return;
+            }
+        };
+        let res = tauri::async_runtime::spawn_blocking(move || -> Result<(), String> {
+            for dir in &dirs {
+                fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+            }
+            for layout in &LAYOUTS {
+                let frame = compose(&img, layout, use_gpu);
+                for dir in &dirs {
+                    frame
+                        .save(dir.join(layout.file))
+                        .map_err(|e| format!("write {}: {e}", layout.file))?;
+                }
+            }
+            let colors = palette(&palette_sample(&img, use_gpu));
+            let json = serde_json::to_vec_pretty(&serde_json::json!({ "colors": colors }))
+                .map_err(|e| format!("serialize palette: {e}"))?;
+            for dir in &dirs {
+                fs::write(dir.join(PALETTE_FILE), &json)
+                    .map_err(|e| format!("write {PALETTE_FILE}: {e}"))?;
+            }
+            Ok(())
+        })
+        .await;
+        match res {
+            Ok(Err(e)) => eprintln!("[layouts] {e}"),
+            Err(e) => eprintln!("[layouts] spawn_blocking join error: {e}"),
+            Ok(Ok(())) => {}
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_layout_config(state: State<'_, SharedStore>) -> LayoutConfig {
+    state.lock().layouts.config.clone()
+}
+
+#[tauri::command]
+pub fn set_layout_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: LayoutConfig,
+) -> Result<(), String> {
+    write_setting(window.app_handle(), "layouts", serde_json::json!(config))?;
+    let mut s = state.lock();
+    s.layouts.config = config;
+    // render for the current track on the next update
+    s.layouts.rendered = None;
+    Ok(())
+}
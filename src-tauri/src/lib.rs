@@ -2,42 +2,455 @@ use lofty::picture::{Picture, PictureType};
 use lofty::prelude::{Accessor, TaggedFileExt};
 use lofty::probe::Probe;
 use parking_lot::lock_api::Mutex;
-use parking_lot::Mutex as PlMutex;
+use parking_lot::{Mutex as PlMutex, RwLock};
 use regex::Regex;
 use rspotify::{
     clients::{BaseClient, OAuthClient},
-    model::{Image, PlayableItem},
-    scopes, AuthCodePkceSpotify, Config, Credentials, OAuth, Token,
+    model::{AlbumId, Id, Image, PlayableItem},
+    scopes, AuthCodePkceSpotify, Config, Credentials, OAuth,
 };
 use serde::{Deserialize, Serialize};
+use settings::{read_settings, write_setting};
 use std::{
     collections::HashMap,
     fs,
-    io::{Read, Write},
-    net::TcpListener,
     path::{Path, PathBuf},
     sync::Arc,
 };
-use tauri::{Emitter, Manager, State};
+use tauri::{Manager, State};
 use tokio_util::sync::CancellationToken;
-use url::Url;
 use walkdir::WalkDir;
 
+mod accessibility;
+mod art_dedupe;
+mod artwork_lookup;
+mod artwork_variants;
+mod auth_status;
+mod auto_export;
+mod backoff;
+mod benchmark;
+mod capabilities;
+mod charts;
+mod cli;
+mod collage;
+mod companion;
+mod compilation;
+mod context;
+mod discord;
+mod dj_history;
+mod events;
+mod export;
+mod export_format;
+mod family;
+mod gpu;
+#[cfg_attr(not(windows), path = "gsmtc_unsupported.rs")]
+mod gsmtc;
+mod history;
+mod icecast;
+mod language;
+mod last_played;
+mod lastfm_import;
+mod layouts;
+mod librespot;
+mod normalizers;
+mod oauth_callback;
+mod obs;
+mod osc;
+mod overlay;
+mod paste_auth;
+mod placeholder;
+mod playback;
+mod playback_change;
+mod playlists;
+mod portable;
+mod progress;
+mod providers;
+mod push;
+mod queue;
+mod resilience;
+mod safe_mode;
+mod saved_tracks;
+mod schema;
+mod server;
+mod settings;
+mod spotify_search;
+mod streaks;
+mod token_store;
+mod traktor;
+mod trivia;
+mod tts;
+mod updater;
+mod usage;
+mod virtual_files;
+mod watchdog;
+mod webnowplaying;
+
 #[derive(Default)]
 struct SpotifyStore {
     // Use Arc so we can clone a handle and drop the lock before we await (fixes the Send error)
     client: Option<Arc<AuthCodePkceSpotify>>,
     watch_started: bool,
     cancel: Option<CancellationToken>,
+    // browser sign-in waiting for the OAuth redirect
+    auth_cancel: Option<CancellationToken>,
+    // paste-the-code sign-in waiting for `submit_auth_code`
+    paste_auth: Option<AuthCodePkceSpotify>,
+    redirect: oauth_callback::RedirectConfig,
+    // pasted in settings; SPOTIFY_CLIENT_ID is the fallback
+    client_id: Option<String>,
+
+    // latest track pushed by the WebNowPlaying browser extension
+    wnp: webnowplaying::WnpInput,
+    wnp_now_playing: Option<NowPlaying>,
+
+    // Spotify Connect receiver mode
+    librespot: librespot::Receiver,
+    librespot_now_playing: Option<NowPlaying>,
+
+    // ICY stream being listened to
+    icecast_url: Option<String>,
+    icecast_cancel: Option<CancellationToken>,
+    icecast_now_playing: Option<NowPlaying>,
+
+    traktor: traktor::TraktorInput,
+    obs: obs::ObsOutput,
+    discord: discord::DiscordPresence,
+    traktor_now_playing: Option<NowPlaying>,
+
+    dj_history: dj_history::History,
+
+    osc: osc::OscInput,
+    osc_now_playing: Option<NowPlaying>,
+
+    manual_override: Option<providers::ManualOverride>,
+
+    trivia: trivia::TriviaConfig,
+    catalog_search: spotify_search::SearchConfig,
+    family_friendly: family::FamilyFriendly,
+    normalizers: normalizers::Normalizers,
+    tts: tts::TtsConfig,
+    companion: companion::CompanionConfig,
+    streaks: streaks::StreakConfig,
+    tts_last_spoken: Option<std::time::Instant>,
+    // announcement held back by the cooldown, read out when it ends
+    tts_deferred: Option<String>,
+
+    export_profiles: Vec<export::ExportProfile>,
+    // shown by the overlay and `/files` when nothing is playing
+    overlay_placeholder: Option<placeholder::Placeholder>,
+    layouts: layouts::Layouts,
+    // blur/composite layouts on the GPU when one is available
+    gpu_images: bool,
+    // per-integration overrides of `resilience`'s defaults
+    retry_policies: std::collections::BTreeMap<String, resilience::RetryPolicy>,
+    // when the watcher loop is due back (see `watchdog`)
+    watchdog: watchdog::Heartbeat,
+    // covers for the end-of-session collage
+    session_covers: collage::SessionCovers,
+
+    // set when this run skipped the integrations (see `safe_mode`)
+    safe_mode: Option<safe_mode::Reason>,
+
+    usage: usage::Usage,
+
+    update_channel: updater::Channel,
+    // found by `updater::check_for_updates`, waiting for `install_update`
+    pending_update: Option<tauri_plugin_updater::Update>,
+
+    provider_chain: providers::ProviderChain,
+    active_source: Option<providers::Provider>,
+
+    // last payload sent as `now_playing_update`, handed to late-loading windows
+    last_now_playing: Option<NowPlaying>,
+    // when it was read, to interpolate the position (see `progress`)
+    last_now_playing_at: Option<std::time::Instant>,
+    artwork_hold: ArtworkHold,
+
+    event_subscriptions: events::Subscriptions,
+    emit_pipeline: events::Pipeline,
+    update_mode: events::UpdateMode,
+
+    // poll every source at once and emit `players_update`
+    aggregate_sessions: bool,
+
+    gsmtc_poll_ms: u64,
+    // watcher cadence for API sources while playing (see `next_poll_in`)
+    poll_interval: std::time::Duration,
+    // `pause_watcher`: nothing is polled or emitted until `resume_watcher`
+    watcher_paused: bool,
+    // `stop_watcher`: stays down until `restart_watcher`, whatever else asks for it
+    watcher_stopped: bool,
+    // failed Spotify polls; no API polls until it runs out
+    spotify_backoff: backoff::Backoff,
+    // cuts the watcher's sleep short, e.g. when GSMTC sees playback start during an idle wait
+    watcher_wake: Arc<tokio::sync::Notify>,
+    // GSMTC event subscriptions (see `gsmtc::start_event_watcher`)
+    gsmtc_cancel: Option<CancellationToken>,
+    // AUMID picked with `gsmtc::select_media_session`
+    gsmtc_pinned_session: Option<String>,
+    // AUMID fragments, most preferred first
+    gsmtc_priority: Vec<String>,
+}
+
+impl SpotifyStore {
+    // Whether the watcher has anything to poll right now
+    fn watcher_has_work(&self) -> bool {
+        self.client.is_some()
+            || self.manual_override.is_some()
+            || self.aggregate_sessions
+            || self
+                .provider_chain
+                .providers
+                .iter()
+                .any(|p| self.polls_without_client(*p))
+    }
 
-    local_art_dir: Option<PathBuf>,
-    art_cache: HashMap<String, String>, // album-key -> cached-art path
-    local_index: HashMap<String, PathBuf>,
+    // Chain entries that have something to poll without a Spotify client: the opt-in
+    // listeners only once they're turned on
+    fn polls_without_client(&self, provider: providers::Provider) -> bool {
+        use providers::Provider;
+        match provider {
+            Provider::Spotify | Provider::Manual => false,
+            Provider::WebNowPlaying => self.wnp.enabled(),
+            Provider::Librespot => self.librespot.enabled(),
+            Provider::Icecast => self.icecast_url.is_some(),
+            Provider::Traktor => self.traktor.enabled(),
+            Provider::DjHistory => self.dj_history.enabled(),
+            Provider::Osc => self.osc.enabled(),
+            Provider::Gsmtc | Provider::Tidal | Provider::Deezer | Provider::AppleMusic => {
+                cfg!(windows)
+            }
+        }
+    }
 }
 
 type SharedStore = Arc<PlMutex<SpotifyStore>>;
 
-#[derive(Serialize)]
+// Watcher cadence while something plays, unless set with `set_poll_interval`; idle users cost
+// one request per `IDLE_POLL` instead
+const DEFAULT_POLL_SECS: u64 = 2;
+const MAX_POLL_SECS: u64 = 60;
+const IDLE_POLL: std::time::Duration = std::time::Duration::from_secs(15);
+// Polls timed to a track's predicted end land this long after it, and never closer together
+// than `MIN_POLL`
+const TRACK_END_MARGIN: std::time::Duration = std::time::Duration::from_millis(300);
+const MIN_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+// How long the watcher sleeps after emitting `np`
+fn next_poll_in(s: &SpotifyStore, np: &NowPlaying) -> std::time::Duration {
+    use std::time::Duration;
+
+    if s.active_source.is_some_and(|p| p.is_gsmtc()) {
+        return Duration::from_millis(s.gsmtc_poll_ms);
+    }
+    let active = s.poll_interval.max(MIN_POLL);
+    let delay = if !np.is_playing {
+        IDLE_POLL.max(active)
+    } else {
+        // close to the end: poll right after it to catch the next track quickly
+        match (np.position_ms, np.duration_ms) {
+            (Some(pos), Some(dur)) => {
+                let remaining = Duration::from_millis(dur.saturating_sub(pos));
+                if remaining < active {
+                    (remaining + TRACK_END_MARGIN).max(MIN_POLL)
+                } else {
+                    active
+                }
+            }
+            _ => active,
+        }
+    };
+    match s.spotify_backoff.remaining() {
+        Some(wait)
+            if s.active_source
+                .is_none_or(|p| p == providers::Provider::Spotify) =>
+        {
+            delay.max(wait)
+        }
+        _ => delay,
+    }
+}
+
+// Handle to the Spotify client, cloned out so the lock isn't held across requests
+fn spotify_client(state: &SharedStore) -> Result<Arc<AuthCodePkceSpotify>, String> {
+    state
+        .lock()
+        .client
+        .clone()
+        .ok_or_else(|| "Not connected to Spotify".to_string())
+}
+
+// Local music folder and its track index, locked apart from the store. A rebuild scans without
+// any lock and swaps in a new `Arc` at the end; lookups clone the `Arc` and search lock-free,
+// so a long scan never holds up a poll.
+#[derive(Default)]
+struct LocalLibrary {
+    dir: Option<PathBuf>,
+    index: Arc<HashMap<String, PathBuf>>,
+}
+
+type SharedLibrary = Arc<RwLock<LocalLibrary>>;
+
+// Lookup caches of the enrichers, also locked apart from the store
+#[derive(Default)]
+struct Caches {
+    art_lookup: HashMap<String, Option<(String, String)>>, // title|artist -> (cover url, album)
+    context: HashMap<String, Option<context::ContextInfo>>, // context uri -> name/cover
+    trivia: HashMap<String, Option<String>>,               // title|artist -> fact
+    catalog: HashMap<String, Option<spotify_search::CatalogMatch>>, // title|artist -> match
+    // (current track id, in Liked Songs, checked at)
+    saved: Option<(String, bool, std::time::Instant)>,
+    // (current track, first queued item, checked at)
+    next_track: Option<(String, Option<queue::QueueItem>, std::time::Instant)>,
+}
+
+type SharedCaches = Arc<PlMutex<Caches>>;
+
+fn library_snapshot(app: &tauri::AppHandle) -> (Option<PathBuf>, Arc<HashMap<String, PathBuf>>) {
+    let library = app.state::<SharedLibrary>();
+    let l = library.read();
+    (l.dir.clone(), l.index.clone())
+}
+
+// Scans `dir` on the blocking pool; lookups keep using the current index until it's done.
+fn rebuild_local_index(app: &tauri::AppHandle, dir: PathBuf) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let index = Arc::new(build_local_index(&dir));
+        let library = app.state::<SharedLibrary>();
+        let mut l = library.write();
+        // another folder was picked while this one was scanning
+        if l.dir.as_ref() == Some(&dir) {
+            l.index = index;
+        }
+    });
+}
+
+#[derive(Serialize, Clone, Copy, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum RestartReason {
+    /// Repeat-one looped the track
+    Repeat,
+    Restart,
+}
+
+/// Payload of `track_restarted`
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+struct TrackRestarted {
+    now_playing: NowPlaying,
+    reason: RestartReason,
+}
+
+/// Payload of `anniversary`: the album came out on this day some years ago
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+struct Anniversary {
+    now_playing: NowPlaying,
+    years: Option<i32>,
+}
+
+// Follows the watcher's output to notice the same track starting over (replayed, or looping
+// on repeat-one), which a track-key compare can't see.
+#[derive(Default)]
+struct PlayTracker {
+    last: Option<playback_change::Seen>,
+}
+
+impl PlayTracker {
+    fn observe(&mut self, np: &NowPlaying) -> bool {
+        let Some(track) = &np.track_name else {
+            self.last = None;
+            return false;
+        };
+        let cur = playback_change::Seen {
+            key: format!("{track}|{}", np.artists.join(",")),
+            status: if np.is_playing { "Playing" } else { "Paused" }.to_string(),
+            position_ms: np.position_ms.and_then(|p| i64::try_from(p).ok()),
+            seen_at: std::time::Instant::now(),
+        };
+        let change = playback_change::detect(self.last.as_ref(), &cur);
+        self.last = Some(cur);
+        change == Some(playback_change::Change::Restart)
+    }
+}
+
+// How long a new track may go without artwork before it counts as having none
+const ARTWORK_GRACE: std::time::Duration = std::time::Duration::from_secs(3);
+
+// Polled sources resolve artwork (local index, catalog lookups) before anything is emitted, but
+// pushed ones often send the cover a moment after the track, and a lookup that failed can
+// succeed on the next poll. Until then the track is flagged `pending_artwork`, so overlays keep
+// the previous image instead of blanking it.
+#[derive(Default)]
+struct ArtworkHold {
+    key: Option<String>,
+    since: Option<std::time::Instant>,
+}
+
+impl ArtworkHold {
+    fn pending(&mut self, np: &NowPlaying) -> bool {
+        let key = np
+            .track_name
+            .as_ref()
+            .map(|t| format!("{t}|{}", np.artists.join(",")));
+        if key != self.key {
+            self.key = key;
+            self.since = Some(std::time::Instant::now());
+        }
+        np.track_name.is_some()
+            && np.artwork_url.is_none()
+            && np.artwork_path.is_none()
+            && self.since.is_some_and(|t| t.elapsed() < ARTWORK_GRACE)
+    }
+}
+
+// The payload minus what moves on its own between polls, for spotting real changes
+fn without_ticking(np: &NowPlaying) -> serde_json::Value {
+    let mut np = np.clone();
+    np.position_ms = None;
+    if let Some(c) = np.companion.as_mut() {
+        c.session_uptime_secs = 0;
+        c.session_uptime.clear();
+    }
+    serde_json::to_value(np).unwrap_or_default()
+}
+
+// Sets `np.pending_artwork` and records `np` as the last payload (persisting it on track
+// changes, see `last_played`); call right before emitting `now_playing_update`. Returns
+// whether to emit it at all (see `events::UpdateMode`).
+fn settle_now_playing(app: &tauri::AppHandle, state: &SharedStore, np: &mut NowPlaying) -> bool {
+    let (new_track, changed, always) = {
+        let mut s = state.lock();
+        np.pending_artwork = s.artwork_hold.pending(np);
+        let previous = s.last_now_playing.replace(np.clone());
+        s.last_now_playing_at = Some(std::time::Instant::now());
+        let changed = previous
+            .as_ref()
+            .is_none_or(|p| without_ticking(p) != without_ticking(np));
+        let new_track = np.track_name.is_some()
+            && previous.is_none_or(|p| {
+                p.stale || p.track_name != np.track_name || p.artists != np.artists
+            });
+        (
+            new_track,
+            changed,
+            s.update_mode == events::UpdateMode::Always,
+        )
+    };
+    layouts::update(app, np);
+    auto_export::on_update(app, np, new_track, changed);
+    discord::update(app, np);
+    if new_track {
+        events::emit(app, "track_changed", &*np);
+        last_played::save(app, np);
+        collage::record(app, np);
+        history::record(app, np);
+        tts::announce(app, np);
+    }
+    changed || always
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, schemars::JsonSchema)]
+#[serde(default)]
 struct NowPlaying {
     is_playing: bool,
     track_name: Option<String>,
@@ -45,18 +458,163 @@ struct NowPlaying {
     album: Option<String>,
     artwork_url: Option<String>,  // remote (Spotify) URL
     artwork_path: Option<String>, // local file path, frontend will convert via convertFileSrc
+    position_ms: Option<u64>,
+    duration_ms: Option<u64>,
+    // "off" | "track" | "context", when the source reports it
+    repeat_mode: Option<String>,
+    shuffle: Option<bool>,
+
+    // provider the track came from
+    source: Option<providers::Provider>,
+    // GSMTC AUMID of the player, for GSMTC-backed sources
+    source_app_id: Option<String>,
+
+    // what the track is playing from (Spotify only)
+    context_type: Option<String>,
+    context_name: Option<String>,
+    // playlist owner, album artists or podcast publisher
+    context_owner: Option<String>,
+    context_artwork_url: Option<String>,
+    // display form, e.g. "Playlist: Focus Beats"
+    context_label: Option<String>,
+
+    // short artist/track fact, when trivia is turned on
+    trivia: Option<String>,
+    // composer / work / movement, for sources in classical mode (see `normalizers`)
+    classical: Option<normalizers::ClassicalInfo>,
+    // language and script, for picking fonts (see `language`)
+    title_lang: Option<language::TextLang>,
+    artist_lang: Option<language::TextLang>,
+
+    // "track" | "episode" (Spotify only)
+    media_kind: Option<String>,
+    episode: Option<EpisodeInfo>,
+
+    // album release date as Spotify gives it: "2001", "2001-03" or "2001-03-12"
+    release_date: Option<String>,
+    album_age_years: Option<i32>,
+    // today is the release date's anniversary (only known for day-precision dates)
+    is_release_anniversary: bool,
+
+    // only Spotify reports this
+    explicit: bool,
+    // the album is credited to "Various Artists" (see `compilation`)
+    compilation: bool,
+    // in the user's Liked Songs (Spotify only)
+    is_saved: Option<bool>,
+    // first item in the Spotify queue
+    next_track: Option<queue::QueueItem>,
+    // new track whose artwork hasn't arrived yet (see `ArtworkHold`)
+    pending_artwork: bool,
+    // restored from the previous run, not seen by any source yet
+    stale: bool,
+    // clock and session uptime, when turned on (see `companion`)
+    companion: Option<companion::Companion>,
+    // listening streak and today's goal, when turned on (see `streaks`)
+    streak: Option<streaks::StreakBadge>,
+}
+
+// Whole years since release, and whether today is the anniversary
+fn release_age(date: &str, today: chrono::NaiveDate) -> (Option<i32>, bool) {
+    use chrono::Datelike;
+
+    let mut parts = date.split('-').map(|p| p.parse::<u32>().ok());
+    let Some(Some(year)) = parts.next() else {
+        return (None, false);
+    };
+    let (month, day) = (parts.next().flatten(), parts.next().flatten());
+
+    let mut age = today.year() - year as i32;
+    if let (Some(m), Some(d)) = (month, day) {
+        if (today.month(), today.day()) < (m, d) {
+            age -= 1;
+        }
+    }
+    let anniversary = age > 0 && month == Some(today.month()) && day == Some(today.day());
+    (Some(age.max(0)), anniversary)
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, schemars::JsonSchema)]
+struct EpisodeInfo {
+    show_name: String,
+    // first couple of sentences of the episode description
+    description: String,
+    release_date: String,
+    duration_ms: i64,
+    resume_position_ms: Option<i64>,
+    fully_played: bool,
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ExportPayload {
-    track_name: String,
+const EPISODE_SNIPPET_CHARS: usize = 200;
+
+fn episode_info(ep: &rspotify::model::FullEpisode) -> EpisodeInfo {
+    let text = ep
+        .description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let description = if text.chars().count() > EPISODE_SNIPPET_CHARS {
+        text.chars()
+            .take(EPISODE_SNIPPET_CHARS - 1)
+            .collect::<String>()
+            + "…"
+    } else {
+        text
+    };
+    EpisodeInfo {
+        show_name: ep.show.name.clone(),
+        description,
+        release_date: ep.release_date.clone(),
+        duration_ms: ep.duration.num_milliseconds(),
+        resume_position_ms: ep
+            .resume_point
+            .as_ref()
+            .map(|r| r.resume_position.num_milliseconds()),
+        fully_played: ep.resume_point.as_ref().is_some_and(|r| r.fully_played),
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct AlbumTrack {
+    id: Option<String>,
+    disc_number: i32,
+    track_number: u32,
+    name: String,
+    artists: Vec<String>,
+    duration_ms: i64,
+    is_current: bool,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct AlbumTracks {
+    album_id: String,
+    name: String,
     artists: Vec<String>,
-    album: Option<String>,
     artwork_url: Option<String>,
-    artwork_path: Option<String>,
+    tracks: Vec<AlbumTrack>,
+}
+
+// Everything a freshly opened window needs to render without waiting for the next poll
+#[derive(Serialize, schemars::JsonSchema)]
+struct FullState {
+    now_playing: Option<NowPlaying>,
+    connected: bool,
+    watcher_running: bool,
+    watcher_paused: bool,
+    active_source: Option<providers::Provider>,
+    settings: SettingsSummary,
+    queue: Vec<queue::QueueItem>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct SettingsSummary {
+    local_art_dir: Option<String>,
+    provider_chain: providers::ProviderChain,
+    aggregate_sessions: bool,
+    poll_interval_secs: u64,
 }
 
+#[cfg(windows)]
 fn looks_like_artists_block(s: &str) -> bool {
     let l = s.to_ascii_lowercase();
     // Signal characters/words that usually mean "multiple artists listed"
@@ -73,6 +631,7 @@ fn looks_like_artists_block(s: &str) -> bool {
         || l.contains(" vs ")
 }
 
+#[cfg(windows)]
 fn parse_artists_prefix_from_title(s: &str) -> Vec<String> {
     // Try to split "ARTISTS —/–/-/: Track"
     // Use the first dash/colon we find.
@@ -133,18 +692,20 @@ fn clean_person(name: &str) -> String {
         '<', '>', '（', '）', '「', '」', '『', '』', '【', '】', '《', '》', '"', '“', '”', '‘',
         '’', '\'', '–', '—', '−', '-', '•', '●',
     ];
-    let mut s = name.trim_matches(TRIM);
+    let s = name.trim_matches(TRIM);
     // collapse inner whitespace
-    let collapsed = s.split_whitespace().collect::<Vec<_>>().join(" ");
-    collapsed
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
+#[cfg(windows)]
 fn has_any_upper(s: &str) -> bool {
     s.chars().any(|c| c.is_uppercase())
 }
+#[cfg(windows)]
 fn alpha_len(s: &str) -> usize {
     s.chars().filter(|c| c.is_alphabetic()).count()
 }
+#[cfg(windows)]
 fn is_short_acronym(s: &str) -> bool {
     let n = alpha_len(s);
     n > 0
@@ -153,6 +714,7 @@ fn is_short_acronym(s: &str) -> bool {
             .filter(|c| c.is_alphabetic())
             .all(|c| c.is_uppercase())
 }
+#[cfg(windows)]
 fn better_cased(existing: &str, candidate: &str) -> bool {
     // Prefer any candidate that has uppercase when existing has none
     if !has_any_upper(existing) && has_any_upper(candidate) {
@@ -165,6 +727,7 @@ fn better_cased(existing: &str, candidate: &str) -> bool {
     false
 }
 
+#[cfg(windows)]
 fn dedup_push(list: &mut Vec<String>, name: &str) {
     let n = clean_person(name);
     if n.is_empty() {
@@ -181,6 +744,7 @@ fn dedup_push(list: &mut Vec<String>, name: &str) {
     }
 }
 
+#[cfg(windows)]
 fn parse_featured_from_title(title: &str) -> Vec<String> {
     // Supports ASCII ()[] and JP full-width （）【】「」『』 and both :/：.
     // Also recognizes &, ＆, x/ｘ, +/＋.
@@ -205,18 +769,6 @@ fn parse_featured_from_title(title: &str) -> Vec<String> {
     out
 }
 
-fn sanitize(s: &str) -> String {
-    let trimmed = s.trim();
-    if trimmed.is_empty() {
-        return String::new();
-    }
-    let bad = ['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\n', '\r'];
-    trimmed
-        .chars()
-        .map(|c| if bad.contains(&c) { '_' } else { c })
-        .collect()
-}
-
 fn build_local_index(dir: &Path) -> HashMap<String, PathBuf> {
     let mut map = HashMap::new();
 
@@ -265,7 +817,8 @@ fn build_local_index(dir: &Path) -> HashMap<String, PathBuf> {
         };
 
         if !title.is_empty() {
-            if !artist.is_empty() {
+            // a compilation's files are found by title and album only
+            if !artist.is_empty() && !compilation::is_various(&artist) {
                 map.insert(key_title_artist(&title, &artist), path.to_path_buf());
             }
             if !album.is_empty() {
@@ -300,7 +853,7 @@ fn extract_embedded_art_to_cache(app: &tauri::AppHandle, audio: &Path) -> Option
     }
     let pic = pic_opt?;
 
-    let bytes: &[u8] = pic.data().as_ref();
+    let bytes: &[u8] = pic.data();
 
     // Decide extension by MIME
     let ext = match pic.mime_type().map(|m| m.as_str()) {
@@ -311,7 +864,7 @@ fn extract_embedded_art_to_cache(app: &tauri::AppHandle, audio: &Path) -> Option
     };
 
     // Cache path under $APP/artcache/<sanitized audio path>.<ext>
-    let cache_dir = app.path().app_local_data_dir().ok()?.join("artcache");
+    let cache_dir = crate::portable::data_dir(app).ok()?.join("artcache");
     let _ = fs::create_dir_all(&cache_dir);
 
     // Make a deterministic filename from the audio path
@@ -319,7 +872,11 @@ fn extract_embedded_art_to_cache(app: &tauri::AppHandle, audio: &Path) -> Option
     name = name.replace(['\\', '/', ':', '*', '?', '"', '<', '>', '|'], "_");
 
     let out_path = cache_dir.join(format!("{}.{}", name, ext));
-    fs::write(&out_path, bytes).ok()?;
+    // replaced rather than written through: it may be hardlinked to other covers (`art_dedupe`)
+    if fs::read(&out_path).ok().as_deref() != Some(bytes) {
+        let _ = fs::remove_file(&out_path);
+        fs::write(&out_path, bytes).ok()?;
+    }
 
     Some(out_path)
 }
@@ -331,6 +888,12 @@ fn build_now_playing_from_ctx(ctx: &rspotify::model::CurrentlyPlayingContext) ->
     let mut artists = Vec::new();
     let mut album = None;
     let mut artwork_url = None;
+    let mut media_kind = None;
+    let mut episode = None;
+    let mut release_date = None;
+    let mut explicit = false;
+    let mut compilation = false;
+    let mut duration_ms = None;
 
     if let Some(item) = &ctx.item {
         match item {
@@ -339,16 +902,35 @@ fn build_now_playing_from_ctx(ctx: &rspotify::model::CurrentlyPlayingContext) ->
                 artists = track.artists.iter().map(|a| a.name.clone()).collect();
                 album = Some(track.album.name.clone());
                 artwork_url = pick_image_url(&track.album.images, 300);
+                media_kind = Some("track".to_string());
+                release_date = track.album.release_date.clone();
+                explicit = track.explicit;
+                compilation = track.album.album_type.as_deref() == Some("compilation")
+                    || track
+                        .album
+                        .artists
+                        .iter()
+                        .any(|a| compilation::is_various(&a.name));
+                duration_ms = u64::try_from(track.duration.num_milliseconds()).ok();
             }
             PlayableItem::Episode(ep) => {
                 track_name = Some(ep.name.clone());
                 album = Some(ep.show.name.clone());
                 artists = vec![ep.show.publisher.clone()];
                 artwork_url = pick_image_url(&ep.images, 300);
+                media_kind = Some("episode".to_string());
+                episode = Some(episode_info(ep));
+                explicit = ep.explicit;
+                duration_ms = u64::try_from(ep.duration.num_milliseconds()).ok();
             }
         }
     }
 
+    let (album_age_years, is_release_anniversary) = release_date
+        .as_deref()
+        .map(|d| release_age(d, chrono::Local::now().date_naive()))
+        .unwrap_or_default();
+
     NowPlaying {
         is_playing: ctx.is_playing,
         track_name,
@@ -356,60 +938,175 @@ fn build_now_playing_from_ctx(ctx: &rspotify::model::CurrentlyPlayingContext) ->
         album,
         artwork_url,
         artwork_path: None,
+        position_ms: ctx
+            .progress
+            .and_then(|p| u64::try_from(p.num_milliseconds()).ok()),
+        duration_ms,
+        // filled in from `playback::Playback`
+        repeat_mode: None,
+        shuffle: None,
+        source: Some(providers::Provider::Spotify),
+        source_app_id: None,
+        // filled in by `context::enrich`
+        context_type: None,
+        context_name: None,
+        context_owner: None,
+        context_artwork_url: None,
+        context_label: None,
+        trivia: None,
+        // filled in by `normalizers::apply` / `language::apply`
+        classical: None,
+        title_lang: None,
+        artist_lang: None,
+        media_kind,
+        episode,
+        album_age_years,
+        is_release_anniversary,
+        release_date,
+        explicit,
+        compilation,
+        // filled in by `saved_tracks::enrich`
+        is_saved: None,
+        // filled in by `queue::enrich`
+        next_track: None,
+        pending_artwork: false,
+        stale: false,
+        companion: None,
+        streak: None,
     }
 }
 
-fn settings_path(window: &tauri::Window) -> Result<PathBuf, String> {
-    let dir = window
-        .app_handle()
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("app_local_data_dir: {e}"))?
-        .join("settings");
-    std::fs::create_dir_all(&dir).map_err(|e| format!("create dir: {e}"))?;
-    Ok(dir.join("settings.json"))
-}
-
 fn save_local_art_dir(window: &tauri::Window, path: &Path) -> Result<(), String> {
-    let p = settings_path(window)?;
-    let json = serde_json::json!({ "local_art_dir": path.to_string_lossy() });
-    fs::write(p, serde_json::to_vec(&json).unwrap()).map_err(|e| e.to_string())
+    write_setting(
+        window.app_handle(),
+        "local_art_dir",
+        serde_json::json!(path.to_string_lossy()),
+    )
 }
 
 fn load_local_art_dir(window: &tauri::Window) -> Option<PathBuf> {
-    let p = settings_path(window).ok()?;
-    let bytes = fs::read(p).ok()?;
-    let v: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
-    v.get("local_art_dir")?.as_str().map(PathBuf::from)
+    load_local_art_dir_from_handle(window.app_handle())
+}
+
+fn load_poll_interval(app: &tauri::AppHandle) -> std::time::Duration {
+    let secs = read_settings(app)
+        .get("poll_interval_secs")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_POLL_SECS);
+    std::time::Duration::from_secs(secs.clamp(1, MAX_POLL_SECS))
+}
+
+// How often the watcher polls API sources (Spotify) while something plays; takes effect on
+// the next poll. Returns the interval actually used.
+#[tauri::command]
+fn set_poll_interval(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    secs: u64,
+) -> Result<u64, String> {
+    let secs = secs.clamp(1, MAX_POLL_SECS);
+    write_setting(
+        window.app_handle(),
+        "poll_interval_secs",
+        serde_json::json!(secs),
+    )?;
+    let mut s = state.lock();
+    s.poll_interval = std::time::Duration::from_secs(secs);
+    s.watcher_wake.notify_one();
+    Ok(secs)
+}
+
+// Stops polling and emitting without tearing the watcher down; not persisted, a restart
+// always starts unpaused
+#[tauri::command]
+fn pause_watcher(state: State<'_, SharedStore>) {
+    state.lock().watcher_paused = true;
+}
+
+#[tauri::command]
+fn resume_watcher(state: State<'_, SharedStore>) {
+    let mut s = state.lock();
+    s.watcher_paused = false;
+    s.watcher_wake.notify_one();
 }
 
-fn settings_path_from_handle(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let dir = app
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("app_local_data_dir: {e}"))?
-        .join("settings");
-    std::fs::create_dir_all(&dir).map_err(|e| format!("create dir: {e}"))?;
-    Ok(dir.join("settings.json"))
+fn cancel_watcher(s: &mut SpotifyStore) {
+    if let Some(t) = s.cancel.take() {
+        t.cancel();
+    }
+    s.watch_started = false;
+    s.active_source = None;
+}
+
+// Ends the watcher loop; unlike `pause_watcher` nothing brings it back but `restart_watcher`
+#[tauri::command]
+fn stop_watcher(state: State<'_, SharedStore>) {
+    let mut s = state.lock();
+    cancel_watcher(&mut s);
+    s.watcher_stopped = true;
+}
+
+// Re-reads the watcher's settings and starts a fresh loop (failover and backoff reset, not
+// paused). Returns whether it's running, which it won't be with nothing to poll.
+#[tauri::command]
+fn restart_watcher(state: State<'_, SharedStore>, window: tauri::Window) -> bool {
+    let app = window.app_handle();
+    let chain = providers::load_chain(app);
+    let aggregate = providers::load_aggregate_mode(app);
+    let poll_interval = load_poll_interval(app);
+    let gsmtc_poll_ms = gsmtc::load_poll_interval(app);
+    {
+        let mut s = state.lock();
+        cancel_watcher(&mut s);
+        s.watcher_stopped = false;
+        s.watcher_paused = false;
+        s.spotify_backoff = backoff::Backoff::default();
+        s.provider_chain = chain;
+        s.aggregate_sessions = aggregate;
+        s.poll_interval = poll_interval;
+        s.gsmtc_poll_ms = gsmtc_poll_ms;
+    }
+    start_watcher_if_needed(app, &state);
+    state.lock().watch_started
 }
 
 fn load_local_art_dir_from_handle(app: &tauri::AppHandle) -> Option<PathBuf> {
-    let p = settings_path_from_handle(app).ok()?;
-    let bytes = fs::read(p).ok()?;
-    let v: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
-    v.get("local_art_dir")?.as_str().map(PathBuf::from)
+    read_settings(app)
+        .get("local_art_dir")?
+        .as_str()
+        .map(PathBuf::from)
+}
+
+// Enrichment and filtering every emitted `now_playing_update` goes through
+async fn finish_now_playing(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    compilation::apply(np);
+    trivia::enrich(app, np).await;
+    finish_local(app, np);
+}
+
+// The steps after the trivia lookup, which need no network; push sources report from
+// synchronous callbacks and go through compilation and just these (`providers::publish_pushed`)
+fn finish_local(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    family::apply(&app.state::<SharedStore>(), np);
+    normalizers::apply(&app.state::<SharedStore>(), np);
+    language::apply(np);
+    companion::apply(app, np);
+    streaks::apply(app, np);
 }
 
 fn start_watcher_if_needed(app: &tauri::AppHandle, state: &SharedStore) {
-    // Take the client and mark watcher started without holding the lock across await.
-    let (client, should_start) = {
+    // Mark the watcher started without holding the lock across await.
+    let should_start = {
         let mut guard = state.lock();
-        let c = guard.client.clone();
-        let should = c.is_some() && !guard.watch_started;
+        // safe mode leaves polling off until `safe_mode::restart_normally`
+        let should = !guard.watch_started
+            && !guard.watcher_stopped
+            && guard.safe_mode.is_none()
+            && guard.watcher_has_work();
         if should {
             guard.watch_started = true;
         }
-        (c, should)
+        should
     };
 
     if !should_start {
@@ -417,7 +1114,6 @@ fn start_watcher_if_needed(app: &tauri::AppHandle, state: &SharedStore) {
     }
 
     let app = app.clone();
-    let client = client.unwrap(); // safe because should_start implies Some
 
     let token = CancellationToken::new();
     {
@@ -426,65 +1122,100 @@ fn start_watcher_if_needed(app: &tauri::AppHandle, state: &SharedStore) {
     }
 
     tauri::async_runtime::spawn(async move {
-        use tokio::time::{sleep, Duration};
+        use tokio::time::sleep;
         let state_handle = app.state::<SharedStore>();
+        let mut failover = providers::FailoverState::default();
+        let mut tracker = PlayTracker::default();
+        // track the `anniversary` event was last sent for
+        let mut celebrated: Option<String> = None;
 
         loop {
             tokio::select! {
               _ = token.cancelled() => break,
 
               _ = async {
-                // if refresh fails -> auth is gone: clear everything and stop
-                if client.auto_reauth().await.is_err() {
-                  let _ = app.emit("auth_lost", &());
-                  let mut s = state_handle.lock();
-                  s.client = None;
-                  s.watch_started = false;
-                  s.cancel = None;
-                  return;
+                let (paused, wake) = {
+                    let s = state_handle.lock();
+                    (s.watcher_paused, s.watcher_wake.clone())
+                };
+                if paused {
+                    watchdog::idle(&state_handle);
+                    wake.notified().await;
+                    return;
                 }
-                let app_handle = app.clone();
-
-
-                match client.current_user_playing_item().await {
-                  Ok(Some(ctx)) => {
-                    let mut np = build_now_playing_from_ctx(&ctx);
-                    maybe_set_local_artwork(&app_handle, &state_handle, &mut np, &ctx);
-                    let _ = app.emit("now_playing_update", &np);
-                  }
-                  Ok(None) => {
-                    let _ = app.emit("now_playing_update", &NowPlaying {
-                      is_playing: false,
-                      track_name: None,
-                      artists: vec![],
-                      album: None,
-                      artwork_url: None,
-                      artwork_path: None,
-
-                    });
-                  }
-                    Err(e) => {
-                        // Transient API error (rate limit, network, 5xx, device issues, etc.)
-                        // Don't mark auth lost; just keep polling.
-                        // Optionally: if you can inspect the HTTP status and it's a hard 401 and reauth fails,
-                        // then treat as fatal.
-                        eprintln!("[poll] now_playing error: {e}");
-                        // Emit a benign "nothing playing" or skip emitting anything:
-                        let _ = app.emit("now_playing_update", &NowPlaying {
-                            is_playing: false,
-                            track_name: None,
-                            artists: vec![],
-                            album: None,
-                            artwork_url: None,
-                            artwork_path: None,
-                        });
-                        // then fall through to the sleep and next loop iteration
+                watchdog::expect_within(&state_handle, std::time::Duration::ZERO);
+                let aggregate = state_handle.lock().aggregate_sessions;
+                // one deadline for the whole round, shorter than the watchdog's
+                let polled = watchdog::within("watcher poll", watchdog::POLL_TIMEOUT, async {
+                    let mut np = if let Some(np) = providers::manual_override(&app, &state_handle) {
+                        np
+                    } else if aggregate {
+                        providers::poll_all(&app, &state_handle).await
+                    } else {
+                        providers::poll_chain(&app, &state_handle, &mut failover).await
+                    };
+                    finish_now_playing(&app, &mut np).await;
+                    np
+                })
+                .await;
+                let np = match polled {
+                    Ok(mut np) => {
+                        if np.is_release_anniversary && celebrated != np.track_name {
+                            celebrated = np.track_name.clone();
+                            events::emit(
+                                &app,
+                                "anniversary",
+                                Anniversary {
+                                    now_playing: np.clone(),
+                                    years: np.album_age_years,
+                                },
+                            );
+                        }
+                        if settle_now_playing(&app, &state_handle, &mut np) {
+                            events::emit(&app, "now_playing_update", &np);
+                        }
+                        if tracker.observe(&np) {
+                            let reason = if np.repeat_mode.as_deref() == Some("track") {
+                                RestartReason::Repeat
+                            } else {
+                                RestartReason::Restart
+                            };
+                            events::emit(
+                                &app,
+                                "track_restarted",
+                                TrackRestarted {
+                                    now_playing: np.clone(),
+                                    reason,
+                                },
+                            );
+                        }
+                        np
                     }
-                }
+                    // slow this round; nothing new to show, try again after the usual interval
+                    Err(_) => state_handle.lock().last_now_playing.clone().unwrap_or_default(),
+                };
 
-                sleep(Duration::from_secs(2)).await;
+                let (interval, wake) = {
+                    let s = state_handle.lock();
+                    (next_poll_in(&s, &np), s.watcher_wake.clone())
+                };
+                watchdog::expect_within(&state_handle, interval);
+                tokio::select! {
+                    _ = sleep(interval) => {}
+                    _ = wake.notified() => {}
+                }
               } => {}
             }
+
+            // Spotify-only chain and auth is gone: stop until the user reconnects
+            let mut s = state_handle.lock();
+            if !s.watcher_has_work() {
+                s.watchdog.stop();
+                s.watch_started = false;
+                s.cancel = None;
+                s.active_source = None;
+                break;
+            }
         }
     });
 }
@@ -502,130 +1233,116 @@ fn pick_image_url(images: &[Image], target: u32) -> Option<String> {
         .map(|img| img.url.clone())
 }
 
-fn read_token_from_disk(window: &tauri::Window) -> Result<Option<Token>, String> {
-    let path = token_cache_path(window)?;
-    if !path.exists() {
-        return Ok(None);
+// Everything the app asks Spotify for
+fn spotify_scopes() -> std::collections::HashSet<String> {
+    scopes!(
+        "user-read-currently-playing",
+        "user-read-playback-state",
+        // Liked Songs heart and `toggle_save_track`
+        "user-library-read",
+        "user-library-modify",
+        // `list_user_playlists` / `add_current_to_playlist`
+        "playlist-read-private",
+        "playlist-read-collaborative",
+        "playlist-modify-public",
+        "playlist-modify-private",
+        // `transfer_playback`
+        "user-modify-playback-state"
+    )
+}
+
+// Settings first, so a packaged build works without a .env next to it
+fn spotify_client_id(app: &tauri::AppHandle) -> Result<String, String> {
+    app.state::<SharedStore>()
+        .lock()
+        .client_id
+        .clone()
+        .or_else(|| std::env::var("SPOTIFY_CLIENT_ID").ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| {
+            "No Spotify client ID: paste the one from your Spotify app in Settings".to_string()
+        })
+}
+
+fn load_client_id(app: &tauri::AppHandle) -> Option<String> {
+    read_settings(app)
+        .get("spotify_client_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+// The effective ID, whether it came from settings or the environment
+#[tauri::command]
+fn get_spotify_client_id(window: tauri::Window) -> Option<String> {
+    spotify_client_id(window.app_handle()).ok()
+}
+
+// `None` or "" forgets the saved ID and goes back to SPOTIFY_CLIENT_ID
+#[tauri::command]
+fn set_spotify_client_id(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    client_id: Option<String>,
+) -> Result<(), String> {
+    let client_id = client_id
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty());
+    if let Some(id) = &client_id {
+        if id.len() != 32 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("A Spotify client ID is 32 letters and digits (0-9, a-f)".into());
+        }
     }
-    let data = fs::read(&path).map_err(|e| format!("read token file: {e}"))?;
-    let token: Token =
-        serde_json::from_slice(&data).map_err(|e| format!("parse token json: {e}"))?;
-    Ok(Some(token))
-}
-
-fn write_token_to_disk(window: &tauri::Window, token: &Token) -> Result<(), String> {
-    let path = token_cache_path(window)?;
-    let data = serde_json::to_vec(token).map_err(|e| format!("serialize token: {e}"))?;
-    fs::write(&path, data).map_err(|e| format!("write token file: {e}"))
-}
-
-// pick a stable cache file; make sure the folder exists
-fn token_cache_path(window: &tauri::Window) -> Result<PathBuf, String> {
-    let path = window
-        .app_handle()
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("app_local_data_dir: {e}"))?
-        .join("spotify")
-        .join("token.json");
-    if let Some(dir) = path.parent() {
-        std::fs::create_dir_all(dir).map_err(|e| format!("create cache dir: {e}"))?;
+    let app = window.app_handle();
+    let before = spotify_client_id(app).ok();
+    write_setting(app, "spotify_client_id", serde_json::json!(client_id))?;
+    state.lock().client_id = client_id;
+
+    // tokens belong to the app that issued them, so a different ID means signing in again
+    if spotify_client_id(app).ok() != before {
+        let signed_out = {
+            let mut s = state.lock();
+            if let Some(t) = s.auth_cancel.take() {
+                t.cancel();
+            }
+            let had_client = s.client.take().is_some();
+            if had_client {
+                cancel_watcher(&mut s);
+            }
+            had_client
+        };
+        token_store::clear(app)?;
+        start_watcher_if_needed(app, &state);
+        if signed_out {
+            events::emit(app, "auth_lost", ());
+        }
     }
-    Ok(path)
+    Ok(())
 }
 
 fn build_spotify(window: &tauri::Window) -> Result<AuthCodePkceSpotify, String> {
-    let client_id =
-        std::env::var("SPOTIFY_CLIENT_ID").map_err(|_| "Missing SPOTIFY_CLIENT_ID".to_string())?;
+    let client_id = spotify_client_id(window.app_handle())?;
 
     let creds = Credentials::new(&client_id, "");
     let oauth = OAuth {
-        redirect_uri: "http://127.0.0.1:5173/callback".to_string(),
-        scopes: scopes!("user-read-currently-playing", "user-read-playback-state"),
+        redirect_uri: window.state::<SharedStore>().lock().redirect.redirect_uri(),
+        scopes: spotify_scopes(),
         ..Default::default()
     };
+    // persisted through `token_store`, not rspotify's plaintext cache file
     let config = Config {
-        token_cached: true,
+        token_cached: false,
         token_refreshing: true,
-        cache_path: token_cache_path(window)?,
         ..Default::default()
     };
 
     Ok(AuthCodePkceSpotify::with_config(creds, oauth, config))
 }
 
-fn clear_token_cache(window: &tauri::Window) -> Result<(), String> {
-    let path = token_cache_path(window)?;
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| format!("remove token file: {e}"))?;
-    }
-    Ok(())
-}
-
-#[tauri::command]
-async fn write_now_playing_assets(
-    _window: tauri::Window,
-    payload: ExportPayload,
-) -> Result<String, String> {
-    use std::fs;
-
-    let exe_dir = std::env::current_exe()
-        .map_err(|e| format!("current_exe: {e}"))?
-        .parent()
-        .ok_or_else(|| "Cannot resolve executable directory".to_string())?
-        .to_path_buf();
-
-    let dir = exe_dir.join("Exported-track");
-    fs::create_dir_all(&dir).map_err(|e| format!("create Exported-track: {e}"))?;
-
-    // --- write the text files ---
-    let song = sanitize(&payload.track_name);
-    let artists = sanitize(&payload.artists.join(", "));
-    let album = sanitize(payload.album.as_deref().unwrap_or(""));
-
-    fs::write(dir.join("song.txt"), song).map_err(|e| e.to_string())?;
-    fs::write(dir.join("artist.txt"), artists).map_err(|e| e.to_string())?;
-    fs::write(dir.join("album.txt"), album).map_err(|e| e.to_string())?;
-
-    // --- artwork -> PNG (prefer local path, else fetch URL) ---
-    let target = dir.join("artwork.png");
-
-    if let Some(ap) = payload.artwork_path.as_deref() {
-        if !ap.is_empty() && Path::new(ap).exists() {
-            if let Ok(img) = image::open(ap) {
-                img.save(&target).map_err(|e| e.to_string())?;
-                return Ok(dir.to_string_lossy().to_string());
-            }
-            if Path::new(ap)
-                .extension()
-                .and_then(|e| e.to_str())
-                .map_or(false, |x| x.eq_ignore_ascii_case("png"))
-            {
-                fs::copy(ap, &target).map_err(|e| e.to_string())?;
-                return Ok(dir.to_string_lossy().to_string());
-            }
-        }
-    }
-
-    if let Some(url) = payload.artwork_url.as_deref() {
-        if !url.is_empty() {
-            let bytes = reqwest::get(url)
-                .await
-                .map_err(|e| e.to_string())?
-                .bytes()
-                .await
-                .map_err(|e| e.to_string())?;
-            let img = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
-            img.save(&target).map_err(|e| e.to_string())?;
-        }
-    }
-
-    Ok(dir.to_string_lossy().to_string())
-}
-
 #[tauri::command]
 fn set_local_art_dir(
-    _state: State<'_, SharedStore>, // underscore to silence unused warning
+    library: State<'_, SharedLibrary>,
     window: tauri::Window,
     path: String,
 ) -> Result<(), String> {
@@ -635,25 +1352,22 @@ fn set_local_art_dir(
     }
     save_local_art_dir(&window, &pb)?;
 
-    let app = window.app_handle().clone(); // ← clone fixes E0597
-    tauri::async_runtime::spawn_blocking(move || {
-        let idx = build_local_index(&pb);
-        let s = app.state::<SharedStore>();
-        let mut g = s.lock();
-        g.local_art_dir = Some(pb);
-        g.art_cache.clear();
-        g.local_index = idx;
-    });
-
+    {
+        let mut l = library.write();
+        l.dir = Some(pb.clone());
+        // the old folder's entries; the fallback scan covers lookups until the new index lands
+        l.index = Arc::default();
+    }
+    rebuild_local_index(window.app_handle(), pb);
     Ok(())
 }
 
 #[tauri::command]
-fn get_local_art_dir(state: State<'_, SharedStore>, window: tauri::Window) -> Option<String> {
+fn get_local_art_dir(library: State<'_, SharedLibrary>, window: tauri::Window) -> Option<String> {
     // prefer in-memory; else try disk
-    let mem = state
-        .lock()
-        .local_art_dir
+    let mem = library
+        .read()
+        .dir
         .clone()
         .or_else(|| load_local_art_dir(&window));
     mem.map(|p| p.to_string_lossy().to_string())
@@ -665,7 +1379,7 @@ async fn restore_spotify(
     window: tauri::Window,
 ) -> Result<bool, String> {
     let spotify = build_spotify(&window)?;
-    if let Some(token) = read_token_from_disk(&window)? {
+    if let Some(token) = token_store::load(window.app_handle())? {
         {
             let token_mutex = spotify.get_token();
             let mut guard = token_mutex
@@ -676,264 +1390,61 @@ async fn restore_spotify(
         }
 
         // ⬇️ check the result; if it fails, clear cache and report false
-        if let Err(_) = spotify.auto_reauth().await {
-            let _ = clear_token_cache(&window);
-            let mut s = state.lock();
-            if let Some(t) = s.cancel.take() {
-                t.cancel();
+        if spotify.auto_reauth().await.is_err() {
+            let _ = token_store::clear(window.app_handle());
+            {
+                let mut s = state.lock();
+                if let Some(t) = s.cancel.take() {
+                    t.cancel();
+                }
+                s.client = None;
+                s.watch_started = false;
             }
-            s.client = None;
-            s.watch_started = false;
+            // other providers in the chain don't need Spotify auth
+            start_watcher_if_needed(window.app_handle(), &state);
             return Ok(false);
         }
 
-        if let Some(tok) = spotify
-            .get_token()
-            .lock()
-            .await
-            .map_err(|_| "Token lock failed".to_string())?
-            .clone()
-        {
-            let _ = write_token_to_disk(&window, &tok);
-        }
+        token_store::persist(&spotify).await;
+        auth_status::check_scopes(window.app_handle(), &spotify).await;
         state.lock().client = Some(Arc::new(spotify));
 
         let app = window.app_handle();
-        start_watcher_if_needed(&app, &state);
+        start_watcher_if_needed(app, &state);
         return Ok(true);
     }
     Ok(false)
 }
 
+// Signs out of Spotify: stops using the client and deletes the stored token, so the next
+// connect goes through the browser again
 #[tauri::command]
-async fn get_current_playing_gsmtc(window: tauri::Window) -> Result<serde_json::Value, String> {
-    use futures::executor::block_on;
-
-    let app_handle = window.app_handle().clone();
-    let win_for_emit = window.clone();
-
-    let res: Result<(serde_json::Value, Option<String>), String> =
-        tauri::async_runtime::spawn_blocking(move || {
-            let result = block_on(async move {
-                use windows::Media::Control::{
-                    GlobalSystemMediaTransportControlsSession,
-                    GlobalSystemMediaTransportControlsSessionManager,
-                };
-                use windows::Storage::Streams::{DataReader, InputStreamOptions};
-
-                let mgr = GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
-                    .map_err(|e| format!("RequestAsync failed: {:?}", e))?
-                    .await
-                    .map_err(|e| format!("Await manager failed: {:?}", e))?;
-
-                let session: Option<GlobalSystemMediaTransportControlsSession> = match mgr
-                    .GetSessions()
-                {
-                    Ok(list) => {
-                        let n = list.Size().unwrap_or(0);
-                        let mut picked = None;
-                        for i in 0..n {
-                            if let Ok(s) = list.GetAt(i) {
-                                if let Ok(aumid) = s.SourceAppUserModelId() {
-                                    if aumid.to_string().to_ascii_lowercase().contains("spotify") {
-                                        picked = Some(s);
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        picked.or_else(|| mgr.GetCurrentSession().ok())
-                    }
-                    Err(_) => mgr.GetCurrentSession().ok(),
-                };
-
-                let Some(session) = session else {
-                    return Ok::<(serde_json::Value, Option<String>), String>((
-                        serde_json::json!({"error": "No active session"}),
-                        None,
-                    ));
-                };
-
-                let status = session
-                    .GetPlaybackInfo()
-                    .ok()
-                    .and_then(|info| info.PlaybackStatus().ok())
-                    .map(|s| format!("{:?}", s))
-                    .unwrap_or_else(|| "Unknown".to_string());
-
-                let props = session
-                    .TryGetMediaPropertiesAsync()
-                    .map_err(|e| format!("TryGetMediaPropertiesAsync: {:?}", e))?
-                    .await
-                    .map_err(|e| format!("await media properties: {:?}", e))?;
-
-                let title = props.Title().unwrap_or_default().to_string();
-                let album = props.AlbumTitle().unwrap_or_default().to_string();
-                let artist = props.Artist().unwrap_or_default().to_string();
-                let album_artist = props.AlbumArtist().unwrap_or_default().to_string();
-                let subtitle = props.Subtitle().unwrap_or_default().to_string(); // ← NEW
-
-                let mut artists_vec: Vec<String> = Vec::new();
-
-                if artists_vec.len() <= 1 {
-                    for n in parse_artists_prefix_from_title(&title) {
-                        dedup_push(&mut artists_vec, &n);
-                    }
-                    for n in parse_artists_prefix_from_title(&subtitle) {
-                        dedup_push(&mut artists_vec, &n);
-                    }
-                }
-
-                // Primary “Artist”
-                for n in parse_artists(&artist) {
-                    dedup_push(&mut artists_vec, &n);
-                }
-
-                // AlbumArtist often has multiple names (labels, teams, etc.)
-                for n in parse_artists(&album_artist) {
-                    dedup_push(&mut artists_vec, &n);
-                }
-
-                // Contributors embedded in the Title (“feat. …”, “with …”)
-                for n in parse_featured_from_title(&title) {
-                    dedup_push(&mut artists_vec, &n);
-                }
-
-                // Only trust subtitle if it *looks* like a list of artists (commas, &, x, +, feat, etc.)
-                if looks_like_artists_block(&subtitle) {
-                    for n in parse_artists(&subtitle) {
-                        dedup_push(&mut artists_vec, &n);
-                    }
-                }
-
-                // Also catch "(feat ...)" shapes inside the subtitle
-                for n in parse_featured_from_title(&subtitle) {
-                    dedup_push(&mut artists_vec, &n);
-                }
-
-                // If we already have a proper multi-letter artist, drop stray 1-letter tokens like "Y"
-                let have_multi_alpha = artists_vec
-                    .iter()
-                    .any(|n| n.chars().filter(|c| c.is_alphabetic()).count() > 1);
-                if have_multi_alpha {
-                    artists_vec.retain(|n| n.chars().filter(|c| c.is_alphabetic()).count() > 1);
-                }
-
-                // Thumbnail → bytes → cache file
-                let mut artwork_path: Option<String> = None;
-                if let Ok(th) = props.Thumbnail() {
-                    if let Ok(op) = th.OpenReadAsync() {
-                        if let Ok(stream) = op.await {
-                            let input = stream
-                                .GetInputStreamAt(0)
-                                .map_err(|e| format!("GetInputStreamAt: {:?}", e))?;
-                            let size = (stream.Size().unwrap_or(0).min(u64::from(u32::MAX))) as u32;
-                            if size > 0 {
-                                let reader = DataReader::CreateDataReader(&input)
-                                    .map_err(|e| format!("CreateDataReader: {:?}", e))?;
-                                reader
-                                    .SetInputStreamOptions(InputStreamOptions::ReadAhead)
-                                    .map_err(|e| format!("SetInputStreamOptions: {:?}", e))?;
-                                reader
-                                    .LoadAsync(size)
-                                    .map_err(|e| format!("LoadAsync: {:?}", e))?
-                                    .await
-                                    .map_err(|e| format!("LoadAsync await: {:?}", e))?;
-
-                                let mut bytes = vec![0u8; size as usize];
-                                reader
-                                    .ReadBytes(bytes.as_mut_slice())
-                                    .map_err(|e| format!("ReadBytes: {:?}", e))?;
-
-                                // Use the cloned app handle (not `window`) here.
-                                let cache_dir = app_handle
-                                    .path()
-                                    .app_local_data_dir()
-                                    .map_err(|e| format!("app_local_data_dir: {e}"))?
-                                    .join("artcache");
-                                let _ = std::fs::create_dir_all(&cache_dir);
-
-                                let safe = |s: &str| {
-                                    s.chars()
-                                        .map(|c| if r#"\/:*?"<>|"#.contains(c) { '_' } else { c })
-                                        .collect::<String>()
-                                };
-
-                                let png_path = cache_dir.join(format!(
-                                    "{}_{}_{}.png",
-                                    safe(&artist),
-                                    safe(&album),
-                                    safe(&title)
-                                ));
-
-                                if let Ok(img) = image::load_from_memory(&bytes) {
-                                    img.save(&png_path).map_err(|e| format!("save png: {e}"))?;
-                                    artwork_path = Some(png_path.to_string_lossy().to_string());
-                                } else {
-                                    let raw_path = cache_dir.join(format!(
-                                        "{}_{}_{}.bin",
-                                        safe(&artist),
-                                        safe(&album),
-                                        safe(&title)
-                                    ));
-                                    std::fs::write(&raw_path, &bytes)
-                                        .map_err(|e| format!("write thumb: {e}"))?;
-                                    artwork_path = Some(raw_path.to_string_lossy().to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-
-                let (position_ms, end_time_ms, last_updated_iso) = match session
-                    .GetTimelineProperties()
-                {
-                    Ok(tl) => {
-                        let pos_ms = tl.Position().ok().map(|ts| ts.Duration / 10_000);
-                        let end_ms = tl.EndTime().ok().map(|ts| ts.Duration / 10_000);
-                        let last_updated = tl.LastUpdatedTime().ok().map(|dt| format!("{:?}", dt));
-                        (pos_ms, end_ms, last_updated)
-                    }
-                    Err(_) => (None, None, None),
-                };
-
-                let payload = serde_json::json!({
-                    "status": status,
-                    "title": title,
-                    "album": album,
-                    "artist": artist,
-                    "artists": artists_vec,
-                    "position_ms": position_ms,
-                    "end_time_ms": end_time_ms,
-                    "last_updated": last_updated_iso,
-                    "source_app_id": session.SourceAppUserModelId().ok().map(|s| s.to_string()),
-                    "artwork_path": artwork_path
-                });
-
-                Ok::<(serde_json::Value, Option<String>), String>((
-                    payload,
-                    Some(format!("{title}|{artist}|{album}")),
-                ))
-            });
-
-            result
-        })
-        .await
-        .map_err(|e| format!("spawn_blocking join error: {e}"))?;
-
-    // Emit from the cloned window AFTER the await
-    if let Ok((payload, Some(key))) = &res {
-        use std::sync::{Mutex as StdMutex, OnceLock};
-        static LAST_GSMTC_TRACK: OnceLock<StdMutex<Option<String>>> = OnceLock::new();
-        let cell = LAST_GSMTC_TRACK.get_or_init(|| StdMutex::new(None));
-        let mut guard = cell.lock().unwrap();
-        if guard.as_deref() != Some(key) {
-            *guard = Some(key.clone());
-            let _ = win_for_emit.emit("gsmtc_track_changed", payload);
+fn logout(state: State<'_, SharedStore>, window: tauri::Window) -> Result<(), String> {
+    let app = window.app_handle();
+    {
+        let mut s = state.lock();
+        if let Some(t) = s.auth_cancel.take() {
+            t.cancel();
+        }
+        if s.client.take().is_some() {
+            cancel_watcher(&mut s);
         }
     }
+    token_store::clear(app)?;
+    // other providers in the chain keep going
+    start_watcher_if_needed(app, &state);
+    events::emit(app, "auth_lost", ());
+    Ok(())
+}
 
-    res.map(|(payload, _)| payload)
+// Gives up on a sign-in in progress and frees the callback port
+#[tauri::command]
+fn cancel_auth(state: State<'_, SharedStore>) {
+    let mut s = state.lock();
+    s.paste_auth = None;
+    if let Some(t) = s.auth_cancel.take() {
+        t.cancel();
+    }
 }
 
 #[tauri::command]
@@ -952,56 +1463,93 @@ async fn connect_spotify(
         return Ok(());
     }
 
-    // 1) Build client + stable cache path
-    let client_id =
-        std::env::var("SPOTIFY_CLIENT_ID").map_err(|_| "Missing SPOTIFY_CLIENT_ID".to_string())?;
-    let redirect_uri = "http://127.0.0.1:5173/callback".to_string();
+    // 1) Build client
+    let client_id = spotify_client_id(window.app_handle())?;
+    let redirect = state.lock().redirect;
 
-    let cache_path = token_cache_path(&window)?;
     let creds = Credentials::new(&client_id, "");
     let oauth = OAuth {
-        redirect_uri: redirect_uri.clone(),
-        scopes: scopes!("user-read-currently-playing", "user-read-playback-state"),
+        redirect_uri: redirect.redirect_uri(),
+        scopes: spotify_scopes(),
         ..Default::default()
     };
     let config = Config {
-        token_cached: true,
+        token_cached: false,
         token_refreshing: true,
-        cache_path,
         ..Default::default()
     };
     let mut spotify = AuthCodePkceSpotify::with_config(creds, oauth, config);
 
-    // 2) Try to reuse a cached token (no browser)
-    let _ = spotify.read_token_cache(true).await; // load even if expired; we'll refresh
-
-    let token_mutex = spotify.get_token();
-    let has_cached = {
-        let guard = token_mutex
+    // 2) Try to reuse a stored token (no browser); load even if expired, we'll refresh
+    let stored = token_store::load(window.app_handle()).unwrap_or_else(|e| {
+        eprintln!("[auth] {e}");
+        None
+    });
+    let has_cached = stored.is_some();
+    if let Some(token) = stored {
+        *spotify
+            .get_token()
             .lock()
             .await
-            .map_err(|_| "Token lock failed".to_string())?;
-        guard.is_some()
-    };
+            .map_err(|_| "Token lock failed".to_string())? = Some(token);
+    }
 
     if has_cached {
         let _ = spotify.auto_reauth().await; // refresh if needed
-        let _ = spotify.write_token_cache().await; // persist any new token
+        token_store::persist(&spotify).await; // persist any new token
+        auth_status::check_scopes(window.app_handle(), &spotify).await;
         state.lock().client = Some(Arc::new(spotify));
         return Ok(());
     }
 
     // 3) First-time auth: open browser, wait for code, exchange, cache, store
+    sign_in_with_browser(&state, &window, &mut spotify).await?;
+    state.lock().client = Some(Arc::new(spotify));
+
+    let app = window.app_handle();
+    start_watcher_if_needed(app, &state);
+
+    Ok(())
+}
+
+// Browser sign-in for `spotify`'s scopes: serves the redirect, opens the authorize URL, waits
+// for the code and stores the token it's exchanged for
+async fn sign_in_with_browser(
+    state: &SharedStore,
+    window: &tauri::Window,
+    spotify: &mut AuthCodePkceSpotify,
+) -> Result<(), String> {
+    let redirect = state.lock().redirect;
+    // a newer attempt takes over from one the user walked away from
+    let cancel = CancellationToken::new();
+    if let Some(old) = state.lock().auth_cancel.replace(cancel.clone()) {
+        old.cancel();
+    }
+    // the listener has to be up first: in auto mode it decides the redirect URI
+    let (listener, bound) = oauth_callback::bind(redirect).await?;
+    if bound.port != redirect.port {
+        let app = window.app_handle();
+        write_setting(app, "oauth_redirect", serde_json::json!(bound))?;
+        state.lock().redirect = bound;
+        eprintln!(
+            "[auth] port {} taken, signing in via {}",
+            redirect.port,
+            bound.redirect_uri()
+        );
+        events::emit(
+            app,
+            "redirect_uri_changed",
+            oauth_callback::RedirectInfo::from(bound),
+        );
+    }
+    spotify.oauth.redirect_uri = bound.redirect_uri();
     let auth_url = spotify.get_authorize_url(None).map_err(|e| e.to_string())?;
     tauri_plugin_opener::open_url(auth_url.as_str(), None::<&str>).map_err(|e| e.to_string())?;
 
-    let (tx, rx) = tokio::sync::oneshot::channel::<String>();
-    let addr = "127.0.0.1:5173".to_string();
-    tauri::async_runtime::spawn_blocking(move || {
-        let _ = run_callback_server_blocking(&addr, tx);
-    });
-
-    let code = rx.await.map_err(|e| format!("Callback wait error: {e}"))?;
+    // rspotify puts a random `state` in the authorize URL; only a callback echoing it counts
+    let expected_state = spotify.get_oauth().state.clone();
+    let code = oauth_callback::wait_for_code(listener, cancel, &expected_state).await?;
+    state.lock().auth_cancel = None;
     spotify
         .request_token(&code)
         .await
@@ -1015,84 +1563,38 @@ async fn connect_spotify(
         .map_err(|_| "Token lock failed".to_string())?
         .clone()
     {
-        let _ = write_token_to_disk(&window, &tok);
+        token_store::save(&tok)?;
     }
-
-    state.lock().client = Some(Arc::new(spotify));
-
-    let app = window.app_handle();
-    start_watcher_if_needed(&app, &state);
-
     Ok(())
 }
 
-// Minimal HTTP server just for the OAuth redirect
-fn run_callback_server_blocking(
-    addr: &str,
-    tx: tokio::sync::oneshot::Sender<String>,
+// Signs in again asking for more scopes: this build's, the ones the token already has and
+// `extra`. The current token keeps working until the new one is granted; nothing else is reset.
+#[tauri::command]
+async fn reauthorize_with_scopes(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    extra: Option<Vec<String>>,
 ) -> Result<(), String> {
-    let listener = TcpListener::bind(addr).map_err(|e| format!("Bind {addr} failed: {e}"))?;
-
-    // Accept exactly one request that contains /callback?code=...
-    for stream in listener.incoming() {
-        let mut stream = stream.map_err(|e| format!("Accept failed: {e}"))?;
-
-        // Read the HTTP request (first packet is enough for our tiny case)
-        let mut buf = [0u8; 4096];
-        let n = stream.read(&mut buf).unwrap_or(0);
-        let req = String::from_utf8_lossy(&buf[..n]);
-
-        // Parse Request-Line: e.g. "GET /callback?code=... HTTP/1.1"
-        let first_line = req.lines().next().unwrap_or("");
-        // Find the path segment between "GET " and " HTTP"
-        let path = if first_line.starts_with("GET ") {
-            let start = 4;
-            if let Some(end) = first_line[start..].find(" HTTP") {
-                &first_line[start..start + end]
-            } else {
-                "/"
-            }
-        } else {
-            "/"
-        };
-
-        // Build a full URL so we can use `url` parser
-        let full = format!("http://localhost{path}");
-        if let Ok(parsed) = Url::parse(&full) {
-            if parsed.path() == "/callback" {
-                if let Some(code) = parsed.query_pairs().find_map(|(k, v)| {
-                    if k == "code" {
-                        Some(v.to_string())
-                    } else {
-                        None
-                    }
-                }) {
-                    // Respond to the browser
-                    let body = "You can close this tab and return to the app. ✅";
-                    let resp = format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
-                        body.len(),
-                        body
-                    );
-                    let _ = stream.write_all(resp.as_bytes());
-
-                    // Deliver the code back to the app and stop
-                    let _ = tx.send(code);
-                    break;
-                }
-            }
+    let mut scopes = spotify_scopes();
+    for scope in extra.unwrap_or_default() {
+        if scope.is_empty() || !scope.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+            return Err(format!("Not a Spotify scope: {scope:?}"));
+        }
+        scopes.insert(scope);
+    }
+    let current = state.lock().client.clone();
+    if let Some(client) = current {
+        if let Ok(token) = client.get_token().lock().await {
+            scopes.extend(token.iter().flat_map(|t| t.scopes.iter().cloned()));
         }
-
-        // Fallback 404 if not the expected route
-        let body = "Not Found";
-        let resp = format!(
-            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
-            body.len(),
-            body
-        );
-        let _ = stream.write_all(resp.as_bytes());
     }
 
+    let mut spotify = build_spotify(&window)?;
+    spotify.oauth.scopes = scopes;
+    sign_in_with_browser(&state, &window, &mut spotify).await?;
+    state.lock().client = Some(Arc::new(spotify));
+    start_watcher_if_needed(window.app_handle(), &state);
     Ok(())
 }
 
@@ -1111,22 +1613,17 @@ fn key_title_album(title: &str, album: &str) -> String {
 }
 
 fn is_audio(p: &Path) -> bool {
-    match p
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_ascii_lowercase())
-    {
+    matches!(
+        p.extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_ascii_lowercase()),
         Some(ref e)
             if [
                 "aa", "aax", "aac", "aiff", "ape", "dsf", "flac", "m4a", "m4b", "m4p", "mp3",
                 "mpc", "mpp", "ogg", "oga", "wav", "wma", "wv", "webm",
             ]
-            .contains(&e.as_str()) =>
-        {
-            true
-        }
-        _ => false,
-    }
+            .contains(&e.as_str())
+    )
 }
 
 fn try_common_names(dir: &Path) -> Option<PathBuf> {
@@ -1269,7 +1766,6 @@ fn find_local_art_in_base(
 
 fn maybe_set_local_artwork(
     app: &tauri::AppHandle,
-    state: &SharedStore,
     np: &mut NowPlaying,
     ctx: &rspotify::model::CurrentlyPlayingContext,
 ) {
@@ -1280,7 +1776,12 @@ fn maybe_set_local_artwork(
 
     let (artist, album, track, _is_local) = match &ctx.item {
         Some(PlayableItem::Track(t)) => {
-            let first_artist = t.artists.get(0).map(|a| a.name.as_str()).unwrap_or("");
+            let first_artist = t
+                .artists
+                .iter()
+                .map(|a| a.name.as_str())
+                .find(|a| !compilation::is_various(a))
+                .unwrap_or("");
             (
                 first_artist.to_string(),
                 Some(t.album.name.clone()),
@@ -1292,22 +1793,16 @@ fn maybe_set_local_artwork(
     };
 
     // Use the local index first
-    let (base_dir, idx_hit) = {
-        let s = state.lock();
-        let base = s.local_art_dir.clone();
-
-        let k1 = key_title_artist(&track, &artist);
-
-        let hit = s.local_index.get(&k1).cloned().or_else(|| {
-            album.as_deref().and_then(|alb| {
-                let k2 = key_title_album(&track, alb);
-                s.local_index.get(&k2).cloned()
-            })
+    let (base_dir, index) = library_snapshot(app);
+    let idx_hit = Some(&artist)
+        .filter(|a| !a.is_empty())
+        .and_then(|a| index.get(&key_title_artist(&track, a)).cloned())
+        .or_else(|| {
+            album
+                .as_deref()
+                .and_then(|alb| index.get(&key_title_album(&track, alb)).cloned())
         });
 
-        (base, hit)
-    };
-
     if let Some(audio_path) = idx_hit {
         // Prefer embedded art
         if let Some(out) = extract_embedded_art_to_cache(app, &audio_path) {
@@ -1344,36 +1839,329 @@ async fn get_current_playing(
             .ok_or_else(|| "Not connected to Spotify".to_string())?
     };
 
-    match client
-        .current_user_playing_item()
+    match playback::current(&client)
         .await
         .map_err(|e| e.to_string())?
     {
-        Some(ctx) => {
-            let mut np = build_now_playing_from_ctx(&ctx);
+        Some(playback) => {
+            let ctx = &playback.ctx;
+            let mut np = build_now_playing_from_ctx(ctx);
+            playback.apply_modes(&mut np);
             let app = window.app_handle();
-            maybe_set_local_artwork(&app, &state, &mut np, &ctx);
+            maybe_set_local_artwork(app, &mut np, ctx);
+            context::enrich(app, &client, ctx, &mut np).await;
+            saved_tracks::enrich(app, &client, ctx, &mut np).await;
+            queue::enrich(app, &client, ctx, &mut np).await;
+            family::apply(&state, &mut np);
             Ok(np)
         }
-        None => Ok(NowPlaying {
-            is_playing: false,
-            track_name: None,
-            artists: vec![],
-            album: None,
-            artwork_url: None,
-            artwork_path: None,
-        }),
+        None => Ok(NowPlaying::default()),
     }
 }
 
+#[tauri::command]
+async fn get_full_state(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+) -> Result<FullState, String> {
+    let (client, mut full) = {
+        let s = state.lock();
+        let full = FullState {
+            now_playing: s.last_now_playing.clone(),
+            connected: s.client.is_some(),
+            watcher_running: s.watch_started,
+            watcher_paused: s.watcher_paused,
+            active_source: s.active_source,
+            settings: SettingsSummary {
+                local_art_dir: library_snapshot(window.app_handle())
+                    .0
+                    .or_else(|| load_local_art_dir(&window))
+                    .map(|p| p.to_string_lossy().to_string()),
+                provider_chain: s.provider_chain.clone(),
+                aggregate_sessions: s.aggregate_sessions,
+                poll_interval_secs: s.poll_interval.as_secs(),
+            },
+            queue: Vec::new(),
+        };
+        (s.client.clone(), full)
+    };
+
+    // Queue needs a live client (and Premium); an empty list is fine otherwise
+    if let Some(client) = client {
+        let queue = watchdog::within(
+            "Spotify queue",
+            watchdog::SPOTIFY_TIMEOUT,
+            client.current_user_queue(),
+        )
+        .await
+        .and_then(|q| q.map_err(|e| e.to_string()));
+        match queue {
+            Ok(q) => full.queue = q.queue.iter().map(queue::queue_item).collect(),
+            Err(e) => eprintln!("[state] queue unavailable: {e}"),
+        }
+    }
+
+    Ok(full)
+}
+
+// Accepts a bare id, a spotify:album: URI or an open.spotify.com link
+fn parse_album_id(raw: &str) -> Result<AlbumId<'static>, String> {
+    let raw = raw.trim();
+    let id = match raw.split_once("/album/") {
+        Some((_, rest)) => rest.split(['?', '/']).next().unwrap_or_default(),
+        None => raw,
+    };
+    AlbumId::from_id_or_uri(id)
+        .map(|id| id.into_static())
+        .map_err(|e| format!("Invalid album id: {e}"))
+}
+
+// Full tracklist of an album, with the playing track flagged. Without `album_id` it uses the
+// album of whatever is playing.
+#[tauri::command]
+async fn get_album_tracks(
+    state: State<'_, SharedStore>,
+    album_id: Option<String>,
+) -> Result<AlbumTracks, String> {
+    use futures::TryStreamExt;
+
+    let client = state
+        .lock()
+        .client
+        .clone()
+        .ok_or_else(|| "Not connected to Spotify".to_string())?;
+
+    let limit = watchdog::SPOTIFY_TIMEOUT;
+    let playing = watchdog::within(
+        "Spotify playback",
+        limit,
+        client.current_user_playing_item(),
+    )
+    .await?
+    .map_err(|e| e.to_string())?
+    .and_then(|ctx| match ctx.item {
+        Some(PlayableItem::Track(t)) => Some(t),
+        _ => None,
+    });
+    let current_id = playing.as_ref().and_then(|t| t.id.clone());
+
+    let album_id = match album_id.as_deref().filter(|a| !a.trim().is_empty()) {
+        Some(raw) => parse_album_id(raw)?,
+        None => playing
+            .and_then(|t| t.album.id)
+            .ok_or_else(|| "Nothing with an album is playing".to_string())?,
+    };
+
+    let album = watchdog::within("Spotify album", limit, client.album(album_id.clone(), None))
+        .await?
+        .map_err(|e| format!("album: {e}"))?;
+    let tracks: Vec<_> = watchdog::within(
+        "Spotify album tracks",
+        limit,
+        client
+            .album_track(album_id.clone(), None)
+            .try_collect::<Vec<_>>(),
+    )
+    .await?
+    .map_err(|e| format!("album tracks: {e}"))?;
+
+    Ok(AlbumTracks {
+        album_id: album_id.id().to_string(),
+        name: album.name,
+        artists: album.artists.into_iter().map(|a| a.name).collect(),
+        artwork_url: pick_image_url(&album.images, 300),
+        tracks: tracks
+            .into_iter()
+            .map(|t| {
+                // relinked tracks report the id of the version that actually played
+                let is_current = current_id.is_some()
+                    && (t.id == current_id
+                        || t.linked_from.as_ref().and_then(|l| l.id.as_ref())
+                            == current_id.as_ref());
+                AlbumTrack {
+                    id: t.id.map(|id| id.id().to_string()),
+                    disc_number: t.disc_number,
+                    track_number: t.track_number,
+                    name: t.name,
+                    artists: t.artists.into_iter().map(|a| a.name).collect(),
+                    duration_ms: t.duration.num_milliseconds(),
+                    is_current,
+                }
+            })
+            .collect(),
+    })
+}
+
+// Every command, plus the platform-specific ones passed in (`generate_handler!` has no
+// `#[cfg]` support of its own)
+macro_rules! app_commands {
+    ($($extra:tt)*) => {
+        tauri::generate_handler![
+            connect_spotify,
+            restore_spotify,
+            get_current_playing,
+            set_local_art_dir,
+            get_local_art_dir,
+            export::write_now_playing_assets,
+            export::preview_export,
+            export::export_artwork_only,
+            art_dedupe::dedupe_artcache,
+            artwork_variants::get_artwork_variant,
+            collage::export_session_collage,
+            export::get_export_profiles,
+            export::set_export_profiles,
+            export::validate_export_profiles,
+            placeholder::get_overlay_placeholder,
+            placeholder::set_overlay_placeholder,
+            librespot::get_librespot_status,
+            librespot::set_librespot_config,
+            icecast::get_icecast_url,
+            icecast::set_icecast_url,
+            traktor::get_traktor_config,
+            traktor::set_traktor_config,
+            obs::get_obs_config,
+            obs::set_obs_config,
+            discord::get_discord_config,
+            discord::set_discord_config,
+            layouts::get_layout_config,
+            layouts::set_layout_config,
+            gpu::get_gpu_status,
+            gpu::set_gpu_images,
+            oauth_callback::get_redirect_config,
+            oauth_callback::set_redirect_config,
+            get_spotify_client_id,
+            set_spotify_client_id,
+            resilience::get_retry_policies,
+            logout,
+            auth_status::get_auth_status,
+            reauthorize_with_scopes,
+            history::import_spotify_history,
+            lastfm_import::import_lastfm_history,
+            history::get_history_summary,
+            charts::get_listening_by_hour,
+            charts::get_top_genres,
+            charts::get_artist_timeline,
+            streaks::get_streaks,
+            streaks::get_streak_config,
+            streaks::set_streak_config,
+            server::get_http_port,
+            server::set_http_port,
+            settings::get_settings,
+            settings::update_settings,
+            settings::export_settings,
+            settings::import_settings,
+            portable::get_portable_mode,
+            portable::set_portable_mode,
+            resilience::set_retry_policy,
+            dj_history::get_dj_history_path,
+            dj_history::set_dj_history_path,
+            osc::get_osc_config,
+            osc::set_osc_config,
+            providers::get_provider_chain,
+            providers::set_provider_chain,
+            providers::get_aggregate_mode,
+            providers::set_aggregate_mode,
+            providers::set_now_playing,
+            providers::clear_now_playing,
+            saved_tracks::toggle_save_track,
+            playlists::list_user_playlists,
+            playlists::add_current_to_playlist,
+            queue::get_queue,
+            playback::list_devices,
+            playback::transfer_playback,
+            playback::set_shuffle,
+            playback::cycle_repeat,
+            trivia::get_trivia_config,
+            trivia::set_trivia_config,
+            tts::get_tts_config,
+            tts::set_tts_config,
+            tts::list_tts_voices,
+            tts::test_tts,
+            accessibility::get_accessibility_prefs,
+            companion::get_companion_config,
+            companion::set_companion_config,
+            events::get_update_mode,
+            events::set_update_mode,
+            set_poll_interval,
+            pause_watcher,
+            resume_watcher,
+            stop_watcher,
+            cancel_auth,
+            paste_auth::start_paste_auth,
+            paste_auth::submit_auth_code,
+            restart_watcher,
+            family::get_family_friendly,
+            family::set_family_friendly,
+            normalizers::get_metadata_normalizers,
+            normalizers::set_metadata_normalizers,
+            get_full_state,
+            get_album_tracks,
+            safe_mode::get_safe_mode,
+            capabilities::get_api_version,
+            capabilities::get_capabilities,
+            safe_mode::restart_normally,
+            updater::get_update_channel,
+            updater::set_update_channel,
+            updater::check_for_updates,
+            updater::install_update,
+            spotify_search::get_catalog_search,
+            spotify_search::set_catalog_search,
+            usage::get_usage_stats,
+            usage::reset_usage_stats,
+            webnowplaying::get_webnowplaying_config,
+            webnowplaying::set_webnowplaying_config,
+            benchmark::run_benchmark,
+            events::subscribe_events,
+            events::unsubscribe_events,
+            events::get_emit_policies,
+            events::set_emit_policy,
+            $($extra)*
+        ]
+    };
+}
+
+#[cfg(windows)]
+fn invoke_handler() -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    app_commands![
+        gsmtc::get_current_playing_gsmtc,
+        gsmtc::get_gsmtc_poll_interval,
+        gsmtc::set_gsmtc_poll_interval,
+        gsmtc::list_media_sessions,
+        gsmtc::select_media_session,
+        gsmtc::get_gsmtc_priority,
+        gsmtc::set_gsmtc_priority,
+        gsmtc::media_play_pause,
+        gsmtc::media_next,
+        gsmtc::media_previous,
+        gsmtc::media_seek,
+    ]
+}
+
+#[cfg(not(windows))]
+fn invoke_handler() -> impl Fn(tauri::ipc::Invoke) -> bool + Send + Sync + 'static {
+    app_commands![]
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    if librespot::forward_hook_event() {
+        return;
+    }
+    if let Some(code) = cli::run_from_args() {
+        std::process::exit(code);
+    }
+
     let store: SharedStore = Arc::new(Mutex::new(SpotifyStore::default()));
+    let library: SharedLibrary = Arc::default();
+    let caches: SharedCaches = Arc::default();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
         .manage(store)
+        .manage(library)
+        .manage(caches)
         .setup(|app| {
             if let Ok(env_path) = app
                 .path()
@@ -1382,34 +2170,86 @@ pub fn run() {
                 let _ = dotenvy::from_path(env_path);
             }
 
-            let store = app.state::<SharedStore>();
-            if let Some(dir) = load_local_art_dir_from_handle(&app.app_handle()) {
-                {
-                    store.lock().local_art_dir = Some(dir.clone());
-                }
+            settings::migrate(app.app_handle());
+            let safe_mode = safe_mode::enter(app.app_handle());
+            // before anything below starts binding ports
+            app.state::<SharedStore>().lock().retry_policies =
+                resilience::load_overrides(app.app_handle());
+            if safe_mode.is_none() {
+                webnowplaying::init(app.app_handle());
+                server::start(
+                    app.app_handle().clone(),
+                    server::load_port(app.app_handle()),
+                );
+            }
 
-                // Build the local index on startup so embedded/sidecar art works right away
-                let app_handle = app.app_handle().clone();
-                tauri::async_runtime::spawn_blocking(move || {
-                    let idx = build_local_index(&dir);
-                    let s = app_handle.state::<SharedStore>();
-                    let mut g = s.lock();
-                    g.local_index = idx;
-                    g.art_cache.clear();
-                });
+            let store = app.state::<SharedStore>();
+            {
+                let mut s = store.lock();
+                s.safe_mode = safe_mode;
+                s.provider_chain = providers::load_chain(app.app_handle());
+                s.aggregate_sessions = providers::load_aggregate_mode(app.app_handle());
+                s.gsmtc_poll_ms = gsmtc::load_poll_interval(app.app_handle());
+                s.poll_interval = load_poll_interval(app.app_handle());
+                s.gsmtc_pinned_session = gsmtc::load_pinned_session(app.app_handle());
+                s.gsmtc_priority = gsmtc::load_priority(app.app_handle());
+                s.trivia = trivia::load_config(app.app_handle());
+                s.catalog_search = spotify_search::load_config(app.app_handle());
+                s.family_friendly = family::load(app.app_handle());
+                s.normalizers = normalizers::load(app.app_handle());
+                s.tts = tts::load_config(app.app_handle());
+                s.companion = companion::load_config(app.app_handle());
+                s.streaks = streaks::load_config(app.app_handle());
+                s.export_profiles = export::load_profiles(app.app_handle());
+                s.overlay_placeholder = placeholder::load_overlay(app.app_handle());
+                s.layouts = layouts::load(app.app_handle());
+                s.gpu_images = gpu::load_enabled(app.app_handle());
+                s.redirect = oauth_callback::load_config(app.app_handle());
+                s.client_id = load_client_id(app.app_handle());
+                s.update_channel = updater::load_channel(app.app_handle());
+                s.usage = usage::load(app.app_handle());
+                s.update_mode = events::load_update_mode(app.app_handle());
+                s.emit_pipeline =
+                    events::Pipeline::with_policies(events::load_policies(app.app_handle()));
+            }
+            history::init(app.app_handle());
+            // something to show before the first poll comes back
+            if let Some(np) = last_played::load(app.app_handle()) {
+                store.lock().last_now_playing = Some(np.clone());
+                events::emit(app.app_handle(), "now_playing_update", &np);
+                auto_export::restore(app.app_handle(), &np);
+            }
+            app.state::<SharedCaches>().lock().art_lookup =
+                artwork_lookup::load_cache(app.app_handle());
+            let art_dir = load_local_art_dir_from_handle(app.app_handle());
+            app.state::<SharedLibrary>().write().dir = art_dir.clone();
+            start_watcher_if_needed(app.app_handle(), &store);
+            watchdog::start(app.app_handle());
+            auto_export::start(app.app_handle());
+            if let Some(reason) = safe_mode {
+                eprintln!("[safe-mode] starting without integrations ({reason:?})");
+                return Ok(());
+            }
+            gsmtc::start_event_watcher(app.app_handle());
+            progress::start(app.app_handle());
+            accessibility::start(app.app_handle());
+            librespot::init(app.app_handle());
+            icecast::init(app.app_handle());
+            traktor::init(app.app_handle());
+            obs::init(app.app_handle());
+            discord::init(app.app_handle());
+            dj_history::init(app.app_handle());
+            osc::init(app.app_handle());
+            art_dedupe::start(app.app_handle());
+
+            // Build the local index on startup so embedded/sidecar art works right away
+            if let Some(dir) = art_dir {
+                rebuild_local_index(app.app_handle(), dir);
             }
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            connect_spotify,
-            restore_spotify,
-            get_current_playing,
-            set_local_art_dir,
-            get_local_art_dir,
-            write_now_playing_assets,
-            get_current_playing_gsmtc,
-        ])
+        .invoke_handler(invoke_handler())
         .on_window_event(|window, event| {
             use tauri::WindowEvent;
 
@@ -1417,17 +2257,29 @@ pub fn run() {
                 // Window is actually gone now
                 WindowEvent::Destroyed => {
                     let app = window.app_handle();
+                    app.state::<SharedStore>()
+                        .lock()
+                        .event_subscriptions
+                        .remove(window.label());
 
                     // If no more windows, terminate the app + poller
                     if app.webview_windows().is_empty() {
                         let state = app.state::<SharedStore>();
+                        safe_mode::mark_clean_exit(app);
+                        usage::flush(app);
+                        collage::finish(app);
+                        history::finish(app);
+                        librespot::stop(&state);
                         let mut s = state.lock();
+                        if let Some(t) = s.gsmtc_cancel.take() {
+                            t.cancel();
+                        }
                         if let Some(t) = s.cancel.take() {
                             t.cancel();
                         }
                         s.client = None;
                         s.watch_started = false;
-                        // let _ = clear_token_cache(&window);
+                        // let _ = token_store::clear(app);
 
                         // Be absolutely sure the process exits (dev on Windows can be sticky)
                         #[cfg(windows)]
@@ -1445,13 +2297,21 @@ pub fn run() {
                 WindowEvent::CloseRequested { .. } if window.label() == "main" => {
                     let app = window.app_handle();
                     let state = app.state::<SharedStore>();
+                    safe_mode::mark_clean_exit(app);
+                    usage::flush(app);
+                    collage::finish(app);
+                    history::finish(app);
+                    librespot::stop(&state);
                     let mut s = state.lock();
+                    if let Some(t) = s.gsmtc_cancel.take() {
+                        t.cancel();
+                    }
                     if let Some(t) = s.cancel.take() {
                         t.cancel();
                     }
                     s.client = None;
                     s.watch_started = false;
-                    // let _ = clear_token_cache(&window);
+                    // let _ = token_store::clear(app);
 
                     #[cfg(windows)]
                     {
@@ -1466,6 +2326,12 @@ pub fn run() {
                 _ => {}
             }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // also covers quitting from the tray or `app.exit` without a window event
+            if let tauri::RunEvent::Exit = event {
+                safe_mode::mark_clean_exit(app);
+            }
+        });
 }
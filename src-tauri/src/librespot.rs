@@ -0,0 +1,305 @@
+// Spotify Connect receiver through a librespot sidecar.
+//
+// librespot isn't linked in. When receiver mode is on we run the user's `librespot` binary so
+// the app shows up as a Spotify Connect device, and hand it our own executable as its
+// `--onevent` hook. librespot runs the hook with the track details in environment variables;
+// that short-lived process (see `forward_hook_event`) relays them to the running app over a
+// loopback socket. Metadata arrives the moment playback changes, without any Web API calls.
+
+use crate::{
+    providers::{self, Provider},
+    read_settings, watchdog, write_setting, NowPlaying, SharedStore,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, process::Child, time::Duration};
+use tauri::{Manager, State};
+use tokio::{io::AsyncReadExt, net::TcpListener};
+
+// set on the librespot process so the hook (which inherits its env) knows where to report
+const HOOK_PORT_ENV: &str = "SNP_LIBRESPOT_HOOK_PORT";
+
+// the hook sends a few hundred bytes of JSON right after connecting
+const MAX_HOOK_BYTES: u64 = 64 * 1024;
+const HOOK_READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+// What librespot passes to `--onevent` programs
+const HOOK_VARS: &[&str] = &[
+    "PLAYER_EVENT",
+    "TRACK_ID",
+    "ITEM_TYPE",
+    "NAME",
+    "ARTISTS",
+    "ALBUM",
+    "SHOW_NAME",
+    "COVERS",
+    "DURATION_MS",
+    "POSITION_MS",
+];
+
+#[derive(Serialize, Deserialize, Clone, schemars::JsonSchema)]
+#[serde(default)]
+pub struct ReceiverConfig {
+    pub enabled: bool,
+    // path to the librespot executable, or just the name if it's on PATH
+    pub binary: String,
+    // name shown in Spotify's device picker
+    pub device_name: String,
+    // passed through as-is, e.g. ["--backend", "pipe", "--bitrate", "320"]
+    pub extra_args: Vec<String>,
+}
+
+impl Default for ReceiverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            binary: "librespot".into(),
+            device_name: "Spotify Now Playing".into(),
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Receiver {
+    config: ReceiverConfig,
+    child: Option<Child>,
+    hook_port: Option<u16>,
+    last_error: Option<String>,
+    // the track librespot last announced; play/pause events don't repeat the metadata
+    track: NowPlaying,
+}
+
+impl Receiver {
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct ReceiverStatus {
+    config: ReceiverConfig,
+    running: bool,
+    last_error: Option<String>,
+}
+
+// Called first thing in `run()`: when librespot starts us as its event hook, relay the event
+// and exit instead of launching another app instance.
+pub fn forward_hook_event() -> bool {
+    let (Ok(_), Ok(port)) = (std::env::var("PLAYER_EVENT"), std::env::var(HOOK_PORT_ENV)) else {
+        return false;
+    };
+    let Ok(port) = port.parse::<u16>() else {
+        return true;
+    };
+
+    let vars: HashMap<&str, String> = HOOK_VARS
+        .iter()
+        .filter_map(|k| std::env::var(k).ok().map(|v| (*k, v)))
+        .collect();
+    if let Ok(mut stream) = std::net::TcpStream::connect(("127.0.0.1", port)) {
+        use std::io::Write;
+        let _ = serde_json::to_writer(&mut stream, &vars);
+        let _ = stream.flush();
+    }
+    true
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let config: ReceiverConfig = read_settings(app)
+        .get("librespot")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    let enabled = config.enabled;
+    app.state::<SharedStore>().lock().librespot.config = config;
+    if enabled {
+        start(app.clone());
+    }
+}
+
+fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let port = match ensure_hook_listener(&app).await {
+            Ok(port) => port,
+            Err(e) => {
+                eprintln!("[librespot] {e}");
+                app.state::<SharedStore>().lock().librespot.last_error = Some(e);
+                return;
+            }
+        };
+
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        let r = &mut s.librespot;
+        if r.child.is_some() {
+            return;
+        }
+        match spawn_librespot(&r.config, port) {
+            Ok(child) => {
+                r.child = Some(child);
+                r.last_error = None;
+            }
+            Err(e) => {
+                eprintln!("[librespot] {e}");
+                r.last_error = Some(e);
+            }
+        }
+    });
+}
+
+fn spawn_librespot(config: &ReceiverConfig, hook_port: u16) -> Result<Child, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("current exe: {e}"))?;
+    let mut cmd = std::process::Command::new(&config.binary);
+    cmd.arg("--name")
+        .arg(&config.device_name)
+        .arg("--onevent")
+        .arg(exe)
+        .args(&config.extra_args)
+        .env(HOOK_PORT_ENV, hook_port.to_string());
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    cmd.spawn().map_err(|e| {
+        format!(
+            "start {}: {e} (librespot 0.5 or newer has to be installed, see the README)",
+            config.binary
+        )
+    })
+}
+
+// The hook listener lives for the rest of the session; restarting librespot reuses it.
+async fn ensure_hook_listener(app: &tauri::AppHandle) -> Result<u16, String> {
+    if let Some(port) = app.state::<SharedStore>().lock().librespot.hook_port {
+        return Ok(port);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .map_err(|e| format!("hook listener: {e}"))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("hook listener: {e}"))?
+        .port();
+    app.state::<SharedStore>().lock().librespot.hook_port = Some(port);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            // one event at a time keeps them in order, so a client that never closes mustn't
+            // hold up the rest
+            let mut buf = Vec::new();
+            let read = (&mut stream).take(MAX_HOOK_BYTES).read_to_end(&mut buf);
+            match watchdog::within("librespot hook read", HOOK_READ_TIMEOUT, read).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    eprintln!("[librespot] hook read: {e}");
+                    continue;
+                }
+                Err(_) => continue,
+            }
+            match serde_json::from_slice::<HashMap<String, String>>(&buf) {
+                Ok(vars) => apply_event(&app, &vars),
+                Err(e) => eprintln!("[librespot] hook payload: {e}"),
+            }
+        }
+    });
+    Ok(port)
+}
+
+fn apply_event(app: &tauri::AppHandle, vars: &HashMap<String, String>) {
+    let get = |k: &str| vars.get(k).map(|v| v.trim()).filter(|v| !v.is_empty());
+    let lines = |k: &str| -> Vec<String> {
+        get(k)
+            .map(|v| v.lines().map(|l| l.trim().to_string()).collect())
+            .unwrap_or_default()
+    };
+
+    let np = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        let track = &mut s.librespot.track;
+        match get("PLAYER_EVENT").unwrap_or_default() {
+            "track_changed" => {
+                let episode = get("ITEM_TYPE") == Some("Episode");
+                *track = NowPlaying {
+                    is_playing: track.is_playing,
+                    track_name: get("NAME").map(str::to_string),
+                    artists: if episode {
+                        get("SHOW_NAME")
+                            .map(|s| vec![s.to_string()])
+                            .unwrap_or_default()
+                    } else {
+                        lines("ARTISTS")
+                    },
+                    album: get("ALBUM").map(str::to_string),
+                    // COVERS lists every size, largest first
+                    artwork_url: lines("COVERS").into_iter().next(),
+                    ..Default::default()
+                };
+            }
+            // `playing`/`paused` are the 0.5+ names, `started`/`changed` older builds
+            "playing" | "started" | "changed" => track.is_playing = true,
+            "paused" => track.is_playing = false,
+            "stopped" | "session_disconnected" => *track = NowPlaying::default(),
+            // volume, seek, preloading, ... don't change what we show
+            _ => return,
+        }
+        let np = track.track_name.is_some().then(|| track.clone());
+        s.librespot_now_playing = np.clone();
+        np
+    };
+    providers::publish_pushed(app, Provider::Librespot, np);
+}
+
+// Kills the sidecar; called when receiver mode is turned off and on exit
+pub fn stop(state: &SharedStore) {
+    let mut s = state.lock();
+    if let Some(mut child) = s.librespot.child.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    s.librespot.track = NowPlaying::default();
+    s.librespot_now_playing = None;
+}
+
+#[tauri::command]
+pub fn get_librespot_status(state: State<'_, SharedStore>) -> ReceiverStatus {
+    let mut s = state.lock();
+    let r = &mut s.librespot;
+    // notice a librespot that exited on its own (bad args, port in use, ...)
+    if let Some(Ok(Some(code))) = r.child.as_mut().map(|c| c.try_wait()) {
+        r.child = None;
+        r.last_error = Some(format!("librespot exited: {code}"));
+    }
+    ReceiverStatus {
+        config: r.config.clone(),
+        running: r.child.is_some(),
+        last_error: r.last_error.clone(),
+    }
+}
+
+#[tauri::command]
+pub fn set_librespot_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: ReceiverConfig,
+) -> Result<(), String> {
+    let app = window.app_handle();
+    write_setting(app, "librespot", serde_json::json!(config))?;
+
+    stop(&state);
+    let enabled = config.enabled;
+    state.lock().librespot.config = config;
+    if enabled {
+        start(app.clone());
+    }
+    crate::start_watcher_if_needed(app, &state);
+    Ok(())
+}
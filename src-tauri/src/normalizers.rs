@@ -0,0 +1,215 @@
+// Metadata normalizers, picked per source: classical mode reads titles like
+// "Beethoven: Symphony No. 5 in C minor, Op. 67: I. Allegro con brio" into composer, work and
+// movement, for sources that carry a classical library. The title itself is left as it is.
+
+use crate::{providers::Provider, read_settings, write_setting, NowPlaying, SharedStore};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::{Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalizer {
+    Classical,
+}
+
+// source -> normalizer; sources not listed are passed through
+pub type Normalizers = BTreeMap<Provider, Normalizer>;
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, JsonSchema)]
+#[serde(default)]
+pub struct ClassicalInfo {
+    pub composer: Option<String>,
+    pub work: Option<String>,
+    // "I. Allegro con brio"
+    pub movement: Option<String>,
+}
+
+// "I. Allegro", "3. Presto"
+static MOVEMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?:[IVXLC]+|\d+)\.\s").unwrap());
+
+// catalogue numbers, keys and the usual forms
+static WORK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?ix)
+        \b(?:op|opus|bwv|kv?|hob|woo|rv)\.?\s*\d
+        | \b(?:d|s|no|nr)\.\s*\d
+        | \bin\s[a-g](?:[-\s](?:flat|sharp)|♭|♯)?\s(?:major|minor)\b
+        | \b(?:symphon|concert|sonat|quartet|quintet|trio|suite|mass|requiem|prelude|fugue
+            |[eé]tude|nocturne|overture|variation|partita|cantata|ballade|scherzo|impromptu
+            |waltz|mazurka|polonaise)",
+    )
+    .unwrap()
+});
+
+pub fn load(app: &tauri::AppHandle) -> Normalizers {
+    read_settings(app)
+        .get("metadata_normalizers")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+// "Beethoven" in "Ludwig van Beethoven", or the other way round
+fn names_artist(head: &str, artists: &[String]) -> bool {
+    let head = head.to_lowercase();
+    artists.iter().any(|a| {
+        let a = a.to_lowercase();
+        !a.is_empty() && (a.contains(&head) || head.contains(&a))
+    })
+}
+
+// None when the title doesn't look like a classical one
+fn parse_classical(title: &str, artists: &[String]) -> Option<ClassicalInfo> {
+    let title = title.trim();
+    // "Composer: Work ...", when the head is one of the artists or the rest is the work
+    let (composer, rest) = match title.split_once(": ") {
+        Some((head, rest))
+            if !WORK.is_match(head)
+                && !MOVEMENT.is_match(rest)
+                && (names_artist(head, artists) || WORK.is_match(rest)) =>
+        {
+            (Some(head.trim()), rest.trim())
+        }
+        _ => (None, title),
+    };
+    let (work, movement) = match rest.rsplit_once(": ") {
+        Some((work, movement)) => (work.trim(), Some(movement.trim())),
+        None => match rest.rsplit_once(" - ") {
+            Some((work, movement)) if MOVEMENT.is_match(movement.trim()) => {
+                (work.trim(), Some(movement.trim()))
+            }
+            _ => (rest, None),
+        },
+    };
+    // without a composer, "Interlude: Shadows" is only a work and movement if one of them
+    // looks the part
+    if composer.is_none() && !WORK.is_match(work) && !movement.is_some_and(|m| MOVEMENT.is_match(m))
+    {
+        return None;
+    }
+    Some(ClassicalInfo {
+        // streaming services list the composer first
+        composer: composer
+            .map(str::to_string)
+            .or_else(|| artists.first().cloned()),
+        work: Some(work.to_string()).filter(|w| !w.is_empty()),
+        movement: movement.map(str::to_string).filter(|m| !m.is_empty()),
+    })
+}
+
+// Applied to everything the watcher emits, after the family-friendly filter
+pub fn apply(state: &SharedStore, np: &mut NowPlaying) {
+    let normalizer = np
+        .source
+        .and_then(|source| state.lock().normalizers.get(&source).copied());
+    np.classical = match (normalizer, np.track_name.as_deref()) {
+        (Some(Normalizer::Classical), Some(title)) => parse_classical(title, &np.artists),
+        _ => None,
+    };
+}
+
+#[tauri::command]
+pub fn get_metadata_normalizers(state: State<'_, SharedStore>) -> Normalizers {
+    state.lock().normalizers.clone()
+}
+
+#[tauri::command]
+pub fn set_metadata_normalizers(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    normalizers: Normalizers,
+) -> Result<(), String> {
+    write_setting(
+        window.app_handle(),
+        "metadata_normalizers",
+        serde_json::json!(normalizers),
+    )?;
+    state.lock().normalizers = normalizers;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artists(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn classical_titles() {
+        // title, artists, (composer, work, movement)
+        let cases = [
+            (
+                "Beethoven: Symphony No. 5 in C minor, Op. 67: I. Allegro con brio",
+                &["Ludwig van Beethoven", "Wiener Philharmoniker"][..],
+                (
+                    "Beethoven",
+                    "Symphony No. 5 in C minor, Op. 67",
+                    Some("I. Allegro con brio"),
+                ),
+            ),
+            (
+                "Cello Suite No. 1 in G major, BWV 1007: I. Prélude",
+                &["Johann Sebastian Bach", "Yo-Yo Ma"][..],
+                (
+                    "Johann Sebastian Bach",
+                    "Cello Suite No. 1 in G major, BWV 1007",
+                    Some("I. Prélude"),
+                ),
+            ),
+            (
+                "Symphony No. 40 in G minor, K. 550 - 1. Molto allegro",
+                &["Wolfgang Amadeus Mozart"][..],
+                (
+                    "Wolfgang Amadeus Mozart",
+                    "Symphony No. 40 in G minor, K. 550",
+                    Some("1. Molto allegro"),
+                ),
+            ),
+            (
+                "Chopin: Nocturne in E-flat major, Op. 9 No. 2",
+                &["Frédéric Chopin", "Maria João Pires"][..],
+                ("Chopin", "Nocturne in E-flat major, Op. 9 No. 2", None),
+            ),
+            (
+                "Requiem in D minor, K. 626: Lacrimosa",
+                &["Wolfgang Amadeus Mozart"][..],
+                (
+                    "Wolfgang Amadeus Mozart",
+                    "Requiem in D minor, K. 626",
+                    Some("Lacrimosa"),
+                ),
+            ),
+        ];
+        for (title, names, (composer, work, movement)) in cases {
+            let info = parse_classical(title, &artists(names))
+                .unwrap_or_else(|| panic!("{title} wasn't read as classical"));
+            assert_eq!(info.composer.as_deref(), Some(composer), "{title}");
+            assert_eq!(info.work.as_deref(), Some(work), "{title}");
+            assert_eq!(info.movement.as_deref(), movement, "{title}");
+        }
+    }
+
+    #[test]
+    fn other_titles_pass_through() {
+        let cases = [
+            ("Mr. Brightside", &["The Killers"][..]),
+            ("Opus", &["Eric Prydz"][..]),
+            ("Interlude: Shadows", &["BTS"][..]),
+            ("Star Wars: Main Title", &["Pop Orchestra"][..]),
+            ("Bad Blood - Remix", &["Taylor Swift", "Kendrick Lamar"][..]),
+            ("Hey Jude - Remastered 2015", &["The Beatles"][..]),
+            ("Cruel Summer - Live from Sydney", &["Taylor Swift"][..]),
+        ];
+        for (title, names) in cases {
+            assert!(
+                parse_classical(title, &artists(names)).is_none(),
+                "{title} was read as classical"
+            );
+        }
+    }
+}
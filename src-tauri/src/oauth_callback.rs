@@ -0,0 +1,414 @@
+// Local server for Spotify's OAuth redirect. It only lives while a sign-in is in progress:
+// it stops once the code arrives, after `TIMEOUT` when the browser tab was abandoned, or when
+// the attempt is cancelled (`cancel_auth`, or a newer `connect_spotify`), so the port is free
+// for the next try.
+//
+// The port is a setting: 5173 by default, which clashes with Vite dev servers, so it can be
+// moved, or picked automatically from the free ephemeral ports. An automatic pick is kept and
+// reused on later sign-ins, so the redirect URI registered in the Spotify dashboard only has to
+// change when that port is taken.
+
+use crate::server::{read_request, write_response, Response};
+use crate::{read_settings, write_setting, SharedStore};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{Manager, State};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+const HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 5173;
+
+const TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// a cancelled attempt may not have dropped its listener yet
+const BIND_ATTEMPTS: u32 = 10;
+const BIND_RETRY: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct RedirectConfig {
+    pub port: u16,
+    // find a free port when `port` is taken; `port` is then updated to the one picked
+    pub auto: bool,
+}
+
+impl Default for RedirectConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            auto: false,
+        }
+    }
+}
+
+impl RedirectConfig {
+    pub fn redirect_uri(&self) -> String {
+        format!("http://{HOST}:{}/callback", self.port)
+    }
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct RedirectInfo {
+    pub port: u16,
+    pub auto: bool,
+    // what has to be listed under Redirect URIs in the Spotify dashboard
+    pub redirect_uri: String,
+}
+
+impl From<RedirectConfig> for RedirectInfo {
+    fn from(c: RedirectConfig) -> Self {
+        Self {
+            port: c.port,
+            auto: c.auto,
+            redirect_uri: c.redirect_uri(),
+        }
+    }
+}
+
+pub fn load_config(app: &tauri::AppHandle) -> RedirectConfig {
+    let config: RedirectConfig = read_settings(app)
+        .get("oauth_redirect")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    if config.port == 0 {
+        return RedirectConfig {
+            port: DEFAULT_PORT,
+            ..config
+        };
+    }
+    config
+}
+
+async fn bind_port(port: u16) -> Result<TcpListener, String> {
+    let mut attempt = 1;
+    loop {
+        match TcpListener::bind((HOST, port)).await {
+            Ok(l) => return Ok(l),
+            Err(e) if attempt >= BIND_ATTEMPTS => {
+                return Err(format!(
+                    "Port {port} for the Spotify sign-in is in use ({e}). Pick another redirect \
+                     port or let the app choose one"
+                ))
+            }
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(BIND_RETRY).await;
+            }
+        }
+    }
+}
+
+// Binds the callback port, falling back to a free one in auto mode. Returns the config the
+// listener actually ended up on; its `redirect_uri()` is the one to send to Spotify.
+pub async fn bind(config: RedirectConfig) -> Result<(TcpListener, RedirectConfig), String> {
+    let fixed = bind_port(config.port).await;
+    if fixed.is_ok() || !config.auto {
+        return fixed.map(|l| (l, config));
+    }
+    let listener = TcpListener::bind((HOST, 0))
+        .await
+        .map_err(|e| format!("Bind {HOST}:0 failed: {e}"))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Callback port: {e}"))?
+        .port();
+    Ok((listener, RedirectConfig { port, ..config }))
+}
+
+// Serves until Spotify redirects back with a code for this attempt. `state` is the value sent
+// along with the authorize URL; a callback carrying anything else (an old tab, another site
+// poking the port) is turned away.
+pub async fn wait_for_code(
+    listener: TcpListener,
+    cancel: CancellationToken,
+    state: &str,
+) -> Result<String, String> {
+    let deadline = tokio::time::sleep(TIMEOUT);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return Err("Sign-in cancelled".into()),
+            _ = &mut deadline => return Err("Timed out waiting for the Spotify sign-in".into()),
+            accepted = listener.accept() => {
+                let Ok((mut stream, _)) = accepted else {
+                    continue;
+                };
+                match handle(&mut stream, state).await {
+                    Callback::Code(code) => return Ok(code),
+                    Callback::Denied(reason) => {
+                        return Err(format!("Spotify sign-in failed: {reason}"))
+                    }
+                    Callback::Ignored => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum Callback {
+    Code(String),
+    // the user said no, or Spotify refused the request
+    Denied(String),
+    Ignored,
+}
+
+const PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+  body { margin: 0; height: 100vh; display: flex; align-items: center; justify-content: center;
+         background: #121212; color: #fff; font: 16px system-ui, sans-serif; }
+  .card { text-align: center; padding: 32px 40px; border-radius: 12px; background: #1e1e1e; }
+  .icon { font-size: 48px; color: {color}; }
+  h1 { font-size: 22px; margin: 12px 0 8px; }
+  p { margin: 0; color: #b3b3b3; }
+</style>
+</head>
+<body>
+<div class="card">
+  <div class="icon">{icon}</div>
+  <h1>{title}</h1>
+  <p>{message}</p>
+</div>
+</body>
+</html>
+"#;
+
+fn page(status: &'static str, ok: bool, title: &str, message: &str) -> Response {
+    let (icon, color) = if ok {
+        ("&#10003;", "#1db954")
+    } else {
+        ("&#10007;", "#e22134")
+    };
+    let html = PAGE
+        .replace("{icon}", icon)
+        .replace("{color}", color)
+        .replace("{title}", &escape(title))
+        .replace("{message}", &escape(message));
+    Response::html(status, &html)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Favicon probes, HEAD checks and anything else just get an answer
+async fn handle(stream: &mut TcpStream, expected_state: &str) -> Callback {
+    let req = match read_request(stream).await {
+        Ok(req) => req,
+        Err(e) => {
+            eprintln!("[callback] {e}");
+            let res = Response::text("400 Bad Request", "Bad Request");
+            let _ = write_response(stream, &res, false).await;
+            return Callback::Ignored;
+        }
+    };
+    let head_only = req.method == "HEAD";
+    if req.method != "GET" && !head_only {
+        let res = Response::text("405 Method Not Allowed", "Method Not Allowed");
+        let _ = write_response(stream, &res, false).await;
+        return Callback::Ignored;
+    }
+
+    let Some(url) = Url::parse(&format!("http://localhost{}", req.path))
+        .ok()
+        .filter(|u| u.path() == "/callback")
+    else {
+        let res = Response::text("404 Not Found", "Not Found");
+        let _ = write_response(stream, &res, head_only).await;
+        return Callback::Ignored;
+    };
+    // a HEAD request doesn't count, the browser still has to come back with a GET
+    if head_only {
+        let _ = write_response(stream, &Response::text("200 OK", ""), true).await;
+        return Callback::Ignored;
+    }
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    };
+
+    if param("state").as_deref() != Some(expected_state) {
+        let res = page(
+            "400 Bad Request",
+            false,
+            "Sign-in link expired",
+            "This page doesn't belong to the current sign-in. Start connecting again from the app.",
+        );
+        let _ = write_response(stream, &res, false).await;
+        return Callback::Ignored;
+    }
+    if let Some(error) = param("error") {
+        let res = page(
+            "200 OK",
+            false,
+            "Spotify wasn't connected",
+            "Access was not granted. You can close this tab and try again from the app.",
+        );
+        let _ = write_response(stream, &res, false).await;
+        return Callback::Denied(error);
+    }
+    let Some(code) = param("code") else {
+        let res = page(
+            "400 Bad Request",
+            false,
+            "Something went wrong",
+            "Spotify didn't send a sign-in code. Start connecting again from the app.",
+        );
+        let _ = write_response(stream, &res, false).await;
+        return Callback::Ignored;
+    };
+    let res = page(
+        "200 OK",
+        true,
+        "Connected to Spotify",
+        "You can close this tab and return to the app.",
+    );
+    let _ = write_response(stream, &res, false).await;
+    Callback::Code(code)
+}
+
+#[tauri::command]
+pub fn get_redirect_config(state: State<'_, SharedStore>) -> RedirectInfo {
+    state.lock().redirect.into()
+}
+
+#[tauri::command]
+pub fn set_redirect_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: RedirectConfig,
+) -> Result<RedirectInfo, String> {
+    // 0 asks the OS for any port, which only makes sense when the app picks one
+    let config = match (config.port, config.auto) {
+        (0, false) => return Err("Pick a port between 1 and 65535".into()),
+        (0, true) => RedirectConfig {
+            port: DEFAULT_PORT,
+            ..config
+        },
+        _ => config,
+    };
+    write_setting(
+        window.app_handle(),
+        "oauth_redirect",
+        serde_json::json!(config),
+    )?;
+    state.lock().redirect = config;
+    Ok(config.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Sends one raw request through `handle`; returns its verdict and the response status line
+    async fn hit(request: &str) -> (Callback, String) {
+        let listener = TcpListener::bind((HOST, 0)).await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let callback = handle(&mut stream, "s").await;
+        drop(stream);
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        let status = response.lines().next().unwrap_or_default().to_string();
+        (callback, status)
+    }
+
+    #[tokio::test]
+    async fn code_with_matching_state() {
+        let (callback, status) = hit("GET /callback?code=abc&state=s HTTP/1.1\r\n\r\n").await;
+        assert_eq!(callback, Callback::Code("abc".into()));
+        assert_eq!(status, "HTTP/1.1 200 OK");
+    }
+
+    #[tokio::test]
+    async fn foreign_state_is_turned_away() {
+        for path in ["/callback?code=abc&state=old", "/callback?code=abc"] {
+            let (callback, status) = hit(&format!("GET {path} HTTP/1.1\r\n\r\n")).await;
+            assert_eq!(callback, Callback::Ignored, "{path}");
+            assert_eq!(status, "HTTP/1.1 400 Bad Request", "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn denied_by_the_user() {
+        let (callback, status) =
+            hit("GET /callback?error=access_denied&state=s HTTP/1.1\r\n\r\n").await;
+        assert_eq!(callback, Callback::Denied("access_denied".into()));
+        assert_eq!(status, "HTTP/1.1 200 OK");
+    }
+
+    #[tokio::test]
+    async fn other_requests_are_ignored() {
+        let cases = [
+            (
+                "GET /favicon.ico HTTP/1.1\r\n\r\n",
+                "HTTP/1.1 404 Not Found",
+            ),
+            (
+                "GET /callback?state=s HTTP/1.1\r\n\r\n",
+                "HTTP/1.1 400 Bad Request",
+            ),
+            (
+                "POST /callback?code=abc&state=s HTTP/1.1\r\n\r\n",
+                "HTTP/1.1 405 Method Not Allowed",
+            ),
+            (
+                "HEAD /callback?code=abc&state=s HTTP/1.1\r\n\r\n",
+                "HTTP/1.1 200 OK",
+            ),
+            ("G@T /callback HTTP/1.1\r\n\r\n", "HTTP/1.1 400 Bad Request"),
+        ];
+        for (request, expected) in cases {
+            let (callback, status) = hit(request).await;
+            assert_eq!(callback, Callback::Ignored, "{request}");
+            assert_eq!(status, expected, "{request}");
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_waiting_past_a_stale_tab() {
+        let listener = TcpListener::bind((HOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let wait = tokio::spawn(wait_for_code(listener, CancellationToken::new(), "s"));
+        for request in [
+            "GET /callback?code=old&state=old HTTP/1.1\r\n\r\n",
+            "GET /callback?code=abc&state=s HTTP/1.1\r\n\r\n",
+        ] {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+        }
+        assert_eq!(wait.await.unwrap(), Ok("abc".to_string()));
+    }
+
+    #[tokio::test]
+    async fn port_is_freed_once_the_code_is_used() {
+        let listener = TcpListener::bind((HOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let wait = tokio::spawn(wait_for_code(listener, CancellationToken::new(), "s"));
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET /callback?code=abc&state=s HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(wait.await.unwrap(), Ok("abc".to_string()));
+
+        // reloading the tab finds nothing listening instead of handing the code out again
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+}
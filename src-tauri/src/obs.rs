@@ -0,0 +1,415 @@
+// OBS Studio through obs-websocket (v5, built into OBS 28+): fills a Text source with the track
+// and points an Image source at the cover, straight from the app, so no export folder has to be
+// wired up in OBS. Optionally re-shows a scene item on every new track so its show transition
+// plays, and hides it again after a while.
+//
+// Updates come from every rendered export (see `auto_export`); the connection task sends only
+// the newest and sends it again after reconnecting.
+
+use crate::{
+    export::{self, RenderedExport},
+    read_settings,
+    resilience::Retry,
+    write_setting, NowPlaying, SharedStore,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+use tokio::{net::TcpStream, sync::watch};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio_util::sync::CancellationToken;
+
+// a connection that lasted this long counts as working; the next drop starts the retry
+// policy over
+const HEALTHY_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, schemars::JsonSchema)]
+#[serde(default)]
+pub struct ObsConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    // Tools > WebSocket Server Settings in OBS; empty when authentication is off there
+    pub password: String,
+    // Text (GDI+ / FreeType 2) source to fill; empty leaves text alone
+    pub text_source: String,
+    // `{song}`, `{artist}`, ... like the export templates
+    pub text_format: String,
+    // Image source showing the cover; empty leaves it alone
+    pub image_source: String,
+    pub animation: Option<ObsAnimation>,
+}
+
+impl Default for ObsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".into(),
+            port: 4455,
+            password: String::new(),
+            text_source: String::new(),
+            text_format: "{artist} - {song}".into(),
+            image_source: String::new(),
+            animation: None,
+        }
+    }
+}
+
+// Scene item hidden and shown again on every new track
+#[derive(Serialize, Deserialize, Clone, PartialEq, schemars::JsonSchema)]
+pub struct ObsAnimation {
+    pub scene: String,
+    pub source: String,
+    // hide it again after this long; 0 leaves it showing
+    #[serde(default)]
+    pub hide_after_secs: u32,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ObsUpdate {
+    track: String,
+    text: String,
+    // empty clears the image
+    artwork: String,
+}
+
+#[derive(Default)]
+pub struct ObsOutput {
+    config: ObsConfig,
+    cancel: Option<CancellationToken>,
+    updates: watch::Sender<Option<ObsUpdate>>,
+    // which of the two cover files is in use, and what it holds
+    cover: Option<(bool, u64)>,
+}
+
+impl ObsOutput {
+    pub fn running(&self) -> bool {
+        self.cancel.is_some()
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let config: ObsConfig = read_settings(app)
+        .get("obs")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    restart(app, config);
+}
+
+fn restart(app: &tauri::AppHandle, config: ObsConfig) {
+    let token = CancellationToken::new();
+    let mut updates = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.obs.cancel.take() {
+            old.cancel();
+        }
+        s.obs.config = config.clone();
+        if !config.enabled {
+            return;
+        }
+        s.obs.cancel = Some(token.clone());
+        s.obs.updates.subscribe()
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut retry = Retry::new(&app, "obs_websocket");
+        loop {
+            let connected_at = Instant::now();
+            tokio::select! {
+                _ = token.cancelled() => break,
+                res = session(&config, &mut updates) => {
+                    if let Err(e) = res {
+                        eprintln!("[obs] {e}");
+                    }
+                }
+            }
+            if connected_at.elapsed() >= HEALTHY_AFTER {
+                retry.reset();
+            }
+            if !retry.wait(&token).await {
+                if !token.is_cancelled() {
+                    eprintln!("[obs] giving up reconnecting");
+                }
+                break;
+            }
+        }
+    });
+}
+
+// OBS can't be told to reload a file it already shows, so the cover alternates between two
+fn write_cover(app: &tauri::AppHandle, png: &[u8]) -> Option<PathBuf> {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    png.hash(&mut hasher);
+    let hash = hasher.finish();
+    let dir = crate::portable::data_dir(app).ok()?.join("obs");
+    let name = |second: bool| dir.join(if second { "cover-b.png" } else { "cover-a.png" });
+
+    let state = app.state::<SharedStore>();
+    let current = state.lock().obs.cover;
+    if let Some((second, _)) = current.filter(|(_, written)| *written == hash) {
+        return Some(name(second));
+    }
+    let second = current.is_some_and(|(second, _)| !second);
+    let _ = std::fs::create_dir_all(&dir);
+    if let Err(e) = std::fs::write(name(second), png) {
+        eprintln!("[obs] write cover: {e}");
+        return None;
+    }
+    state.lock().obs.cover = Some((second, hash));
+    Some(name(second))
+}
+
+// Called with every rendered export
+pub fn update(app: &tauri::AppHandle, np: &NowPlaying, rendered: &RenderedExport) {
+    let config = {
+        let state = app.state::<SharedStore>();
+        let s = state.lock();
+        if s.obs.cancel.is_none() {
+            return;
+        }
+        s.obs.config.clone()
+    };
+    let artwork = if config.image_source.is_empty() {
+        None
+    } else {
+        rendered
+            .artwork_png
+            .as_deref()
+            .and_then(|png| write_cover(app, png))
+    };
+    let update = ObsUpdate {
+        track: format!(
+            "{}|{}",
+            np.track_name.as_deref().unwrap_or_default(),
+            np.artists.join(",")
+        ),
+        text: export::fill_template(&config.text_format, &rendered.files),
+        artwork: artwork
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+    app.state::<SharedStore>()
+        .lock()
+        .obs
+        .updates
+        .send_if_modified(|current| {
+            let changed = current.as_ref() != Some(&update);
+            *current = Some(update);
+            changed
+        });
+}
+
+// base64(sha256(base64(sha256(password + salt)) + challenge))
+fn auth_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = STANDARD.encode(Sha256::digest(format!("{password}{salt}")));
+    STANDARD.encode(Sha256::digest(format!("{secret}{challenge}")))
+}
+
+struct Connection {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Connection {
+    async fn send(&mut self, op: u8, d: Value) -> Result<(), String> {
+        let msg = json!({ "op": op, "d": d }).to_string();
+        self.ws
+            .send(Message::text(msg))
+            .await
+            .map_err(|e| format!("send: {e}"))
+    }
+
+    // The next message with opcode `op`; others are skipped
+    async fn receive(&mut self, op: u8) -> Result<Value, String> {
+        loop {
+            let msg = match self.ws.next().await {
+                Some(msg) => msg.map_err(|e| format!("read: {e}"))?,
+                None => return Err("OBS closed the connection".into()),
+            };
+            if let Message::Close(frame) = &msg {
+                let reason = frame.as_ref().map(|f| f.reason.to_string());
+                return Err(format!(
+                    "OBS closed the connection: {}",
+                    reason.unwrap_or_default()
+                ));
+            }
+            let Ok(text) = msg.to_text() else {
+                continue;
+            };
+            let Ok(value) = serde_json::from_str::<Value>(text) else {
+                continue;
+            };
+            if value["op"] == op {
+                return Ok(value["d"].clone());
+            }
+        }
+    }
+
+    // Outer error: the connection failed. Inner error: OBS refused the request, e.g. because
+    // a source doesn't exist.
+    async fn request(&mut self, kind: &str, data: Value) -> Result<Result<Value, String>, String> {
+        self.next_id += 1;
+        let id = self.next_id.to_string();
+        self.send(
+            6,
+            json!({ "requestType": kind, "requestId": id, "requestData": data }),
+        )
+        .await?;
+        loop {
+            let d = self.receive(7).await?;
+            if d["requestId"] != id.as_str() {
+                continue;
+            }
+            let status = &d["requestStatus"];
+            if status["result"].as_bool() != Some(true) {
+                let comment = status["comment"].as_str().unwrap_or("request failed");
+                return Ok(Err(format!("{kind}: {comment}")));
+            }
+            return Ok(Ok(d["responseData"].clone()));
+        }
+    }
+
+    async fn set_scene_item(
+        &mut self,
+        animation: &ObsAnimation,
+        enabled: bool,
+    ) -> Result<(), String> {
+        let id = match self
+            .request(
+                "GetSceneItemId",
+                json!({ "sceneName": animation.scene, "sourceName": animation.source }),
+            )
+            .await?
+        {
+            Ok(data) => data["sceneItemId"].clone(),
+            Err(e) => {
+                eprintln!("[obs] {e}");
+                return Ok(());
+            }
+        };
+        let res = self
+            .request(
+                "SetSceneItemEnabled",
+                json!({ "sceneName": animation.scene, "sceneItemId": id, "sceneItemEnabled": enabled }),
+            )
+            .await?;
+        if let Err(e) = res {
+            eprintln!("[obs] {e}");
+        }
+        Ok(())
+    }
+
+    async fn set_input(&mut self, input: &str, settings: Value) -> Result<(), String> {
+        let res = self
+            .request(
+                "SetInputSettings",
+                json!({ "inputName": input, "inputSettings": settings }),
+            )
+            .await?;
+        if let Err(e) = res {
+            eprintln!("[obs] {input}: {e}");
+        }
+        Ok(())
+    }
+}
+
+async fn session(
+    config: &ObsConfig,
+    updates: &mut watch::Receiver<Option<ObsUpdate>>,
+) -> Result<(), String> {
+    let url = format!("ws://{}:{}", config.host, config.port);
+    let (ws, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .map_err(|e| {
+            format!("connect {url}: {e} (is OBS running with its WebSocket server on?)")
+        })?;
+    let mut obs = Connection { ws, next_id: 0 };
+
+    let hello = obs.receive(0).await?;
+    let mut identify = json!({ "rpcVersion": 1, "eventSubscriptions": 0 });
+    if let Some(auth) = hello.get("authentication") {
+        let salt = auth["salt"].as_str().unwrap_or_default();
+        let challenge = auth["challenge"].as_str().unwrap_or_default();
+        identify["authentication"] = json!(auth_string(&config.password, salt, challenge));
+    }
+    obs.send(1, identify).await?;
+    obs.receive(2)
+        .await
+        .map_err(|e| format!("identify: {e} (check the password)"))?;
+
+    // whatever is showing now, then every change
+    updates.mark_changed();
+    let mut shown_track: Option<String> = None;
+    let mut hide_at: Option<tokio::time::Instant> = None;
+    loop {
+        tokio::select! {
+            changed = updates.changed() => {
+                changed.map_err(|_| "app is shutting down".to_string())?;
+                let Some(update) = updates.borrow_and_update().clone() else {
+                    continue;
+                };
+                if !config.text_source.is_empty() {
+                    obs.set_input(&config.text_source, json!({ "text": update.text })).await?;
+                }
+                if !config.image_source.is_empty() {
+                    obs.set_input(&config.image_source, json!({ "file": update.artwork })).await?;
+                }
+                if let Some(animation) = &config.animation {
+                    if shown_track.as_ref() != Some(&update.track) {
+                        shown_track = Some(update.track.clone());
+                        obs.set_scene_item(animation, false).await?;
+                        obs.set_scene_item(animation, true).await?;
+                        hide_at = (animation.hide_after_secs > 0).then(|| {
+                            tokio::time::Instant::now()
+                                + Duration::from_secs(animation.hide_after_secs.into())
+                        });
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(hide_at.unwrap_or_else(tokio::time::Instant::now)), if hide_at.is_some() => {
+                hide_at = None;
+                if let Some(animation) = &config.animation {
+                    obs.set_scene_item(animation, false).await?;
+                }
+            }
+            // OBS closing the socket shows up here while idle
+            msg = obs.ws.next() => match msg {
+                None | Some(Ok(Message::Close(_))) => return Err("OBS closed the connection".into()),
+                Some(Err(e)) => return Err(format!("read: {e}")),
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_obs_config(state: State<'_, SharedStore>) -> ObsConfig {
+    state.lock().obs.config.clone()
+}
+
+#[tauri::command]
+pub fn set_obs_config(window: tauri::Window, config: ObsConfig) -> Result<(), String> {
+    if config.enabled && (config.host.trim().is_empty() || config.port == 0) {
+        return Err("OBS WebSocket host and port must be set".into());
+    }
+    if config
+        .animation
+        .as_ref()
+        .is_some_and(|a| a.scene.trim().is_empty() || a.source.trim().is_empty())
+    {
+        return Err("Pick the scene and the source to animate".into());
+    }
+    let app = window.app_handle();
+    write_setting(app, "obs", serde_json::json!(config))?;
+    restart(app, config);
+    Ok(())
+}
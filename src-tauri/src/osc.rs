@@ -0,0 +1,402 @@
+// OSC input.
+//
+// VRChat / TouchDesigner / Max rigs can push track info as OSC messages over UDP, e.g.
+// `/nowplaying/title "Windowlicker"`, `/nowplaying/artist "Aphex Twin"`,
+// `/nowplaying/playing 1`. Each message updates one field of a `NowPlaying` that feeds the
+// provider chain like any other push-based source.
+
+use crate::{
+    parse_artists,
+    providers::{self, Provider},
+    read_settings, resilience, write_setting, NowPlaying, SharedStore,
+};
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Serialize, Deserialize, Clone, schemars::JsonSchema)]
+#[serde(default)]
+pub struct OscConfig {
+    pub enabled: bool,
+    pub port: u16,
+    // address prefix the field names hang off
+    pub prefix: String,
+    // accept messages from other machines (a VRChat or TouchDesigner PC), not just this one
+    pub listen_on_lan: bool,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9005,
+            prefix: "/nowplaying".into(),
+            listen_on_lan: false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct OscInput {
+    config: OscConfig,
+    cancel: Option<CancellationToken>,
+    // fields received so far; messages arrive one field at a time
+    track: NowPlaying,
+}
+
+impl OscInput {
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+}
+
+#[derive(Debug)]
+enum Arg {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Arg {
+    fn as_text(&self) -> String {
+        match self {
+            Arg::Int(i) => i.to_string(),
+            Arg::Float(f) => f.to_string(),
+            Arg::Str(s) => s.clone(),
+            Arg::Bool(b) => b.to_string(),
+        }
+    }
+
+    fn as_bool(&self) -> bool {
+        match self {
+            Arg::Int(i) => *i != 0,
+            Arg::Float(f) => *f != 0.0,
+            Arg::Str(s) => matches!(s.to_ascii_lowercase().as_str(), "1" | "true" | "playing"),
+            Arg::Bool(b) => *b,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Arg::Int(i) => u64::try_from(*i).ok(),
+            Arg::Float(f) => (*f >= 0.0).then_some(*f as u64),
+            Arg::Str(s) => s.trim().parse().ok(),
+            Arg::Bool(_) => None,
+        }
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let config: OscConfig = read_settings(app)
+        .get("osc")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    restart(app, config);
+}
+
+fn restart(app: &tauri::AppHandle, config: OscConfig) {
+    let token = CancellationToken::new();
+    {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.osc.cancel.take() {
+            old.cancel();
+        }
+        s.osc.track = NowPlaying::default();
+        s.osc_now_playing = None;
+        s.osc.config = config.clone();
+        if !config.enabled {
+            return;
+        }
+        s.osc.cancel = Some(token.clone());
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let host = if config.listen_on_lan {
+            "0.0.0.0"
+        } else {
+            "127.0.0.1"
+        };
+        let bind = || async {
+            UdpSocket::bind((host, config.port))
+                .await
+                .map_err(|e| format!("bind udp {}: {e}", config.port))
+        };
+        let Some(socket) = resilience::retry(&app, "osc", &token, bind).await else {
+            return;
+        };
+        let mut buf = vec![0u8; 65_536];
+        loop {
+            let n = tokio::select! {
+                _ = token.cancelled() => break,
+                res = socket.recv_from(&mut buf) => match res {
+                    Ok((n, _)) => n,
+                    Err(e) => {
+                        eprintln!("[osc] recv: {e}");
+                        continue;
+                    }
+                },
+            };
+            let mut messages = Vec::new();
+            parse_packet(&buf[..n], &mut messages);
+            if !messages.is_empty() {
+                apply(&app, &config.prefix, messages);
+            }
+        }
+    });
+}
+
+fn apply(app: &tauri::AppHandle, prefix: &str, messages: Vec<(String, Vec<Arg>)>) {
+    let prefix = prefix.trim_end_matches('/');
+    let np = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        let track = &mut s.osc.track;
+        for (addr, args) in messages {
+            let Some(field) = addr.strip_prefix(prefix).and_then(|f| f.strip_prefix('/')) else {
+                continue;
+            };
+            let text = || {
+                args.first()
+                    .map(Arg::as_text)
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+            };
+            match field {
+                "title" => track.track_name = text(),
+                // several string args = several artists, one string gets split like any tag
+                "artist" | "artists" => {
+                    track.artists = if args.len() > 1 {
+                        args.iter().map(Arg::as_text).collect()
+                    } else {
+                        text().map(|t| parse_artists(&t)).unwrap_or_default()
+                    }
+                }
+                "album" => track.album = text(),
+                // URLs only: a local (or UNC) path from whoever can reach the port would get
+                // opened by the exports and `/artwork`
+                "artwork" => {
+                    track.artwork_url =
+                        text().filter(|a| a.starts_with("http://") || a.starts_with("https://"));
+                }
+                "playing" => track.is_playing = args.first().is_some_and(Arg::as_bool),
+                "position" => track.position_ms = args.first().and_then(Arg::as_u64),
+                "clear" => *track = NowPlaying::default(),
+                _ => {}
+            }
+        }
+        let np = track.track_name.is_some().then(|| track.clone());
+        s.osc_now_playing = np.clone();
+        np
+    };
+    providers::publish_pushed(app, Provider::Osc, np);
+}
+
+// Collects the messages of a packet, unpacking bundles
+fn parse_packet(buf: &[u8], out: &mut Vec<(String, Vec<Arg>)>) {
+    if let Some(mut rest) = buf.strip_prefix(b"#bundle\0") {
+        // skip the time tag, elements are `i32 size | packet`
+        rest = rest.get(8..).unwrap_or_default();
+        while let Some(size) = rest.get(..4) {
+            let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+            let Some(elem) = rest.get(4..4 + size) else {
+                return;
+            };
+            parse_packet(elem, out);
+            rest = &rest[4 + size..];
+        }
+        return;
+    }
+    if let Some(msg) = parse_message(buf) {
+        out.push(msg);
+    }
+}
+
+fn parse_message(buf: &[u8]) -> Option<(String, Vec<Arg>)> {
+    let (addr, mut rest) = osc_string(buf)?;
+    if !addr.starts_with('/') {
+        return None;
+    }
+    let tags = match osc_string(rest) {
+        Some((t, r)) if t.starts_with(',') => {
+            rest = r;
+            t
+        }
+        // very old senders leave the type tags out
+        _ => return Some((addr, Vec::new())),
+    };
+
+    let mut args = Vec::new();
+    for tag in tags.chars().skip(1) {
+        let arg = match tag {
+            'i' => {
+                let (b, r) = take(rest, 4)?;
+                rest = r;
+                Arg::Int(i32::from_be_bytes(b.try_into().ok()?) as i64)
+            }
+            'h' => {
+                let (b, r) = take(rest, 8)?;
+                rest = r;
+                Arg::Int(i64::from_be_bytes(b.try_into().ok()?))
+            }
+            'f' => {
+                let (b, r) = take(rest, 4)?;
+                rest = r;
+                Arg::Float(f32::from_be_bytes(b.try_into().ok()?) as f64)
+            }
+            'd' => {
+                let (b, r) = take(rest, 8)?;
+                rest = r;
+                Arg::Float(f64::from_be_bytes(b.try_into().ok()?))
+            }
+            's' | 'S' => {
+                let (s, r) = osc_string(rest)?;
+                rest = r;
+                Arg::Str(s)
+            }
+            'T' => Arg::Bool(true),
+            'F' => Arg::Bool(false),
+            // blobs, colors, MIDI, ... aren't useful here and we can't skip unknown sizes
+            'N' | 'I' => continue,
+            _ => break,
+        };
+        args.push(arg);
+    }
+    Some((addr, args))
+}
+
+fn take(buf: &[u8], n: usize) -> Option<(&[u8], &[u8])> {
+    (buf.len() >= n).then(|| buf.split_at(n))
+}
+
+// Null-terminated, padded to a multiple of 4
+fn osc_string(buf: &[u8]) -> Option<(String, &[u8])> {
+    let end = buf.iter().position(|b| *b == 0)?;
+    let s = String::from_utf8_lossy(&buf[..end]).into_owned();
+    let padded = (end + 4) & !3;
+    Some((s, buf.get(padded..).unwrap_or_default()))
+}
+
+#[tauri::command]
+pub fn get_osc_config(state: State<'_, SharedStore>) -> OscConfig {
+    state.lock().osc.config.clone()
+}
+
+#[tauri::command]
+pub fn set_osc_config(window: tauri::Window, config: OscConfig) -> Result<(), String> {
+    if config.enabled && config.port == 0 {
+        return Err("OSC port must be set".into());
+    }
+    let app = window.app_handle();
+    write_setting(app, "osc", serde_json::json!(config))?;
+    restart(app, config);
+    // a listener turned on may be all the watcher has to poll
+    crate::start_watcher_if_needed(app, &app.state::<SharedStore>());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Null-terminated and padded to a multiple of 4, like `osc_string` expects
+    fn string(s: &str) -> Vec<u8> {
+        let mut b = s.as_bytes().to_vec();
+        b.resize((s.len() + 4) & !3, 0);
+        b
+    }
+
+    fn message(addr: &str, tags: &str, args: &[u8]) -> Vec<u8> {
+        [string(addr), string(tags), args.to_vec()].concat()
+    }
+
+    fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+        let mut b = b"#bundle\0".to_vec();
+        b.extend_from_slice(&1u64.to_be_bytes());
+        for e in elements {
+            b.extend_from_slice(&(e.len() as u32).to_be_bytes());
+            b.extend_from_slice(e);
+        }
+        b
+    }
+
+    fn parse(buf: &[u8]) -> Vec<(String, Vec<Arg>)> {
+        let mut out = Vec::new();
+        parse_packet(buf, &mut out);
+        out
+    }
+
+    #[test]
+    fn message_with_args() {
+        let args = [
+            string("Windowlicker"),
+            7i32.to_be_bytes().to_vec(),
+            1.5f32.to_be_bytes().to_vec(),
+        ]
+        .concat();
+        let msgs = parse(&message("/nowplaying/title", ",sifT", &args));
+        assert_eq!(msgs.len(), 1);
+        let (addr, args) = &msgs[0];
+        assert_eq!(addr, "/nowplaying/title");
+        assert!(matches!(&args[..], [
+            Arg::Str(s),
+            Arg::Int(7),
+            Arg::Float(f),
+            Arg::Bool(true),
+        ] if s == "Windowlicker" && *f == 1.5));
+    }
+
+    #[test]
+    fn message_without_type_tags() {
+        let msgs = parse(&string("/nowplaying/clear"));
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs[0].1.is_empty());
+    }
+
+    #[test]
+    fn nested_bundles() {
+        let title = message("/nowplaying/title", ",s", &string("Xtal"));
+        let artist = message("/nowplaying/artist", ",s", &string("Aphex Twin"));
+        let msgs = parse(&bundle(&[title, bundle(&[artist])]));
+        let addrs: Vec<_> = msgs.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(addrs, ["/nowplaying/title", "/nowplaying/artist"]);
+    }
+
+    #[test]
+    fn truncated_message() {
+        // the int is cut short
+        let msg = message("/nowplaying/position", ",i", &[0, 0]);
+        assert!(parse(&msg).is_empty());
+        // the string never ends
+        let mut msg = message("/nowplaying/title", ",s", b"Windowlick");
+        msg.truncate(msg.len() - 2);
+        assert!(parse(&msg).is_empty());
+        // the address never ends
+        assert!(parse(b"/nowplaying").is_empty());
+        assert!(parse(b"").is_empty());
+    }
+
+    #[test]
+    fn truncated_bundle() {
+        let title = message("/nowplaying/title", ",s", &string("Xtal"));
+        let mut b = bundle(&[title.clone(), title]);
+        b.truncate(b.len() - 3);
+        assert_eq!(parse(&b).len(), 1);
+        // cut inside the time tag
+        assert!(parse(b"#bundle\0\0\0").is_empty());
+    }
+
+    #[test]
+    fn oversized_bundle_element() {
+        let title = message("/nowplaying/title", ",s", &string("Xtal"));
+        let mut b = bundle(&[title]);
+        b.extend_from_slice(&u32::MAX.to_be_bytes());
+        b.extend_from_slice(&string("/nowplaying/album"));
+        let msgs = parse(&b);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].0, "/nowplaying/title");
+    }
+}
@@ -0,0 +1,15 @@
+// The built-in OBS browser source, served by `server` at /overlay: track, artist, artwork and a
+// progress bar, updated over /events. Themed with query parameters (see the page itself);
+// `overlay.css` in the data folder is loaded after the built-in styles for anything beyond them.
+
+use std::fs;
+
+pub const PAGE: &str = include_str!("../overlay/overlay.html");
+
+// Empty when the user hasn't made one
+pub fn custom_css(app: &tauri::AppHandle) -> Vec<u8> {
+    crate::portable::data_dir(app)
+        .ok()
+        .and_then(|dir| fs::read(dir.join("overlay.css")).ok())
+        .unwrap_or_default()
+}
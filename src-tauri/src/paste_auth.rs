@@ -0,0 +1,133 @@
+// Sign-in without the loopback callback server, for machines where a local port can't be
+// opened (corporate laptops, strict firewalls). The user opens the authorize URL, approves, and
+// pastes back the address the browser was sent to (it fails to load, but the code is in it),
+// or just the `code` from it.
+
+use crate::{build_spotify, start_watcher_if_needed, token_store, watchdog, SharedStore};
+use rspotify::{
+    clients::{BaseClient, OAuthClient},
+    AuthCodePkceSpotify,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{Manager, State};
+use url::Url;
+
+#[derive(Serialize, JsonSchema)]
+pub struct PasteAuth {
+    // to open in any browser, on this machine or another
+    pub authorize_url: String,
+    // where Spotify sends the browser afterwards; the page won't load, that's expected
+    pub redirect_uri: String,
+}
+
+// Starts a paste-the-code sign-in and opens the authorize URL. Replaces one already started;
+// a code from the older URL won't be accepted.
+#[tauri::command]
+pub fn start_paste_auth(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+) -> Result<PasteAuth, String> {
+    let mut spotify = build_spotify(&window)?;
+    let authorize_url = spotify
+        .get_authorize_url(None)
+        .map_err(|e| format!("authorize url: {e}"))?;
+    let redirect_uri = spotify.get_oauth().redirect_uri.clone();
+    state.lock().paste_auth = Some(spotify);
+    if let Err(e) = tauri_plugin_opener::open_url(&authorize_url, None::<&str>) {
+        // shown in the UI to copy anyway
+        eprintln!("[auth] open browser: {e}");
+    }
+    Ok(PasteAuth {
+        authorize_url,
+        redirect_uri,
+    })
+}
+
+// The code from a pasted redirect address, or the pasted code itself
+fn code_from(input: &str, expected_state: &str) -> Result<String, String> {
+    let input = input.trim();
+    let url = Url::parse(input).ok().or_else(|| {
+        // copied from an address bar that hides the scheme
+        input
+            .contains('?')
+            .then(|| Url::parse(&format!("http://{input}")).ok())
+            .flatten()
+    });
+    let Some(url) = url else {
+        if input.is_empty() || input.contains(char::is_whitespace) {
+            return Err("Paste the address Spotify sent the browser to, or the code in it".into());
+        }
+        return Ok(input.to_string());
+    };
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    };
+    if param("state").is_some_and(|s| s != expected_state) {
+        return Err("That address is from an earlier sign-in; use the latest link".into());
+    }
+    if let Some(error) = param("error") {
+        return Err(format!("Access was not granted ({error})"));
+    }
+    param("code").ok_or_else(|| "No code in that address".to_string())
+}
+
+#[tauri::command]
+pub async fn submit_auth_code(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    input: String,
+) -> Result<(), String> {
+    let spotify = state
+        .lock()
+        .paste_auth
+        .take()
+        .ok_or("Start the paste-code sign-in first")?;
+    let code = match code_from(&input, &spotify.get_oauth().state) {
+        Ok(code) => code,
+        Err(e) => {
+            state.lock().paste_auth.get_or_insert(spotify);
+            return Err(e);
+        }
+    };
+    if let Err(e) = exchange(&spotify, &code).await {
+        // a mistyped code; let them paste again
+        state.lock().paste_auth.get_or_insert(spotify);
+        return Err(e);
+    }
+
+    {
+        let mut s = state.lock();
+        // a browser sign-in started alongside isn't needed anymore
+        if let Some(t) = s.auth_cancel.take() {
+            t.cancel();
+        }
+        s.client = Some(Arc::new(spotify));
+    }
+    start_watcher_if_needed(window.app_handle(), &state);
+    Ok(())
+}
+
+async fn exchange(spotify: &AuthCodePkceSpotify, code: &str) -> Result<(), String> {
+    watchdog::within(
+        "Spotify token exchange",
+        watchdog::SPOTIFY_TIMEOUT,
+        spotify.request_token(code),
+    )
+    .await?
+    .map_err(|e| format!("Token exchange failed: {e}"))?;
+    let token = spotify
+        .get_token()
+        .lock()
+        .await
+        .map_err(|_| "Token lock failed".to_string())?
+        .clone();
+    if let Some(token) = token {
+        token_store::save(&token)?;
+    }
+    Ok(())
+}
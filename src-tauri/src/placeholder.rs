@@ -0,0 +1,112 @@
+// What exports and the built-in overlay show instead of the track when nothing is playing (or,
+// if asked, while it's paused), rather than keeping the last song's files around. Each export
+// profile has its own (`ExportProfile::placeholder`); the overlay uses the "overlay_placeholder"
+// setting, which the export without profiles uses as well. Manual
+// profiles switch to theirs when the track stops too (see `auto_export::on_update`), and back
+// on the next manual export.
+
+use crate::{
+    export::{self, ExportPayload, ExportProfile, RenderedExport},
+    read_settings, write_setting, NowPlaying, SharedStore,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default, JsonSchema)]
+#[serde(default)]
+pub struct Placeholder {
+    // song.txt, now_playing_plain.txt and the overlay's title line; the other files are
+    // written empty
+    pub text: String,
+    // image file used as the artwork; None leaves no artwork.png behind
+    pub artwork: Option<String>,
+    // also while the track is paused, not only when nothing is playing
+    pub when_paused: bool,
+}
+
+impl Placeholder {
+    pub fn applies(&self, np: &NowPlaying) -> bool {
+        np.track_name.is_none() || (self.when_paused && !np.is_playing)
+    }
+
+    // Checked on save, so a moved image shows up in settings rather than as a failed export
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(path) = &self.artwork {
+            image::open(path).map_err(|e| format!("placeholder artwork {path}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+// `profile` writes its placeholder for `np` instead of the track
+pub fn shows(profile: &ExportProfile, np: &NowPlaying) -> bool {
+    profile.placeholder.as_ref().is_some_and(|p| p.applies(np))
+}
+
+pub async fn render(placeholder: &Placeholder) -> Result<RenderedExport, String> {
+    // the file list of an empty export, so templates and `/files` see the same names
+    let mut rendered = export::render_export(&ExportPayload::from(&NowPlaying::default())).await?;
+    for (name, contents) in &mut rendered.files {
+        *contents = match *name {
+            "song.txt" | "now_playing_plain.txt" => placeholder.text.clone(),
+            _ => String::new(),
+        };
+    }
+    rendered.artwork_png = match &placeholder.artwork {
+        Some(path) => {
+            let img = image::open(path).map_err(|e| format!("placeholder artwork: {e}"))?;
+            Some(export::encode_png(&img)?)
+        }
+        None => None,
+    };
+    Ok(rendered)
+}
+
+// Renders and writes `profile`'s placeholder; without placeholder artwork the last cover is
+// removed too
+pub async fn write(app: &tauri::AppHandle, profile: &ExportProfile) -> Result<(), String> {
+    let Some(placeholder) = &profile.placeholder else {
+        return Ok(());
+    };
+    let rendered = render(placeholder).await?;
+    let dir = export::write_with_fallback(app, &rendered, profile)?;
+    if rendered.artwork_png.is_none() {
+        let artwork = export::long_path(&dir).join(profile.format.file_name("artwork.png"));
+        if artwork.exists() {
+            std::fs::remove_file(&artwork).map_err(|e| format!("remove artwork: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn load_overlay(app: &tauri::AppHandle) -> Option<Placeholder> {
+    read_settings(app)
+        .get("overlay_placeholder")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+#[tauri::command]
+pub fn get_overlay_placeholder(state: State<'_, SharedStore>) -> Option<Placeholder> {
+    state.lock().overlay_placeholder.clone()
+}
+
+// None turns it off: the overlay hides (or says "Nothing playing" with `idle=1`) and `/files`
+// keeps the last track
+#[tauri::command]
+pub fn set_overlay_placeholder(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    placeholder: Option<Placeholder>,
+) -> Result<(), String> {
+    if let Some(p) = &placeholder {
+        p.check()?;
+    }
+    write_setting(
+        window.app_handle(),
+        "overlay_placeholder",
+        serde_json::json!(placeholder),
+    )?;
+    state.lock().overlay_placeholder = placeholder;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+// Spotify Connect playback control through the Web API (needs Premium): which devices are
+// around and moving playback between them, e.g. from the phone to the PC, and the shuffle /
+// repeat modes.
+
+use crate::{spotify_client, NowPlaying, SharedStore};
+use rspotify::{
+    clients::OAuthClient,
+    model::{AdditionalType, CurrentPlaybackContext, CurrentlyPlayingContext, RepeatState},
+    AuthCodePkceSpotify, ClientError,
+};
+use serde::Serialize;
+use tauri::State;
+
+// What the poll reads: the currently-playing context plus the playback modes, which only the
+// full playback endpoint reports.
+pub struct Playback {
+    pub ctx: CurrentlyPlayingContext,
+    shuffle: bool,
+    repeat: RepeatState,
+}
+
+impl Playback {
+    pub fn apply_modes(&self, np: &mut NowPlaying) {
+        np.shuffle = Some(self.shuffle);
+        np.repeat_mode = Some(repeat_name(self.repeat).to_string());
+    }
+}
+
+fn repeat_name(state: RepeatState) -> &'static str {
+    match state {
+        RepeatState::Off => "off",
+        RepeatState::Track => "track",
+        RepeatState::Context => "context",
+    }
+}
+
+async fn current_playback(
+    client: &AuthCodePkceSpotify,
+) -> Result<Option<CurrentPlaybackContext>, ClientError> {
+    client
+        .current_playback(
+            None,
+            Some(&[AdditionalType::Track, AdditionalType::Episode]),
+        )
+        .await
+}
+
+pub async fn current(client: &AuthCodePkceSpotify) -> Result<Option<Playback>, ClientError> {
+    Ok(current_playback(client).await?.map(|p| Playback {
+        shuffle: p.shuffle_state,
+        repeat: p.repeat_state,
+        ctx: CurrentlyPlayingContext {
+            context: p.context,
+            timestamp: p.timestamp,
+            progress: p.progress,
+            is_playing: p.is_playing,
+            item: p.item,
+            currently_playing_type: p.currently_playing_type,
+            actions: p.actions,
+        },
+    }))
+}
+
+#[tauri::command]
+pub async fn set_shuffle(state: State<'_, SharedStore>, shuffle: bool) -> Result<(), String> {
+    let client = spotify_client(&state)?;
+    client
+        .shuffle(shuffle, None)
+        .await
+        .map_err(|e| format!("set shuffle: {e}"))
+}
+
+// off -> context -> track -> off, like the button in the Spotify app. Returns the new mode.
+#[tauri::command]
+pub async fn cycle_repeat(state: State<'_, SharedStore>) -> Result<String, String> {
+    let client = spotify_client(&state)?;
+    let current = current_playback(&client)
+        .await
+        .map_err(|e| format!("playback state: {e}"))?
+        .ok_or("No active playback")?
+        .repeat_state;
+    let next = match current {
+        RepeatState::Off => RepeatState::Context,
+        RepeatState::Context => RepeatState::Track,
+        RepeatState::Track => RepeatState::Off,
+    };
+    client
+        .repeat(next, None)
+        .await
+        .map_err(|e| format!("set repeat: {e}"))?;
+    Ok(repeat_name(next).to_string())
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct PlaybackDevice {
+    // missing for devices Spotify won't let us address
+    id: Option<String>,
+    name: String,
+    // "Computer", "Smartphone", "Speaker", ...
+    kind: String,
+    is_active: bool,
+    // restricted devices don't accept Web API commands
+    is_restricted: bool,
+    volume_percent: Option<u32>,
+}
+
+#[tauri::command]
+pub async fn list_devices(state: State<'_, SharedStore>) -> Result<Vec<PlaybackDevice>, String> {
+    let client = spotify_client(&state)?;
+    let devices = client
+        .device()
+        .await
+        .map_err(|e| format!("list devices: {e}"))?;
+    Ok(devices
+        .into_iter()
+        .map(|d| PlaybackDevice {
+            id: d.id,
+            name: d.name,
+            kind: format!("{:?}", d._type),
+            is_active: d.is_active,
+            is_restricted: d.is_restricted,
+            volume_percent: d.volume_percent,
+        })
+        .collect())
+}
+
+// `play: None` keeps the current play/pause state
+#[tauri::command]
+pub async fn transfer_playback(
+    state: State<'_, SharedStore>,
+    device_id: String,
+    play: Option<bool>,
+) -> Result<(), String> {
+    let client = spotify_client(&state)?;
+    client
+        .transfer_playback(&device_id, play)
+        .await
+        .map_err(|e| format!("transfer playback: {e}"))
+}
@@ -0,0 +1,72 @@
+// Change detection between two looks at the same player: a different track, the same one
+// starting over (replayed, or looping on repeat-one), a seek, or a status change. Shared by
+// GSMTC's `gsmtc_*` events and the watcher's `track_restarted`.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::time::Instant;
+
+// Jumps smaller than this are just poll jitter
+const SEEK_TOLERANCE_MS: i64 = 2_500;
+// a jump back to within this much of the start is a restart, not a seek
+const RESTART_WINDOW_MS: i64 = 2 * SEEK_TOLERANCE_MS;
+
+// What one poll saw
+pub struct Seen {
+    // track identity, e.g. "title|artists"
+    pub key: String,
+    // GSMTC's names ("Playing", "Paused", "Stopped", ...); the position only moves on its own
+    // while "Playing"
+    pub status: String,
+    pub position_ms: Option<i64>,
+    pub seen_at: Instant,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Track,
+    /// Same track, position went back to (near) the start: replay / repeat-one
+    Restart,
+    Seek,
+    Status,
+}
+
+/// Payload of `gsmtc_track_changed`, `gsmtc_seeked` and `gsmtc_status_changed`: the raw GSMTC
+/// session fields plus what changed
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct SessionChange {
+    pub change: Change,
+    #[serde(flatten)]
+    pub session: serde_json::Map<String, serde_json::Value>,
+}
+
+pub fn detect(prev: Option<&Seen>, cur: &Seen) -> Option<Change> {
+    let Some(prev) = prev else {
+        return Some(Change::Track);
+    };
+    if prev.key != cur.key {
+        return Some(Change::Track);
+    }
+
+    if let (Some(before), Some(now)) = (prev.position_ms, cur.position_ms) {
+        let elapsed = if prev.status == "Playing" {
+            cur.seen_at.duration_since(prev.seen_at).as_millis() as i64
+        } else {
+            0
+        };
+        let expected = before + elapsed;
+        if now + SEEK_TOLERANCE_MS < expected {
+            return Some(if now < RESTART_WINDOW_MS {
+                Change::Restart
+            } else {
+                Change::Seek
+            });
+        }
+        if now > expected + SEEK_TOLERANCE_MS {
+            return Some(Change::Seek);
+        }
+    }
+
+    (prev.status != cur.status).then_some(Change::Status)
+}
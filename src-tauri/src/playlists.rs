@@ -0,0 +1,87 @@
+// Adding the current track to one of the user's playlists, e.g. a "Stream Finds" list filled
+// from the widget while live.
+
+use crate::{pick_image_url, spotify_client, SharedStore};
+use rspotify::{
+    clients::OAuthClient,
+    model::{Id, PlayableId, PlayableItem, PlaylistId},
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::State;
+
+// Spotify's maximum page size for this endpoint
+const PAGE_SIZE: u32 = 50;
+
+#[derive(Serialize, JsonSchema)]
+pub struct UserPlaylist {
+    id: String,
+    name: String,
+    artwork_url: Option<String>,
+    tracks: u32,
+    // owned by the user or collaborative; Spotify refuses additions to anything else
+    editable: bool,
+}
+
+#[tauri::command]
+pub async fn list_user_playlists(
+    state: State<'_, SharedStore>,
+) -> Result<Vec<UserPlaylist>, String> {
+    let client = spotify_client(&state)?;
+    let me = client
+        .current_user()
+        .await
+        .map_err(|e| format!("current user: {e}"))?;
+
+    let mut out = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = client
+            .current_user_playlists_manual(Some(PAGE_SIZE), Some(offset))
+            .await
+            .map_err(|e| format!("list playlists: {e}"))?;
+        let fetched = page.items.len() as u32;
+        out.extend(page.items.into_iter().map(|p| UserPlaylist {
+            editable: p.collaborative || p.owner.id == me.id,
+            id: p.id.id().to_string(),
+            name: p.name,
+            artwork_url: pick_image_url(&p.images, 300),
+            tracks: p.tracks.total,
+        }));
+        offset += fetched;
+        if fetched == 0 || page.next.is_none() {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+// `playlist_id` is the bare id or the `spotify:playlist:` URI from `list_user_playlists`
+#[tauri::command]
+pub async fn add_current_to_playlist(
+    state: State<'_, SharedStore>,
+    playlist_id: String,
+) -> Result<(), String> {
+    let playlist = PlaylistId::from_id_or_uri(&playlist_id)
+        .map_err(|e| format!("invalid playlist id: {e}"))?;
+    let client = spotify_client(&state)?;
+
+    let ctx = client
+        .current_user_playing_item()
+        .await
+        .map_err(|e| format!("currently playing: {e}"))?
+        .ok_or_else(|| "Nothing is playing".to_string())?;
+    let item: PlayableId<'static> = match ctx.item {
+        Some(PlayableItem::Track(t)) => t.id.map(PlayableId::Track),
+        Some(PlayableItem::Episode(ep)) => Some(PlayableId::Episode(ep.id)),
+        None => None,
+    }
+    // local files have no id
+    .ok_or_else(|| "This item can't be added to a playlist".to_string())?;
+
+    client
+        .playlist_add_items(playlist, [item], None)
+        .await
+        .map_err(|e| format!("add to playlist: {e}"))?;
+    Ok(())
+}
@@ -0,0 +1,114 @@
+// Portable mode, for running from a USB stick: with a file named `portable` next to the
+// executable, settings, the Spotify token, history and the artwork cache live in `data` beside
+// it instead of the user's app data, and the token is kept in a file there rather than the
+// machine's credential store. Decided once per run; switching takes a restart.
+
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+const MARKER: &str = "portable";
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()?
+        .parent()
+        .map(Path::to_path_buf)
+}
+
+// `data` next to the executable, in portable mode
+static PORTABLE_DIR: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    exe_dir()
+        .filter(|d| d.join(MARKER).is_file())
+        .map(|d| d.join("data"))
+});
+
+pub fn enabled() -> bool {
+    PORTABLE_DIR.is_some()
+}
+
+// Where everything this app keeps is stored
+pub fn data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    match PORTABLE_DIR.as_ref() {
+        Some(dir) => Ok(dir.clone()),
+        None => app
+            .path()
+            .app_local_data_dir()
+            .map_err(|e| format!("app_local_data_dir: {e}")),
+    }
+}
+
+// The Spotify token, in portable mode
+pub fn token_file() -> Option<PathBuf> {
+    PORTABLE_DIR
+        .as_ref()
+        .map(|d| d.join("spotify").join("token.json"))
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct PortableMode {
+    pub enabled: bool,
+    pub data_dir: String,
+    // the marker was added or removed; applies on the next start
+    pub restart_required: bool,
+}
+
+fn status(app: &tauri::AppHandle) -> Result<PortableMode, String> {
+    let marked = exe_dir().is_some_and(|d| d.join(MARKER).is_file());
+    Ok(PortableMode {
+        enabled: enabled(),
+        data_dir: data_dir(app)?.to_string_lossy().to_string(),
+        restart_required: marked != enabled(),
+    })
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)?.flatten() {
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_portable_mode(window: tauri::Window) -> Result<PortableMode, String> {
+    status(window.app_handle())
+}
+
+// Adds or removes the marker next to the executable. Settings are copied over when the other
+// location has none yet; history, the cache and the sign-in start fresh there.
+#[tauri::command]
+pub fn set_portable_mode(window: tauri::Window, enabled: bool) -> Result<PortableMode, String> {
+    let app = window.app_handle();
+    let exe_dir = exe_dir().ok_or("Can't tell where the executable is")?;
+    let marker = exe_dir.join(MARKER);
+    let target = if enabled {
+        exe_dir.join("data")
+    } else {
+        app.path()
+            .app_local_data_dir()
+            .map_err(|e| format!("app_local_data_dir: {e}"))?
+    };
+
+    let settings = data_dir(app)?.join("settings");
+    if target.join("settings") != settings && !target.join("settings").exists() && settings.is_dir()
+    {
+        copy_dir(&settings, &target.join("settings"))
+            .map_err(|e| format!("copy settings to {}: {e}", target.display()))?;
+    }
+    if enabled {
+        fs::write(&marker, b"")
+            .map_err(|e| format!("write {}: {e} (is the folder read-only?)", marker.display()))?;
+    } else if marker.exists() {
+        fs::remove_file(&marker).map_err(|e| format!("remove {}: {e}", marker.display()))?;
+    }
+    status(app)
+}
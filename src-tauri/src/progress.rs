@@ -0,0 +1,58 @@
+// `playback_progress` between polls for the non-GSMTC sources (Spotify, WebNowPlaying, ...):
+// the last reported position plus the time since, so progress bars move every second instead
+// of jumping with each 2s poll. GSMTC sources get the same event from
+// `gsmtc::start_event_watcher`.
+
+use crate::{events, SharedStore};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::time::Duration;
+use tauri::Manager;
+
+const TICK: Duration = Duration::from_secs(1);
+
+/// Payload of `playback_progress`
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct PlaybackProgress {
+    pub position_ms: u64,
+    pub duration_ms: Option<u64>,
+    pub is_playing: bool,
+    /// GSMTC AUMID of the player, when it's a GSMTC source
+    pub source_app_id: Option<String>,
+}
+
+fn current(state: &SharedStore) -> Option<PlaybackProgress> {
+    let s = state.lock();
+    if s.active_source.is_none_or(|p| p.is_gsmtc()) {
+        return None;
+    }
+    let np = s
+        .last_now_playing
+        .as_ref()
+        .filter(|np| np.is_playing && !np.stale)?;
+    let elapsed = s.last_now_playing_at?.elapsed().as_millis() as u64;
+    let mut position = np.position_ms? + elapsed;
+    if let Some(d) = np.duration_ms {
+        position = position.min(d);
+    }
+    Some(PlaybackProgress {
+        position_ms: position,
+        duration_ms: np.duration_ms,
+        is_playing: true,
+        source_app_id: np.source_app_id.clone(),
+    })
+}
+
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut tick = tokio::time::interval(TICK);
+        loop {
+            tick.tick().await;
+            let progress = current(&app.state::<SharedStore>());
+            if let Some(p) = progress {
+                events::emit(&app, "playback_progress", p);
+            }
+        }
+    });
+}
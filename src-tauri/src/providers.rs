@@ -0,0 +1,711 @@
+// Now-playing providers and the failover chain the watcher walks on every poll.
+//
+// The chain is an ordered list of sources. The watcher sticks with the currently live
+// provider until it has reported nothing playing (or errored) for `failover_after`
+// consecutive polls, then moves down the list. A higher-priority provider that starts
+// playing again takes over immediately.
+
+use crate::{
+    artwork_lookup, backoff, build_now_playing_from_ctx, compilation, context, dj_history, events,
+    gsmtc, maybe_set_local_artwork, parse_artists, playback, queue, read_settings, saved_tracks,
+    spotify_search, start_watcher_if_needed, token_store, usage, watchdog, write_setting,
+    NowPlaying, SharedStore,
+};
+use rspotify::clients::BaseClient;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+pub const DEFAULT_FAILOVER_AFTER: u32 = 3;
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    Spotify,
+    Gsmtc,
+    WebNowPlaying,
+    // Spotify Connect receiver (librespot sidecar)
+    Librespot,
+    // ICY metadata from an Icecast/Shoutcast stream
+    Icecast,
+    // Traktor's broadcast, received locally (see `traktor`)
+    Traktor,
+    // newest entry of a Serato / rekordbox history file
+    DjHistory,
+    // pushed over OSC (VRChat, TouchDesigner, ...)
+    Osc,
+    // set by hand through `set_now_playing`
+    Manual,
+    // desktop apps, matched by their GSMTC AUMID
+    Tidal,
+    Deezer,
+    AppleMusic,
+}
+
+impl Provider {
+    // Sources read from GSMTC, which follow the GSMTC poll interval
+    pub fn is_gsmtc(self) -> bool {
+        matches!(
+            self,
+            Provider::Gsmtc | Provider::Tidal | Provider::Deezer | Provider::AppleMusic
+        )
+    }
+
+    // GSMTC AUMID fragment for providers that are a specific desktop app
+    fn app_match(self) -> Option<&'static str> {
+        match self {
+            Provider::Tidal => Some("tidal"),
+            Provider::Deezer => Some("deezer"),
+            // AUMID is "AppleInc.AppleMusicWin_..."
+            Provider::AppleMusic => Some("applemusic"),
+            _ => None,
+        }
+    }
+
+    pub fn from_app_id(app_id: Option<&str>) -> Provider {
+        let id = app_id.unwrap_or_default().to_ascii_lowercase();
+        [Provider::Tidal, Provider::Deezer, Provider::AppleMusic]
+            .into_iter()
+            .find(|p| p.app_match().is_some_and(|m| id.contains(m)))
+            .unwrap_or(Provider::Gsmtc)
+    }
+}
+
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+pub struct ProviderChain {
+    pub providers: Vec<Provider>,
+    pub failover_after: u32,
+}
+
+impl Default for ProviderChain {
+    fn default() -> Self {
+        Self {
+            providers: vec![Provider::Spotify, Provider::WebNowPlaying],
+            failover_after: DEFAULT_FAILOVER_AFTER,
+        }
+    }
+}
+
+// Per-watcher bookkeeping: which chain entry is live and how often each one missed in a row.
+#[derive(Default)]
+pub struct FailoverState {
+    active: usize,
+    misses: Vec<u32>,
+    // last error of each entry, logged once rather than on every poll
+    errors: Vec<Option<String>>,
+}
+
+pub fn load_chain(app: &tauri::AppHandle) -> ProviderChain {
+    let v = read_settings(app);
+    let mut chain = ProviderChain::default();
+    if let Some(list) = v
+        .get("provider_chain")
+        .and_then(|l| serde_json::from_value::<Vec<Provider>>(l.clone()).ok())
+    {
+        chain.providers = list;
+    }
+    if let Some(n) = v.get("failover_after").and_then(|n| n.as_u64()) {
+        chain.failover_after = n as u32;
+    }
+    chain
+}
+
+async fn poll(
+    app: &tauri::AppHandle,
+    state: &SharedStore,
+    provider: Provider,
+) -> Result<Option<NowPlaying>, String> {
+    let np = read_provider(app, state, provider).await?;
+    Ok(np.map(|mut np| {
+        np.source.get_or_insert(provider);
+        np
+    }))
+}
+
+async fn read_provider(
+    app: &tauri::AppHandle,
+    state: &SharedStore,
+    provider: Provider,
+) -> Result<Option<NowPlaying>, String> {
+    match provider {
+        Provider::Spotify => {
+            let client = state
+                .lock()
+                .client
+                .clone()
+                .ok_or_else(|| "Not connected to Spotify".to_string())?;
+
+            // if refresh fails -> auth is gone: drop the client, the rest of the chain keeps going
+            let reauth = watchdog::within(
+                "Spotify token refresh",
+                watchdog::SPOTIFY_TIMEOUT,
+                client.auto_reauth(),
+            )
+            .await?;
+            if reauth.is_err() {
+                events::emit(app, "auth_lost", ());
+                state.lock().client = None;
+                return Err("Spotify auth lost".into());
+            }
+            token_store::persist(&client).await;
+
+            if let Some(wait) = state.lock().spotify_backoff.remaining() {
+                return Err(format!("backing off, {}s left", wait.as_secs()));
+            }
+            let current = watchdog::within(
+                "Spotify playback",
+                watchdog::SPOTIFY_TIMEOUT,
+                playback::current(&client),
+            )
+            .await?;
+            let playback = match current {
+                Ok(p) => {
+                    backoff::record_success(app, state);
+                    p
+                }
+                Err(e) => {
+                    backoff::record_failure(app, state, &e);
+                    return Err(e.to_string());
+                }
+            };
+            match playback {
+                Some(playback) => {
+                    let ctx = &playback.ctx;
+                    let mut np = build_now_playing_from_ctx(ctx);
+                    playback.apply_modes(&mut np);
+                    maybe_set_local_artwork(app, &mut np, ctx);
+                    // extras: a slow one is skipped rather than holding up the update
+                    let limit = watchdog::SPOTIFY_TIMEOUT;
+                    let _ = watchdog::within(
+                        "Spotify context",
+                        limit,
+                        context::enrich(app, &client, ctx, &mut np),
+                    )
+                    .await;
+                    let _ = watchdog::within(
+                        "Spotify saved tracks",
+                        limit,
+                        saved_tracks::enrich(app, &client, ctx, &mut np),
+                    )
+                    .await;
+                    let _ = watchdog::within(
+                        "Spotify queue",
+                        limit,
+                        queue::enrich(app, &client, ctx, &mut np),
+                    )
+                    .await;
+                    Ok(Some(np))
+                }
+                None => Ok(None),
+            }
+        }
+        Provider::Gsmtc => {
+            let (payload, _) = gsmtc::read_gsmtc(app.clone()).await?;
+            let Some(mut np) = gsmtc::to_now_playing(&payload) else {
+                return Ok(None);
+            };
+            spotify_search::enrich(app, &mut np).await;
+            Ok(Some(np))
+        }
+        Provider::WebNowPlaying => Ok(state.lock().wnp_now_playing.clone()),
+        Provider::Librespot => Ok(state.lock().librespot_now_playing.clone()),
+        Provider::Icecast => Ok(state.lock().icecast_now_playing.clone()),
+        Provider::Traktor => Ok(state.lock().traktor_now_playing.clone()),
+        Provider::DjHistory => dj_history::current(state).await,
+        Provider::Osc => Ok(state.lock().osc_now_playing.clone()),
+        Provider::Manual => Ok(manual_override(app, state)),
+        Provider::Tidal | Provider::Deezer | Provider::AppleMusic => {
+            let app_match = provider.app_match().unwrap_or_default();
+            let (payload, _) = gsmtc::read_app_session(app.clone(), app_match, false).await?;
+            let Some(mut np) = gsmtc::to_now_playing(&payload) else {
+                return Ok(None);
+            };
+            fill_app_artwork(app, provider, &payload, &mut np).await;
+            spotify_search::enrich(app, &mut np).await;
+            Ok(Some(np))
+        }
+    }
+}
+
+// Desktop apps that don't give GSMTC a usable thumbnail get one from a catalog search.
+async fn fill_app_artwork(
+    app: &tauri::AppHandle,
+    provider: Provider,
+    payload: &serde_json::Value,
+    np: &mut NowPlaying,
+) {
+    if provider == Provider::AppleMusic {
+        split_apple_music_artist(payload, np);
+    }
+    if np.artwork_path.is_some() {
+        return;
+    }
+    match provider {
+        Provider::AppleMusic => artwork_lookup::fill_from_itunes(app, np).await,
+        _ => artwork_lookup::fill_from_deezer(app, np).await,
+    }
+}
+
+// Apple Music reports "Artist — Album" as the artist and leaves the album empty
+fn split_apple_music_artist(payload: &serde_json::Value, np: &mut NowPlaying) {
+    if np.album.is_some() {
+        return;
+    }
+    let raw = payload
+        .get("artist")
+        .and_then(|a| a.as_str())
+        .unwrap_or_default();
+    if let Some((artist, album)) = raw.split_once(" — ") {
+        np.artists = parse_artists(artist);
+        np.album = Some(album.trim().to_string()).filter(|a| !a.is_empty());
+    }
+}
+
+// Polls the chain in priority order and returns what should be shown this tick.
+pub async fn poll_chain(
+    app: &tauri::AppHandle,
+    state: &SharedStore,
+    failover: &mut FailoverState,
+) -> NowPlaying {
+    let chain = state.lock().provider_chain.clone();
+    if chain.providers.is_empty() {
+        return NowPlaying::default();
+    }
+    let threshold = chain.failover_after.max(1);
+
+    failover.misses.resize(chain.providers.len(), 0);
+    failover.errors.resize(chain.providers.len(), None);
+    if failover.active >= chain.providers.len() {
+        failover.active = 0;
+    }
+
+    let mut chosen = None;
+    let mut active_result = None;
+    for (i, provider) in chain.providers.iter().enumerate() {
+        let (np, error) = match poll(app, state, *provider).await {
+            Ok(np) => (np, None),
+            Err(e) => (None, Some(e)),
+        };
+        if error != failover.errors[i] {
+            match &error {
+                Some(e) => eprintln!("[poll] {provider:?} error: {e}"),
+                None => eprintln!("[poll] {provider:?} is back"),
+            }
+            failover.errors[i] = error;
+        }
+
+        let playing = np.as_ref().is_some_and(|n| n.is_playing);
+        failover.misses[i] = if playing {
+            0
+        } else {
+            failover.misses[i].saturating_add(1)
+        };
+
+        // A playing provider always wins over the ones below it; the live one also gets
+        // `threshold` polls of grace before we look further down the list.
+        if playing || (i == failover.active && failover.misses[i] < threshold) {
+            chosen = Some((i, np.unwrap_or_default()));
+            break;
+        }
+        if i == failover.active {
+            active_result = np;
+        }
+    }
+
+    // Nobody is playing: stay on the current provider and show whatever it reported.
+    let (idx, np) = chosen.unwrap_or((failover.active, active_result.unwrap_or_default()));
+
+    failover.active = idx;
+    set_active_source(app, state, chain.providers[idx]);
+
+    np
+}
+
+fn set_active_source(app: &tauri::AppHandle, state: &SharedStore, source: Provider) {
+    let changed = {
+        let mut s = state.lock();
+        let changed = s.active_source != Some(source);
+        s.active_source = Some(source);
+        changed
+    };
+    if changed {
+        if let Some(name) = serde_json::to_value(source)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+        {
+            usage::record(app, &format!("source:{name}"));
+        }
+        events::emit(
+            app,
+            "source_changed",
+            SourceChanged {
+                source: Some(source),
+            },
+        );
+    }
+}
+
+/// Payload of `source_changed`
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+pub struct SourceChanged {
+    pub source: Option<Provider>,
+}
+
+/// Payload of `players_update`
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+pub struct PlayersUpdate {
+    pub players: Vec<PlayerEntry>,
+}
+
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+pub struct PlayerEntry {
+    source: Provider,
+    // GSMTC AUMID, e.g. "Spotify.exe" or "AppleInc.AppleMusicWin_..."
+    app_id: Option<String>,
+    primary: bool,
+    now_playing: NowPlaying,
+}
+
+// Aggregation mode: poll the Spotify API, every GSMTC session and the push-based sources,
+// emit the whole list as `players_update`, and return the primary player for the regular
+// `now_playing_update`.
+pub async fn poll_all(app: &tauri::AppHandle, state: &SharedStore) -> NowPlaying {
+    let (chain, connected) = {
+        let s = state.lock();
+        (s.provider_chain.clone(), s.client.is_some())
+    };
+    let entry = |source, app_id, now_playing| PlayerEntry {
+        source,
+        app_id,
+        primary: false,
+        now_playing,
+    };
+    let mut players = Vec::new();
+
+    if connected {
+        match poll(app, state, Provider::Spotify).await {
+            Ok(Some(np)) => players.push(entry(Provider::Spotify, None, np)),
+            Ok(None) => {}
+            Err(e) => eprintln!("[poll] Spotify error: {e}"),
+        }
+    }
+    let spotify_api_live = !players.is_empty();
+
+    match gsmtc::read_all_gsmtc(app.clone()).await {
+        Ok(sessions) => {
+            for payload in sessions {
+                let app_id = payload
+                    .get("source_app_id")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                // The Spotify desktop app shows up here too; the Web API entry already covers it
+                if spotify_api_live
+                    && app_id
+                        .as_deref()
+                        .is_some_and(|id| id.to_ascii_lowercase().contains("spotify"))
+                {
+                    continue;
+                }
+                if let Some(mut np) = gsmtc::to_now_playing(&payload) {
+                    let source = Provider::from_app_id(app_id.as_deref());
+                    if source != Provider::Gsmtc {
+                        fill_app_artwork(app, source, &payload, &mut np).await;
+                    }
+                    spotify_search::enrich(app, &mut np).await;
+                    players.push(entry(source, app_id, np));
+                }
+            }
+        }
+        Err(e) => eprintln!("[poll] GSMTC error: {e}"),
+    }
+
+    let (wnp, receiver, stream, traktor, osc) = {
+        let s = state.lock();
+        (
+            s.wnp_now_playing.clone(),
+            s.librespot_now_playing.clone(),
+            s.icecast_now_playing.clone(),
+            s.traktor_now_playing.clone(),
+            s.osc_now_playing.clone(),
+        )
+    };
+    if let Some(np) = wnp {
+        players.push(entry(Provider::WebNowPlaying, None, np));
+    }
+    if let Some(np) = receiver {
+        players.push(entry(Provider::Librespot, None, np));
+    }
+    if let Some(np) = stream {
+        players.push(entry(Provider::Icecast, None, np));
+    }
+    if let Some(np) = traktor {
+        players.push(entry(Provider::Traktor, None, np));
+    }
+    if let Some(np) = osc {
+        players.push(entry(Provider::Osc, None, np));
+    }
+
+    // Primary: anything playing beats paused, then chain order; ties keep discovery order
+    let rank = |p: &PlayerEntry| {
+        (
+            !p.now_playing.is_playing,
+            chain
+                .providers
+                .iter()
+                .position(|c| *c == p.source)
+                .unwrap_or(usize::MAX),
+        )
+    };
+    let primary = players
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| rank(p))
+        .map(|(i, _)| i);
+    if let Some(i) = primary {
+        players[i].primary = true;
+        set_active_source(app, state, players[i].source);
+    }
+
+    let np = primary
+        .map(|i| players[i].now_playing.clone())
+        .unwrap_or_default();
+    events::emit(app, "players_update", PlayersUpdate { players });
+    np
+}
+
+pub fn load_aggregate_mode(app: &tauri::AppHandle) -> bool {
+    read_settings(app)
+        .get("aggregate_sessions")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+#[tauri::command]
+pub fn get_aggregate_mode(state: State<'_, SharedStore>) -> bool {
+    state.lock().aggregate_sessions
+}
+
+#[tauri::command]
+pub fn set_aggregate_mode(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    enabled: bool,
+) -> Result<(), String> {
+    let app = window.app_handle();
+    write_setting(app, "aggregate_sessions", serde_json::json!(enabled))?;
+    state.lock().aggregate_sessions = enabled;
+    start_watcher_if_needed(app, &state);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_provider_chain(state: State<'_, SharedStore>) -> ProviderChain {
+    state.lock().provider_chain.clone()
+}
+
+#[tauri::command]
+pub fn set_provider_chain(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    providers: Vec<Provider>,
+    failover_after: Option<u32>,
+) -> Result<(), String> {
+    if providers.is_empty() {
+        return Err("Provider chain cannot be empty".into());
+    }
+    let mut deduped: Vec<Provider> = Vec::new();
+    for p in providers {
+        if !deduped.contains(&p) {
+            deduped.push(p);
+        }
+    }
+    let chain = ProviderChain {
+        providers: deduped,
+        failover_after: failover_after.unwrap_or(DEFAULT_FAILOVER_AFTER).max(1),
+    };
+
+    let app = window.app_handle();
+    write_setting(app, "provider_chain", serde_json::json!(chain.providers))?;
+    write_setting(
+        app,
+        "failover_after",
+        serde_json::json!(chain.failover_after),
+    )?;
+    state.lock().provider_chain = chain;
+
+    // A chain that no longer needs Spotify may have to start the watcher on its own.
+    start_watcher_if_needed(app, &state);
+    Ok(())
+}
+
+// GSMTC change notifications land here: re-read the live provider right away instead of
+// waiting for the next poll, if it is a GSMTC-backed one.
+#[cfg(windows)]
+pub async fn refresh_active(app: &tauri::AppHandle) {
+    let state = app.state::<SharedStore>();
+    let (active, manual, paused) = {
+        let s = state.lock();
+        (
+            s.active_source,
+            s.manual_override.is_some(),
+            s.watcher_paused,
+        )
+    };
+    if paused {
+        return;
+    }
+    let Some(provider) = active.filter(|p| p.is_gsmtc() && !manual) else {
+        // playback may have just started while the watcher is idling
+        state.lock().watcher_wake.notify_one();
+        return;
+    };
+    let mut np = match poll(app, &state, provider).await {
+        Ok(np) => np.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("[gsmtc] refresh {provider:?}: {e}");
+            return;
+        }
+    };
+    crate::finish_now_playing(app, &mut np).await;
+    if crate::settle_now_playing(app, &state, &mut np) {
+        events::emit(app, "now_playing_update", &np);
+    }
+}
+
+// Push-based sources (WebNowPlaying, librespot, Icecast, OSC) aren't polled; they may only
+// emit directly while they are the live source, otherwise they would fight the watcher.
+pub fn publish_pushed(app: &tauri::AppHandle, provider: Provider, np: Option<NowPlaying>) {
+    let state = app.state::<SharedStore>();
+    {
+        let s = state.lock();
+        if s.active_source != Some(provider) {
+            // playing while the live source is idle, or above it: let the chain re-evaluate
+            // now, not on the next (idle) poll. Only then, as WebNowPlaying reports the
+            // position every second.
+            let rank = |p: Option<Provider>| {
+                let chain = &s.provider_chain.providers;
+                p.and_then(|p| chain.iter().position(|c| *c == p))
+                    .unwrap_or(chain.len())
+            };
+            let playing = np.as_ref().is_some_and(|n| n.is_playing);
+            let live_playing = s.last_now_playing.as_ref().is_some_and(|n| n.is_playing);
+            let in_chain = rank(Some(provider)) < s.provider_chain.providers.len();
+            if playing
+                && in_chain
+                && (!live_playing || rank(Some(provider)) < rank(s.active_source))
+            {
+                s.watcher_wake.notify_one();
+            }
+            return;
+        }
+    }
+    let mut np = np.unwrap_or_default();
+    np.source.get_or_insert(provider);
+    compilation::apply(&mut np);
+    crate::finish_local(app, &mut np);
+    if crate::settle_now_playing(app, &state, &mut np) {
+        events::emit(app, "now_playing_update", &np);
+    }
+}
+
+// Manual override (vinyl, cassettes, anything no provider can see): wins over the whole chain
+// until it expires or is cleared.
+pub struct ManualOverride {
+    now_playing: NowPlaying,
+    until: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManualTrack {
+    title: String,
+    #[serde(default)]
+    artists: Vec<String>,
+    album: Option<String>,
+    artwork_path: Option<String>,
+    artwork_url: Option<String>,
+    #[serde(default = "default_true")]
+    is_playing: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// The override if one is active; expired ones are dropped on the way.
+pub fn manual_override(app: &tauri::AppHandle, state: &SharedStore) -> Option<NowPlaying> {
+    let np = {
+        let mut s = state.lock();
+        let active = s
+            .manual_override
+            .as_ref()
+            .map(|o| o.until.is_none_or(|t| Instant::now() < t));
+        match active {
+            Some(true) => s.manual_override.as_ref().map(|o| o.now_playing.clone()),
+            Some(false) => {
+                s.manual_override = None;
+                None
+            }
+            None => None,
+        }
+    };
+    if np.is_some() {
+        set_active_source(app, state, Provider::Manual);
+    }
+    np
+}
+
+// `duration_secs: None` keeps the override until `clear_now_playing`
+#[tauri::command]
+pub fn set_now_playing(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    track: ManualTrack,
+    duration_secs: Option<u64>,
+) -> Result<(), String> {
+    let title = track.title.trim();
+    if title.is_empty() {
+        return Err("Title is required".into());
+    }
+    let until = match duration_secs {
+        Some(d) => Some(
+            Instant::now()
+                .checked_add(Duration::from_secs(d))
+                .ok_or("Duration is too long; leave it out to keep the track until cleared")?,
+        ),
+        None => None,
+    };
+    let np = NowPlaying {
+        is_playing: track.is_playing,
+        track_name: Some(title.to_string()),
+        artists: track.artists,
+        album: track.album.filter(|a| !a.trim().is_empty()),
+        artwork_url: track.artwork_url.filter(|u| !u.trim().is_empty()),
+        artwork_path: track.artwork_path.filter(|p| !p.trim().is_empty()),
+        source: Some(Provider::Manual),
+        ..Default::default()
+    };
+
+    // the watcher picks it up right away, so it is finished and settled like any other source
+    // (filters, `track_changed`, history, exports)
+    {
+        let mut s = state.lock();
+        s.manual_override = Some(ManualOverride {
+            now_playing: np,
+            until,
+        });
+        s.watcher_wake.notify_one();
+    }
+    start_watcher_if_needed(window.app_handle(), &state);
+    Ok(())
+}
+
+// Hands control back to the automatic sources, polled right away
+#[tauri::command]
+pub fn clear_now_playing(state: State<'_, SharedStore>) {
+    let mut s = state.lock();
+    s.manual_override = None;
+    s.watcher_wake.notify_one();
+}
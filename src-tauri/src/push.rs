@@ -0,0 +1,193 @@
+// Push channels for browser overlays, so nothing has to poll /nowplaying. Both get every
+// `now_playing_update` and `track_changed` as it is emitted, starting with a
+// `now_playing_update` of the current state right after connecting:
+//
+//   GET /ws       WebSocket; each message is `{"event": "...", "payload": {...}}`. The server
+//                 pings every `PING_EVERY` and drops clients that stop answering. Browsers
+//                 can't send ping frames themselves, so a text "ping" is answered with "pong".
+//   GET /events   Server-Sent Events (`event:` + one-line JSON `data:`), with a comment line
+//                 every `PING_EVERY` to keep proxies from closing it
+//
+// `server` reads the request and hands the connection over.
+
+use crate::{NowPlaying, SharedStore};
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tauri::Manager;
+use tokio::{io::AsyncWriteExt, net::TcpStream, sync::broadcast};
+use tokio_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+
+const PING_EVERY: Duration = Duration::from_secs(15);
+
+const EVENTS: &[&str] = &["now_playing_update", "track_changed"];
+
+#[derive(Clone)]
+struct Pushed {
+    event: &'static str,
+    // serialized once for every client
+    payload: Arc<str>,
+}
+
+impl Pushed {
+    fn new<S: Serialize>(event: &'static str, payload: &S) -> Option<Self> {
+        let payload = serde_json::to_string(payload).ok()?;
+        Some(Self {
+            event,
+            payload: payload.into(),
+        })
+    }
+
+    fn websocket(&self) -> Message {
+        Message::text(format!(
+            r#"{{"event":"{}","payload":{}}}"#,
+            self.event, self.payload
+        ))
+    }
+
+    fn sse(&self) -> String {
+        format!("event: {}\ndata: {}\n\n", self.event, self.payload)
+    }
+}
+
+// slow clients skip to the newest
+static UPDATES: Lazy<broadcast::Sender<Pushed>> = Lazy::new(|| broadcast::channel(16).0);
+
+// Called by `events` for everything it delivers to the windows
+pub fn publish<S: Serialize>(event: &str, payload: &S) {
+    if UPDATES.receiver_count() == 0 {
+        return;
+    }
+    let Some(event) = EVENTS.iter().find(|e| **e == event) else {
+        return;
+    };
+    if let Some(pushed) = Pushed::new(event, payload) {
+        let _ = UPDATES.send(pushed);
+    }
+}
+
+// Subscribed before the snapshot is taken, so an update in between isn't lost
+fn subscribe(app: &tauri::AppHandle) -> (broadcast::Receiver<Pushed>, Option<Pushed>) {
+    let updates = UPDATES.subscribe();
+    let np: NowPlaying = app
+        .state::<SharedStore>()
+        .lock()
+        .last_now_playing
+        .clone()
+        .unwrap_or_default();
+    (updates, Pushed::new("now_playing_update", &np))
+}
+
+// None once the channel is gone
+async fn next(updates: &mut broadcast::Receiver<Pushed>) -> Option<Pushed> {
+    loop {
+        match updates.recv().await {
+            Ok(pushed) => return Some(pushed),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+async fn write_raw(stream: &mut TcpStream, text: &str) -> Result<(), String> {
+    stream
+        .write_all(text.as_bytes())
+        .await
+        .map_err(|e| format!("write: {e}"))
+}
+
+// Finishes the upgrade, then streams until the client leaves
+pub async fn serve_websocket(
+    app: &tauri::AppHandle,
+    mut stream: TcpStream,
+    key: &str,
+) -> Result<(), String> {
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    write_raw(&mut stream, &head).await?;
+    let mut ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+
+    let (mut updates, snapshot) = subscribe(app);
+    if let Some(snapshot) = snapshot {
+        ws.send(snapshot.websocket())
+            .await
+            .map_err(|e| format!("send: {e}"))?;
+    }
+
+    let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + PING_EVERY, PING_EVERY);
+    let mut last_heard = Instant::now();
+    loop {
+        tokio::select! {
+            pushed = next(&mut updates) => {
+                let Some(pushed) = pushed else {
+                    return Ok(());
+                };
+                ws.send(pushed.websocket()).await.map_err(|e| format!("send: {e}"))?;
+            }
+            msg = ws.next() => {
+                let msg = match msg {
+                    Some(msg) => msg.map_err(|e| format!("read: {e}"))?,
+                    None => return Ok(()),
+                };
+                last_heard = Instant::now();
+                if msg.is_close() {
+                    return Ok(());
+                }
+                if msg.to_text().is_ok_and(|t| t.trim().eq_ignore_ascii_case("ping")) {
+                    ws.send(Message::text("pong"))
+                        .await
+                        .map_err(|e| format!("send: {e}"))?;
+                }
+            }
+            _ = ping.tick() => {
+                if last_heard.elapsed() > PING_EVERY * 2 {
+                    return Err("client stopped answering pings".into());
+                }
+                ws.send(Message::Ping(Default::default()))
+                    .await
+                    .map_err(|e| format!("send: {e}"))?;
+            }
+        }
+    }
+}
+
+// Streams until a write fails, which is how a closed EventSource shows up
+pub async fn serve_events(app: &tauri::AppHandle, mut stream: TcpStream) -> Result<(), String> {
+    // no Content-Length: the body runs until either side closes
+    write_raw(
+        &mut stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: keep-alive\r\n\r\nretry: 3000\n\n",
+    )
+    .await?;
+
+    let (mut updates, snapshot) = subscribe(app);
+    if let Some(snapshot) = snapshot {
+        write_raw(&mut stream, &snapshot.sse()).await?;
+    }
+
+    let mut keep_alive =
+        tokio::time::interval_at(tokio::time::Instant::now() + PING_EVERY, PING_EVERY);
+    loop {
+        let chunk = tokio::select! {
+            pushed = next(&mut updates) => match pushed {
+                Some(pushed) => pushed.sse(),
+                None => return Ok(()),
+            },
+            _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+        };
+        if write_raw(&mut stream, &chunk).await.is_err() {
+            return Ok(());
+        }
+    }
+}
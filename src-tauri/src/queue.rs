@@ -0,0 +1,102 @@
+// The Spotify queue: `get_queue` for a full "coming up" list, and `next_track` on the polled
+// payload for overlays that show "Up next: ...".
+
+use crate::{pick_image_url, spotify_client, NowPlaying, SharedCaches, SharedStore};
+use rspotify::{
+    clients::OAuthClient,
+    model::{CurrentlyPlayingContext, PlayableItem},
+    AuthCodePkceSpotify,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+// Songs queued from the Spotify app show up after this long
+const RECHECK_AFTER: Duration = Duration::from_secs(20);
+const DEFAULT_LIMIT: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct QueueItem {
+    track_name: String,
+    artists: Vec<String>,
+    album: Option<String>,
+    artwork_url: Option<String>,
+}
+
+pub fn queue_item(item: &PlayableItem) -> QueueItem {
+    match item {
+        PlayableItem::Track(track) => QueueItem {
+            track_name: track.name.clone(),
+            artists: track.artists.iter().map(|a| a.name.clone()).collect(),
+            album: Some(track.album.name.clone()),
+            artwork_url: pick_image_url(&track.album.images, 300),
+        },
+        PlayableItem::Episode(ep) => QueueItem {
+            track_name: ep.name.clone(),
+            artists: vec![ep.show.publisher.clone()],
+            album: Some(ep.show.name.clone()),
+            artwork_url: pick_image_url(&ep.images, 300),
+        },
+    }
+}
+
+fn item_key(ctx: &CurrentlyPlayingContext) -> Option<String> {
+    match ctx.item.as_ref()? {
+        PlayableItem::Track(t) => Some(format!("{}|{}", t.name, t.album.name)),
+        PlayableItem::Episode(ep) => Some(ep.id.to_string()),
+    }
+}
+
+// Sets `np.next_track`. The queue is fetched once per track (and every `RECHECK_AFTER`), not on
+// every poll. Without Premium the endpoint fails and the field stays empty.
+pub async fn enrich(
+    app: &tauri::AppHandle,
+    client: &AuthCodePkceSpotify,
+    ctx: &CurrentlyPlayingContext,
+    np: &mut NowPlaying,
+) {
+    let Some(key) = item_key(ctx) else {
+        return;
+    };
+
+    let caches = app.state::<SharedCaches>();
+    let cached = caches
+        .lock()
+        .next_track
+        .as_ref()
+        .filter(|(cached_key, _, at)| *cached_key == key && at.elapsed() < RECHECK_AFTER)
+        .map(|(_, next, _)| next.clone());
+    np.next_track = match cached {
+        Some(next) => next,
+        None => {
+            let next = match client.current_user_queue().await {
+                Ok(q) => q.queue.first().map(queue_item),
+                Err(e) => {
+                    eprintln!("[queue] {e}");
+                    None
+                }
+            };
+            caches.lock().next_track = Some((key, next.clone(), Instant::now()));
+            next
+        }
+    };
+}
+
+// Up to `limit` upcoming items (10 by default; Spotify returns at most 20)
+#[tauri::command]
+pub async fn get_queue(
+    state: State<'_, SharedStore>,
+    limit: Option<usize>,
+) -> Result<Vec<QueueItem>, String> {
+    let client = spotify_client(&state)?;
+    let q = client
+        .current_user_queue()
+        .await
+        .map_err(|e| format!("queue: {e}"))?;
+    Ok(q.queue
+        .iter()
+        .take(limit.unwrap_or(DEFAULT_LIMIT))
+        .map(queue_item)
+        .collect())
+}
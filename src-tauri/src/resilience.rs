@@ -0,0 +1,230 @@
+// How hard each integration tries again after losing its connection or failing to bind its
+// port. A stream that drops should be reconnected for as long as the app runs; a port taken by
+// another program is worth a few tries, not an endless loop. Defaults live in `DEFAULTS`;
+// `set_retry_policy` overrides them per integration under the "retry_policies" setting.
+
+use crate::{read_settings, write_setting, SharedStore};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+use tauri::{Manager, State};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, JsonSchema)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // attempts after the first one; None keeps going forever
+    pub max_retries: Option<u32>,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    // each wait is the previous one times this, up to `max_delay_ms`
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: Some(5),
+            initial_delay_ms: 1000,
+            max_delay_ms: 30_000,
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    const fn forever(initial_delay_ms: u64, max_delay_ms: u64) -> Self {
+        Self {
+            max_retries: None,
+            initial_delay_ms,
+            max_delay_ms,
+            multiplier: 2.0,
+        }
+    }
+
+    const fn limited(max_retries: u32, delay_ms: u64) -> Self {
+        Self {
+            max_retries: Some(max_retries),
+            initial_delay_ms: delay_ms,
+            max_delay_ms: delay_ms * 8,
+            multiplier: 2.0,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.initial_delay_ms == 0 {
+            return Err("initial_delay_ms must be at least 1".into());
+        }
+        if self.max_delay_ms < self.initial_delay_ms {
+            return Err("max_delay_ms can't be below initial_delay_ms".into());
+        }
+        if !(1.0..=10.0).contains(&self.multiplier) {
+            return Err("multiplier must be between 1 and 10".into());
+        }
+        Ok(())
+    }
+}
+
+// Every integration with a policy, and what it does out of the box
+const DEFAULTS: &[(&str, RetryPolicy)] = &[
+    // radio streams drop and come back; keep listening
+    ("icecast", RetryPolicy::forever(5_000, 60_000)),
+    // OBS comes and goes with the streamer's session
+    ("obs_websocket", RetryPolicy::forever(2_000, 30_000)),
+    // likewise the Discord client
+    ("discord", RetryPolicy::forever(5_000, 60_000)),
+    // local ports: usually free again after a restart of the other program
+    ("traktor", RetryPolicy::limited(5, 2_000)),
+    ("osc", RetryPolicy::limited(5, 2_000)),
+    ("webnowplaying", RetryPolicy::limited(5, 2_000)),
+    ("http_server", RetryPolicy::limited(5, 2_000)),
+];
+
+pub fn load_overrides(app: &tauri::AppHandle) -> BTreeMap<String, RetryPolicy> {
+    read_settings(app)
+        .get("retry_policies")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn policy(app: &tauri::AppHandle, integration: &str) -> RetryPolicy {
+    let state = app.state::<SharedStore>();
+    let overridden = state.lock().retry_policies.get(integration).copied();
+    overridden
+        .or_else(|| {
+            DEFAULTS
+                .iter()
+                .find(|(name, _)| *name == integration)
+                .map(|(_, p)| *p)
+        })
+        .unwrap_or_default()
+}
+
+// Counts attempts against a policy. `reset` after a connection that worked, so the next
+// outage starts over from the short delay.
+pub struct Retry {
+    policy: RetryPolicy,
+    retries: u32,
+}
+
+impl Retry {
+    pub fn new(app: &tauri::AppHandle, integration: &str) -> Self {
+        Self {
+            policy: policy(app, integration),
+            retries: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.retries = 0;
+    }
+
+    // Wait before the next attempt, or None once the policy has run out
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self
+            .policy
+            .max_retries
+            .is_some_and(|max| self.retries >= max)
+        {
+            return None;
+        }
+        let factor = self.policy.multiplier.powi(self.retries.min(32) as i32);
+        let ms = (self.policy.initial_delay_ms as f64 * factor).min(self.policy.max_delay_ms as f64)
+            as u64;
+        self.retries += 1;
+        Some(Duration::from_millis(ms))
+    }
+
+    // Sleeps out the next delay; false when it's time to give up (or `cancel` fired)
+    pub async fn wait(&mut self, cancel: &CancellationToken) -> bool {
+        let Some(delay) = self.next_delay() else {
+            return false;
+        };
+        tokio::select! {
+            _ = cancel.cancelled() => false,
+            _ = tokio::time::sleep(delay) => true,
+        }
+    }
+}
+
+// Runs `attempt` until it succeeds or `integration`'s policy gives up, logging each failure
+pub async fn retry<T, E, F, Fut>(
+    app: &tauri::AppHandle,
+    integration: &str,
+    cancel: &CancellationToken,
+    mut attempt: F,
+) -> Option<T>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retry = Retry::new(app, integration);
+    loop {
+        match attempt().await {
+            Ok(v) => return Some(v),
+            Err(e) => eprintln!("[{integration}] {e}"),
+        }
+        if !retry.wait(cancel).await {
+            if !cancel.is_cancelled() {
+                eprintln!("[{integration}] giving up");
+            }
+            return None;
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct IntegrationPolicy {
+    pub policy: RetryPolicy,
+    // false when the default is in effect
+    pub custom: bool,
+}
+
+#[tauri::command]
+pub fn get_retry_policies(state: State<'_, SharedStore>) -> BTreeMap<String, IntegrationPolicy> {
+    let s = state.lock();
+    DEFAULTS
+        .iter()
+        .map(|(name, default)| {
+            let custom = s.retry_policies.get(*name);
+            let entry = IntegrationPolicy {
+                policy: custom.copied().unwrap_or(*default),
+                custom: custom.is_some(),
+            };
+            (name.to_string(), entry)
+        })
+        .collect()
+}
+
+// `policy: None` goes back to the default. Takes effect the next time the integration
+// (re)starts.
+#[tauri::command]
+pub fn set_retry_policy(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    integration: String,
+    policy: Option<RetryPolicy>,
+) -> Result<(), String> {
+    if !DEFAULTS.iter().any(|(name, _)| *name == integration) {
+        return Err(format!("No retry policy for {integration:?}"));
+    }
+    if let Some(p) = &policy {
+        p.validate()?;
+    }
+    let mut overrides = state.lock().retry_policies.clone();
+    match policy {
+        Some(p) => overrides.insert(integration, p),
+        None => overrides.remove(&integration),
+    };
+    write_setting(
+        window.app_handle(),
+        "retry_policies",
+        serde_json::json!(overrides),
+    )?;
+    state.lock().retry_policies = overrides;
+    Ok(())
+}
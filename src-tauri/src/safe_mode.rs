@@ -0,0 +1,72 @@
+// Safe mode: a run that ends without going through the normal exit path leaves a marker file
+// behind. The next start then skips everything that touches the outside world (the watcher,
+// local art index, WebNowPlaying server, GSMTC events and the push integrations) so a bad
+// setting that hangs or crashes one of them can still be fixed from the settings UI.
+
+use crate::SharedStore;
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{Manager, State};
+
+const MARKER: &str = "running.flag";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    // the previous run didn't exit cleanly
+    Crash,
+    // started with --safe-mode
+    Requested,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct SafeModeStatus {
+    active: bool,
+    reason: Option<Reason>,
+}
+
+fn marker_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    crate::portable::data_dir(app).ok().map(|d| d.join(MARKER))
+}
+
+// Decides whether this run is a safe-mode one, and marks the run as in progress
+pub fn enter(app: &tauri::AppHandle) -> Option<Reason> {
+    let marker = marker_path(app)?;
+    let reason = if std::env::args().any(|a| a == "--safe-mode") {
+        Some(Reason::Requested)
+    } else if marker.exists() {
+        Some(Reason::Crash)
+    } else {
+        None
+    };
+    if let Some(dir) = marker.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = std::fs::write(&marker, b"") {
+        eprintln!("[safe-mode] write {}: {e}", marker.display());
+    }
+    reason
+}
+
+pub fn mark_clean_exit(app: &tauri::AppHandle) {
+    if let Some(marker) = marker_path(app) {
+        let _ = std::fs::remove_file(marker);
+    }
+}
+
+#[tauri::command]
+pub fn get_safe_mode(state: State<'_, SharedStore>) -> SafeModeStatus {
+    let reason = state.lock().safe_mode;
+    SafeModeStatus {
+        active: reason.is_some(),
+        reason,
+    }
+}
+
+// Once the configuration is fixed: restart with everything enabled
+#[tauri::command]
+pub fn restart_normally(window: tauri::Window) {
+    let app = window.app_handle();
+    mark_clean_exit(app);
+    app.restart();
+}
@@ -0,0 +1,112 @@
+// "Liked Songs" state of the current Spotify track, for overlays that show a heart, and
+// `toggle_save_track` so streamers can like a song without leaving the stream.
+
+use crate::{spotify_client, NowPlaying, SharedCaches, SharedStore};
+use rspotify::{
+    clients::{BaseClient, OAuthClient},
+    model::{CurrentlyPlayingContext, Id, PlayableItem, TrackId},
+    AuthCodePkceSpotify,
+};
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+// Likes made in the Spotify app itself show up after this long
+const RECHECK_AFTER: Duration = Duration::from_secs(30);
+
+fn track_id(ctx: &CurrentlyPlayingContext) -> Option<TrackId<'static>> {
+    match &ctx.item {
+        // local files have no id and can't be saved
+        Some(PlayableItem::Track(t)) => t.id.clone(),
+        _ => None,
+    }
+}
+
+// Tokens from before the library scopes were requested can't read Liked Songs; they're
+// skipped until `reauthorize_with_scopes` instead of failing a request on every track
+async fn can_read_library(client: &AuthCodePkceSpotify) -> bool {
+    match client.get_token().lock().await {
+        Ok(token) => token
+            .as_ref()
+            .is_some_and(|t| t.scopes.contains("user-library-read")),
+        Err(_) => false,
+    }
+}
+
+// Sets `np.is_saved`. Only the current track is remembered, so this is one request per track
+// change (plus one every `RECHECK_AFTER`) rather than per poll.
+pub async fn enrich(
+    app: &tauri::AppHandle,
+    client: &AuthCodePkceSpotify,
+    ctx: &CurrentlyPlayingContext,
+    np: &mut NowPlaying,
+) {
+    let Some(id) = track_id(ctx) else {
+        return;
+    };
+
+    let caches = app.state::<SharedCaches>();
+    let cached = caches
+        .lock()
+        .saved
+        .as_ref()
+        .filter(|(cached_id, _, at)| cached_id == id.id() && at.elapsed() < RECHECK_AFTER)
+        .map(|(_, saved, _)| *saved);
+    let saved = match cached {
+        Some(saved) => saved,
+        None if !can_read_library(client).await => return,
+        None => match client
+            .current_user_saved_tracks_contains([id.clone()])
+            .await
+        {
+            Ok(res) => {
+                let saved = res.first().copied().unwrap_or(false);
+                caches.lock().saved = Some((id.id().to_string(), saved, Instant::now()));
+                saved
+            }
+            Err(e) => {
+                eprintln!("[saved] {e}");
+                return;
+            }
+        },
+    };
+    np.is_saved = Some(saved);
+}
+
+// Likes the current track, or removes it from Liked Songs if it's already there. Returns the
+// new state.
+#[tauri::command]
+pub async fn toggle_save_track(
+    state: State<'_, SharedStore>,
+    caches: State<'_, SharedCaches>,
+) -> Result<bool, String> {
+    let client = spotify_client(&state)?;
+
+    let ctx = client
+        .current_user_playing_item()
+        .await
+        .map_err(|e| format!("currently playing: {e}"))?
+        .ok_or_else(|| "Nothing is playing".to_string())?;
+    let id = track_id(&ctx).ok_or_else(|| "This item can't be saved".to_string())?;
+
+    let saved = client
+        .current_user_saved_tracks_contains([id.clone()])
+        .await
+        .map_err(|e| format!("saved check: {e}"))?
+        .first()
+        .copied()
+        .unwrap_or(false);
+    if saved {
+        client
+            .current_user_saved_tracks_delete([id.clone()])
+            .await
+            .map_err(|e| format!("remove saved track: {e}"))?;
+    } else {
+        client
+            .current_user_saved_tracks_add([id.clone()])
+            .await
+            .map_err(|e| format!("save track: {e}"))?;
+    }
+
+    caches.lock().saved = Some((id.id().to_string(), !saved, Instant::now()));
+    Ok(!saved)
+}
@@ -0,0 +1,296 @@
+// JSON Schema of the payloads overlays and tools can consume, served as `GET /schema` by
+// `server`. Every entry is the type that is actually emitted or returned, and their `///`
+// docs end up as descriptions. `commands` has every command `invoke_handler` registers (the
+// test below fails when one is missing) and `rest` the JSON answers of `server`. Bump `VERSION` whenever a field is renamed or removed;
+// additions don't count.
+
+use crate::{
+    accessibility::AccessibilityPrefs,
+    art_dedupe::DedupeReport,
+    auth_status::{AuthStatus, ScopesMissing},
+    backoff::WatcherStatus,
+    benchmark::BenchmarkReport,
+    capabilities::{ApiVersion, Capabilities},
+    charts::{ArtistTimeline, HourBucket, TopGenres},
+    companion::CompanionConfig,
+    discord::DiscordConfig,
+    events::{EmitPolicy, UpdateMode},
+    export::{ExportFallback, ExportIssue, ExportPreview, ExportProfile},
+    family::FamilyFriendly,
+    gpu::GpuStatus,
+    history::{HistorySummary, ImportSummary},
+    layouts::LayoutConfig,
+    librespot::ReceiverStatus,
+    normalizers::Normalizers,
+    oauth_callback::RedirectInfo,
+    obs::ObsConfig,
+    osc::OscConfig,
+    paste_auth::PasteAuth,
+    placeholder::Placeholder,
+    playback::PlaybackDevice,
+    playback_change::SessionChange,
+    playlists::UserPlaylist,
+    portable::PortableMode,
+    progress::PlaybackProgress,
+    providers::{PlayersUpdate, ProviderChain, SourceChanged},
+    queue::QueueItem,
+    resilience::IntegrationPolicy,
+    safe_mode::SafeModeStatus,
+    server::{Exported, Health, PlaceholderView},
+    settings::{AppSettings, SettingsChanged},
+    spotify_search::SearchConfigView,
+    streaks::{StreakConfig, Streaks},
+    traktor::TraktorConfig,
+    trivia::TriviaConfig,
+    tts::TtsConfig,
+    updater::{self, Channel, UpdateInfo},
+    usage::UsageStats,
+    watchdog::WatcherRestarted,
+    webnowplaying::WnpConfig,
+    AlbumTracks, Anniversary, FullState, NowPlaying, TrackRestarted,
+};
+use schemars::{schema_for, Schema};
+use std::collections::{BTreeMap, HashMap};
+
+pub const VERSION: u32 = 1;
+
+// name -> schema; `json!` recurses once per entry and would hit the recursion limit here
+macro_rules! schemas {
+    ($($name:literal => $ty:ty),* $(,)?) => {
+        BTreeMap::<&str, Schema>::from([$(($name, schema_for!($ty))),*])
+    };
+}
+
+pub fn document() -> serde_json::Value {
+    let events = schemas! {
+        "now_playing_update" => NowPlaying,
+        // a different track started; the `now_playing_update` for it follows
+        "track_changed" => NowPlaying,
+        "gsmtc_update" => NowPlaying,
+        "players_update" => PlayersUpdate,
+        "source_changed" => SourceChanged,
+        "track_restarted" => TrackRestarted,
+        "anniversary" => Anniversary,
+        "playback_progress" => PlaybackProgress,
+        "gsmtc_track_changed" => SessionChange,
+        "gsmtc_seeked" => SessionChange,
+        "gsmtc_status_changed" => SessionChange,
+        "auth_lost" => (),
+        // a profile's directory refused the export; the files went to `dir` instead
+        "export_fallback" => ExportFallback,
+        "update_progress" => updater::Progress,
+        "update_downloaded" => (),
+        "accessibility_changed" => AccessibilityPrefs,
+        "watcher_status" => WatcherStatus,
+        "redirect_uri_changed" => RedirectInfo,
+        "watcher_restarted" => WatcherRestarted,
+        "scopes_missing" => ScopesMissing,
+        "settings_changed" => SettingsChanged,
+    };
+
+    // command name -> what it resolves to; errors are strings unless noted
+    #[allow(unused_mut)]
+    let mut commands = schemas! {
+        "connect_spotify" => (),
+        "restore_spotify" => bool,
+        "get_current_playing" => NowPlaying,
+        "set_local_art_dir" => (),
+        "get_local_art_dir" => Option<String>,
+        "write_now_playing_assets" => String,
+        "preview_export" => ExportPreview,
+        "export_artwork_only" => String,
+        "dedupe_artcache" => DedupeReport,
+        "get_artwork_variant" => String,
+        "export_session_collage" => Option<String>,
+        "get_export_profiles" => Vec<ExportProfile>,
+        // rejects with the `validate_export_profiles` issues
+        "set_export_profiles" => (),
+        "validate_export_profiles" => Vec<ExportIssue>,
+        "get_overlay_placeholder" => Option<Placeholder>,
+        "set_overlay_placeholder" => (),
+        "get_librespot_status" => ReceiverStatus,
+        "set_librespot_config" => (),
+        "get_icecast_url" => Option<String>,
+        "set_icecast_url" => (),
+        "get_traktor_config" => TraktorConfig,
+        "set_traktor_config" => (),
+        "get_obs_config" => ObsConfig,
+        "set_obs_config" => (),
+        "get_discord_config" => DiscordConfig,
+        "set_discord_config" => (),
+        "get_layout_config" => LayoutConfig,
+        "set_layout_config" => (),
+        "get_gpu_status" => GpuStatus,
+        "set_gpu_images" => (),
+        "get_redirect_config" => RedirectInfo,
+        "set_redirect_config" => RedirectInfo,
+        "get_spotify_client_id" => Option<String>,
+        "set_spotify_client_id" => (),
+        "get_retry_policies" => BTreeMap<String, IntegrationPolicy>,
+        "logout" => (),
+        "get_auth_status" => AuthStatus,
+        "reauthorize_with_scopes" => (),
+        "import_spotify_history" => ImportSummary,
+        "import_lastfm_history" => ImportSummary,
+        "get_history_summary" => HistorySummary,
+        "get_listening_by_hour" => Vec<HourBucket>,
+        "get_top_genres" => TopGenres,
+        "get_artist_timeline" => ArtistTimeline,
+        "get_streaks" => Streaks,
+        "get_streak_config" => StreakConfig,
+        "set_streak_config" => (),
+        "get_http_port" => u16,
+        "set_http_port" => (),
+        "get_settings" => AppSettings,
+        "update_settings" => AppSettings,
+        "export_settings" => (),
+        "import_settings" => Vec<String>,
+        "get_portable_mode" => PortableMode,
+        "set_portable_mode" => PortableMode,
+        "set_retry_policy" => (),
+        "get_dj_history_path" => Option<String>,
+        "set_dj_history_path" => (),
+        "get_osc_config" => OscConfig,
+        "set_osc_config" => (),
+        "get_provider_chain" => ProviderChain,
+        "set_provider_chain" => (),
+        "get_aggregate_mode" => bool,
+        "set_aggregate_mode" => (),
+        "set_now_playing" => (),
+        "clear_now_playing" => (),
+        "toggle_save_track" => bool,
+        "list_user_playlists" => Vec<UserPlaylist>,
+        "add_current_to_playlist" => (),
+        "get_queue" => Vec<QueueItem>,
+        "list_devices" => Vec<PlaybackDevice>,
+        "transfer_playback" => (),
+        "set_shuffle" => (),
+        "cycle_repeat" => String,
+        "get_trivia_config" => TriviaConfig,
+        "set_trivia_config" => (),
+        "get_tts_config" => TtsConfig,
+        "set_tts_config" => (),
+        "list_tts_voices" => Vec<String>,
+        "test_tts" => (),
+        "get_accessibility_prefs" => AccessibilityPrefs,
+        "get_companion_config" => CompanionConfig,
+        "set_companion_config" => (),
+        "get_update_mode" => UpdateMode,
+        "set_update_mode" => (),
+        "set_poll_interval" => u64,
+        "pause_watcher" => (),
+        "resume_watcher" => (),
+        "stop_watcher" => (),
+        "cancel_auth" => (),
+        "start_paste_auth" => PasteAuth,
+        "submit_auth_code" => (),
+        "restart_watcher" => bool,
+        "get_family_friendly" => FamilyFriendly,
+        "set_family_friendly" => (),
+        "get_metadata_normalizers" => Normalizers,
+        "set_metadata_normalizers" => (),
+        "get_full_state" => FullState,
+        "get_album_tracks" => AlbumTracks,
+        "get_safe_mode" => SafeModeStatus,
+        "get_api_version" => ApiVersion,
+        "get_capabilities" => Capabilities,
+        "restart_normally" => (),
+        "get_update_channel" => Channel,
+        "set_update_channel" => (),
+        "check_for_updates" => Option<UpdateInfo>,
+        "install_update" => (),
+        "get_catalog_search" => SearchConfigView,
+        "set_catalog_search" => (),
+        "get_usage_stats" => UsageStats,
+        "reset_usage_stats" => (),
+        "get_webnowplaying_config" => WnpConfig,
+        "set_webnowplaying_config" => (),
+        "run_benchmark" => BenchmarkReport,
+        "subscribe_events" => (),
+        "unsubscribe_events" => (),
+        "get_emit_policies" => HashMap<String, EmitPolicy>,
+        "set_emit_policy" => (),
+    };
+    #[cfg(windows)]
+    commands.extend(schemas! {
+        "get_current_playing_gsmtc" => NowPlaying,
+        "get_gsmtc_poll_interval" => u64,
+        "set_gsmtc_poll_interval" => u64,
+        "list_media_sessions" => Vec<crate::gsmtc::MediaSession>,
+        "select_media_session" => (),
+        "get_gsmtc_priority" => Vec<String>,
+        "set_gsmtc_priority" => (),
+        "media_play_pause" => (),
+        "media_next" => (),
+        "media_previous" => (),
+        "media_seek" => (),
+    });
+
+    // `server` route -> its JSON body
+    let mut rest = schemas! {
+        "GET /health" => Health,
+        "GET /capabilities" => Capabilities,
+        "GET /placeholder" => Option<PlaceholderView>,
+        "GET /files" => Vec<String>,
+        "POST /export" => Exported,
+    };
+    let mut now_playing = schema_for!(NowPlaying);
+    // `/nowplaying` leaves the local path out
+    if let Some(props) = now_playing
+        .get_mut("properties")
+        .and_then(|p| p.as_object_mut())
+    {
+        props.remove("artwork_path");
+    }
+    rest.insert("GET /nowplaying", now_playing);
+
+    serde_json::json!({
+        "version": VERSION,
+        "events": events,
+        "commands": commands,
+        "rest": rest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the names between `start` and `end` in `invoke_handler`'s command list
+    fn registered(start: &str, end: &str) -> Vec<&'static str> {
+        let lib = include_str!("lib.rs");
+        let (_, list) = lib.split_once(start).expect("start of the command list");
+        let (list, _) = list.split_once(end).expect("end of the command list");
+        list.split(',')
+            .filter_map(|n| n.trim().rsplit("::").next())
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn every_registered_command_is_documented() {
+        let doc = document();
+        let commands = doc["commands"].as_object().unwrap();
+        let mut names = registered("tauri::generate_handler![", "$($extra)*");
+        if cfg!(windows) {
+            names.extend(registered("    app_commands![\n", "]"));
+        }
+        assert!(names.len() > 100, "command list not found: {names:?}");
+        let missing: Vec<_> = names
+            .into_iter()
+            .filter(|n| !commands.contains_key(*n))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "commands missing from the schema: {missing:?}"
+        );
+    }
+
+    #[test]
+    fn nowplaying_route_has_no_local_path() {
+        let doc = document();
+        let props = &doc["rest"]["GET /nowplaying"]["properties"];
+        assert!(props.get("track_name").is_some());
+        assert!(props.get("artwork_path").is_none());
+    }
+}
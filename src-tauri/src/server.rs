@@ -0,0 +1,567 @@
+// Local HTTP server for overlay authors, Stream Deck plugins and scripts, bound to 127.0.0.1
+// only, on `DEFAULT_PORT` unless `http_port` says otherwise.
+//
+//   GET /nowplaying     the current `now_playing_update` payload, minus the local `artwork_path`
+//   GET /artwork        the current cover, as rendered (PNG) for the exports
+//   GET /health         whether the app is up, signed in, and which source is live
+//   GET /ws             WebSocket pushing `now_playing_update` / `track_changed` (see `push`)
+//   GET /events         the same as Server-Sent Events
+//   GET /overlay        ready-made OBS browser source (see `overlay`)
+//   GET /placeholder    the overlay placeholder, `null` when off; its image at /placeholder/artwork
+//   GET /schema         JSON Schema of the event, command and REST payloads (see `schema`)
+//   GET /capabilities   API version and the subsystems and sources available (see `capabilities`)
+//   GET /files          names of the export files below, as a JSON array
+//   GET /files/{name}   one file of the first enabled export profile (or of the export without
+//                       profiles), served from memory (see `virtual_files`);
+//                       `/files/{profile}/{name}` for any other profile
+//   POST /export        exports the current track to the manual export profiles; needs the
+//                       `ACTION_HEADER`, which a web page can't send here (see `handle`)
+
+use crate::{
+    capabilities, export, overlay, providers::Provider, push, read_settings, resilience, schema,
+    virtual_files, write_setting, SharedStore,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{Manager, State};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tokio_util::sync::CancellationToken;
+
+pub const DEFAULT_PORT: u16 = 8975;
+
+// required on requests that change something
+pub const ACTION_HEADER: &str = "X-Now-Playing-Action";
+
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// stops the running server when it moves to another port
+static RUNNING: Lazy<Mutex<Option<CancellationToken>>> = Lazy::new(Mutex::default);
+
+pub fn load_port(app: &tauri::AppHandle) -> u16 {
+    read_settings(app)
+        .get("http_port")
+        .and_then(|v| v.as_u64())
+        .and_then(|p| u16::try_from(p).ok())
+        .filter(|p| *p != 0)
+        .unwrap_or(DEFAULT_PORT)
+}
+
+// (Re)starts the server on `port`; one already running is stopped first
+pub fn start(app: tauri::AppHandle, port: u16) {
+    let cancel = CancellationToken::new();
+    if let Some(old) = RUNNING.lock().replace(cancel.clone()) {
+        old.cancel();
+    }
+    tauri::async_runtime::spawn(async move {
+        let bind = || async {
+            TcpListener::bind(("127.0.0.1", port))
+                .await
+                .map_err(|e| format!("bind 127.0.0.1:{port}: {e}"))
+        };
+        let Some(listener) = resilience::retry(&app, "http_server", &cancel, bind).await else {
+            return;
+        };
+        loop {
+            let stream = tokio::select! {
+                _ = cancel.cancelled() => return,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(_) => continue,
+                },
+            };
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = handle(&app, stream).await {
+                    eprintln!("[server] {e}");
+                }
+            });
+        }
+    });
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    // Sec-WebSocket-Key of an upgrade request
+    pub websocket_key: Option<String>,
+    // carries `ACTION_HEADER`
+    pub action: bool,
+    pub authorization: Option<String>,
+    // browsers send `Origin` with every cross-origin request, so this came from a web page
+    pub origin: bool,
+    // the start of the body, read along with the head
+    pub rest: Vec<u8>,
+}
+
+fn header<'a>(headers: &[httparse::Header<'a>], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .and_then(|h| std::str::from_utf8(h.value).ok())
+}
+
+// Reads until the head is complete; whatever of the body came along is left in `rest`
+pub async fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = timeout(READ_TIMEOUT, stream.read(&mut chunk))
+            .await
+            .map_err(|_| "request timed out".to_string())?
+            .map_err(|e| format!("read: {e}"))?;
+        if n == 0 {
+            return Err("connection closed mid-request".into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut req = httparse::Request::new(&mut headers);
+        match req.parse(&buf) {
+            Ok(httparse::Status::Complete(len)) => {
+                let upgrade = header(req.headers, "upgrade")
+                    .is_some_and(|u| u.trim().eq_ignore_ascii_case("websocket"));
+                return Ok(Request {
+                    method: req.method.unwrap_or_default().to_string(),
+                    path: req.path.unwrap_or_default().to_string(),
+                    websocket_key: header(req.headers, "sec-websocket-key")
+                        .filter(|_| upgrade)
+                        .map(|k| k.trim().to_string()),
+                    action: header(req.headers, ACTION_HEADER).is_some(),
+                    authorization: header(req.headers, "authorization").map(str::to_string),
+                    origin: header(req.headers, "origin").is_some(),
+                    rest: buf[len..].to_vec(),
+                });
+            }
+            Ok(httparse::Status::Partial) if buf.len() < MAX_REQUEST_BYTES => {}
+            Ok(httparse::Status::Partial) => return Err("request head too large".into()),
+            Err(e) => return Err(format!("parse request: {e}")),
+        }
+    }
+}
+
+pub struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+    // methods listed with a 405
+    allow: &'static str,
+    // sends `Access-Control-Allow-Origin: *`, so any web page can read it
+    cors: bool,
+}
+
+impl Response {
+    fn json(value: &serde_json::Value) -> Self {
+        Self {
+            status: "200 OK",
+            content_type: "application/json",
+            body: serde_json::to_vec_pretty(value).unwrap_or_default(),
+            allow: "GET, HEAD",
+            cors: true,
+        }
+    }
+
+    pub fn html(status: &'static str, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: body.as_bytes().to_vec(),
+            allow: "GET, HEAD",
+            cors: true,
+        }
+    }
+
+    fn bytes(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+            allow: "GET, HEAD",
+            cors: true,
+        }
+    }
+
+    pub fn text(status: &'static str, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.as_bytes().to_vec(),
+            allow: "GET, HEAD",
+            cors: true,
+        }
+    }
+
+    fn not_allowed(allow: &'static str) -> Self {
+        Self {
+            allow,
+            ..Self::text("405 Method Not Allowed", "method not allowed")
+        }
+    }
+
+    fn without_cors(self) -> Self {
+        Self {
+            cors: false,
+            ..self
+        }
+    }
+}
+
+fn image_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    match ext.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        _ => "image/jpeg",
+    }
+}
+
+fn now_playing(app: &tauri::AppHandle) -> Response {
+    let np = app
+        .state::<SharedStore>()
+        .lock()
+        .last_now_playing
+        .clone()
+        .unwrap_or_default();
+    // any page can read this; a local path would give away the user's name and folders
+    let mut body = serde_json::json!(np);
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("artwork_path");
+    }
+    Response::json(&body)
+}
+
+// The cover as rendered for the exports: decoded and re-encoded as PNG, so whatever path the
+// source handed us is never read and served as it is
+fn artwork() -> Response {
+    match virtual_files::cover() {
+        Some(body) => Response::bytes("image/png", body),
+        None => Response::text("404 Not Found", "no artwork"),
+    }
+}
+
+// `path` is `{name}` or `{profile}/{name}`, percent-encoded
+fn export_file(app: &tauri::AppHandle, path: &str) -> Response {
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let (profile, name) = match path.rsplit_once('/') {
+        Some((profile, name)) => (profile.to_string(), name),
+        None => {
+            let state = app.state::<SharedStore>();
+            let first = state
+                .lock()
+                .export_profiles
+                .iter()
+                .find(|p| p.enabled)
+                .map(|p| p.name.clone());
+            (first.unwrap_or_default(), path.as_ref())
+        }
+    };
+    match virtual_files::get(&profile, name) {
+        Some((content_type, body)) => Response::bytes(content_type, body),
+        None => Response::text("404 Not Found", "no such file, or nothing exported yet"),
+    }
+}
+
+// `GET /placeholder`: the local artwork path is swapped for the URL serving it
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct PlaceholderView {
+    pub text: String,
+    pub artwork: Option<&'static str>,
+    pub when_paused: bool,
+}
+
+// The overlay placeholder for the built-in overlay, `null` when it's off; its artwork is at
+// `/placeholder/artwork`
+fn placeholder(app: &tauri::AppHandle) -> Response {
+    let p = app
+        .state::<SharedStore>()
+        .lock()
+        .overlay_placeholder
+        .clone();
+    Response::json(&serde_json::json!(p.map(|p| PlaceholderView {
+        text: p.text,
+        artwork: p.artwork.map(|_| "/placeholder/artwork"),
+        when_paused: p.when_paused,
+    })))
+}
+
+fn placeholder_artwork(app: &tauri::AppHandle) -> Response {
+    let path = app
+        .state::<SharedStore>()
+        .lock()
+        .overlay_placeholder
+        .as_ref()
+        .and_then(|p| p.artwork.clone());
+    match path.map(|p| (std::fs::read(&p), p)) {
+        Some((Ok(body), path)) => Response::bytes(image_type(Path::new(&path)), body),
+        _ => Response::text("404 Not Found", "no placeholder artwork"),
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct Exported {
+    pub dir: String,
+}
+
+// Same as `write_now_playing_assets` with what's showing; JSON `{"dir": ...}` on success
+async fn export(app: &tauri::AppHandle) -> Response {
+    let np = app.state::<SharedStore>().lock().last_now_playing.clone();
+    let Some(np) = np.filter(|np| np.track_name.is_some()) else {
+        return Response::text("409 Conflict", "nothing playing");
+    };
+    match export::export_manual(app, &export::ExportPayload::from(&np)).await {
+        Ok(dir) => Response::json(&serde_json::json!(Exported { dir })),
+        Err(e) => Response::text("500 Internal Server Error", &e),
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct Health {
+    // always "ok"; a stuck app doesn't answer at all
+    pub status: &'static str,
+    pub version: &'static str,
+    pub signed_in: bool,
+    pub source: Option<Provider>,
+    pub safe_mode: bool,
+}
+
+fn health(app: &tauri::AppHandle) -> Response {
+    let s = app.state::<SharedStore>();
+    let s = s.lock();
+    Response::json(&serde_json::json!(Health {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        signed_in: s.client.is_some(),
+        source: s.active_source,
+        safe_mode: s.safe_mode.is_some(),
+    }))
+}
+
+async fn handle(app: &tauri::AppHandle, mut stream: TcpStream) -> Result<(), String> {
+    let req = match read_request(&mut stream).await {
+        Ok(req) => req,
+        Err(e) => {
+            let res = Response::text("400 Bad Request", &e);
+            let _ = write_response(&mut stream, &res, false).await;
+            return Err(e);
+        }
+    };
+    let path = req.path.split('?').next().unwrap_or_default();
+    let head_only = req.method == "HEAD";
+    if let ("GET", "/ws", Some(key)) = (req.method.as_str(), path, &req.websocket_key) {
+        return push::serve_websocket(app, stream, key).await;
+    }
+    if (req.method.as_str(), path) == ("GET", "/events") {
+        return push::serve_events(app, stream).await;
+    }
+
+    // A page in the browser can't send a custom header cross-origin without a CORS preflight,
+    // which is never approved, so it can't trigger anything here; and the answers to these
+    // routes aren't shared with other origins.
+    let res = match (req.method.as_str(), path) {
+        ("POST", "/export") if req.action => export(app).await.without_cors(),
+        ("POST", "/export") => Response::text(
+            "403 Forbidden",
+            &format!("send the {ACTION_HEADER} header to export"),
+        )
+        .without_cors(),
+        (_, "/export") => Response::not_allowed("POST").without_cors(),
+        ("GET" | "HEAD", "/nowplaying") => now_playing(app),
+        ("GET" | "HEAD", "/artwork") => artwork(),
+        ("GET" | "HEAD", "/placeholder") => placeholder(app),
+        ("GET" | "HEAD", "/placeholder/artwork") => placeholder_artwork(app),
+        ("GET" | "HEAD", "/health") => health(app),
+        ("GET" | "HEAD", "/ws") => Response::text("426 Upgrade Required", "WebSocket only"),
+        ("GET" | "HEAD", "/overlay") => Response::html("200 OK", overlay::PAGE),
+        ("GET" | "HEAD", "/overlay.css") => {
+            Response::bytes("text/css; charset=utf-8", overlay::custom_css(app))
+        }
+        ("GET" | "HEAD", "/schema") => Response::json(&schema::document()),
+        ("GET" | "HEAD", "/capabilities") => {
+            Response::json(&serde_json::json!(capabilities::capabilities(app)))
+        }
+        ("GET" | "HEAD", "/files" | "/files/") => {
+            Response::json(&serde_json::json!(virtual_files::names()))
+        }
+        ("GET" | "HEAD", _) if path.starts_with("/files/") => {
+            export_file(app, &path["/files/".len()..])
+        }
+        ("GET" | "HEAD", _) => Response::text("404 Not Found", "not found"),
+        _ => Response::not_allowed("GET, HEAD"),
+    };
+    write_response(&mut stream, &res, head_only).await
+}
+
+pub async fn write_response(
+    stream: &mut TcpStream,
+    res: &Response,
+    head_only: bool,
+) -> Result<(), String> {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n",
+        res.status,
+        res.content_type,
+        res.body.len()
+    );
+    if res.cors {
+        head.push_str("Access-Control-Allow-Origin: *\r\n");
+    }
+    if res.status.starts_with("405") {
+        head.push_str(&format!("Allow: {}\r\n", res.allow));
+    }
+    head.push_str("\r\n");
+
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .map_err(|e| format!("write: {e}"))?;
+    if !head_only {
+        stream
+            .write_all(&res.body)
+            .await
+            .map_err(|e| format!("write: {e}"))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_http_port(window: tauri::Window) -> u16 {
+    load_port(window.app_handle())
+}
+
+// Moves the server to `port` right away
+#[tauri::command]
+pub fn set_http_port(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    port: u16,
+) -> Result<(), String> {
+    if port == 0 {
+        return Err("Pick a port from 1 to 65535".into());
+    }
+    let app = window.app_handle();
+    write_setting(app, "http_port", serde_json::json!(port))?;
+    if state.lock().safe_mode.is_none() {
+        start(app.clone(), port);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `parts` one at a time, with a pause in between, and parses what arrives
+    async fn parse(parts: Vec<Vec<u8>>) -> Result<Request, String> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            for part in parts {
+                if client.write_all(&part).await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_request(&mut stream).await
+    }
+
+    #[tokio::test]
+    async fn head_split_across_reads() {
+        let req = parse(vec![
+            b"GET /nowpl".to_vec(),
+            b"aying?x=1 HTTP/1.1\r\nHost: 127.0.0.1\r".to_vec(),
+            b"\n\r\n".to_vec(),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/nowplaying?x=1");
+        assert_eq!(req.websocket_key, None);
+    }
+
+    #[tokio::test]
+    async fn websocket_key_only_on_upgrade() {
+        let req = parse(vec![b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\n\
+              Sec-WebSocket-Key: abc==\r\n\r\n"
+            .to_vec()])
+        .await
+        .unwrap();
+        assert_eq!(req.websocket_key.as_deref(), Some("abc=="));
+
+        let req = parse(vec![
+            b"GET /ws HTTP/1.1\r\nSec-WebSocket-Key: abc==\r\n\r\n".to_vec(),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(req.websocket_key, None);
+    }
+
+    #[tokio::test]
+    async fn action_header() {
+        let req = parse(vec![
+            b"POST /export HTTP/1.1\r\nx-now-playing-action: 1\r\n\r\n".to_vec(),
+        ])
+        .await
+        .unwrap();
+        assert!(req.action);
+
+        let req = parse(vec![b"POST /export HTTP/1.1\r\n\r\n".to_vec()])
+            .await
+            .unwrap();
+        assert!(!req.action);
+    }
+
+    #[tokio::test]
+    async fn body_is_left_unread() {
+        let mut body = b"POST /export HTTP/1.1\r\nContent-Length: 100000\r\n\r\n".to_vec();
+        body.extend(vec![b'x'; 100_000]);
+        let req = parse(vec![body]).await.unwrap();
+        assert_eq!(
+            (req.method.as_str(), req.path.as_str()),
+            ("POST", "/export")
+        );
+    }
+
+    #[tokio::test]
+    async fn oversize_head_is_rejected() {
+        let mut head = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        head.extend(vec![b'x'; MAX_REQUEST_BYTES * 2]);
+        assert_eq!(
+            parse(vec![head]).await.err().as_deref(),
+            Some("request head too large")
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_method_is_rejected() {
+        let err = parse(vec![b"G@T / HTTP/1.1\r\n\r\n".to_vec()]).await.err();
+        assert!(err.is_some_and(|e| e.starts_with("parse request")));
+    }
+
+    #[tokio::test]
+    async fn closed_mid_head() {
+        assert_eq!(
+            parse(vec![b"GET / HTTP/1.1\r\nHost: 127.0.0.1\r\n".to_vec()])
+                .await
+                .err()
+                .as_deref(),
+            Some("connection closed mid-request")
+        );
+    }
+}
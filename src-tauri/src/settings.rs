@@ -0,0 +1,322 @@
+// settings.json: one JSON object in the data directory's `settings` folder (see `portable`).
+// Every module owns its keys (reads them with `read_settings` when it loads, saves them through
+// its own setter); this keeps the file itself, writes it atomically, stamps it with a format
+// `VERSION`, and tells windows about every saved key with `settings_changed`. `AppSettings` is
+// the typed view of the common settings for the settings window and tools, with the rest passed
+// through as is. The whole file can be exported without its secrets and imported elsewhere.
+
+use crate::{
+    events::{self, UpdateMode},
+    export::ExportProfile,
+    providers::{self, Provider, DEFAULT_FAILOVER_AFTER},
+    streaks::{self, StreakConfig},
+    SharedLibrary, SharedStore, DEFAULT_POLL_SECS,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{Manager, State};
+
+// Bump with a step in `migrate` whenever a key is renamed or changes shape
+pub const VERSION: u32 = 1;
+
+// (key, field) of values that stay on this machine: `export_settings` leaves them out, and
+// `import_settings` keeps the ones already here
+const SECRETS: &[(&str, &str)] = &[
+    ("catalog_search", "client_secret"),
+    ("trivia", "lastfm_api_key"),
+    ("traktor", "password"),
+    ("obs", "password"),
+];
+
+// read-modify-write of the file, so two setters saving at once don't drop each other's key
+static WRITE: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AppSettings {
+    pub version: u32,
+    // how often Spotify is polled while something plays
+    pub poll_interval_secs: u64,
+    // sources in priority order
+    pub provider_chain: Vec<Provider>,
+    pub failover_after: u32,
+    pub aggregate_sessions: bool,
+    pub export_profiles: Vec<ExportProfile>,
+    pub local_art_dir: Option<String>,
+    // None uses SPOTIFY_CLIENT_ID from the environment
+    pub spotify_client_id: Option<String>,
+    // when overlays get `now_playing_update`
+    pub now_playing_updates: UpdateMode,
+    // daily goal and the overlay's streak badge
+    pub listening_goal: StreakConfig,
+    // every other module's keys, as stored; change those through their own commands
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            poll_interval_secs: DEFAULT_POLL_SECS,
+            provider_chain: providers::ProviderChain::default().providers,
+            failover_after: DEFAULT_FAILOVER_AFTER,
+            aggregate_sessions: false,
+            export_profiles: Vec::new(),
+            local_art_dir: None,
+            spotify_client_id: None,
+            now_playing_updates: UpdateMode::default(),
+            listening_goal: StreakConfig::default(),
+            other: Map::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct SettingsChanged {
+    pub keys: Vec<String>,
+}
+
+pub fn path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = crate::portable::data_dir(app)?.join("settings");
+    fs::create_dir_all(&dir).map_err(|e| format!("create dir: {e}"))?;
+    Ok(dir.join("settings.json"))
+}
+
+fn read_file(path: &PathBuf) -> Option<Map<String, Value>> {
+    let bytes = fs::read(path).ok()?;
+    match serde_json::from_slice(&bytes).ok()? {
+        Value::Object(map) => Some(map),
+        _ => None,
+    }
+}
+
+// Whole settings.json as a JSON object (empty if missing or unreadable)
+pub fn read_settings(app: &tauri::AppHandle) -> Value {
+    Value::Object(
+        path(app)
+            .ok()
+            .and_then(|p| read_file(&p))
+            .unwrap_or_default(),
+    )
+}
+
+// Through a temporary file, so a crash mid-write leaves the previous settings rather than half
+// of them
+fn write_file(path: &PathBuf, settings: &Map<String, Value>) -> Result<(), String> {
+    let bytes =
+        serde_json::to_vec_pretty(settings).map_err(|e| format!("serialize settings: {e}"))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, bytes).map_err(|e| format!("write settings: {e}"))?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("write settings: {e}")
+    })
+}
+
+// Update a single key, keeping everything else in settings.json intact
+pub fn write_setting(app: &tauri::AppHandle, key: &str, value: Value) -> Result<(), String> {
+    {
+        let _guard = WRITE.lock();
+        let path = path(app)?;
+        let mut settings = read_file(&path).unwrap_or_default();
+        settings.insert("version".into(), VERSION.into());
+        settings.insert(key.to_string(), value);
+        write_file(&path, &settings)?;
+    }
+    events::emit(
+        app,
+        "settings_changed",
+        SettingsChanged {
+            keys: vec![key.to_string()],
+        },
+    );
+    Ok(())
+}
+
+// Brings a file from an older build up to `VERSION`, keeping a copy of it next to the new one.
+// Files from before versioning (version 0) only get stamped: their keys are still the same.
+pub fn migrate(app: &tauri::AppHandle) {
+    let _guard = WRITE.lock();
+    let Ok(path) = path(app) else {
+        return;
+    };
+    let Some(mut settings) = read_file(&path) else {
+        return;
+    };
+    let from = settings.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if from >= VERSION {
+        return;
+    }
+    let backup = path.with_extension(format!("v{from}.json"));
+    if let Err(e) = fs::copy(&path, &backup) {
+        eprintln!("[settings] back up {}: {e}", backup.display());
+        return;
+    }
+    settings.insert("version".into(), VERSION.into());
+    match write_file(&path, &settings) {
+        Ok(()) => eprintln!("[settings] migrated from version {from} to {VERSION}"),
+        Err(e) => eprintln!("[settings] migrate: {e}"),
+    }
+}
+
+// Keys left out of the file read as their defaults
+fn typed(settings: Value) -> Result<AppSettings, String> {
+    serde_json::from_value(settings).map_err(|e| format!("settings.json: {e}"))
+}
+
+#[tauri::command]
+pub fn get_settings(window: tauri::Window) -> Result<AppSettings, String> {
+    typed(read_settings(window.app_handle()))
+}
+
+// Changes some of the typed settings at once: `patch` holds only the keys to change. Each one
+// goes through the same checks and side effects as its own setter (a new client ID signs out,
+// a new art folder gets indexed, ...). Keys are applied in order and the first one refused
+// stops the rest; the ones before it stay saved. Returns the settings afterwards.
+#[tauri::command]
+pub fn update_settings(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    patch: Map<String, Value>,
+) -> Result<AppSettings, String> {
+    let app = window.app_handle();
+    let mut merged = read_settings(app);
+    for (key, value) in &patch {
+        merged[key] = value.clone();
+    }
+    let next = typed(merged)?;
+    if let Some(key) = patch
+        .keys()
+        .find(|k| next.other.contains_key(*k) || *k == "version")
+    {
+        return Err(format!(
+            "\"{key}\" can't be changed through update_settings"
+        ));
+    }
+
+    for key in patch.keys() {
+        match key.as_str() {
+            "poll_interval_secs" => {
+                crate::set_poll_interval(state.clone(), window.clone(), next.poll_interval_secs)
+                    .map(drop)
+            }
+            // saved together
+            "provider_chain" | "failover_after" => providers::set_provider_chain(
+                state.clone(),
+                window.clone(),
+                next.provider_chain.clone(),
+                Some(next.failover_after),
+            ),
+            "aggregate_sessions" => providers::set_aggregate_mode(
+                state.clone(),
+                window.clone(),
+                next.aggregate_sessions,
+            ),
+            "export_profiles" => crate::export::set_export_profiles(
+                state.clone(),
+                window.clone(),
+                next.export_profiles.clone(),
+            )
+            .map_err(|issues| {
+                issues
+                    .into_iter()
+                    .map(|i| i.message)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            }),
+            "local_art_dir" => match &next.local_art_dir {
+                Some(dir) => crate::set_local_art_dir(
+                    app.state::<SharedLibrary>(),
+                    window.clone(),
+                    dir.clone(),
+                ),
+                None => Err("The local art folder can be changed but not removed".into()),
+            },
+            "spotify_client_id" => crate::set_spotify_client_id(
+                state.clone(),
+                window.clone(),
+                next.spotify_client_id.clone(),
+            ),
+            "now_playing_updates" => {
+                events::set_update_mode(state.clone(), window.clone(), next.now_playing_updates)
+            }
+            "listening_goal" => streaks::set_streak_config(
+                state.clone(),
+                window.clone(),
+                next.listening_goal.clone(),
+            ),
+            _ => Ok(()),
+        }
+        .map_err(|e| format!("{key}: {e}"))?;
+    }
+    typed(read_settings(app))
+}
+
+// Writes the settings to `file` for `import_settings` on another machine. The Spotify token
+// isn't part of them, and `SECRETS` are left out.
+#[tauri::command]
+pub fn export_settings(window: tauri::Window, file: String) -> Result<(), String> {
+    let mut settings = read_settings(window.app_handle());
+    for (key, field) in SECRETS {
+        if let Some(obj) = settings.get_mut(*key).and_then(Value::as_object_mut) {
+            obj.remove(*field);
+        }
+    }
+    settings["version"] = VERSION.into();
+    let bytes =
+        serde_json::to_vec_pretty(&settings).map_err(|e| format!("serialize settings: {e}"))?;
+    fs::write(&file, bytes).map_err(|e| format!("write {file}: {e}"))
+}
+
+// Reads a file from `export_settings` over the current settings; keys it doesn't have are
+// kept, and so are the secrets here. Most modules read their settings on start, so the app
+// should be restarted afterwards. Returns the keys imported.
+#[tauri::command]
+pub fn import_settings(window: tauri::Window, file: String) -> Result<Vec<String>, String> {
+    let app = window.app_handle();
+    let bytes = fs::read(&file).map_err(|e| format!("read {file}: {e}"))?;
+    let Ok(Value::Object(mut imported)) = serde_json::from_slice(&bytes) else {
+        return Err("That file isn't exported settings".into());
+    };
+    let from = imported
+        .remove("version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    if from > u64::from(VERSION) {
+        return Err(
+            "Those settings are from a newer version of the app; update this one first".into(),
+        );
+    }
+    // the same checks `get_settings` would fail on later
+    typed(Value::Object(imported.clone()))?;
+
+    let keys: Vec<String> = imported.keys().cloned().collect();
+    {
+        let _guard = WRITE.lock();
+        let path = path(app)?;
+        let mut settings = read_file(&path).unwrap_or_default();
+        for (key, mut value) in imported {
+            for (_, field) in SECRETS.iter().filter(|(k, _)| *k == key) {
+                let kept = settings.get(&key).and_then(|v| v.get(*field)).cloned();
+                if let (Some(kept), Some(obj)) = (kept, value.as_object_mut()) {
+                    obj.entry(field.to_string()).or_insert(kept);
+                }
+            }
+            settings.insert(key, value);
+        }
+        settings.insert("version".into(), VERSION.into());
+        write_file(&path, &settings)?;
+    }
+    events::emit(
+        app,
+        "settings_changed",
+        SettingsChanged { keys: keys.clone() },
+    );
+    Ok(keys)
+}
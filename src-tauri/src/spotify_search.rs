@@ -0,0 +1,239 @@
+// Catalog enrichment for GSMTC-only setups: players often report just a title and a
+// "Artist, Artist" string, no album or duration. A Spotify search with app credentials
+// (client-credentials flow, no user login) recovers the album, cover, duration and the real
+// artist list. Without a client secret it falls back to Deezer's public search.
+
+use crate::{
+    artwork_lookup::HTTP, compilation, read_settings, write_setting, NowPlaying, SharedCaches,
+    SharedStore,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rspotify::{
+    clients::BaseClient,
+    model::{SearchResult, SearchType},
+    ClientCredsSpotify, Config, Credentials,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{Manager, State};
+use url::Url;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub enabled: bool,
+    // both fall back to SPOTIFY_CLIENT_ID / SPOTIFY_CLIENT_SECRET
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl SearchConfig {
+    fn credentials(&self) -> Option<(String, String)> {
+        let pick = |v: &Option<String>, env: &str| {
+            v.clone()
+                .or_else(|| std::env::var(env).ok())
+                .filter(|s| !s.trim().is_empty())
+        };
+        Some((
+            pick(&self.client_id, "SPOTIFY_CLIENT_ID")?,
+            pick(&self.client_secret, "SPOTIFY_CLIENT_SECRET")?,
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct CatalogMatch {
+    album: Option<String>,
+    artwork_url: Option<String>,
+    duration_ms: Option<u64>,
+    artists: Vec<String>,
+}
+
+// ((client id, secret), client), so changing either gets a fresh token
+type CachedClient = Option<((String, String), Arc<ClientCredsSpotify>)>;
+static CLIENT: Lazy<Mutex<CachedClient>> = Lazy::new(|| Mutex::new(None));
+
+pub fn load_config(app: &tauri::AppHandle) -> SearchConfig {
+    read_settings(app)
+        .get("catalog_search")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+async fn client(id: &str, secret: &str) -> Result<Arc<ClientCredsSpotify>, String> {
+    if let Some(((cached_id, cached_secret), c)) = CLIENT.lock().as_ref() {
+        if cached_id == id && cached_secret == secret {
+            return Ok(c.clone());
+        }
+    }
+    let c = Arc::new(ClientCredsSpotify::with_config(
+        Credentials::new(id, secret),
+        Config {
+            token_refreshing: true,
+            ..Default::default()
+        },
+    ));
+    c.request_token()
+        .await
+        .map_err(|e| format!("client credentials: {e}"))?;
+    *CLIENT.lock() = Some(((id.to_string(), secret.to_string()), c.clone()));
+    Ok(c)
+}
+
+async fn spotify(
+    id: &str,
+    secret: &str,
+    title: &str,
+    artist: &str,
+) -> Result<Option<CatalogMatch>, String> {
+    let client = client(id, secret).await?;
+    let q = format!("track:\"{title}\" artist:\"{artist}\"");
+    let res = client
+        .search(&q, SearchType::Track, None, None, Some(1), None)
+        .await
+        .map_err(|e| format!("spotify search: {e}"))?;
+    let SearchResult::Tracks(page) = res else {
+        return Ok(None);
+    };
+    Ok(page.items.into_iter().next().map(|t| CatalogMatch {
+        album: Some(t.album.name),
+        artwork_url: crate::pick_image_url(&t.album.images, 300),
+        duration_ms: u64::try_from(t.duration.num_milliseconds()).ok(),
+        artists: t.artists.into_iter().map(|a| a.name).collect(),
+    }))
+}
+
+async fn deezer(title: &str, artist: &str) -> Result<Option<CatalogMatch>, String> {
+    let q = format!("artist:\"{artist}\" track:\"{title}\"");
+    let url = Url::parse_with_params("https://api.deezer.com/search", &[("q", q.as_str())])
+        .map_err(|e| e.to_string())?;
+    let bytes = HTTP
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("deezer search: {e}"))?
+        .bytes()
+        .await
+        .map_err(|e| format!("deezer search body: {e}"))?;
+    let v: serde_json::Value =
+        serde_json::from_slice(&bytes).map_err(|e| format!("deezer search json: {e}"))?;
+
+    let t = &v["data"][0];
+    if t.is_null() {
+        return Ok(None);
+    }
+    let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+    Ok(Some(CatalogMatch {
+        album: text(&t["album"]["title"]),
+        artwork_url: text(&t["album"]["cover_xl"]).or_else(|| text(&t["album"]["cover_big"])),
+        duration_ms: t["duration"].as_u64().map(|s| s * 1000),
+        // search results only carry the main artist
+        artists: text(&t["artist"]["name"]).into_iter().collect(),
+    }))
+}
+
+// Fills what the player left out. The artist list is only replaced when the match agrees
+// on the main artist, so a bad search hit can't rename the track's artists.
+pub async fn enrich(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    let state = app.state::<SharedStore>();
+    let config = state.lock().catalog_search.clone();
+    if !config.enabled {
+        return;
+    }
+    let (Some(title), Some(artist)) = (
+        np.track_name.clone(),
+        compilation::first_real_artist(&np.artists).cloned(),
+    ) else {
+        return;
+    };
+    let key = format!("{}|{}", title.to_lowercase(), artist.to_lowercase());
+
+    let caches = app.state::<SharedCaches>();
+    let cached = caches.lock().catalog.get(&key).cloned();
+    let hit = match cached {
+        Some(hit) => hit,
+        None => {
+            let res = match config.credentials() {
+                Some((id, secret)) => spotify(&id, &secret, &title, &artist).await,
+                None => deezer(&title, &artist).await,
+            };
+            let hit = match res {
+                Ok(hit) => hit,
+                Err(e) => {
+                    // don't cache transient failures
+                    eprintln!("[catalog] {e}");
+                    return;
+                }
+            };
+            caches.lock().catalog.insert(key, hit.clone());
+            hit
+        }
+    };
+    let Some(hit) = hit else {
+        return;
+    };
+
+    if np.album.is_none() {
+        np.album = hit.album;
+    }
+    if np.artwork_url.is_none() && np.artwork_path.is_none() {
+        np.artwork_url = hit.artwork_url;
+    }
+    if np.duration_ms.is_none() {
+        np.duration_ms = hit.duration_ms;
+    }
+    if hit
+        .artists
+        .first()
+        .is_some_and(|a| a.eq_ignore_ascii_case(&artist))
+        && hit.artists.len() > np.artists.len()
+    {
+        np.artists = hit.artists;
+    }
+}
+
+// The secret stays in the backend; the settings UI only learns whether one is saved
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct SearchConfigView {
+    pub enabled: bool,
+    pub client_id: Option<String>,
+    pub has_client_secret: bool,
+}
+
+#[tauri::command]
+pub fn get_catalog_search(state: State<'_, SharedStore>) -> SearchConfigView {
+    let s = state.lock();
+    let config = &s.catalog_search;
+    SearchConfigView {
+        enabled: config.enabled,
+        client_id: config.client_id.clone(),
+        has_client_secret: config
+            .client_secret
+            .as_deref()
+            .is_some_and(|s| !s.is_empty()),
+    }
+}
+
+// Without a `client_secret` the saved one is kept; an empty one removes it
+#[tauri::command]
+pub fn set_catalog_search(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    mut config: SearchConfig,
+) -> Result<(), String> {
+    if config.client_secret.is_none() {
+        config.client_secret = state.lock().catalog_search.client_secret.clone();
+    }
+    config.client_secret = config.client_secret.filter(|s| !s.is_empty());
+    write_setting(
+        window.app_handle(),
+        "catalog_search",
+        serde_json::json!(config),
+    )?;
+    let mut s = state.lock();
+    s.catalog_search = config;
+    // the other backend may well find something else
+    window.state::<SharedCaches>().lock().catalog.clear();
+    Ok(())
+}
@@ -0,0 +1,264 @@
+// Daily streaks, a daily listening goal and how the year is going, worked out from the local
+// history (see `history`). A day counts once anything was played on it, local time. With the
+// overlay turned on, a short version rides on every `now_playing_update` as `streak`.
+
+use crate::{history, read_settings, write_setting, NowPlaying, SharedStore};
+use chrono::{Datelike, Days, Local, NaiveDate};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+// the overlay reads this on every poll; the history only changes as tracks end
+const REFRESH_AFTER: Duration = Duration::from_secs(60);
+// days before today the average is taken over, quiet days included
+const AVERAGE_OVER_DAYS: u64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[serde(default)]
+pub struct StreakConfig {
+    pub daily_goal_minutes: Option<u32>,
+    // add `streak` to the now-playing payload
+    pub overlay: bool,
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct YearProgress {
+    pub year: i32,
+    // share of the year gone by, 0-1
+    pub elapsed: f64,
+    pub days_listened: u32,
+    pub minutes: u64,
+    // last year up to the same day, to compare against
+    pub minutes_last_year_to_date: u64,
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct Streaks {
+    // days in a row with listening, up to today (or yesterday, while today is still open)
+    pub current_days: u32,
+    pub longest_days: u32,
+    // local dates, YYYY-MM-DD
+    pub longest_start: Option<String>,
+    pub longest_end: Option<String>,
+    pub today_minutes: u64,
+    pub average_minutes: f64,
+    pub goal_minutes: Option<u32>,
+    // today's minutes over the goal; above 1 once it's beaten
+    pub goal_progress: Option<f64>,
+    // days in a row the goal was met, counted like `current_days`
+    pub goal_streak_days: u32,
+    pub year: YearProgress,
+}
+
+// What the overlay gets
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[serde(default)]
+pub struct StreakBadge {
+    pub current_days: u32,
+    pub today_minutes: u64,
+    pub goal_minutes: Option<u32>,
+    pub goal_met: bool,
+}
+
+static CACHE: Lazy<Mutex<Option<(Instant, Streaks)>>> = Lazy::new(|| Mutex::new(None));
+// a background refresh of `CACHE` is running
+static REFRESHING: AtomicBool = AtomicBool::new(false);
+
+pub fn load_config(app: &tauri::AppHandle) -> StreakConfig {
+    read_settings(app)
+        .get("listening_goal")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+// Listening time per local day
+fn daily_ms(app: &tauri::AppHandle) -> Result<BTreeMap<NaiveDate, u64>, String> {
+    history::with_db(app, |conn| {
+        let mut q = conn
+            .prepare(
+                "SELECT date(played_at, 'localtime') AS day, SUM(ms_played) FROM plays
+                 GROUP BY day",
+            )
+            .map_err(|e| format!("read history: {e}"))?;
+        let rows = q
+            .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))
+            .map_err(|e| format!("read history: {e}"))?;
+        Ok(rows
+            .flatten()
+            .filter_map(|(day, ms)| {
+                let day = NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?;
+                Some((day, ms.max(0) as u64))
+            })
+            .collect())
+    })
+}
+
+// Days in a row ending today, or yesterday if today doesn't count (yet)
+fn run_until(today: NaiveDate, counts: impl Fn(NaiveDate) -> bool) -> u32 {
+    let mut day = if counts(today) {
+        today
+    } else {
+        match today.pred_opt() {
+            Some(d) => d,
+            None => return 0,
+        }
+    };
+    let mut run = 0;
+    while counts(day) {
+        run += 1;
+        match day.pred_opt() {
+            Some(d) => day = d,
+            None => break,
+        }
+    }
+    run
+}
+
+fn minutes(ms: u64) -> u64 {
+    ms / 60_000
+}
+
+fn compute(days: &BTreeMap<NaiveDate, u64>, goal: Option<u32>, today: NaiveDate) -> Streaks {
+    let current_days = run_until(today, |d| days.contains_key(&d));
+
+    let mut longest: Option<(NaiveDate, NaiveDate)> = None;
+    let mut run: Option<(NaiveDate, NaiveDate)> = None;
+    for &day in days.keys() {
+        run = match run {
+            Some((start, end)) if end.succ_opt() == Some(day) => Some((start, day)),
+            _ => Some((day, day)),
+        };
+        let len = |r: (NaiveDate, NaiveDate)| (r.1 - r.0).num_days();
+        if let Some(r) = run.filter(|r| longest.is_none_or(|l| len(*r) > len(l))) {
+            longest = Some(r);
+        }
+    }
+
+    let today_ms = days.get(&today).copied().unwrap_or(0);
+    let window_start = today - Days::new(AVERAGE_OVER_DAYS);
+    let window_ms: u64 = days.range(window_start..today).map(|(_, ms)| ms).sum();
+
+    let goal_ms = goal.map(|g| u64::from(g) * 60_000);
+    let goal_streak_days = match goal_ms {
+        Some(g) if g > 0 => run_until(today, |d| days.get(&d).is_some_and(|ms| *ms >= g)),
+        _ => 0,
+    };
+
+    let year_start = NaiveDate::from_yo_opt(today.year(), 1).unwrap_or(today);
+    let year_days = if NaiveDate::from_ymd_opt(today.year(), 2, 29).is_some() {
+        366.0
+    } else {
+        365.0
+    };
+    let this_year: Vec<u64> = days.range(year_start..).map(|(_, ms)| *ms).collect();
+    let last_year_ms: u64 = days
+        .iter()
+        .filter(|(d, _)| d.year() == today.year() - 1 && d.ordinal() <= today.ordinal())
+        .map(|(_, ms)| ms)
+        .sum();
+
+    Streaks {
+        current_days,
+        longest_days: longest.map_or(0, |(s, e)| (e - s).num_days() as u32 + 1),
+        longest_start: longest.map(|(s, _)| s.to_string()),
+        longest_end: longest.map(|(_, e)| e.to_string()),
+        today_minutes: minutes(today_ms),
+        average_minutes: window_ms as f64 / 60_000.0 / AVERAGE_OVER_DAYS as f64,
+        goal_minutes: goal,
+        goal_progress: goal_ms
+            .filter(|g| *g > 0)
+            .map(|g| today_ms as f64 / g as f64),
+        goal_streak_days,
+        year: YearProgress {
+            year: today.year(),
+            elapsed: today.ordinal() as f64 / year_days,
+            days_listened: this_year.len() as u32,
+            minutes: minutes(this_year.iter().sum()),
+            minutes_last_year_to_date: minutes(last_year_ms),
+        },
+    }
+}
+
+fn streaks(app: &tauri::AppHandle) -> Result<Streaks, String> {
+    if let Some((at, s)) = CACHE.lock().as_ref() {
+        if at.elapsed() < REFRESH_AFTER {
+            return Ok(s.clone());
+        }
+    }
+    let goal = app.state::<SharedStore>().lock().streaks.daily_goal_minutes;
+    let s = compute(&daily_ms(app)?, goal, Local::now().date_naive());
+    *CACHE.lock() = Some((Instant::now(), s.clone()));
+    Ok(s)
+}
+
+// Recomputes a stale `CACHE` on a blocking thread, one refresh at a time
+fn refresh_in_background(app: &tauri::AppHandle) {
+    let fresh = CACHE
+        .lock()
+        .as_ref()
+        .is_some_and(|(at, _)| at.elapsed() < REFRESH_AFTER);
+    if fresh || REFRESHING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = streaks(&app) {
+            eprintln!("[streaks] {e}");
+        }
+        REFRESHING.store(false, Ordering::Release);
+    });
+}
+
+// Runs on every poll and push, so it only reads the cache; a stale one is refreshed for the
+// next update
+pub fn apply(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    if !app.state::<SharedStore>().lock().streaks.overlay {
+        np.streak = None;
+        return;
+    }
+    refresh_in_background(app);
+    np.streak = CACHE.lock().as_ref().map(|(_, s)| StreakBadge {
+        current_days: s.current_days,
+        today_minutes: s.today_minutes,
+        goal_minutes: s.goal_minutes,
+        goal_met: s.goal_progress.is_some_and(|p| p >= 1.0),
+    });
+}
+
+#[tauri::command]
+pub async fn get_streaks(window: tauri::Window) -> Result<Streaks, String> {
+    // goes through every day of history when the cache is stale
+    let app = window.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || streaks(&app))
+        .await
+        .map_err(|e| format!("streaks task: {e}"))?
+}
+
+#[tauri::command]
+pub fn get_streak_config(state: State<'_, SharedStore>) -> StreakConfig {
+    state.lock().streaks.clone()
+}
+
+#[tauri::command]
+pub fn set_streak_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: StreakConfig,
+) -> Result<(), String> {
+    if config.daily_goal_minutes == Some(0) {
+        return Err("A daily goal needs at least one minute".into());
+    }
+    write_setting(
+        window.app_handle(),
+        "listening_goal",
+        serde_json::json!(config),
+    )?;
+    state.lock().streaks = config;
+    *CACHE.lock() = None;
+    Ok(())
+}
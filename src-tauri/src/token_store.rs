@@ -0,0 +1,133 @@
+// The Spotify token lives in the OS credential store (Windows Credential Manager, the macOS
+// keychain, the Secret Service on Linux) instead of a plaintext file in app data. Builds that
+// wrote `spotify/token.json` get it moved over on first load and the file deleted.
+//
+// In portable mode (see `portable`) it stays in a file next to the executable instead, so it
+// travels with the stick.
+//
+// rspotify's own file cache is off (`token_cached: false`), so a refreshed token is only saved
+// when someone calls `persist`; the watcher does after each `auto_reauth`.
+
+use crate::portable;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rspotify::{clients::BaseClient, AuthCodePkceSpotify, Token};
+use std::{fs, path::PathBuf};
+
+const SERVICE: &str = "Now-Playing";
+const ACCOUNT: &str = "spotify-token";
+
+// access token last written, so polls that didn't refresh don't touch the keychain
+static SAVED: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+fn entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, ACCOUNT).map_err(|e| format!("credential store: {e}"))
+}
+
+// Where older builds kept the token
+fn legacy_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    crate::portable::data_dir(app)
+        .ok()
+        .map(|d| d.join("spotify").join("token.json"))
+}
+
+fn load_portable(path: &PathBuf) -> Result<Option<Token>, String> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("read token file: {e}")),
+    };
+    let token: Token =
+        serde_json::from_slice(&data).map_err(|e| format!("parse token json: {e}"))?;
+    *SAVED.lock() = Some(token.access_token.clone());
+    Ok(Some(token))
+}
+
+// The saved token, without looking for one left by older builds; for `cli`, which runs without
+// the app's paths
+pub fn load_saved() -> Result<Option<Token>, String> {
+    if let Some(path) = portable::token_file() {
+        return load_portable(&path);
+    }
+    match entry()?.get_password() {
+        Ok(json) => {
+            let token: Token =
+                serde_json::from_str(&json).map_err(|e| format!("parse stored token: {e}"))?;
+            *SAVED.lock() = Some(token.access_token.clone());
+            Ok(Some(token))
+        }
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("read stored token: {e}")),
+    }
+}
+
+pub fn load(app: &tauri::AppHandle) -> Result<Option<Token>, String> {
+    match load_saved()? {
+        Some(token) => Ok(Some(token)),
+        None if portable::enabled() => Ok(None),
+        None => migrate(app),
+    }
+}
+
+fn migrate(app: &tauri::AppHandle) -> Result<Option<Token>, String> {
+    let Some(path) = legacy_path(app).filter(|p| p.exists()) else {
+        return Ok(None);
+    };
+    let data = fs::read(&path).map_err(|e| format!("read token file: {e}"))?;
+    let token: Token =
+        serde_json::from_slice(&data).map_err(|e| format!("parse token json: {e}"))?;
+    save(&token)?;
+    // only once the credential store has it
+    fs::remove_file(&path).map_err(|e| format!("remove token file: {e}"))?;
+    eprintln!("[auth] moved the Spotify token from token.json to the credential store");
+    Ok(Some(token))
+}
+
+pub fn save(token: &Token) -> Result<(), String> {
+    let json = serde_json::to_string(token).map_err(|e| format!("serialize token: {e}"))?;
+    if let Some(path) = portable::token_file() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("create dir: {e}"))?;
+        }
+        fs::write(&path, json).map_err(|e| format!("write token file: {e}"))?;
+    } else {
+        entry()?
+            .set_password(&json)
+            .map_err(|e| format!("store token: {e}"))?;
+    }
+    *SAVED.lock() = Some(token.access_token.clone());
+    Ok(())
+}
+
+// Saves the client's token if it changed since the last save (i.e. it was refreshed)
+pub async fn persist(client: &AuthCodePkceSpotify) {
+    let token = match client.get_token().lock().await {
+        Ok(guard) => guard.clone(),
+        Err(_) => return,
+    };
+    let Some(token) = token else {
+        return;
+    };
+    if SAVED.lock().as_deref() == Some(token.access_token.as_str()) {
+        return;
+    }
+    if let Err(e) = save(&token) {
+        eprintln!("[auth] {e}");
+    }
+}
+
+// Forgets the token everywhere it may be, including a leftover token.json
+pub fn clear(app: &tauri::AppHandle) -> Result<(), String> {
+    *SAVED.lock() = None;
+    if let Some(path) = portable::token_file().filter(|p| p.exists()) {
+        return fs::remove_file(&path).map_err(|e| format!("remove token file: {e}"));
+    }
+    match entry()?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(format!("delete stored token: {e}")),
+    }
+    if let Some(path) = legacy_path(app).filter(|p| p.exists()) {
+        fs::remove_file(&path).map_err(|e| format!("remove token file: {e}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,482 @@
+// Traktor broadcast input.
+//
+// Traktor's "Broadcasting" sends its mix to an Icecast server, and the track info travels
+// inside the Ogg stream: every new track starts a logical stream whose Vorbis comment header
+// carries ARTIST/TITLE/ALBUM. We pose as that Icecast server on localhost, read the comment
+// headers and throw the audio away, so nothing leaves the machine. Tools that update metadata
+// through Icecast's `/admin/metadata?mode=updinfo&song=...` work as well.
+
+use crate::{
+    artwork_lookup, icecast, parse_artists,
+    providers::{self, Provider},
+    read_settings, resilience,
+    server::read_request,
+    write_setting, NowPlaying, SharedStore,
+};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{Manager, State};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+// comment headers are small; anything bigger is audio we failed to frame
+const MAX_PACKET_BYTES: usize = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, schemars::JsonSchema)]
+#[serde(default)]
+pub struct TraktorConfig {
+    pub enabled: bool,
+    // what Traktor's broadcast settings point at (Address 127.0.0.1)
+    pub port: u16,
+    // checked when set; Traktor always sends one, so any value works otherwise
+    pub password: Option<String>,
+}
+
+impl Default for TraktorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8000,
+            password: None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TraktorInput {
+    config: TraktorConfig,
+    cancel: Option<CancellationToken>,
+}
+
+impl TraktorInput {
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let config: TraktorConfig = read_settings(app)
+        .get("traktor")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    restart(app, config);
+}
+
+fn restart(app: &tauri::AppHandle, config: TraktorConfig) {
+    let token = CancellationToken::new();
+    {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.traktor.cancel.take() {
+            old.cancel();
+        }
+        s.traktor_now_playing = None;
+        s.traktor.config = config.clone();
+        if !config.enabled {
+            return;
+        }
+        s.traktor.cancel = Some(token.clone());
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let bind = || async {
+            TcpListener::bind(("127.0.0.1", config.port))
+                .await
+                .map_err(|e| format!("bind 127.0.0.1:{}: {e}", config.port))
+        };
+        let Some(listener) = resilience::retry(&app, "traktor", &token, bind).await else {
+            return;
+        };
+        loop {
+            let stream = tokio::select! {
+                _ = token.cancelled() => break,
+                res = listener.accept() => match res {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        eprintln!("[traktor] accept: {e}");
+                        continue;
+                    }
+                },
+            };
+            let (app, config, token) = (app.clone(), config.clone(), token.clone());
+            tauri::async_runtime::spawn(async move {
+                tokio::select! {
+                    _ = token.cancelled() => {}
+                    res = handle(&app, &config, stream) => {
+                        if let Err(e) = res {
+                            eprintln!("[traktor] {e}");
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+// Icecast source clients log in as "source:<password>"
+fn authorized(config: &TraktorConfig, header: Option<&str>) -> bool {
+    let Some(password) = config.password.as_deref().filter(|p| !p.is_empty()) else {
+        return true;
+    };
+    header
+        .and_then(|h| h.strip_prefix("Basic "))
+        .and_then(|b| {
+            base64::engine::general_purpose::STANDARD
+                .decode(b.trim())
+                .ok()
+        })
+        .and_then(|d| String::from_utf8(d).ok())
+        .and_then(|d| d.split_once(':').map(|(_, p)| p == password))
+        .unwrap_or(false)
+}
+
+async fn reply(stream: &mut TcpStream, status: &str, extra: &str, body: &str) {
+    let res = format!(
+        "HTTP/1.0 {status}\r\nServer: Icecast 2.4.4\r\n{extra}Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(res.as_bytes()).await;
+}
+
+async fn handle(
+    app: &tauri::AppHandle,
+    config: &TraktorConfig,
+    mut stream: TcpStream,
+) -> Result<(), String> {
+    let head = read_request(&mut stream).await?;
+    if !authorized(config, head.authorization.as_deref()) {
+        reply(
+            &mut stream,
+            "401 Unauthorized",
+            "WWW-Authenticate: Basic realm=\"Icecast2 Server\"\r\n",
+            "",
+        )
+        .await;
+        return Ok(());
+    }
+
+    match head.method.as_str() {
+        // the broadcast itself
+        "SOURCE" | "PUT" => {
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n").await;
+            let res = read_source(app, &mut stream, head.rest).await;
+            // broadcast stopped: nothing is playing until it's back
+            publish(app, None).await;
+            res
+        }
+        // a page open in the browser could `fetch` this without a password and put any text
+        // on stream; Traktor and metadata tools don't send `Origin`
+        "GET" if head.origin => {
+            reply(&mut stream, "403 Forbidden", "", "").await;
+            Ok(())
+        }
+        "GET" => {
+            let song = Url::parse(&format!("http://localhost{}", head.path))
+                .ok()
+                .filter(|u| u.path() == "/admin/metadata")
+                .and_then(|u| {
+                    u.query_pairs()
+                        .find(|(k, _)| k == "song")
+                        .map(|(_, v)| v.to_string())
+                });
+            let Some(song) = song else {
+                reply(&mut stream, "404 Not Found", "", "").await;
+                return Ok(());
+            };
+            reply(
+                &mut stream,
+                "200 OK",
+                "Content-Type: text/xml\r\n",
+                "<?xml version=\"1.0\"?>\n<iceresponse><message>Metadata update successful</message><return>1</return></iceresponse>\n",
+            )
+            .await;
+            publish(app, icecast::song_to_now_playing(&song)).await;
+            Ok(())
+        }
+        _ => {
+            reply(&mut stream, "405 Method Not Allowed", "", "").await;
+            Ok(())
+        }
+    }
+}
+
+async fn read_source(
+    app: &tauri::AppHandle,
+    stream: &mut TcpStream,
+    first: Vec<u8>,
+) -> Result<(), String> {
+    let mut ogg = OggReader::default();
+    let mut chunk = vec![0u8; 16 * 1024];
+    let mut data = first;
+    let mut last: Option<(Option<String>, Vec<String>)> = None;
+    loop {
+        for packet in ogg.push(&data) {
+            let Some(np) = comments(&packet).and_then(comments_to_now_playing) else {
+                continue;
+            };
+            let key = (np.track_name.clone(), np.artists.clone());
+            if last.as_ref() != Some(&key) {
+                last = Some(key);
+                publish(app, Some(np)).await;
+            }
+        }
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| format!("read: {e}"))?;
+        if n == 0 {
+            return Ok(());
+        }
+        data = chunk[..n].to_vec();
+    }
+}
+
+// Reassembles Ogg packets from the page stream
+#[derive(Default)]
+struct OggReader {
+    buf: Vec<u8>,
+    // packet continued on the next page
+    packet: Vec<u8>,
+}
+
+impl OggReader {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buf.extend_from_slice(data);
+        let mut packets = Vec::new();
+        loop {
+            // (re)sync on the capture pattern
+            let Some(start) = self.buf.windows(4).position(|w| w == b"OggS") else {
+                let keep = self.buf.len().min(3);
+                self.buf.drain(..self.buf.len() - keep);
+                break;
+            };
+            self.buf.drain(..start);
+            // 27-byte header, then one lacing value per segment
+            let Some(&segments) = self.buf.get(26) else {
+                break;
+            };
+            let Some(lacing) = self.buf.get(27..27 + segments as usize) else {
+                break;
+            };
+            let lacing = lacing.to_vec();
+            let body_start = 27 + lacing.len();
+            let total = body_start + lacing.iter().map(|l| *l as usize).sum::<usize>();
+            if self.buf.len() < total {
+                break;
+            }
+            let continued = self.buf[5] & 0x01 != 0;
+            if !continued {
+                self.packet.clear();
+            }
+            let mut pos = body_start;
+            for len in lacing {
+                let len = len as usize;
+                self.packet.extend_from_slice(&self.buf[pos..pos + len]);
+                pos += len;
+                // a segment under 255 bytes ends the packet
+                if len < 255 {
+                    packets.push(std::mem::take(&mut self.packet));
+                }
+            }
+            if self.packet.len() > MAX_PACKET_BYTES {
+                self.packet.clear();
+            }
+            self.buf.drain(..total);
+        }
+        packets
+    }
+}
+
+fn u32_le(b: &[u8]) -> Option<(usize, &[u8])> {
+    let n = u32::from_le_bytes(b.get(..4)?.try_into().ok()?) as usize;
+    Some((n, &b[4..]))
+}
+
+// Vorbis (and Opus) comment header -> upper-cased field name -> value
+fn comments(packet: &[u8]) -> Option<HashMap<String, String>> {
+    let rest = packet
+        .strip_prefix(b"\x03vorbis")
+        .or_else(|| packet.strip_prefix(b"OpusTags"))?;
+    let (vendor_len, rest) = u32_le(rest)?;
+    let (count, mut rest) = u32_le(rest.get(vendor_len..)?)?;
+    let mut fields = HashMap::new();
+    for _ in 0..count {
+        let (len, r) = u32_le(rest)?;
+        let field = String::from_utf8_lossy(r.get(..len)?);
+        if let Some((k, v)) = field.split_once('=') {
+            fields.insert(k.to_ascii_uppercase(), v.trim().to_string());
+        }
+        rest = &r[len..];
+    }
+    Some(fields)
+}
+
+fn comments_to_now_playing(mut fields: HashMap<String, String>) -> Option<NowPlaying> {
+    let title = fields.remove("TITLE").filter(|t| !t.is_empty())?;
+    Some(NowPlaying {
+        is_playing: true,
+        track_name: Some(title),
+        artists: fields
+            .get("ARTIST")
+            .map(|a| parse_artists(a))
+            .unwrap_or_default(),
+        album: fields.remove("ALBUM").filter(|a| !a.is_empty()),
+        ..Default::default()
+    })
+}
+
+async fn publish(app: &tauri::AppHandle, np: Option<NowPlaying>) {
+    let np = match np {
+        Some(mut np) => {
+            artwork_lookup::fill_with_fallbacks(app, &mut np).await;
+            Some(np)
+        }
+        None => None,
+    };
+    app.state::<SharedStore>().lock().traktor_now_playing = np.clone();
+    providers::publish_pushed(app, Provider::Traktor, np);
+}
+
+#[tauri::command]
+pub fn get_traktor_config(state: State<'_, SharedStore>) -> TraktorConfig {
+    state.lock().traktor.config.clone()
+}
+
+#[tauri::command]
+pub fn set_traktor_config(window: tauri::Window, config: TraktorConfig) -> Result<(), String> {
+    if config.enabled && config.port == 0 {
+        return Err("Traktor listener port must be set".into());
+    }
+    let app = window.app_handle();
+    write_setting(app, "traktor", serde_json::json!(config))?;
+    restart(app, config);
+    // a listener turned on may be all the watcher has to poll
+    crate::start_watcher_if_needed(app, &app.state::<SharedStore>());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One Ogg page holding `body`, laced into 255-byte segments; `ends` says whether the
+    // packet ends on this page
+    fn page(continued: bool, body: &[u8], ends: bool) -> Vec<u8> {
+        let mut lacing = vec![255u8; body.len() / 255];
+        if ends {
+            lacing.push((body.len() % 255) as u8);
+        }
+        let mut p = b"OggS\0".to_vec();
+        p.push(if continued { 0x01 } else { 0 });
+        p.extend_from_slice(&[0; 20]);
+        p.push(lacing.len() as u8);
+        p.extend_from_slice(&lacing);
+        p.extend_from_slice(body);
+        p
+    }
+
+    fn vorbis_comments(fields: &[&str]) -> Vec<u8> {
+        let mut b = b"\x03vorbis".to_vec();
+        b.extend_from_slice(&6u32.to_le_bytes());
+        b.extend_from_slice(b"Mixxx ");
+        b.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+        for f in fields {
+            b.extend_from_slice(&(f.len() as u32).to_le_bytes());
+            b.extend_from_slice(f.as_bytes());
+        }
+        b.push(1);
+        b
+    }
+
+    fn now_playing(packet: &[u8]) -> Option<NowPlaying> {
+        comments(packet).and_then(comments_to_now_playing)
+    }
+
+    #[test]
+    fn comment_header() {
+        let packet = vorbis_comments(&["title=Windowlicker", "ARTIST=Aphex Twin", "Album="]);
+        let mut ogg = OggReader::default();
+        let packets =
+            ogg.push(&[page(false, b"\x01vorbis", true), page(false, &packet, true)].concat());
+        assert_eq!(packets.len(), 2);
+        assert!(now_playing(&packets[0]).is_none());
+        let np = now_playing(&packets[1]).unwrap();
+        assert_eq!(np.track_name.as_deref(), Some("Windowlicker"));
+        assert_eq!(np.artists, ["Aphex Twin"]);
+        assert_eq!(np.album, None);
+    }
+
+    #[test]
+    fn byte_at_a_time_with_garbage_and_page_breaks() {
+        let comment = format!("COMMENT={}", "x".repeat(600));
+        let packet = vorbis_comments(&["TITLE=Xtal", comment.as_str()]);
+        let (head, tail) = packet.split_at(510);
+        let stream = [
+            b"not ogg".to_vec(),
+            page(false, head, false),
+            page(true, tail, true),
+        ]
+        .concat();
+        let mut ogg = OggReader::default();
+        let packets: Vec<_> = stream.iter().flat_map(|b| ogg.push(&[*b])).collect();
+        assert_eq!(packets, [packet]);
+        assert_eq!(
+            now_playing(&packets[0]).unwrap().track_name.as_deref(),
+            Some("Xtal")
+        );
+    }
+
+    #[test]
+    fn truncated_page_waits_for_the_rest() {
+        let p = page(false, &vorbis_comments(&["TITLE=Xtal"]), true);
+        let mut ogg = OggReader::default();
+        assert!(ogg.push(&p[..20]).is_empty());
+        assert!(ogg.push(&p[20..p.len() - 1]).is_empty());
+        assert_eq!(ogg.push(&p[p.len() - 1..]).len(), 1);
+    }
+
+    #[test]
+    fn truncated_comments() {
+        let packet = vorbis_comments(&["TITLE=Xtal", "ARTIST=Aphex Twin"]);
+        assert!(comments(&packet[..packet.len() - 6]).is_none());
+        assert!(comments(b"\x03vorbis\x06\0").is_none());
+        assert!(comments(b"\x03vorbis").is_none());
+    }
+
+    #[test]
+    fn oversized_lengths() {
+        // vendor string longer than the packet
+        let mut packet = b"\x03vorbis".to_vec();
+        packet.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(comments(&packet).is_none());
+
+        // a field longer than the packet, and more fields than there are bytes
+        let mut packet = vorbis_comments(&[]);
+        packet.truncate(packet.len() - 5);
+        packet.extend_from_slice(&u32::MAX.to_le_bytes());
+        packet.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(comments(&packet).is_none());
+    }
+
+    #[test]
+    fn oversized_packet_is_dropped() {
+        let body = vec![0u8; 255 * 255];
+        let mut ogg = OggReader::default();
+        let mut packets = ogg.push(&page(false, &body, false));
+        for _ in 0..MAX_PACKET_BYTES / body.len() + 1 {
+            packets.extend(ogg.push(&page(true, &body, false)));
+        }
+        packets.extend(ogg.push(&page(true, b"tail", true)));
+        let packet = vorbis_comments(&["TITLE=Xtal"]);
+        packets.extend(ogg.push(&page(false, &packet, true)));
+
+        assert!(packets.iter().all(|p| p.len() <= MAX_PACKET_BYTES));
+        assert_eq!(packets.last(), Some(&packet));
+    }
+}
@@ -0,0 +1,180 @@
+// "Did you know" trivia: a short track fact or artist bio per track, for overlay rotations.
+//
+// Last.fm has track wikis as well as artist bios but needs an API key; TheAudioDB only has
+// artist bios but works with its public test key. Lookups are cached per track (misses too),
+// so each track costs at most one or two requests.
+
+use crate::{
+    artwork_lookup::HTTP, read_settings, write_setting, NowPlaying, SharedCaches, SharedStore,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+use url::Url;
+
+const MAX_CHARS: usize = 280;
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TriviaSource {
+    #[default]
+    Theaudiodb,
+    Lastfm,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, schemars::JsonSchema)]
+#[serde(default)]
+pub struct TriviaConfig {
+    pub enabled: bool,
+    pub source: TriviaSource,
+    pub lastfm_api_key: Option<String>,
+}
+
+pub fn load_config(app: &tauri::AppHandle) -> TriviaConfig {
+    read_settings(app)
+        .get("trivia")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+async fn get_json(url: Url) -> Result<serde_json::Value, String> {
+    let bytes = HTTP
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("trivia: {e}"))?
+        .bytes()
+        .await
+        .map_err(|e| format!("trivia body: {e}"))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("trivia json: {e}"))
+}
+
+async fn lastfm(key: &str, title: &str, artist: &str) -> Result<Option<String>, String> {
+    let call = |method: &str, extra: &[(&str, &str)]| {
+        let mut params = vec![
+            ("method", method),
+            ("api_key", key),
+            ("artist", artist),
+            ("format", "json"),
+            ("autocorrect", "1"),
+        ];
+        params.extend_from_slice(extra);
+        Url::parse_with_params("https://ws.audioscrobbler.com/2.0/", &params)
+            .map_err(|e| e.to_string())
+    };
+
+    let track = get_json(call("track.getInfo", &[("track", title)])?).await?;
+    if let Some(s) = track["track"]["wiki"]["summary"].as_str() {
+        return Ok(Some(s.to_string()));
+    }
+    let artist = get_json(call("artist.getInfo", &[])?).await?;
+    Ok(artist["artist"]["bio"]["summary"]
+        .as_str()
+        .map(str::to_string))
+}
+
+async fn theaudiodb(artist: &str) -> Result<Option<String>, String> {
+    let url = Url::parse_with_params(
+        "https://www.theaudiodb.com/api/v1/json/2/search.php",
+        &[("s", artist)],
+    )
+    .map_err(|e| e.to_string())?;
+    let v = get_json(url).await?;
+    Ok(v["artists"][0]["strBiographyEN"]
+        .as_str()
+        .map(str::to_string))
+}
+
+// Strips Last.fm's "Read more on Last.fm" link and other markup, and keeps the first
+// sentences that fit an overlay line.
+fn shorten(raw: &str) -> Option<String> {
+    let tags = Regex::new(r"<[^>]*>").ok()?;
+    let text = tags.replace_all(raw, "");
+    let text = text
+        .replace("Read more on Last.fm", "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut out = String::new();
+    for sentence in text.split_inclusive(". ") {
+        if !out.is_empty() && out.chars().count() + sentence.chars().count() > MAX_CHARS {
+            break;
+        }
+        out.push_str(sentence);
+    }
+    if out.chars().count() > MAX_CHARS {
+        out = out.chars().take(MAX_CHARS - 1).collect::<String>() + "…";
+    }
+    Some(out.trim().to_string())
+}
+
+// Fills `np.trivia` when trivia is turned on
+pub async fn enrich(app: &tauri::AppHandle, np: &mut NowPlaying) {
+    let state = app.state::<SharedStore>();
+    let config = state.lock().trivia.clone();
+    if !config.enabled {
+        return;
+    }
+    let (Some(title), Some(artist)) = (np.track_name.clone(), np.artists.first().cloned()) else {
+        return;
+    };
+    let key = format!("{}|{}", title.to_lowercase(), artist.to_lowercase());
+
+    let caches = app.state::<SharedCaches>();
+    let cached = caches.lock().trivia.get(&key).cloned();
+    let fact = match cached {
+        Some(fact) => fact,
+        None => {
+            let res = match (config.source, config.lastfm_api_key.as_deref()) {
+                (TriviaSource::Lastfm, Some(k)) if !k.is_empty() => {
+                    lastfm(k, &title, &artist).await
+                }
+                (TriviaSource::Lastfm, _) => Err("Last.fm trivia needs an API key".into()),
+                (TriviaSource::Theaudiodb, _) => theaudiodb(&artist).await,
+            };
+            let fact = match res {
+                Ok(raw) => raw.as_deref().and_then(shorten),
+                Err(e) => {
+                    eprintln!("[trivia] {e}");
+                    return;
+                }
+            };
+            caches.lock().trivia.insert(key, fact.clone());
+            fact
+        }
+    };
+    np.trivia = fact;
+}
+
+#[tauri::command]
+pub fn get_trivia_config(state: State<'_, SharedStore>) -> TriviaConfig {
+    state.lock().trivia.clone()
+}
+
+#[tauri::command]
+pub fn set_trivia_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: TriviaConfig,
+) -> Result<(), String> {
+    if config.enabled
+        && config.source == TriviaSource::Lastfm
+        && config.lastfm_api_key.as_deref().is_none_or(str::is_empty)
+    {
+        return Err("Last.fm trivia needs an API key".into());
+    }
+    write_setting(window.app_handle(), "trivia", serde_json::json!(config))?;
+    let mut s = state.lock();
+    // a different source gives different facts
+    if s.trivia.source != config.source {
+        window.state::<SharedCaches>().lock().trivia.clear();
+    }
+    s.trivia = config;
+    Ok(())
+}
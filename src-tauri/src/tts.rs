@@ -0,0 +1,230 @@
+// Spoken "Now playing X by Y" on track changes, for radio-style streams and for listeners who
+// don't watch the screen. Uses the Windows speech voices (WinRT SpeechSynthesizer); other
+// platforms only keep the settings.
+
+use crate::{read_settings, write_setting, NowPlaying, SharedStore};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, schemars::JsonSchema)]
+#[serde(default)]
+pub struct TtsConfig {
+    pub enabled: bool,
+    // display name (or part of it) of an installed voice; the system default otherwise
+    pub voice: Option<String>,
+    // 0-100
+    pub volume: u8,
+    // changes within this many seconds of the last announcement wait for it to end, and only
+    // the one still playing then is read out, so skipping through a playlist doesn't queue up
+    // a string of titles
+    pub cooldown_secs: u64,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            voice: None,
+            volume: 80,
+            cooldown_secs: 20,
+        }
+    }
+}
+
+pub fn load_config(app: &tauri::AppHandle) -> TtsConfig {
+    read_settings(app)
+        .get("tts")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn announcement(np: &NowPlaying) -> Option<String> {
+    let title = np.track_name.as_deref()?;
+    Some(if np.artists.is_empty() {
+        format!("Now playing {title}")
+    } else {
+        format!("Now playing {title} by {}", np.artists.join(", "))
+    })
+}
+
+// Called once per new track (see `settle_now_playing`)
+pub fn announce(app: &tauri::AppHandle, np: &NowPlaying) {
+    if !np.is_playing {
+        return;
+    }
+    let Some(text) = announcement(np) else {
+        return;
+    };
+    let config = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if !s.tts.enabled {
+            return;
+        }
+        let cooldown = Duration::from_secs(s.tts.cooldown_secs);
+        let left = s
+            .tts_last_spoken
+            .and_then(|t| cooldown.checked_sub(t.elapsed()))
+            .filter(|d| !d.is_zero());
+        if let Some(left) = left {
+            // the latest track wins; the first one held back starts the timer
+            if s.tts_deferred.replace(text).is_none() {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    tokio::time::sleep(left).await;
+                    announce_deferred(&app);
+                });
+            }
+            return;
+        }
+        s.tts_last_spoken = Some(Instant::now());
+        s.tts.clone()
+    };
+    speak(text, config);
+}
+
+// End of the cooldown: reads out the held-back track if it's still the one playing
+fn announce_deferred(app: &tauri::AppHandle) {
+    let (text, config) = {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        let Some(text) = s.tts_deferred.take() else {
+            return;
+        };
+        let current = s
+            .last_now_playing
+            .as_ref()
+            .filter(|np| np.is_playing)
+            .and_then(announcement);
+        if !s.tts.enabled || current.as_deref() != Some(text.as_str()) {
+            return;
+        }
+        s.tts_last_spoken = Some(Instant::now());
+        (text, s.tts.clone())
+    };
+    speak(text, config);
+}
+
+fn speak(text: String, config: TtsConfig) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = voice::speak(&text, &config).await {
+            eprintln!("[tts] {e}");
+        }
+    });
+}
+
+#[cfg(windows)]
+mod voice {
+    use super::TtsConfig;
+    use parking_lot::Mutex;
+    use windows::core::HSTRING;
+    use windows::Media::Core::MediaSource;
+    use windows::Media::Playback::MediaPlayer;
+    use windows::Media::SpeechSynthesis::{SpeechSynthesizer, VoiceInformation};
+
+    // the announcement being played; replacing it cuts off the previous one
+    static PLAYER: Mutex<Option<MediaPlayer>> = Mutex::new(None);
+
+    pub fn names() -> Result<Vec<String>, String> {
+        let voices = SpeechSynthesizer::AllVoices().map_err(|e| format!("list voices: {e:?}"))?;
+        Ok(voices
+            .into_iter()
+            .filter_map(|v| v.DisplayName().ok())
+            .map(|n| n.to_string())
+            .collect())
+    }
+
+    fn find(name: &str) -> Option<VoiceInformation> {
+        let name = name.to_lowercase();
+        SpeechSynthesizer::AllVoices().ok()?.into_iter().find(|v| {
+            v.DisplayName()
+                .is_ok_and(|n| n.to_string().to_lowercase().contains(&name))
+        })
+    }
+
+    pub async fn speak(text: &str, config: &TtsConfig) -> Result<(), String> {
+        let synth = SpeechSynthesizer::new().map_err(|e| format!("speech synthesizer: {e:?}"))?;
+        if let Some(voice) = config.voice.as_deref().filter(|v| !v.is_empty()) {
+            match find(voice) {
+                Some(v) => synth
+                    .SetVoice(&v)
+                    .map_err(|e| format!("set voice: {e:?}"))?,
+                None => eprintln!("[tts] voice {voice:?} not installed, using the default"),
+            }
+        }
+        let stream = synth
+            .SynthesizeTextToStreamAsync(&HSTRING::from(text))
+            .map_err(|e| format!("synthesize: {e:?}"))?
+            .await
+            .map_err(|e| format!("synthesize: {e:?}"))?;
+        let content_type = stream
+            .ContentType()
+            .map_err(|e| format!("speech stream: {e:?}"))?;
+        let source = MediaSource::CreateFromStream(&stream, &content_type)
+            .map_err(|e| format!("media source: {e:?}"))?;
+
+        let player = MediaPlayer::new().map_err(|e| format!("media player: {e:?}"))?;
+        player
+            .SetVolume(f64::from(config.volume.min(100)) / 100.0)
+            .map_err(|e| format!("set volume: {e:?}"))?;
+        player
+            .SetSource(&source)
+            .map_err(|e| format!("set source: {e:?}"))?;
+        player.Play().map_err(|e| format!("play: {e:?}"))?;
+        *PLAYER.lock() = Some(player);
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+mod voice {
+    use super::TtsConfig;
+
+    pub fn names() -> Result<Vec<String>, String> {
+        Ok(Vec::new())
+    }
+
+    pub async fn speak(_text: &str, _config: &TtsConfig) -> Result<(), String> {
+        Err("Spoken announcements are only available on Windows".into())
+    }
+}
+
+#[tauri::command]
+pub fn get_tts_config(state: State<'_, SharedStore>) -> TtsConfig {
+    state.lock().tts.clone()
+}
+
+#[tauri::command]
+pub fn set_tts_config(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    config: TtsConfig,
+) -> Result<(), String> {
+    if config.volume > 100 {
+        return Err("Volume must be between 0 and 100".into());
+    }
+    write_setting(window.app_handle(), "tts", serde_json::json!(config))?;
+    state.lock().tts = config;
+    Ok(())
+}
+
+// Installed voices, for the voice picker
+#[tauri::command]
+pub fn list_tts_voices() -> Result<Vec<String>, String> {
+    voice::names()
+}
+
+// Reads the current track out right away, ignoring the cooldown; for trying out settings
+#[tauri::command]
+pub async fn test_tts(state: State<'_, SharedStore>) -> Result<(), String> {
+    let (config, np) = {
+        let s = state.lock();
+        (s.tts.clone(), s.last_now_playing.clone())
+    };
+    let text = np
+        .as_ref()
+        .and_then(announcement)
+        .unwrap_or_else(|| "Now playing nothing".into());
+    voice::speak(&text, &config).await
+}
@@ -0,0 +1,155 @@
+// Self-update through the Tauri updater. Releases publish a signed `latest.json`; the beta
+// channel reads it from a rolling `beta` release instead of the latest stable one.
+//
+// Builds without an updater public key in tauri.conf.json can't verify anything, so checks
+// are refused there rather than failing halfway through an install.
+
+use crate::{events, read_settings, write_setting, SharedStore};
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+use tauri_plugin_updater::UpdaterExt;
+
+const STABLE_URL: &str =
+    "https://github.com/JalenDmarion25/spotify-now-playing-v2/releases/latest/download/latest.json";
+const BETA_URL: &str =
+    "https://github.com/JalenDmarion25/spotify-now-playing-v2/releases/download/beta/latest.json";
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+impl Channel {
+    fn endpoint(self) -> url::Url {
+        let raw = match self {
+            Channel::Stable => STABLE_URL,
+            Channel::Beta => BETA_URL,
+        };
+        url::Url::parse(raw).expect("valid updater endpoint")
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct UpdateInfo {
+    version: String,
+    current_version: String,
+    notes: Option<String>,
+    date: Option<String>,
+}
+
+#[derive(Serialize, Clone, schemars::JsonSchema)]
+pub struct Progress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+pub fn load_channel(app: &tauri::AppHandle) -> Channel {
+    read_settings(app)
+        .get("update_channel")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn has_pubkey(app: &tauri::AppHandle) -> bool {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|u| u.get("pubkey"))
+        .and_then(|k| k.as_str())
+        .is_some_and(|k| !k.trim().is_empty())
+}
+
+#[tauri::command]
+pub fn get_update_channel(state: State<'_, SharedStore>) -> Channel {
+    state.lock().update_channel
+}
+
+#[tauri::command]
+pub fn set_update_channel(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+    channel: Channel,
+) -> Result<(), String> {
+    write_setting(
+        window.app_handle(),
+        "update_channel",
+        serde_json::json!(channel),
+    )?;
+    let mut s = state.lock();
+    s.update_channel = channel;
+    // found on the other channel
+    s.pending_update = None;
+    Ok(())
+}
+
+// `None` when already up to date. The update found is kept for `install_update`.
+#[tauri::command]
+pub async fn check_for_updates(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+) -> Result<Option<UpdateInfo>, String> {
+    let app = window.app_handle();
+    if !has_pubkey(app) {
+        return Err("This build has no updater key, updates are disabled".into());
+    }
+    let channel = state.lock().update_channel;
+    let update = app
+        .updater_builder()
+        .endpoints(vec![channel.endpoint()])
+        .map_err(|e| format!("updater: {e}"))?
+        .build()
+        .map_err(|e| format!("updater: {e}"))?
+        .check()
+        .await
+        .map_err(|e| format!("check for updates: {e}"))?;
+
+    let info = update.as_ref().map(|u| UpdateInfo {
+        version: u.version.clone(),
+        current_version: u.current_version.clone(),
+        notes: u.body.clone(),
+        date: u.date.map(|d| d.to_string()),
+    });
+    state.lock().pending_update = update;
+    Ok(info)
+}
+
+// Downloads and installs the update from the last check, emitting `update_progress`
+// ({ downloaded, total }) per chunk, then restarts into the new version.
+#[tauri::command]
+pub async fn install_update(
+    state: State<'_, SharedStore>,
+    window: tauri::Window,
+) -> Result<(), String> {
+    let app = window.app_handle().clone();
+    let update = state
+        .lock()
+        .pending_update
+        .take()
+        .ok_or("No update to install, check for updates first")?;
+
+    let mut downloaded = 0u64;
+    let progress_app = app.clone();
+    update
+        .download_and_install(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                events::emit(
+                    &progress_app,
+                    "update_progress",
+                    Progress { downloaded, total },
+                );
+            },
+            || events::emit(&app, "update_downloaded", ()),
+        )
+        .await
+        .map_err(|e| format!("install update: {e}"))?;
+
+    crate::safe_mode::mark_clean_exit(&app);
+    app.restart();
+}
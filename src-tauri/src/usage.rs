@@ -0,0 +1,130 @@
+// Local usage statistics: how often features get used and how long the app runs, kept in
+// `usage.json` next to the other app data. Nothing here is ever sent anywhere; it's for
+// users to look at (or paste into a bug report) when describing their setup.
+
+use crate::SharedStore;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, time::Instant};
+use tauri::{Manager, State};
+
+#[derive(Serialize, Deserialize, Clone, Default, schemars::JsonSchema)]
+#[serde(default)]
+pub struct Totals {
+    // "exports", "source:spotify", ... -> count
+    counts: BTreeMap<String, u64>,
+    // finished runs only; the current one is added on top when read
+    uptime_secs: u64,
+    runs: u64,
+    // RFC 3339, when stats started being recorded
+    since: Option<String>,
+}
+
+pub struct Usage {
+    totals: Totals,
+    started: Instant,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self {
+            totals: Totals::default(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Usage {
+    pub fn session_uptime(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    // totals with this run's uptime folded in
+    fn snapshot(&self) -> Totals {
+        let mut t = self.totals.clone();
+        t.uptime_secs += self.started.elapsed().as_secs();
+        t
+    }
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+pub struct UsageStats {
+    #[serde(flatten)]
+    totals: Totals,
+    session_uptime_secs: u64,
+    app_version: String,
+    os: &'static str,
+}
+
+fn stats_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    crate::portable::data_dir(app)
+        .ok()
+        .map(|d| d.join("usage.json"))
+}
+
+pub fn load(app: &tauri::AppHandle) -> Usage {
+    let mut totals: Totals = stats_path(app)
+        .and_then(|p| std::fs::read(p).ok())
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .unwrap_or_default();
+    totals.runs += 1;
+    totals
+        .since
+        .get_or_insert_with(|| chrono::Local::now().to_rfc3339());
+    Usage {
+        totals,
+        started: Instant::now(),
+    }
+}
+
+fn save(app: &tauri::AppHandle, totals: &Totals) {
+    let Some(path) = stats_path(app) else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(bytes) = serde_json::to_vec_pretty(totals) {
+        if let Err(e) = std::fs::write(&path, bytes) {
+            eprintln!("[usage] save: {e}");
+        }
+    }
+}
+
+// Bumps a counter and writes the stats out
+pub fn record(app: &tauri::AppHandle, what: &str) {
+    let state = app.state::<SharedStore>();
+    let snapshot = {
+        let mut s = state.lock();
+        *s.usage.totals.counts.entry(what.to_string()).or_default() += 1;
+        s.usage.snapshot()
+    };
+    save(app, &snapshot);
+}
+
+// On the way out, so this run's uptime is kept
+pub fn flush(app: &tauri::AppHandle) {
+    let snapshot = app.state::<SharedStore>().lock().usage.snapshot();
+    save(app, &snapshot);
+}
+
+#[tauri::command]
+pub fn get_usage_stats(state: State<'_, SharedStore>, window: tauri::Window) -> UsageStats {
+    let s = state.lock();
+    UsageStats {
+        totals: s.usage.snapshot(),
+        session_uptime_secs: s.usage.started.elapsed().as_secs(),
+        app_version: window.app_handle().package_info().version.to_string(),
+        os: std::env::consts::OS,
+    }
+}
+
+#[tauri::command]
+pub fn reset_usage_stats(state: State<'_, SharedStore>, window: tauri::Window) {
+    let totals = {
+        let mut s = state.lock();
+        s.usage = Usage::default();
+        s.usage.totals.since = Some(chrono::Local::now().to_rfc3339());
+        s.usage.totals.clone()
+    };
+    save(window.app_handle(), &totals);
+}
@@ -0,0 +1,65 @@
+// Each export profile's files kept in memory and served by `server` as
+// `GET /files/{profile}/{name}`, for PCs where nothing may be written to disk: OBS reads them with
+// a URL-based text source or a browser source instead. They're exactly what the profile writes
+// (templates, text format, artwork size) and are refreshed with every write, whether or not
+// the disk takes it. The export without profiles is kept under the empty name.
+//
+// Also holds the cover of the current track as rendered for the exports, for `GET /artwork`.
+
+use crate::export::OutputFile;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+
+// file name -> (content type, bytes)
+type Files = BTreeMap<String, (&'static str, Vec<u8>)>;
+
+// profile name -> its files
+static FILES: Lazy<RwLock<BTreeMap<String, Files>>> = Lazy::new(RwLock::default);
+static COVER: Lazy<RwLock<Option<Vec<u8>>>> = Lazy::new(RwLock::default);
+
+// `files` as from `export::profile_files`
+pub fn set(profile: &str, files: Vec<OutputFile>) {
+    let files = files
+        .into_iter()
+        .map(|(name, content_type, body)| (name, (content_type, body)))
+        .collect();
+    FILES.write().insert(profile.to_string(), files);
+}
+
+// Drops the files of profiles that are gone
+pub fn retain(keep: impl Fn(&str) -> bool) {
+    FILES.write().retain(|profile, _| keep(profile));
+}
+
+// Any casing, like the names profiles write
+pub fn get(profile: &str, name: &str) -> Option<(&'static str, Vec<u8>)> {
+    FILES
+        .read()
+        .get(profile)?
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, file)| file.clone())
+}
+
+// `{profile}/{name}`, or just `{name}` for the export without profiles
+pub fn names() -> Vec<String> {
+    FILES
+        .read()
+        .iter()
+        .flat_map(|(profile, files)| {
+            files.keys().map(move |name| match profile.as_str() {
+                "" => name.clone(),
+                profile => format!("{profile}/{name}"),
+            })
+        })
+        .collect()
+}
+
+pub fn set_cover(png: Option<Vec<u8>>) {
+    *COVER.write() = png;
+}
+
+pub fn cover() -> Option<Vec<u8>> {
+    COVER.read().clone()
+}
@@ -0,0 +1,105 @@
+// Keeps the watcher from freezing on an await that never returns: a Spotify request on a
+// half-dead connection, a WinRT call GSMTC never completes, an artwork host that accepts the
+// socket and then goes quiet. External calls go through `within` so they give up after a
+// while, and in case something still hangs, the watcher reports when it expects to be back
+// and `start` restarts it once it's overdue.
+
+use crate::{events, SharedStore};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+pub const SPOTIFY_TIMEOUT: Duration = Duration::from_secs(15);
+#[cfg(windows)]
+pub const GSMTC_TIMEOUT: Duration = Duration::from_secs(5);
+pub const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+// one whole watcher poll, enrichment included: several calls under their own timeouts add up
+// to more than this, so a slow round is cut short instead of looking like a hang
+pub const POLL_TIMEOUT: Duration = Duration::from_secs(45);
+// on top of the expected sleep; a poll gives up by `POLL_TIMEOUT`, the rest is the margin
+// for what the watcher does around it
+const STALL_AFTER: Duration = Duration::from_secs(POLL_TIMEOUT.as_secs() + 15);
+const CHECK_EVERY: Duration = Duration::from_secs(10);
+
+pub async fn within<F: Future>(what: &str, limit: Duration, fut: F) -> Result<F::Output, String> {
+    tokio::time::timeout(limit, fut).await.map_err(|_| {
+        eprintln!("[watchdog] {what} timed out after {}s", limit.as_secs());
+        format!("{what} timed out")
+    })
+}
+
+#[derive(Default)]
+pub struct Heartbeat {
+    // the watcher loop should have come around by then; None while it waits indefinitely
+    due: Option<Instant>,
+    restarts: u32,
+}
+
+impl Heartbeat {
+    pub fn stop(&mut self) {
+        self.due = None;
+    }
+}
+
+// Called by the watcher before a poll (`wait` zero) and before sleeping
+pub fn expect_within(state: &SharedStore, wait: Duration) {
+    state.lock().watchdog.due = Some(Instant::now() + wait + STALL_AFTER);
+}
+
+// Paused: nothing to expect until someone resumes it
+pub fn idle(state: &SharedStore) {
+    state.lock().watchdog.stop();
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct WatcherRestarted {
+    // seconds the watcher was overdue by
+    pub stalled_secs: u64,
+    // restarts since the app started
+    pub restarts: u32,
+}
+
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<SharedStore>();
+        loop {
+            tokio::time::sleep(CHECK_EVERY).await;
+            let overdue = {
+                let s = state.lock();
+                if !s.watch_started || s.watcher_paused {
+                    continue;
+                }
+                s.watchdog
+                    .due
+                    .map(|due| Instant::now().saturating_duration_since(due))
+                    .filter(|d| !d.is_zero())
+            };
+            let Some(overdue) = overdue else {
+                continue;
+            };
+
+            let restarts = {
+                let mut s = state.lock();
+                crate::cancel_watcher(&mut s);
+                s.watchdog.stop();
+                s.watchdog.restarts += 1;
+                s.watchdog.restarts
+            };
+            let stalled_secs = (STALL_AFTER + overdue).as_secs();
+            eprintln!("[watchdog] watcher stuck for {stalled_secs}s, restarting (#{restarts})");
+            crate::start_watcher_if_needed(&app, &state);
+            events::emit(
+                &app,
+                "watcher_restarted",
+                WatcherRestarted {
+                    stalled_secs,
+                    restarts,
+                },
+            );
+        }
+    });
+}
@@ -0,0 +1,303 @@
+// WebNowPlaying bridge.
+//
+// The WebNowPlaying browser extension (the same one Rainmeter's plugin talks to) connects to
+// ws://127.0.0.1:8974 and pushes "KEY:VALUE" text frames for whatever the tab is playing
+// (SoundCloud, Bandcamp, YouTube, ...). Every tab gets its own connection, so we keep one
+// player per socket and surface the most relevant one as a regular `NowPlaying`.
+//
+// Off until turned on in the settings, since Rainmeter's own plugin wants the same port; the
+// port can be changed to match an extension set up for another one.
+
+use crate::{
+    parse_artists,
+    providers::{self, Provider},
+    read_settings, resilience, write_setting, NowPlaying, SharedStore,
+};
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc, time::Instant};
+use tauri::{Manager, State};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Serialize, Deserialize, Clone, schemars::JsonSchema)]
+#[serde(default)]
+pub struct WnpConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for WnpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8974,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WnpInput {
+    config: WnpConfig,
+    cancel: Option<CancellationToken>,
+}
+
+impl WnpInput {
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+}
+
+#[derive(Default, Clone)]
+struct WnpPlayer {
+    player: String,
+    // 0 = stopped, 1 = playing, 2 = paused
+    state: u8,
+    title: String,
+    artist: String,
+    album: String,
+    cover: String,
+    duration_secs: Option<u64>,
+    position_secs: Option<u64>,
+    // 0 = off, 1 = all, 2 = one
+    repeat: u8,
+    updated: Option<Instant>,
+}
+
+type Players = Arc<Mutex<HashMap<u64, WnpPlayer>>>;
+
+pub fn init(app: &tauri::AppHandle) {
+    let config: WnpConfig = read_settings(app)
+        .get("webnowplaying")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    restart(app, config);
+}
+
+fn restart(app: &tauri::AppHandle, config: WnpConfig) {
+    let token = CancellationToken::new();
+    {
+        let state = app.state::<SharedStore>();
+        let mut s = state.lock();
+        if let Some(old) = s.wnp.cancel.take() {
+            old.cancel();
+        }
+        s.wnp_now_playing = None;
+        s.wnp.config = config.clone();
+        if !config.enabled {
+            return;
+        }
+        s.wnp.cancel = Some(token.clone());
+    }
+
+    let app = app.clone();
+    let port = config.port;
+    tauri::async_runtime::spawn(async move {
+        // Most likely Rainmeter's own WebNowPlaying plugin owns the port when this fails
+        let bind = || async {
+            TcpListener::bind(("127.0.0.1", port))
+                .await
+                .map_err(|e| format!("bind 127.0.0.1:{port} failed: {e}"))
+        };
+        let Some(listener) = resilience::retry(&app, "webnowplaying", &token, bind).await else {
+            return;
+        };
+
+        let players: Players = Arc::new(Mutex::new(HashMap::new()));
+        let mut next_id = 0u64;
+
+        loop {
+            let stream = tokio::select! {
+                _ = token.cancelled() => break,
+                res = listener.accept() => match res {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        eprintln!("[wnp] accept failed: {e}");
+                        continue;
+                    }
+                },
+            };
+            next_id += 1;
+            let id = next_id;
+            let app = app.clone();
+            let players = players.clone();
+            let token = token.clone();
+
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = handle_connection(&app, &players, id, stream, &token).await {
+                    eprintln!("[wnp] connection {id}: {e}");
+                }
+                // Turned off or moved to another port: `restart` already cleared the track
+                if token.is_cancelled() {
+                    return;
+                }
+                // Tab closed / extension reloaded: forget that player
+                players.lock().remove(&id);
+                publish(&app, &players);
+            });
+        }
+    });
+}
+
+async fn handle_connection(
+    app: &tauri::AppHandle,
+    players: &Players,
+    id: u64,
+    stream: TcpStream,
+    token: &CancellationToken,
+) -> Result<(), String> {
+    let mut ws = tokio_tungstenite::accept_async(stream)
+        .await
+        .map_err(|e| format!("handshake: {e}"))?;
+
+    loop {
+        let msg = tokio::select! {
+            _ = token.cancelled() => break,
+            msg = ws.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+        };
+        let msg = msg.map_err(|e| format!("read: {e}"))?;
+        if msg.is_close() {
+            break;
+        }
+        let Ok(text) = msg.to_text() else {
+            continue;
+        };
+        let Some((key, value)) = text.split_once(':') else {
+            continue;
+        };
+
+        let changed = {
+            let mut g = players.lock();
+            apply_field(g.entry(id).or_default(), key, value)
+        };
+        if changed {
+            publish(app, players);
+        }
+    }
+    Ok(())
+}
+
+// Returns true when the player should be published again. POSITION ticks every second; it's
+// published so progress and seeks follow the tab, but the position alone doesn't make
+// `now_playing_update` fire (see `without_ticking`).
+fn apply_field(p: &mut WnpPlayer, key: &str, value: &str) -> bool {
+    let value = value.trim();
+    let changed = match key.trim().to_ascii_uppercase().as_str() {
+        "PLAYER" => replace(&mut p.player, value),
+        "STATE" => {
+            let s = value.parse().unwrap_or(0);
+            let changed = p.state != s;
+            p.state = s;
+            changed
+        }
+        "TITLE" => replace(&mut p.title, value),
+        "ARTIST" => replace(&mut p.artist, value),
+        "ALBUM" => replace(&mut p.album, value),
+        "COVER" => replace(&mut p.cover, value),
+        "DURATION" => {
+            let duration = parse_timestamp(value);
+            let changed = p.duration_secs != duration;
+            p.duration_secs = duration;
+            changed
+        }
+        "POSITION" => {
+            let position = parse_timestamp(value);
+            let changed = p.position_secs != position;
+            p.position_secs = position;
+            changed
+        }
+        "REPEAT" => {
+            p.repeat = value.parse().unwrap_or(0);
+            false
+        }
+        "ERROR" | "ERRORDEBUG" => {
+            eprintln!("[wnp] extension reported: {value}");
+            false
+        }
+        // VOLUME, RATING, SHUFFLE, ... are not shown anywhere yet
+        _ => false,
+    };
+    p.updated = Some(Instant::now());
+    changed
+}
+
+fn replace(field: &mut String, value: &str) -> bool {
+    if field == value {
+        return false;
+    }
+    *field = value.to_string();
+    true
+}
+
+// "3:45" / "1:02:03" -> seconds; None for anything that doesn't fit in milliseconds either
+fn parse_timestamp(s: &str) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+    let secs = s.split(':').try_fold(0u64, |acc, part| {
+        acc.checked_mul(60)?
+            .checked_add(part.trim().parse::<u64>().ok()?)
+    })?;
+    secs.checked_mul(1000).map(|_| secs)
+}
+
+// Prefer the most recently updated tab that is actually playing, else the most recent one.
+fn active_player(players: &HashMap<u64, WnpPlayer>) -> Option<WnpPlayer> {
+    let with_title = || players.values().filter(|p| !p.title.is_empty());
+    with_title()
+        .filter(|p| p.state == 1)
+        .max_by_key(|p| p.updated)
+        .or_else(|| with_title().max_by_key(|p| p.updated))
+        .cloned()
+}
+
+fn to_now_playing(p: &WnpPlayer) -> NowPlaying {
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    NowPlaying {
+        is_playing: p.state == 1,
+        track_name: non_empty(&p.title),
+        artists: parse_artists(&p.artist),
+        album: non_empty(&p.album),
+        artwork_url: non_empty(&p.cover),
+        artwork_path: None,
+        position_ms: p.position_secs.map(|s| s * 1000),
+        duration_ms: p.duration_secs.map(|s| s * 1000),
+        repeat_mode: Some(
+            match p.repeat {
+                1 => "context",
+                2 => "track",
+                _ => "off",
+            }
+            .to_string(),
+        ),
+        ..Default::default()
+    }
+}
+
+fn publish(app: &tauri::AppHandle, players: &Players) {
+    let np = active_player(&players.lock()).map(|p| to_now_playing(&p));
+    app.state::<SharedStore>().lock().wnp_now_playing = np.clone();
+    providers::publish_pushed(app, Provider::WebNowPlaying, np);
+}
+
+#[tauri::command]
+pub fn get_webnowplaying_config(state: State<'_, SharedStore>) -> WnpConfig {
+    state.lock().wnp.config.clone()
+}
+
+#[tauri::command]
+pub fn set_webnowplaying_config(window: tauri::Window, config: WnpConfig) -> Result<(), String> {
+    if config.enabled && config.port == 0 {
+        return Err("WebNowPlaying port must be set".into());
+    }
+    let app = window.app_handle();
+    write_setting(app, "webnowplaying", serde_json::json!(config))?;
+    restart(app, config);
+    // a listener turned on may be all the watcher has to poll
+    crate::start_watcher_if_needed(app, &app.state::<SharedStore>());
+    Ok(())
+}
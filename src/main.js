@@ -1,6 +1,9 @@
 const { invoke, convertFileSrc } = window.__TAURI__.core;
-const { listen } = window.__TAURI__.event;
-const { WebviewWindow, getAll } = window.__TAURI__.webviewWindow;
+const { WebviewWindow, getAll, getCurrentWebviewWindow } =
+  window.__TAURI__.webviewWindow;
+// window-scoped (see widget.js)
+const listen = (name, handler) =>
+  getCurrentWebviewWindow().listen(name, handler);
 const { open } = window.__TAURI__.dialog;
 
 document.addEventListener("DOMContentLoaded", async () => {
@@ -214,54 +217,41 @@ document.addEventListener("DOMContentLoaded", async () => {
   // --- Poll GSMTC and log when the track changes ---
   let lastGSMTCKey = "";
 
+  // GSMTC reports paused sessions too, so a track name is what counts as active
   function gsmKey(d) {
-    const isPlaying =
-      (d?.status || "").toLowerCase() === "playing" ||
-      (d?.status || "").toLowerCase() === "paused" ||
-      d?.position_ms != null;
-
-    if (!isPlaying) return null;
-
-    const title = (d?.title || "").trim().toLowerCase();
-    const artists = (Array.isArray(d?.artists) ? d.artists : [d?.artist || ""])
+    if (!d?.track_name) return null;
+    const title = d.track_name.trim().toLowerCase();
+    const artists = (d.artists || [])
       .map((a) => (a || "").trim().toLowerCase())
       .filter(Boolean)
       .join(","); // stable joined list
-    const album = (d?.album || "").trim().toLowerCase();
-
-    if (!title && !artists && !album) return null; // <-- use artists
-    return [title, artists, album].join("||"); // <-- use artists
+    const album = (d.album || "").trim().toLowerCase();
+    return [title, artists, album].join("||");
   }
 
-  async function pollGSMTC() {
+  function onGSMTC(d) {
     try {
-      const d = await window.__TAURI__.core.invoke("get_current_playing_gsmtc");
       const key = gsmKey(d);
       if (key && key !== lastGSMTCKey) {
         lastGSMTCKey = key;
-        const artistsText =
-          Array.isArray(d?.artists) && d.artists.length
-            ? d.artists.join(", ")
-            : d?.artist || "?";
+        const artistsText = (d.artists || []).join(", ") || "?";
         console.log(
-          `[GSMTC] Now playing: Song: ${
-            d?.title || "?"
-          } — Artist: ${artistsText}` + (d?.album ? ` — Album: ${d.album}` : "")
+          `[GSMTC] Now playing: Song: ${d.track_name} — Artist: ${artistsText}` +
+            (d.album ? ` — Album: ${d.album}` : "")
         );
       }
-      // NEW: render every poll (cheap)
       renderNowPlayingGSMTC(d);
     } catch (e) {
       // optional: quiet
     }
   }
 
-  // kick off immediately, then poll every 2s
-  pollGSMTC();
-  const gsmPoll = setInterval(pollGSMTC, 2000);
-
-  // (optional) stop polling when page unloads
-  window.addEventListener("beforeunload", () => clearInterval(gsmPoll));
+  // read once now, then the backend pushes `gsmtc_update` whenever the session changes
+  invoke("get_current_playing_gsmtc").then(onGSMTC).catch(() => {});
+  const gsmUnlisten = await listen("gsmtc_update", (evt) =>
+    onGSMTC(evt.payload)
+  );
+  window.addEventListener("beforeunload", () => gsmUnlisten());
 
   const THEME_KEYS = {
     bg: "theme:bg",
@@ -395,6 +385,10 @@ document.addEventListener("DOMContentLoaded", async () => {
         album: d.album || null,
         artworkUrl: d.artwork_url || null,
         artworkPath: d.artwork_path || null,
+        contextLabel: d.context_label || null,
+        trivia: d.trivia || null,
+        episode: d.episode || null,
+        classical: d.classical || null,
       },
     });
   }
@@ -439,6 +433,16 @@ document.addEventListener("DOMContentLoaded", async () => {
     console.log("Restore Catch:", e);
   }
 
+  // safe mode: integrations were skipped, let the user fix settings and then restart
+  try {
+    const safe = await invoke("get_safe_mode");
+    if (safe?.active) {
+      const why = safe.reason === "crash" ? "the last run didn't exit cleanly" : "requested";
+      setStatus(`Safe mode (${why}). Click here to restart normally.`, "error");
+      statusEl.addEventListener("click", () => invoke("restart_normally"), { once: true });
+    }
+  } catch {}
+
   // load and display the saved folder on startup
   try {
     const existingDir = await invoke("get_local_art_dir");
@@ -464,20 +468,15 @@ document.addEventListener("DOMContentLoaded", async () => {
     art.removeAttribute("src");
     np.textContent = "";
 
-    const status = (d?.status || "").toLowerCase();
-    const active =
-      ["playing", "paused"].includes(status) || d?.position_ms != null;
-    if (!active) {
+    // paused sessions still show their track
+    if (!d.track_name) {
       np.textContent = "Nothing is currently playing.";
       return;
     }
 
-    const title = (d?.title || "").trim();
-    const artistsText =
-      Array.isArray(d?.artists) && d.artists.length
-        ? d.artists.join(", ")
-        : (d?.artist || "").trim(); // fallback if array missing
-    const album = (d?.album || "").trim();
+    const title = d.track_name.trim();
+    const artistsText = (d.artists || []).join(", ");
+    const album = (d.album || "").trim();
 
     if (title || artistsText || album) {
       np.textContent = `▶ ${title}${artistsText ? " — " + artistsText : ""}${
@@ -494,13 +493,26 @@ document.addEventListener("DOMContentLoaded", async () => {
   function renderNowPlaying(d) {
     if (!artworkEl) return;
 
+    // new track whose artwork is still on its way: keep the previous image up
+    const holdArt = !!(
+      d?.is_playing &&
+      d.pending_artwork &&
+      artworkEl.getAttribute("src")
+    );
+
     // reset visuals
-    artworkEl.style.display = "none";
-    artworkEl.removeAttribute("src");
+    if (!holdArt) {
+      artworkEl.style.display = "none";
+      artworkEl.removeAttribute("src");
+    }
     if (nowPlayingEl) nowPlayingEl.textContent = "";
 
     // nothing playing
-    if (!d || !d.is_playing || (!d.artwork_url && !d.artwork_path)) {
+    if (
+      !d ||
+      !d.is_playing ||
+      (!d.artwork_url && !d.artwork_path && !holdArt)
+    ) {
       if (nowPlayingEl)
         nowPlayingEl.textContent = "Nothing is currently playing.";
       return;
@@ -543,6 +555,24 @@ document.addEventListener("DOMContentLoaded", async () => {
     if (artworkEl) artworkEl.style.display = "none";
   });
 
+  await listen("watcher_status", (evt) => {
+    const s = evt.payload;
+    if (s.state === "ok") {
+      setStatus("Connected!!!", "connected");
+      return;
+    }
+    const why = s.error === "rate_limited" ? "Rate limited" : "Spotify error";
+    setStatus(`${why}, retrying in ${s.retry_in_secs}s`, "not-connected");
+  });
+
+  // the sign-in moved to a free port; Spotify only accepts registered redirect URIs
+  await listen("redirect_uri_changed", (evt) => {
+    setStatus(
+      `Add ${evt.payload.redirect_uri} to Redirect URIs in your Spotify app settings`,
+      "not-connected"
+    );
+  });
+
   // choose local folder (requires tauri-plugin-dialog on the Rust side)
   if (chooseBtn) {
     chooseBtn.addEventListener("click", async () => {
@@ -575,6 +605,37 @@ document.addEventListener("DOMContentLoaded", async () => {
     });
   }
 
+  // paste-the-code sign-in, no local callback port needed
+  const pasteStart = document.getElementById("paste-auth-start");
+  const pasteForm = document.getElementById("paste-auth-form");
+  const pasteUrl = document.getElementById("paste-auth-url");
+  const pasteInput = document.getElementById("paste-auth-input");
+  pasteStart?.addEventListener("click", async () => {
+    try {
+      const auth = await invoke("start_paste_auth");
+      if (pasteUrl) pasteUrl.value = auth.authorize_url;
+      if (pasteForm) pasteForm.hidden = false;
+      setStatus(
+        "Approve in the browser, then paste the address it ends up on",
+        "not-connected"
+      );
+    } catch (err) {
+      setStatus("Connect failed: " + err, "error");
+    }
+  });
+  document
+    .getElementById("paste-auth-submit")
+    ?.addEventListener("click", async () => {
+      try {
+        await invoke("submit_auth_code", { input: pasteInput?.value || "" });
+        if (pasteForm) pasteForm.hidden = true;
+        if (pasteInput) pasteInput.value = "";
+        setStatus("Connected!!!", "connected");
+      } catch (err) {
+        setStatus("Connect failed: " + err, "error");
+      }
+    });
+
   // refresh button
   const refresh = document.getElementById("refresh-btn");
   if (refresh) {
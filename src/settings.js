@@ -1,10 +1,22 @@
-const { event, webviewWindow } = window.__TAURI__ || {};
+const { core, event, webviewWindow, dialog } = window.__TAURI__ || {};
+// window-scoped (see widget.js)
+const listen = (name, handler) =>
+  webviewWindow.getCurrentWebviewWindow().listen(name, handler);
 
 const bgInput = document.getElementById("bg-color");
 const titleInput = document.getElementById("title-color");
 const metaInput = document.getElementById("meta-color");
 const resetBtn = document.getElementById("reset-theme");
 const closeBtn = document.getElementById("close");
+const clientIdInput = document.getElementById("client-id");
+const clientIdSave = document.getElementById("save-client-id");
+const clientIdStatus = document.getElementById("client-id-status");
+const authStatusEl = document.getElementById("auth-status");
+const reauthorizeBtn = document.getElementById("reauthorize");
+const exportSettingsBtn = document.getElementById("export-settings");
+const importSettingsBtn = document.getElementById("import-settings");
+const portableInput = document.getElementById("portable-mode");
+const settingsFileStatus = document.getElementById("settings-file-status");
 
 // Preview colors inside the settings window, too
 function applyThemeLocal(theme) {
@@ -26,6 +38,39 @@ function setInputs(theme) {
   if (metaInput) metaInput.value = theme.meta || "#ffffff";
 }
 
+// "Connected as X, token valid for 43 min"
+async function showAuthStatus() {
+  if (!authStatusEl) return;
+  try {
+    const s = await core.invoke("get_auth_status");
+    if (!s.connected) {
+      authStatusEl.textContent = s.signing_in ? "Signing in..." : "Not connected";
+      return;
+    }
+    const who = s.user?.display_name || s.user?.id;
+    let text = who ? `Connected as ${who}` : "Connected";
+    if (s.expires_in_secs != null)
+      text += `, token valid for ${Math.round(s.expires_in_secs / 60)} min`;
+    if (s.missing_scopes.length) text += " (new permissions needed)";
+    authStatusEl.textContent = text;
+    if (reauthorizeBtn) reauthorizeBtn.hidden = !s.missing_scopes.length;
+  } catch (err) {
+    authStatusEl.textContent = String(err);
+  }
+}
+
+function showSettingsFileStatus(text) {
+  if (settingsFileStatus) settingsFileStatus.textContent = text;
+}
+
+async function showPortableMode() {
+  try {
+    const p = await core.invoke("get_portable_mode");
+    if (portableInput) portableInput.checked = p.enabled !== p.restart_required;
+    if (p.restart_required) showSettingsFileStatus("Restart the app to switch");
+  } catch {}
+}
+
 async function emitChange() {
   const next = readInputs();
   try {
@@ -35,7 +80,7 @@ async function emitChange() {
 
 window.addEventListener("DOMContentLoaded", async () => {
   // Live listen for theme updates (e.g., broadcast from main or reset elsewhere)
-  await event.listen("theme_update", (evt) => setInputs(evt.payload || {}));
+  await listen("theme_update", (evt) => setInputs(evt.payload || {}));
   await event.emit("request_theme");
 
   // Hook inputs
@@ -49,6 +94,80 @@ window.addEventListener("DOMContentLoaded", async () => {
     await emitChange();
   });
 
+  // Spotify client ID, for builds without SPOTIFY_CLIENT_ID in a .env
+  try {
+    const id = await core.invoke("get_spotify_client_id");
+    if (clientIdInput) clientIdInput.value = id || "";
+  } catch {}
+  clientIdSave?.addEventListener("click", async () => {
+    try {
+      await core.invoke("set_spotify_client_id", {
+        clientId: clientIdInput?.value || null,
+      });
+      if (clientIdStatus) clientIdStatus.textContent = "Saved";
+      await showAuthStatus();
+    } catch (err) {
+      if (clientIdStatus) clientIdStatus.textContent = String(err);
+    }
+  });
+
+  await showAuthStatus();
+  await listen("auth_lost", showAuthStatus);
+  await listen("scopes_missing", showAuthStatus);
+  reauthorizeBtn?.addEventListener("click", async () => {
+    if (authStatusEl) authStatusEl.textContent = "Waiting for the browser...";
+    try {
+      await core.invoke("reauthorize_with_scopes");
+    } catch (err) {
+      if (authStatusEl) authStatusEl.textContent = String(err);
+      return;
+    }
+    await showAuthStatus();
+  });
+
+  // Settings file, to move a setup to another machine (secrets stay here)
+  exportSettingsBtn?.addEventListener("click", async () => {
+    const file = await dialog.save({
+      defaultPath: "now-playing-settings.json",
+      filters: [{ name: "Settings", extensions: ["json"] }],
+    });
+    if (!file) return;
+    try {
+      await core.invoke("export_settings", { file });
+      showSettingsFileStatus("Settings exported");
+    } catch (err) {
+      showSettingsFileStatus(String(err));
+    }
+  });
+  importSettingsBtn?.addEventListener("click", async () => {
+    const file = await dialog.open({
+      multiple: false,
+      filters: [{ name: "Settings", extensions: ["json"] }],
+    });
+    if (!file) return;
+    try {
+      await core.invoke("import_settings", { file });
+      showSettingsFileStatus("Settings imported; restart the app to apply them");
+    } catch (err) {
+      showSettingsFileStatus(String(err));
+    }
+  });
+
+  await showPortableMode();
+  portableInput?.addEventListener("change", async () => {
+    try {
+      const p = await core.invoke("set_portable_mode", {
+        enabled: portableInput.checked,
+      });
+      showSettingsFileStatus(
+        p.restart_required ? "Restart the app to switch" : ""
+      );
+    } catch (err) {
+      portableInput.checked = !portableInput.checked;
+      showSettingsFileStatus(String(err));
+    }
+  });
+
   closeBtn?.addEventListener("click", async () => {
     try {
       const me = await webviewWindow.getCurrent();
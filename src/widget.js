@@ -8,13 +8,20 @@ const loopMeasure = new WeakMap();
 
 let lastKey = "";
 
+// Scoped to this window, so `subscribe_events` can filter what it gets; a global
+// `event.listen` receives every event regardless
+function listen(name, handler) {
+  const { getCurrentWebviewWindow } = window.__TAURI__.webviewWindow;
+  return getCurrentWebviewWindow().listen(name, handler);
+}
+
 // --- THEME (local preview only; actual source of truth is main window) ---
 const THEME_KEYS = { bg: "theme:bg", title: "theme:title", meta: "theme:meta" };
 const SOURCE_KEY = "source:mode";
 const GSMTC_APP_KEY = "gsmtc:app"; // "spotify" | "apple" | "ytm"
 let gsmtcAppFilter = localStorage.getItem(GSMTC_APP_KEY) || "spotify";
 let sourceMode = "spotify"; // default
-let gsmPollId = null;
+let gsmUnsub = null;
 
 function setSourceMode(next) {
   sourceMode = next === "gsmtc" ? "gsmtc" : "spotify";
@@ -23,36 +30,35 @@ function setSourceMode(next) {
 }
 
 function stopGSMTCPoll() {
-  if (gsmPollId) {
-    clearInterval(gsmPollId);
-    gsmPollId = null;
+  if (typeof gsmUnsub === "function") {
+    gsmUnsub();
+    gsmUnsub = null;
   }
 }
 
-function startGSMTCPoll() {
+// Named for history: the backend now pushes `gsmtc_update` on session changes
+async function startGSMTCPoll() {
   stopGSMTCPoll();
-  const poll = async () => {
-    try {
-      const d = await window.__TAURI__.core.invoke("get_current_playing_gsmtc");
-
-      // DEBUG: see the actual app id so we can tweak matching if needed
-      if (d?.source_app_id) {
-        console.log("[GSMTC] source_app_id:", d.source_app_id);
-      }
-
-      renderGSMTC(d);
-    } catch (e) {
-      console.warn(e);
+  const onUpdate = (d) => {
+    // DEBUG: see the actual app id so we can tweak matching if needed
+    if (d?.source_app_id) {
+      console.log("[GSMTC] source_app_id:", d.source_app_id);
     }
+    renderGSMTC(d);
   };
-  poll();
-  gsmPollId = setInterval(poll, 2000);
+  window.__TAURI__.core
+    .invoke("get_current_playing_gsmtc")
+    .then(onUpdate)
+    .catch((e) => console.warn(e));
+  gsmUnsub = await listen("gsmtc_update", (evt) =>
+    onUpdate(evt.payload)
+  );
 }
 
 let spotifyUnsub = null;
 async function startSpotifyListener() {
   stopSpotifyListener();
-  spotifyUnsub = await window.__TAURI__.event.listen(
+  spotifyUnsub = await listen(
     "now_playing_update",
     (evt) => {
       render(evt.payload);
@@ -129,30 +135,12 @@ function renderGSMTC(d) {
     return;
   }
 
-  const status = (d?.status || "").toLowerCase();
-  const active =
-    ["playing", "paused"].includes(status) || d?.position_ms != null;
-  if (!active) {
+  // paused sessions still show their track
+  if (!d?.track_name) {
     render({ is_playing: false });
     return;
   }
-
-  const track_name = (d?.title || "").trim();
-  // ✅ prefer the array, fallback to single string
-  const artists =
-    Array.isArray(d?.artists) && d.artists.length
-      ? d.artists
-      : (d?.artist || "").trim();
-  const album = (d?.album || "").trim();
-
-  render({
-    is_playing: true,
-    track_name,
-    artists,            // ← now an array when available
-    album,
-    artwork_url: null,
-    artwork_path: d?.artwork_path || null,
-  });
+  render({ ...d, is_playing: true });
 }
 
 function getTheme() {
@@ -324,21 +312,25 @@ function render(d) {
   const meta =
     typeof d.artists === "string" ? d.artists : (d.artists || []).join(", ");
   const art = resolveArtUrl(d);
-  const key = `${title}|${meta}|${art}`;
+  // artwork for the new track is still on its way: leave the previous image up meanwhile
+  const holdArt = !art && d.pending_artwork;
+  const key = `${title}|${meta}`;
 
   if (key !== lastKey) {
     swapTextLikeOld(titleEl, title);
     swapTextLikeOld(metaEl, meta);
-    showArtworkWithFade(art);
+    if (!holdArt) showArtworkWithFade(art);
     lastKey = key;
   } else {
     ensureSpan(titleEl).textContent = title;
     ensureSpan(metaEl).textContent = meta;
     applyLoopIfOverflow(titleEl);
     applyLoopIfOverflow(metaEl);
-    if (art) {
-      if (artworkEl.src !== art) artworkEl.src = art;
-      artworkEl.classList.add("show");
+    if (holdArt) {
+      // keep whatever is showing
+    } else if (art) {
+      if (artworkEl.src !== art) showArtworkWithFade(art);
+      else artworkEl.classList.add("show");
     } else {
       artworkEl.classList.remove("show");
       artworkEl.removeAttribute("src");
@@ -352,12 +344,25 @@ window.addEventListener("DOMContentLoaded", async () => {
 
   const { event, core } = tauri;
 
+  // OS high-contrast / reduced-motion settings
+  const applyAccessibility = (prefs) => {
+    document.body.classList.toggle("high-contrast", !!prefs?.high_contrast);
+    document.body.classList.toggle("reduced-motion", !!prefs?.reduced_motion);
+  };
+  await listen("accessibility_changed", (evt) =>
+    applyAccessibility(evt.payload)
+  );
+  core
+    .invoke("get_accessibility_prefs")
+    .then(applyAccessibility)
+    .catch(() => {});
+
   // Theme channel (unchanged)
-  await event.listen("theme_update", (evt) => applyTheme(evt.payload));
+  await listen("theme_update", (evt) => applyTheme(evt.payload));
   await event.emit("request_theme");
 
   // Source mode channel: keep in sync with main window
-  await event.listen("source_mode_update", (evt) => {
+  await listen("source_mode_update", (evt) => {
     const mode = evt?.payload?.mode;
     if (mode) setSourceMode(mode);
   });
@@ -365,7 +370,7 @@ window.addEventListener("DOMContentLoaded", async () => {
   // Ask main window what to use
   await event.emit("request_source_mode");
 
-  await event.listen("gsmtc_app_filter_update", (evt) => {
+  await listen("gsmtc_app_filter_update", (evt) => {
     const v = evt?.payload?.value;
     if (v) {
       gsmtcAppFilter = v;